@@ -5,6 +5,9 @@ use std::io::Error;
 use galileo_mvt::error::GalileoMvtError;
 use thiserror::Error;
 
+use crate::layer::vector_tile_layer::tile_provider::loader::TileLoadError;
+use crate::tile_schema::TileSchemaError;
+
 /// Galileo error type.
 #[derive(Debug, Error, Clone)]
 #[non_exhaustive]
@@ -34,6 +37,44 @@ pub enum GalileoError {
     /// Invalid configuration for the type.
     #[error("invalid configuration: {0}")]
     Configuration(String),
+    /// Decoded tile image does not have the size of the tiles of the tile schema.
+    #[error("unexpected tile image size: {width}x{height}")]
+    UnexpectedTileSize {
+        /// Width of the image in pixels.
+        width: u32,
+        /// Height of the image in pixels.
+        height: u32,
+    },
+}
+
+/// Error type of the operations with tile schemas and tiles.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum TileError {
+    /// Tile schema is invalid.
+    #[error(transparent)]
+    Schema(#[from] TileSchemaError),
+    /// Failed to load or decode a tile.
+    #[error(transparent)]
+    Provider(#[from] GalileoError),
+    /// Error reading/writing tile data.
+    #[error("tile i/o error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Failed to parse tile related data (urls, capabilities documents etc).
+    #[error("failed to parse {0}")]
+    Parse(String),
+}
+
+impl From<TileLoadError> for TileError {
+    fn from(value: TileLoadError) -> Self {
+        let error = match value {
+            TileLoadError::Network => GalileoError::IO,
+            TileLoadError::DoesNotExist => GalileoError::NotFound,
+            TileLoadError::Decoding => GalileoError::Generic("failed to decode tile".to_string()),
+        };
+
+        Self::Provider(error)
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -68,3 +109,24 @@ impl From<js_sys::Object> for GalileoError {
         GalileoError::Wasm(Some(format!("Failed to cast {value:?} into target type")))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_schema_error_into_tile_error() {
+        let error: TileError = TileSchemaError::NoZLevelsProvided.into();
+        assert!(matches!(
+            error,
+            TileError::Schema(TileSchemaError::NoZLevelsProvided)
+        ));
+        assert_eq!(error.to_string(), "No zoom levels provided");
+    }
+
+    #[test]
+    fn tile_load_error_into_tile_error() {
+        let error: TileError = TileLoadError::DoesNotExist.into();
+        assert!(matches!(error, TileError::Provider(GalileoError::NotFound)));
+    }
+}
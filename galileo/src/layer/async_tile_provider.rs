@@ -0,0 +1,672 @@
+//! Asynchronous source of tiles and its adapter to the synchronous tile containers.
+
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use ahash::{HashMap, HashSet};
+use maybe_sync::{MaybeSend, MaybeSync};
+use parking_lot::Mutex;
+
+use crate::layer::tiles::{batch_tile_states, Clock, SystemClock, TileProvider, TileState};
+use crate::render::PackedBundle;
+use crate::tile_schema::TileIndex;
+
+/// Future of a tile load spawned by [`AsyncTileProviderAdapter`].
+#[cfg(not(target_arch = "wasm32"))]
+pub type TileLoadFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Future of a tile load spawned by [`AsyncTileProviderAdapter`].
+#[cfg(target_arch = "wasm32")]
+pub type TileLoadFuture = Pin<Box<dyn Future<Output = ()> + 'static>>;
+
+type Spawner = Box<dyn Fn(TileLoadFuture) + MaybeSend + MaybeSync>;
+
+/// Source of tiles that loads them asynchronously, e.g. over network.
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+pub trait AsyncTileProvider<StyleId>: MaybeSend + MaybeSync {
+    /// Loads the tile with the given index and style. Returns `None` if the tile cannot be
+    /// provided.
+    async fn load_tile(&self, index: TileIndex, style_id: StyleId)
+        -> Option<Arc<dyn PackedBundle>>;
+}
+
+/// State of a tile requested from an [`AsyncTileProviderAdapter`].
+enum TileLoad {
+    /// Load with the given id is running since `started_at`.
+    Pending {
+        id: u64,
+        started_at: web_time::Instant,
+    },
+    Loaded(Arc<dyn PackedBundle>),
+    Failed,
+}
+
+struct LoadState<StyleId> {
+    tiles: HashMap<(TileIndex, StyleId), TileLoad>,
+    /// Tiles waiting for a free load slot, in the order they will be loaded.
+    queue: Vec<(TileIndex, StyleId)>,
+    /// Tiles of the last [`TileProvider::get_tiles`] call that were put to the queue.
+    batch: HashSet<(TileIndex, StyleId)>,
+    in_flight: usize,
+    next_load_id: u64,
+}
+
+impl<StyleId> Default for LoadState<StyleId> {
+    fn default() -> Self {
+        Self {
+            tiles: HashMap::default(),
+            queue: Vec::new(),
+            batch: HashSet::default(),
+            in_flight: 0,
+            next_load_id: 0,
+        }
+    }
+}
+
+impl<StyleId: Copy + Hash + Eq> LoadState<StyleId> {
+    /// Marks the tile as pending if there is a free load slot, otherwise puts it to the queue.
+    /// Returns the id of the load if it must be spawned.
+    fn start_or_queue(
+        &mut self,
+        key: (TileIndex, StyleId),
+        max_loads: usize,
+        now: web_time::Instant,
+    ) -> Option<u64> {
+        if self.in_flight < max_loads {
+            Some(self.start(key, now))
+        } else {
+            if !self.queue.contains(&key) {
+                self.queue.push(key);
+            }
+            None
+        }
+    }
+
+    /// Takes the tiles from the head of the queue while there are free load slots.
+    fn dequeue(
+        &mut self,
+        max_loads: usize,
+        now: web_time::Instant,
+    ) -> Vec<(TileIndex, StyleId, u64)> {
+        let count = max_loads
+            .saturating_sub(self.in_flight)
+            .min(self.queue.len());
+        let keys: Vec<_> = self.queue.drain(..count).collect();
+        keys.into_iter()
+            .map(|key| (key.0, key.1, self.start(key, now)))
+            .collect()
+    }
+
+    fn start(&mut self, key: (TileIndex, StyleId), now: web_time::Instant) -> u64 {
+        let id = self.next_load_id;
+        self.next_load_id += 1;
+        self.tiles.insert(
+            key,
+            TileLoad::Pending {
+                id,
+                started_at: now,
+            },
+        );
+        self.in_flight += 1;
+        id
+    }
+
+    /// Marks the loads running for `timeout` or longer as failed and frees their slots.
+    fn expire(&mut self, now: web_time::Instant, timeout: Duration) {
+        for load in self.tiles.values_mut() {
+            if let TileLoad::Pending { started_at, .. } = load {
+                if now.duration_since(*started_at) >= timeout {
+                    *load = TileLoad::Failed;
+                    self.in_flight -= 1;
+                }
+            }
+        }
+    }
+}
+
+struct Shared<StyleId, Provider> {
+    provider: Provider,
+    state: Mutex<LoadState<StyleId>>,
+    max_concurrent_loads: AtomicUsize,
+    fetch_timeout: Mutex<Option<Duration>>,
+    clock: Mutex<Arc<dyn Clock>>,
+    spawner: Spawner,
+}
+
+impl<StyleId, Provider> Shared<StyleId, Provider> {
+    fn now(&self) -> web_time::Instant {
+        self.clock.lock().now()
+    }
+}
+
+/// Adapter that allows using an [`AsyncTileProvider`] as a synchronous tile provider.
+///
+/// The first request for a tile spawns its load and returns `None`. Until the load is finished
+/// all the requests for the same tile return `None` without starting new loads. After that the
+/// loaded tile is returned.
+///
+/// The number of loads running at the same time can be limited with
+/// [`AsyncTileProviderAdapter::set_max_concurrent_loads`]. Tiles requested over the limit are
+/// queued and loaded when the running loads finish.
+///
+/// Loads that do not finish in time can be treated as failed with
+/// [`AsyncTileProviderAdapter::set_fetch_timeout`].
+pub struct AsyncTileProviderAdapter<StyleId, Provider> {
+    shared: Arc<Shared<StyleId, Provider>>,
+}
+
+impl<StyleId, Provider> AsyncTileProviderAdapter<StyleId, Provider>
+where
+    StyleId: Copy + Hash + Eq + MaybeSend + MaybeSync + 'static,
+    Provider: AsyncTileProvider<StyleId> + 'static,
+{
+    /// Creates a new adapter that spawns the loads with the async runtime of the platform.
+    pub fn new(provider: Provider) -> Self {
+        Self::with_spawner(provider, crate::async_runtime::spawn)
+    }
+
+    /// Creates a new adapter that spawns the loads with the given function.
+    pub fn with_spawner(
+        provider: Provider,
+        spawner: impl Fn(TileLoadFuture) + MaybeSend + MaybeSync + 'static,
+    ) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                provider,
+                state: Mutex::default(),
+                max_concurrent_loads: AtomicUsize::new(usize::MAX),
+                fetch_timeout: Mutex::new(None),
+                clock: Mutex::new(Arc::new(SystemClock)),
+                spawner: Box::new(spawner),
+            }),
+        }
+    }
+
+    /// Maximum number of tile loads that run at the same time. Not limited by default.
+    pub fn max_concurrent_loads(&self) -> usize {
+        self.shared.max_concurrent_loads.load(Ordering::Relaxed)
+    }
+
+    /// Sets the maximum number of tile loads that run at the same time. Loads that are already
+    /// running are not affected.
+    pub fn set_max_concurrent_loads(&self, max_loads: usize) {
+        self.shared
+            .max_concurrent_loads
+            .store(max_loads, Ordering::Relaxed);
+        let now = self.shared.now();
+        let started = self.shared.state.lock().dequeue(max_loads, now);
+        for (index, style_id, id) in started {
+            Self::spawn_load(&self.shared, index, style_id, id);
+        }
+    }
+
+    /// Time after which a running load is treated as failed. Not limited by default.
+    pub fn fetch_timeout(&self) -> Option<Duration> {
+        *self.shared.fetch_timeout.lock()
+    }
+
+    /// Sets the time after which a running load is treated as failed, so that it can be retried
+    /// by the container. The time is counted from the start of the load, not from the moment the
+    /// tile was queued, and it is checked when the tiles are requested. The result of a timed out
+    /// load is ignored.
+    pub fn set_fetch_timeout(&self, timeout: Option<Duration>) {
+        *self.shared.fetch_timeout.lock() = timeout;
+    }
+
+    /// Sets the clock the fetch timeout is measured by. [`SystemClock`] is used by default.
+    pub fn set_clock(&self, clock: Arc<dyn Clock>) {
+        *self.shared.clock.lock() = clock;
+    }
+
+    /// Returns true if the load of the tile is in flight.
+    pub fn is_pending(&self, index: TileIndex, style_id: StyleId) -> bool {
+        matches!(
+            self.shared.state.lock().tiles.get(&(index, style_id)),
+            Some(TileLoad::Pending { .. })
+        )
+    }
+
+    /// Returns true if the tile waits in the queue for a free load slot.
+    pub fn is_queued(&self, index: TileIndex, style_id: StyleId) -> bool {
+        self.shared.state.lock().queue.contains(&(index, style_id))
+    }
+
+    fn spawn_load(
+        shared: &Arc<Shared<StyleId, Provider>>,
+        index: TileIndex,
+        style_id: StyleId,
+        id: u64,
+    ) {
+        let cloned = shared.clone();
+        (shared.spawner)(Box::pin(async move {
+            let shared = cloned;
+            let load = match shared.provider.load_tile(index, style_id).await {
+                Some(bundle) => TileLoad::Loaded(bundle),
+                None => TileLoad::Failed,
+            };
+
+            let now = shared.now();
+            let started = {
+                let mut state = shared.state.lock();
+                // A timed out load already freed its slot, so its result is dropped.
+                let is_current = matches!(
+                    state.tiles.get(&(index, style_id)),
+                    Some(TileLoad::Pending { id: pending, .. }) if *pending == id
+                );
+                if !is_current {
+                    return;
+                }
+
+                state.tiles.insert((index, style_id), load);
+                state.in_flight -= 1;
+                state.dequeue(shared.max_concurrent_loads.load(Ordering::Relaxed), now)
+            };
+
+            for (index, style_id, id) in started {
+                Self::spawn_load(&shared, index, style_id, id);
+            }
+        }));
+    }
+
+    /// Marks the timed out loads as failed.
+    fn expire_loads(&self, state: &mut LoadState<StyleId>, now: web_time::Instant) {
+        if let Some(timeout) = self.fetch_timeout() {
+            state.expire(now, timeout);
+        }
+    }
+}
+
+impl<StyleId, Provider> TileProvider<StyleId> for AsyncTileProviderAdapter<StyleId, Provider>
+where
+    StyleId: Copy + Hash + Eq + MaybeSend + MaybeSync + 'static,
+    Provider: AsyncTileProvider<StyleId> + 'static,
+{
+    fn get_tile(&self, index: TileIndex, style_id: StyleId) -> Option<Arc<dyn PackedBundle>> {
+        let max_loads = self.max_concurrent_loads();
+        let now = self.shared.now();
+        let (result, to_spawn) = {
+            let mut state = self.shared.state.lock();
+            self.expire_loads(&mut state, now);
+            let mut to_spawn = state.dequeue(max_loads, now);
+            let result = match state.tiles.get(&(index, style_id)) {
+                Some(TileLoad::Loaded(bundle)) => Some(bundle.clone()),
+                Some(TileLoad::Pending { .. }) | Some(TileLoad::Failed) => None,
+                None => {
+                    if let Some(id) = state.start_or_queue((index, style_id), max_loads, now) {
+                        to_spawn.push((index, style_id, id));
+                    }
+                    None
+                }
+            };
+
+            (result, to_spawn)
+        };
+
+        // The lock is released before spawning, so that a spawner that polls the future right
+        // away does not deadlock.
+        for (index, style_id, id) in to_spawn {
+            Self::spawn_load(&self.shared, index, style_id, id);
+        }
+
+        result
+    }
+
+    /// Returns the tiles that are loaded and starts loading the rest of them.
+    ///
+    /// If the number of loads is limited, the tiles are loaded in the order they are requested in,
+    /// e.g. closest to the focus of the tiles container first. The tiles queued by the previous
+    /// call that are not requested anymore are removed from the queue, so they are never loaded.
+    /// The tiles queued by [`TileProvider::get_tile`], e.g. the prefetched ones, stay in the queue
+    /// after the tiles of this request.
+    fn get_tiles(
+        &self,
+        indices: &[TileIndex],
+        style_id: StyleId,
+    ) -> Vec<Option<Arc<dyn PackedBundle>>>
+    where
+        StyleId: Copy,
+    {
+        let max_loads = self.max_concurrent_loads();
+        let now = self.shared.now();
+        let mut to_spawn = vec![];
+        let result = {
+            let mut state = self.shared.state.lock();
+            self.expire_loads(&mut state, now);
+
+            let requested: HashSet<_> = indices.iter().map(|index| (*index, style_id)).collect();
+            let previous = std::mem::take(&mut state.batch);
+            state
+                .queue
+                .retain(|key| !previous.contains(key) && !requested.contains(key));
+            let kept = std::mem::take(&mut state.queue);
+
+            let missing: Vec<TileIndex> = indices
+                .iter()
+                .filter(|index| !state.tiles.contains_key(&(**index, style_id)))
+                .copied()
+                .collect();
+
+            for index in missing {
+                if let Some(id) = state.start_or_queue((index, style_id), max_loads, now) {
+                    to_spawn.push((index, id));
+                }
+            }
+            state.batch = state.queue.iter().copied().collect();
+            state.queue.extend(kept);
+
+            indices
+                .iter()
+                .map(|index| match state.tiles.get(&(*index, style_id)) {
+                    Some(TileLoad::Loaded(bundle)) => Some(bundle.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        for (index, id) in to_spawn {
+            Self::spawn_load(&self.shared, index, style_id, id);
+        }
+
+        result
+    }
+
+    fn tile_states(&self, indices: &[TileIndex], style_id: StyleId) -> Vec<TileState> {
+        batch_tile_states(self, indices, style_id)
+    }
+
+    fn is_failed(&self, index: TileIndex, style_id: StyleId) -> bool {
+        matches!(
+            self.shared.state.lock().tiles.get(&(index, style_id)),
+            Some(TileLoad::Failed)
+        )
+    }
+
+    fn retry(&self, index: TileIndex, style_id: StyleId) {
+        let mut state = self.shared.state.lock();
+        if matches!(state.tiles.get(&(index, style_id)), Some(TileLoad::Failed)) {
+            state.tiles.remove(&(index, style_id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+    use std::task::Context;
+
+    use futures::task::noop_waker_ref;
+
+    use super::*;
+    use crate::layer::tiles::{ManualClock, TilesContainer};
+    use crate::tile_schema::{TileSchema, WrappingTileIndex};
+
+    struct TestBundle;
+
+    impl PackedBundle for TestBundle {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    #[derive(Default)]
+    struct MockProvider {
+        loads: Mutex<Vec<TileIndex>>,
+    }
+
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    impl AsyncTileProvider<u32> for MockProvider {
+        async fn load_tile(
+            &self,
+            index: TileIndex,
+            style_id: u32,
+        ) -> Option<Arc<dyn PackedBundle>> {
+            self.loads.lock().push(index);
+            if style_id == 0 {
+                Some(Arc::new(TestBundle))
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Executor that only runs the spawned futures when asked to.
+    #[derive(Clone, Default)]
+    struct ManualExecutor {
+        queue: Arc<Mutex<Vec<TileLoadFuture>>>,
+    }
+
+    impl ManualExecutor {
+        fn spawner(&self) -> impl Fn(TileLoadFuture) + Send + Sync + 'static {
+            let queue = self.queue.clone();
+            move |future| queue.lock().push(future)
+        }
+
+        fn queued(&self) -> usize {
+            self.queue.lock().len()
+        }
+
+        fn run_all(&self) {
+            let futures = std::mem::take(&mut *self.queue.lock());
+            let mut cx = Context::from_waker(noop_waker_ref());
+            for mut future in futures {
+                assert!(future.as_mut().poll(&mut cx).is_ready());
+            }
+        }
+    }
+
+    /// Clock that only moves when it is advanced.
+    fn adapter(executor: &ManualExecutor) -> AsyncTileProviderAdapter<u32, MockProvider> {
+        AsyncTileProviderAdapter::with_spawner(MockProvider::default(), executor.spawner())
+    }
+
+    #[test]
+    fn returns_tile_after_load() {
+        let executor = ManualExecutor::default();
+        let adapter = adapter(&executor);
+        let index = TileIndex::new(1, 2, 3);
+
+        assert!(adapter.get_tile(index, 0).is_none());
+        assert!(adapter.is_pending(index, 0));
+        assert_eq!(executor.queued(), 1);
+
+        executor.run_all();
+        assert!(!adapter.is_pending(index, 0));
+        assert!(adapter.get_tile(index, 0).is_some());
+        assert!(!adapter.is_failed(index, 0));
+        assert_eq!(executor.queued(), 0);
+    }
+
+    #[test]
+    fn coalesces_in_flight_requests() {
+        let executor = ManualExecutor::default();
+        let adapter = adapter(&executor);
+        let index = TileIndex::new(1, 2, 3);
+
+        for _ in 0..3 {
+            assert!(adapter.get_tile(index, 0).is_none());
+        }
+        assert!(adapter.get_tile(index, 1).is_none());
+        assert!(adapter.get_tile(TileIndex::new(0, 0, 3), 0).is_none());
+        assert_eq!(executor.queued(), 3);
+
+        executor.run_all();
+        assert!(adapter.get_tile(index, 0).is_some());
+        assert_eq!(executor.queued(), 0);
+        assert_eq!(adapter.shared.provider.loads.lock().len(), 3);
+    }
+
+    #[test]
+    fn failed_load_is_not_retried() {
+        let executor = ManualExecutor::default();
+        let adapter = adapter(&executor);
+        let index = TileIndex::new(1, 2, 3);
+
+        assert!(adapter.get_tile(index, 1).is_none());
+        executor.run_all();
+
+        assert!(adapter.is_failed(index, 1));
+        assert!(adapter.get_tile(index, 1).is_none());
+        assert_eq!(executor.queued(), 0);
+
+        adapter.retry(index, 1);
+        assert!(!adapter.is_failed(index, 1));
+        assert!(adapter.get_tile(index, 1).is_none());
+        assert_eq!(executor.queued(), 1);
+    }
+
+    #[test]
+    fn timed_out_load_fails() {
+        let executor = ManualExecutor::default();
+        let adapter = adapter(&executor);
+        let clock = Arc::new(ManualClock::new());
+        adapter.set_clock(clock.clone());
+        adapter.set_max_concurrent_loads(1);
+        adapter.set_fetch_timeout(Some(Duration::from_secs(1)));
+
+        let slow = TileIndex::new(0, 0, 3);
+        let queued = TileIndex::new(1, 0, 3);
+        assert!(adapter.get_tile(slow, 0).is_none());
+        assert!(adapter.get_tile(queued, 0).is_none());
+        assert!(adapter.is_queued(queued, 0));
+
+        clock.advance(Duration::from_millis(999));
+        assert!(adapter.get_tile(slow, 0).is_none());
+        assert!(adapter.is_pending(slow, 0));
+
+        // The timed out load frees its slot for the queued tile.
+        clock.advance(Duration::from_millis(1));
+        assert!(adapter.get_tile(slow, 0).is_none());
+        assert!(adapter.is_failed(slow, 0));
+        assert!(adapter.is_pending(queued, 0));
+
+        // Time spent in the queue does not count for the timeout.
+        clock.advance(Duration::from_millis(500));
+        assert!(adapter.get_tile(queued, 0).is_none());
+        assert!(adapter.is_pending(queued, 0));
+
+        // The late result of the timed out load is ignored.
+        executor.run_all();
+        assert!(adapter.is_failed(slow, 0));
+        assert!(adapter.get_tile(slow, 0).is_none());
+        assert!(adapter.get_tile(queued, 0).is_some());
+        assert_eq!(adapter.shared.state.lock().in_flight, 0);
+    }
+
+    #[test]
+    fn limits_concurrent_loads() {
+        let executor = ManualExecutor::default();
+        let adapter = adapter(&executor);
+        adapter.set_max_concurrent_loads(2);
+
+        let row: Vec<TileIndex> = (0..5).map(|x| TileIndex::new(x, 0, 3)).collect();
+        assert!(adapter.get_tiles(&row, 0).iter().all(Option::is_none));
+
+        // The tiles are loaded in the order they are requested in, the rest wait in the queue.
+        assert_eq!(executor.queued(), 2);
+        assert!(adapter.is_pending(row[0], 0));
+        assert!(adapter.is_pending(row[1], 0));
+        assert_eq!(
+            row.iter()
+                .filter(|index| adapter.is_queued(**index, 0))
+                .count(),
+            3
+        );
+        assert!(adapter.shared.provider.loads.lock().is_empty());
+
+        // Repeated requests do not start more loads.
+        adapter.get_tiles(&row, 0);
+        assert_eq!(executor.queued(), 2);
+
+        // Finished loads start the next queued tiles.
+        executor.run_all();
+        assert_eq!(adapter.shared.provider.loads.lock().len(), 2);
+        assert_eq!(executor.queued(), 2);
+        assert_eq!(
+            row.iter()
+                .filter(|index| adapter.is_queued(**index, 0))
+                .count(),
+            1
+        );
+
+        // Tiles that are not requested anymore are removed from the queue.
+        let queued = *row
+            .iter()
+            .find(|index| adapter.is_queued(**index, 0))
+            .unwrap();
+        let still_needed: Vec<TileIndex> = row
+            .iter()
+            .copied()
+            .filter(|index| *index != queued)
+            .collect();
+        adapter.get_tiles(&still_needed, 0);
+        assert!(!adapter.is_queued(queued, 0));
+
+        executor.run_all();
+        assert_eq!(executor.queued(), 0);
+        assert!(adapter.get_tile(queued, 0).is_none());
+        assert_eq!(executor.queued(), 1);
+        assert!(still_needed
+            .iter()
+            .all(|index| adapter.get_tile(*index, 0).is_some()));
+    }
+
+    #[test]
+    fn queued_prefetch_survives_requests() {
+        let executor = ManualExecutor::default();
+        let adapter = adapter(&executor);
+        adapter.set_max_concurrent_loads(1);
+
+        let row: Vec<TileIndex> = (0..3).map(|x| TileIndex::new(x, 0, 3)).collect();
+        let prefetched = TileIndex::new(5, 0, 3);
+        adapter.get_tiles(&row, 0);
+        assert!(adapter.get_tile(prefetched, 0).is_none());
+        assert!(adapter.is_queued(prefetched, 0));
+
+        // The next request drops only its own tiles that are not needed anymore.
+        adapter.get_tiles(&row[..2], 0);
+        assert!(adapter.is_queued(row[1], 0));
+        assert!(!adapter.is_queued(row[2], 0));
+        assert!(adapter.is_queued(prefetched, 0));
+        assert_eq!(
+            adapter.shared.state.lock().queue,
+            vec![(row[1], 0), (prefetched, 0)]
+        );
+
+        executor.run_all();
+        executor.run_all();
+        executor.run_all();
+        assert!(adapter.get_tile(prefetched, 0).is_some());
+    }
+
+    #[test]
+    fn loads_closest_to_focus_first() {
+        let executor = ManualExecutor::default();
+        let adapter = adapter(&executor);
+        adapter.set_max_concurrent_loads(2);
+        let container = TilesContainer::new(TileSchema::web(18), adapter);
+
+        let needed: Vec<_> = (0..4)
+            .flat_map(|x| (0..4).map(move |y| WrappingTileIndex::new(x, y, 2)))
+            .collect();
+        let corner = TileIndex::new(3, 3, 2);
+        let focus = container.tile_schema().tile_center(corner).unwrap();
+        container.set_focus(Some(focus));
+        container.update_displayed_tiles(needed, 0);
+
+        // The tile at the focus and one of its neighbours take the load slots, even though they
+        // are the last of the needed tiles and far from the center of the view.
+        let adapter = container.tile_provider();
+        assert_eq!(executor.queued(), 2);
+        assert!(adapter.is_pending(corner, 0));
+        assert!([TileIndex::new(2, 3, 2), TileIndex::new(3, 2, 2)]
+            .iter()
+            .any(|index| adapter.is_pending(*index, 0)));
+        assert!(adapter.is_queued(TileIndex::new(0, 0, 2), 0));
+    }
+}
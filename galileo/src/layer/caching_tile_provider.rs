@@ -0,0 +1,293 @@
+//! Tile provider decorator that caches the tiles of another provider for a limited time.
+
+use std::hash::Hash;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ahash::HashMap;
+use parking_lot::Mutex;
+
+use crate::layer::tiles::{batch_tile_states, Clock, SystemClock, TileProvider, TileState};
+use crate::render::PackedBundle;
+use crate::tile_schema::TileIndex;
+
+/// Cached tile with the time it was cached at.
+type CachedTile = (Arc<dyn PackedBundle>, web_time::Instant);
+
+/// Wrapper around a tile provider that caches the tiles it returns for a limited time.
+///
+/// Useful for the sources that change over time, e.g. weather radar images updated every few
+/// minutes. A cached tile is returned until its time-to-live lapses, after that the tile is
+/// requested from the inner provider again. Expired tiles are dropped from the cache when they are
+/// accessed. Missing tiles are not cached.
+pub struct CachingTileProvider<StyleId, Provider> {
+    inner: Provider,
+    ttl: Duration,
+    cache: Mutex<HashMap<(TileIndex, StyleId), CachedTile>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl<StyleId, Provider> CachingTileProvider<StyleId, Provider> {
+    /// Creates a new caching provider that keeps the tiles of the `inner` provider for `ttl`.
+    pub fn with_ttl(inner: Provider, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            cache: Mutex::new(HashMap::default()),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Provider the tiles are requested from.
+    pub fn inner(&self) -> &Provider {
+        &self.inner
+    }
+
+    /// Time a tile is kept in the cache after it was received from the inner provider.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Sets the clock the age of the cached tiles is measured by. [`SystemClock`] is used by
+    /// default.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Number of tiles in the cache, including the expired ones that were not accessed yet.
+    pub fn cached_count(&self) -> usize {
+        self.cache.lock().len()
+    }
+
+    /// Removes all the tiles from the cache.
+    pub fn clear(&self) {
+        self.cache.lock().clear();
+    }
+}
+
+impl<StyleId, Provider> CachingTileProvider<StyleId, Provider>
+where
+    StyleId: Copy + Hash + Eq,
+{
+    /// Returns the cached tile if it has not expired yet, dropping it from the cache otherwise.
+    fn cached(&self, index: TileIndex, style_id: StyleId) -> Option<Arc<dyn PackedBundle>> {
+        let now = self.clock.now();
+        let mut cache = self.cache.lock();
+        let (bundle, cached_at) = cache.get(&(index, style_id))?;
+        if now.duration_since(*cached_at) < self.ttl {
+            return Some(bundle.clone());
+        }
+
+        cache.remove(&(index, style_id));
+        None
+    }
+
+    fn store(&self, index: TileIndex, style_id: StyleId, bundle: &Option<Arc<dyn PackedBundle>>) {
+        if let Some(bundle) = bundle {
+            self.cache
+                .lock()
+                .insert((index, style_id), (bundle.clone(), self.clock.now()));
+        }
+    }
+}
+
+impl<StyleId, Provider> TileProvider<StyleId> for CachingTileProvider<StyleId, Provider>
+where
+    StyleId: Copy + Hash + Eq,
+    Provider: TileProvider<StyleId>,
+{
+    fn get_tile(&self, index: TileIndex, style_id: StyleId) -> Option<Arc<dyn PackedBundle>> {
+        if let Some(bundle) = self.cached(index, style_id) {
+            return Some(bundle);
+        }
+
+        let bundle = self.inner.get_tile(index, style_id);
+        self.store(index, style_id, &bundle);
+        bundle
+    }
+
+    /// Returns the cached tiles and requests the rest of them from the inner provider with a
+    /// single batch call.
+    fn get_tiles(
+        &self,
+        indices: &[TileIndex],
+        style_id: StyleId,
+    ) -> Vec<Option<Arc<dyn PackedBundle>>>
+    where
+        StyleId: Copy,
+    {
+        let mut result: Vec<_> = indices
+            .iter()
+            .map(|index| self.cached(*index, style_id))
+            .collect();
+        let missing: Vec<TileIndex> = indices
+            .iter()
+            .zip(&result)
+            .filter(|(_, bundle)| bundle.is_none())
+            .map(|(index, _)| *index)
+            .collect();
+        if missing.is_empty() {
+            return result;
+        }
+
+        let mut fetched = missing
+            .iter()
+            .zip(self.inner.get_tiles(&missing, style_id))
+            .map(|(index, bundle)| {
+                self.store(*index, style_id, &bundle);
+                bundle
+            });
+        for bundle in result.iter_mut().filter(|bundle| bundle.is_none()) {
+            *bundle = fetched.next().flatten();
+        }
+
+        result
+    }
+
+    fn tile_states(&self, indices: &[TileIndex], style_id: StyleId) -> Vec<TileState> {
+        batch_tile_states(self, indices, style_id)
+    }
+
+    fn fetch_tile_now(&self, index: TileIndex, style_id: StyleId) -> Option<Arc<dyn PackedBundle>> {
+        if let Some(bundle) = self.cached(index, style_id) {
+            return Some(bundle);
+        }
+
+        let bundle = self.inner.fetch_tile_now(index, style_id);
+        self.store(index, style_id, &bundle);
+        bundle
+    }
+
+    fn is_failed(&self, index: TileIndex, style_id: StyleId) -> bool {
+        self.inner.is_failed(index, style_id)
+    }
+
+    fn retry(&self, index: TileIndex, style_id: StyleId) {
+        self.inner.retry(index, style_id);
+    }
+
+    fn supported_zoom_range(&self) -> Option<RangeInclusive<u32>> {
+        self.inner.supported_zoom_range()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+
+    use super::*;
+    use crate::layer::tiles::ManualClock;
+
+    struct TestBundle;
+
+    impl PackedBundle for TestBundle {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    /// Provider that has only the tiles of the row 0 and records all the requests.
+    #[derive(Default)]
+    struct RecordingProvider {
+        requests: Mutex<Vec<TileIndex>>,
+    }
+
+    impl RecordingProvider {
+        fn request_count(&self) -> usize {
+            self.requests.lock().len()
+        }
+    }
+
+    impl TileProvider<u8> for RecordingProvider {
+        fn get_tile(&self, index: TileIndex, _style_id: u8) -> Option<Arc<dyn PackedBundle>> {
+            self.requests.lock().push(index);
+            (index.y == 0).then(|| Arc::new(TestBundle) as Arc<dyn PackedBundle>)
+        }
+    }
+
+    fn provider(ttl: Duration) -> (CachingTileProvider<u8, RecordingProvider>, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new());
+        let mut provider = CachingTileProvider::with_ttl(RecordingProvider::default(), ttl);
+        provider.set_clock(clock.clone());
+
+        (provider, clock)
+    }
+
+    #[test]
+    fn refetches_after_expiry() {
+        let (provider, clock) = provider(Duration::from_secs(600));
+        let index = TileIndex::new(0, 0, 1);
+
+        assert!(provider.get_tile(index, 0).is_some());
+        assert_eq!(provider.inner().request_count(), 1);
+
+        clock.advance(Duration::from_secs(599));
+        assert!(provider.get_tile(index, 0).is_some());
+        assert_eq!(provider.inner().request_count(), 1);
+
+        // Other styles are cached separately.
+        assert!(provider.get_tile(index, 1).is_some());
+        assert_eq!(provider.inner().request_count(), 2);
+
+        clock.advance(Duration::from_secs(1));
+        assert!(provider.get_tile(index, 0).is_some());
+        assert_eq!(provider.inner().request_count(), 3);
+
+        clock.advance(Duration::from_secs(300));
+        assert!(provider.get_tile(index, 0).is_some());
+        assert_eq!(provider.inner().request_count(), 3);
+    }
+
+    #[test]
+    fn expired_tiles_are_evicted_on_access() {
+        let (provider, clock) = provider(Duration::from_secs(10));
+        let first = TileIndex::new(0, 0, 1);
+        let second = TileIndex::new(1, 0, 1);
+
+        provider.get_tile(first, 0);
+        provider.get_tile(second, 0);
+        assert_eq!(provider.cached_count(), 2);
+
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(provider.cached_count(), 2);
+        assert!(provider.cached(first, 0).is_none());
+        assert_eq!(provider.cached_count(), 1);
+    }
+
+    #[test]
+    fn missing_tiles_are_not_cached() {
+        let (provider, _) = provider(Duration::from_secs(10));
+        let index = TileIndex::new(0, 1, 1);
+
+        assert!(provider.get_tile(index, 0).is_none());
+        assert!(provider.get_tile(index, 0).is_none());
+        assert_eq!(provider.inner().request_count(), 2);
+        assert_eq!(provider.cached_count(), 0);
+    }
+
+    #[test]
+    fn batch_requests_only_missing_tiles() {
+        let (provider, clock) = provider(Duration::from_secs(10));
+        let cached = TileIndex::new(0, 0, 1);
+        let fresh = TileIndex::new(1, 0, 1);
+        let missing = TileIndex::new(0, 1, 1);
+
+        provider.get_tile(cached, 0);
+        clock.advance(Duration::from_secs(5));
+
+        let tiles = provider.get_tiles(&[cached, fresh, missing], 0);
+        assert!(tiles[0].is_some());
+        assert!(tiles[1].is_some());
+        assert!(tiles[2].is_none());
+        assert_eq!(
+            *provider.inner().requests.lock(),
+            vec![cached, fresh, missing]
+        );
+
+        clock.advance(Duration::from_secs(7));
+        let tiles = provider.get_tiles(&[cached, fresh], 0);
+        assert!(tiles.iter().all(Option::is_some));
+        assert_eq!(provider.inner().request_count(), 4);
+    }
+}
@@ -12,15 +12,22 @@ use crate::render::Canvas;
 use crate::view::MapView;
 use crate::TileSchema;
 
+pub mod async_tile_provider;
 pub mod attribution;
+pub mod caching_tile_provider;
 pub mod data_provider;
 pub mod feature_layer;
 pub mod raster_tile_layer;
+mod tile_policies;
 pub(crate) mod tiles;
 pub mod vector_tile_layer;
 
 pub use feature_layer::{FeatureId, FeatureLayer};
 pub use raster_tile_layer::RasterTileLayer;
+pub use tiles::{
+    Clock, Easing, ErrorPolicy, MultiLayerUpdater, SystemClock, TileCacheStats, TileDelta,
+    TilesMetrics, UpdateTiming,
+};
 pub use vector_tile_layer::VectorTileLayer;
 
 /// Layers specify a data source and the way the data should be rendered to the map.
@@ -1,8 +1,10 @@
+use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
 
 use bytes::Bytes;
+use maybe_sync::{MaybeSend, MaybeSync};
 
-use super::{RasterTileLayer, RasterTileLoader, RestTileLoader};
+use super::{RasterTileLayer, RasterTileLoader, RestTileLoader, TileValidator};
 use crate::error::GalileoError;
 use crate::layer::attribution::Attribution;
 use crate::layer::data_provider::{
@@ -33,6 +35,9 @@ pub struct RasterTileLayerBuilder {
     messenger: Option<Box<dyn Messenger>>,
     cache: CacheType,
     offline_mode: bool,
+    density_fallback: Option<Box<dyn UrlSource<TileIndex>>>,
+    tile_validator: Option<Box<TileValidator>>,
+    zoom_range: Option<RangeInclusive<u32>>,
     attribution: Option<Attribution>,
 }
 
@@ -69,6 +74,9 @@ impl RasterTileLayerBuilder {
             messenger: None,
             cache: CacheType::None,
             offline_mode: false,
+            density_fallback: None,
+            tile_validator: None,
+            zoom_range: None,
             attribution: None,
         }
     }
@@ -97,6 +105,9 @@ impl RasterTileLayerBuilder {
             messenger: None,
             cache: CacheType::None,
             offline_mode: false,
+            density_fallback: None,
+            tile_validator: None,
+            zoom_range: None,
             attribution: Some(Attribution::new(
                 "© OpenStreetMap contributors".to_string(),
                 Some("https://www.openstreetmap.org/copyright".to_string()),
@@ -130,6 +141,9 @@ impl RasterTileLayerBuilder {
             messenger: None,
             cache: CacheType::None,
             offline_mode: false,
+            density_fallback: None,
+            tile_validator: None,
+            zoom_range: None,
             attribution: None,
         }
     }
@@ -339,6 +353,87 @@ impl RasterTileLayerBuilder {
         self
     }
 
+    /// Sets the url source of the same tiles published with another pixel density, which is used
+    /// for the tiles that cannot be loaded from the main source.
+    ///
+    /// This keeps the map populated when only one density (for example, `@1x`) is published for
+    /// some of the tiles.
+    ///
+    /// Cannot be used with custom tile provider given by
+    /// [`RasterTileLayerBuilder::new_with_loader()`] method, in this case building will return an
+    /// error.
+    ///
+    /// ```
+    /// use galileo::layer::raster_tile_layer::RasterTileLayerBuilder;
+    ///
+    /// let layer = RasterTileLayerBuilder::new_rest(
+    ///     |index| {
+    ///         format!(
+    ///             "https://example.com/{}/{}/{}@2x.png",
+    ///             index.z, index.x, index.y
+    ///         )
+    ///     })
+    ///     .with_density_fallback(|index| {
+    ///         format!(
+    ///             "https://example.com/{}/{}/{}.png",
+    ///             index.z, index.x, index.y
+    ///         )
+    ///     })
+    ///     .build()?;
+    /// # Ok::<(), galileo::error::GalileoError>(())
+    /// ```
+    pub fn with_density_fallback(
+        mut self,
+        fallback_source: impl UrlSource<TileIndex> + 'static,
+    ) -> Self {
+        self.density_fallback = Some(Box::new(fallback_source));
+        self
+    }
+
+    /// Sets a predicate that checks the raw data of every loaded tile. Tiles rejected by the
+    /// `validator` (e.g. blank placeholders returned by some servers instead of an error) are
+    /// treated as missing, so substitute tiles are displayed in their place.
+    ///
+    /// Cannot be used with custom tile provider given by
+    /// [`RasterTileLayerBuilder::new_with_loader()`] method, in this case building will return an
+    /// error.
+    ///
+    /// ```
+    /// use galileo::layer::raster_tile_layer::RasterTileLayerBuilder;
+    ///
+    /// const PLACEHOLDER_SIZE: usize = 103;
+    ///
+    /// let layer = RasterTileLayerBuilder::new_osm()
+    ///     .with_tile_validator(|data| data.len() != PLACEHOLDER_SIZE)
+    ///     .build()?;
+    /// # Ok::<(), galileo::error::GalileoError>(())
+    /// ```
+    pub fn with_tile_validator(
+        mut self,
+        validator: impl Fn(&[u8]) -> bool + MaybeSend + MaybeSync + 'static,
+    ) -> Self {
+        self.tile_validator = Some(Box::new(validator));
+        self
+    }
+
+    /// Sets the range of z-levels the tile source has tiles for.
+    ///
+    /// Tiles of the levels outside of the range are not requested. Instead of the tiles finer than
+    /// the range, their ancestors at the finest supported level are displayed stretched.
+    ///
+    /// ```
+    /// use galileo::layer::raster_tile_layer::RasterTileLayerBuilder;
+    ///
+    /// let layer = RasterTileLayerBuilder::new_osm()
+    ///     .with_zoom_range(0..=16)
+    ///     .build()?;
+    /// # Ok::<(), galileo::error::GalileoError>(())
+    /// ```
+    pub fn with_zoom_range(mut self, range: RangeInclusive<u32>) -> Self {
+        self.zoom_range = Some(range);
+        self
+    }
+
     /// Sets the layer's tile schema.
     ///
     /// Defaults to `TileSchema::web(18)`.
@@ -400,6 +495,9 @@ impl RasterTileLayerBuilder {
             messenger,
             cache,
             offline_mode,
+            density_fallback,
+            tile_validator,
+            zoom_range,
             attribution,
         } = self;
 
@@ -420,12 +518,31 @@ impl RasterTileLayerBuilder {
         }
 
         let provider: Box<dyn RasterTileLoader> = match provider_type {
-            LoaderType::Rest(url_source) => Box::new(RestTileLoader::new(
-                url_source,
-                cache_controller,
-                offline_mode,
-            )),
+            LoaderType::Rest(url_source) => {
+                let mut loader = RestTileLoader::new(url_source, cache_controller, offline_mode);
+                if let Some(fallback_source) = density_fallback {
+                    loader = loader.with_density_fallback(fallback_source);
+                }
+                if let Some(validator) = tile_validator {
+                    loader = loader.with_tile_validator(validator);
+                }
+
+                Box::new(loader)
+            }
             LoaderType::Custom(raster_tile_provider) => {
+                if density_fallback.is_some() {
+                    return Err(GalileoError::Configuration(
+                        "custom tile provider cannot be used together with a density fallback"
+                            .into(),
+                    ));
+                }
+
+                if tile_validator.is_some() {
+                    return Err(GalileoError::Configuration(
+                        "custom tile provider cannot be used together with a tile validator".into(),
+                    ));
+                }
+
                 if cache_controller.is_some() {
                     return Err(GalileoError::Configuration(
                         "custom tile provider cannot be used together with a cache controller"
@@ -442,6 +559,7 @@ impl RasterTileLayerBuilder {
             tile_schema,
             messenger,
             attribution,
+            zoom_range,
         ))
     }
 }
@@ -517,6 +635,28 @@ mod tests {
         assert_compact_debug_snapshot!(result, @r#"Err(Configuration("custom tile provider cannot be used together with a cache controller"))"#);
     }
 
+    #[test]
+    fn with_density_fallback_fails_build_if_custom_provider() {
+        let provider = RestTileLoader::new(|_| unimplemented!(), None, false);
+        let result = RasterTileLayerBuilder::new_with_loader(provider)
+            .with_density_fallback(|_| unimplemented!())
+            .build();
+
+        assert!(result.is_err());
+        assert_compact_debug_snapshot!(result, @r#"Err(Configuration("custom tile provider cannot be used together with a density fallback"))"#);
+    }
+
+    #[test]
+    fn with_tile_validator_fails_build_if_custom_provider() {
+        let provider = RestTileLoader::new(|_| unimplemented!(), None, false);
+        let result = RasterTileLayerBuilder::new_with_loader(provider)
+            .with_tile_validator(|_| true)
+            .build();
+
+        assert!(result.is_err());
+        assert_compact_debug_snapshot!(result, @r#"Err(Configuration("custom tile provider cannot be used together with a tile validator"))"#);
+    }
+
     #[test]
     fn with_offline_mode_does_not_work_without_cache() {
         let result = RasterTileLayerBuilder::new_rest(|_| unimplemented!())
@@ -1,26 +1,55 @@
 //! Raster tile layer and its providers
 
 use std::any::Any;
+use std::ops::RangeInclusive;
 use std::sync::Arc;
 
-use galileo_types::cartesian::Vector2;
+use ahash::HashSet;
+use futures::Stream;
+use galileo_types::cartesian::{CartesianPoint3d, Point2, Rect, Vector2};
+use maybe_sync::{MaybeSend, MaybeSync};
 use provider::RasterTileProvider;
 use web_time::Duration;
 
-use super::tiles::TilesContainer;
+use super::tiles::{
+    Clock, Easing, ErrorPolicy, TileCacheStats, TileDelta, TilesContainer, TilesMetrics,
+    UpdateTiming,
+};
 use super::Layer;
 use crate::layer::attribution::Attribution;
 use crate::messenger::Messenger;
 use crate::render::{BundleToDraw, Canvas, RenderOptions};
-use crate::tile_schema::{TileIndex, TileSchema};
+use crate::tile_schema::{TileIndex, TileSchema, WrappingTileIndex};
 use crate::view::MapView;
 
 mod provider;
-pub use provider::{RasterTileLoader, RestTileLoader};
+pub use provider::{RasterTileLoader, RestTileLoader, TileValidator};
 
 mod builder;
 pub use builder::RasterTileLayerBuilder;
 
+/// Progress of a region download started with [`RasterTileLayer::download_region`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadProgress {
+    /// Number of tiles that were processed so far, including the ones that failed to load.
+    pub completed: usize,
+    /// Total number of tiles in the region.
+    pub total: usize,
+    /// Size of the decoded images loaded so far in bytes.
+    pub bytes: u64,
+}
+
+impl DownloadProgress {
+    /// Returns the part of the region that is processed as a percentage from 0 to 100.
+    pub fn percentage(&self) -> f64 {
+        if self.total == 0 {
+            return 100.0;
+        }
+
+        self.completed as f64 / self.total as f64 * 100.0
+    }
+}
+
 /// Raster tile layers load prerendered tile sets using [tile loader](RasterTileLoader) and render them to the map.
 pub struct RasterTileLayer {
     tile_loader: Arc<dyn RasterTileLoader>,
@@ -49,7 +78,7 @@ impl RasterTileLayer {
             tile_loader: Arc::new(tile_loader),
             tile_container: Arc::new(TilesContainer::new(
                 tile_schema.clone(),
-                RasterTileProvider::new(tile_schema.clone()),
+                RasterTileProvider::new(tile_schema.clone(), None),
             )),
             tile_schema,
             messenger,
@@ -62,12 +91,13 @@ impl RasterTileLayer {
         tile_schema: TileSchema,
         messenger: Option<Box<dyn Messenger>>,
         attribution: Option<Attribution>,
+        zoom_range: Option<RangeInclusive<u32>>,
     ) -> Self {
         Self {
             tile_loader: tile_loader.into(),
             tile_container: Arc::new(TilesContainer::new(
                 tile_schema.clone(),
-                RasterTileProvider::new(tile_schema.clone()),
+                RasterTileProvider::new(tile_schema.clone(), zoom_range),
             )),
             tile_schema,
             messenger: messenger.map(|m| m.into()),
@@ -80,18 +110,404 @@ impl RasterTileLayer {
         self.tile_container.set_fade_in_duration(duration);
     }
 
-    fn update_displayed_tiles(&self, view: &MapView, canvas: &dyn Canvas) {
-        let Some(tile_iter) = self.tile_schema.iter_tiles(view) else {
-            return;
-        };
+    /// Sets the curve of the opacity change during the fade in of newly loaded tiles.
+    pub fn set_fade_easing(&mut self, easing: Easing) {
+        self.tile_container.set_fade_easing(easing);
+    }
 
-        let needed_indices: Vec<_> = tile_iter.collect();
-        let mut to_pack: Vec<TileIndex> = needed_indices.iter().map(|t| (*t).into()).collect();
-        to_pack.dedup();
+    /// If set, the fade in of the tiles is done in linear light, so that the tiles do not look
+    /// too dark in the middle of the fade.
+    pub fn set_fade_in_linear_space(&mut self, enabled: bool) {
+        self.tile_container.set_fade_in_linear_space(enabled);
+    }
+
+    /// Sets fade out duration for the tiles removed from the map. By default the tiles disappear
+    /// immediately.
+    pub fn set_fade_out_duration(&mut self, duration: Duration) {
+        self.tile_container.set_fade_out_duration(duration);
+    }
 
+    /// Sets maximum number of newly loaded tiles that are added to the map in one frame. The rest of
+    /// the loaded tiles are added in the next frames.
+    pub fn set_max_tile_promotions_per_frame(&mut self, max_promotions: usize) {
         self.tile_container
-            .tile_provider
+            .set_max_promotions_per_frame(max_promotions);
+    }
+
+    /// Sets minimum time a tile is kept in memory after it's no longer displayed, so that it can
+    /// be shown again without reloading if the view quickly returns back to it.
+    pub fn set_min_tile_retention(&mut self, duration: Duration) {
+        self.tile_container.set_min_retention(duration);
+    }
+
+    /// Sets maximum ratio between resolutions of a loaded tile and a missing one, for the loaded
+    /// tile to be displayed in place of the missing one while it is loading.
+    pub fn set_max_substitution_scale(&mut self, scale: f64) {
+        self.tile_container.set_max_substitution_scale(scale);
+    }
+
+    /// Sets maximum number of tiles kept by the layer. The tiles that were not displayed for the
+    /// longest time are dropped first when the limit is exceeded.
+    pub fn set_max_cached_tiles(&mut self, max_tiles: usize) {
+        self.tile_container.set_max_cached_tiles(max_tiles);
+    }
+
+    /// Sets maximum total size in bytes of the tiles kept by the layer. The tiles that were not
+    /// displayed for the longest time are dropped first when the limit is exceeded.
+    pub fn set_max_cache_bytes(&mut self, max_bytes: u64) {
+        self.tile_container.set_max_cache_bytes(max_bytes);
+    }
+
+    /// Sets maximum number of z-levels displayed at the same time. Substitute tiles of the levels
+    /// farthest from the needed one are dropped when the limit is exceeded.
+    pub fn set_max_displayed_levels(&mut self, max_levels: usize) {
+        self.tile_container.set_max_displayed_levels(max_levels);
+    }
+
+    /// Sets how many levels coarser (`parents`) and finer (`children`) than a missing tile its
+    /// substitutes can be.
+    pub fn set_substitution_depth(&mut self, parents: u32, children: u32) {
+        self.tile_container.set_substitution_parents(parents);
+        self.tile_container.set_substitution_children(children);
+    }
+
+    /// Sets the opacity lost by a coarse substitute tile for every level it is coarser than the
+    /// missing tile it replaces, so that blurry substitutes are less prominent.
+    pub fn set_substitute_opacity_falloff(&mut self, falloff: f32) {
+        self.tile_container.set_substitute_opacity_falloff(falloff);
+    }
+
+    /// If set, the tiles of the coarsest level covering the view are requested before any other
+    /// tiles and displayed as soon as they are loaded, so that the view is not blank while the
+    /// needed tiles are loading.
+    pub fn set_eager_first_frame(&mut self, eager: bool) {
+        self.tile_container.set_eager_first_frame(eager);
+    }
+
+    /// Enables or disables fade animations of the tiles. When disabled, the tiles are displayed
+    /// fully opaque as soon as they are loaded, which makes rendered frames reproducible.
+    pub fn set_animations_enabled(&mut self, enabled: bool) {
+        self.tile_container.set_animations_enabled(enabled);
+    }
+
+    /// Pauses or resumes the fade animations of the tiles, e.g. while the application is in the
+    /// background. Paused tiles keep their opacity and do not request redraws to advance the fades.
+    pub fn set_animations_paused(&mut self, paused: bool) {
+        self.tile_container.set_animations_paused(paused);
+    }
+
+    /// Sets the clock the fade animations of the tiles are driven by, so that the frames can be
+    /// rendered for a fixed time. The real time is used by default.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.tile_container.set_clock(clock);
+    }
+
+    /// If set, when the map is zoomed in beyond the finest level of the tile schema, the tiles of
+    /// the finest level are displayed scaled up instead of leaving the map blank.
+    pub fn set_overzoom(&mut self, enabled: bool) {
+        self.tile_container.set_overzoom(enabled);
+    }
+
+    /// Limits the z-levels the layer is displayed at, e.g. to switch to another layer beyond them.
+    /// The tiles of the levels outside of `min_zoom..=max_zoom` are neither loaded nor displayed.
+    pub fn set_zoom_limits(&mut self, min_zoom: u32, max_zoom: u32) {
+        self.tile_container.set_min_zoom(min_zoom);
+        self.tile_container.set_max_zoom(max_zoom);
+    }
+
+    /// If set, after zooming in the coarse tiles that were displayed stay under the finer ones
+    /// until all of them are loaded, and then fade out.
+    pub fn set_coarse_underlay_until_loaded(&mut self, enabled: bool) {
+        self.tile_container
+            .set_coarse_underlay_until_loaded(enabled);
+    }
+
+    /// If set, looking for substitutes of a missing tile stops at the first opaque tile that
+    /// covers it completely. This makes rendering faster when there are many cached tiles, at the
+    /// cost of keeping fewer substitutes.
+    pub fn set_early_substitution_termination(&mut self, enabled: bool) {
+        self.tile_container
+            .set_early_substitution_termination(enabled);
+    }
+
+    /// If set, a missing tile without substitutes within the substitution limits is replaced by
+    /// its deepest cached ancestor, so that no holes appear when zooming in quickly.
+    pub fn set_ancestor_fallback(&mut self, enabled: bool) {
+        self.tile_container.set_ancestor_fallback(enabled);
+    }
+
+    /// Sets the number of rows and columns of tiles around the view that are loaded in advance.
+    pub fn set_prefetch_ring(&mut self, ring: usize) {
+        self.tile_container.set_prefetch_ring(ring);
+    }
+
+    /// Sets whether the tiles needed for the view are only recomputed when the view crosses a tile
+    /// boundary. Enabled by default.
+    pub fn set_track_pan(&mut self, enabled: bool) {
+        self.tile_container.set_track_pan(enabled);
+    }
+
+    /// Sets the function that rewrites tile indices before the tiles are requested.
+    ///
+    /// Returning `None` from the function skips the tile, and returning a different index loads
+    /// that tile instead, which can be used for example to map the tiles to a sharded grid of a
+    /// mirror server.
+    pub fn set_request_filter(
+        &mut self,
+        filter: impl Fn(WrappingTileIndex) -> Option<WrappingTileIndex>
+            + MaybeSend
+            + MaybeSync
+            + 'static,
+    ) {
+        self.tile_container
+            .set_request_filter(Some(Box::new(filter)));
+    }
+
+    /// Sets what is displayed in place of the tiles that failed to load. By default substitutes
+    /// of such tiles are displayed, as if they were still loading.
+    pub fn set_on_tile_error(&mut self, policy: ErrorPolicy) {
+        self.tile_container.set_on_tile_error(policy);
+    }
+
+    /// Sets the delay before the first retry of a tile that failed to load. The delay doubles
+    /// after every next failure of the tile. By default failed tiles are not retried.
+    pub fn set_retry_backoff(&mut self, backoff: Duration) {
+        self.tile_container.set_retry_backoff(backoff);
+    }
+
+    /// Sets the maximum delay between retries of a tile that failed to load. Defaults to 5
+    /// minutes.
+    pub fn set_max_retry_backoff(&mut self, backoff: Duration) {
+        self.tile_container.set_max_retry_backoff(backoff);
+    }
+
+    /// Sets the interval a missing tile must stay needed for before it is loaded, so that the tiles
+    /// passed by during fast panning are not loaded at all. The tiles that are already loaded are
+    /// displayed without delay. By default tiles are loaded immediately.
+    pub fn set_request_debounce(&mut self, debounce: Option<Duration>) {
+        self.tile_container.set_request_debounce(debounce);
+    }
+
+    /// Sets the difference between the z-levels of the tiles requested from the tile loader and
+    /// the z-levels of the tile schema, for the sources that label their levels differently.
+    ///
+    /// Already loaded tiles are kept, only the tiles requested after the change use the new
+    /// offset.
+    pub fn set_zoom_offset(&mut self, offset: i32) {
+        self.tile_container.rekey_for_offset(offset);
+    }
+
+    /// Sets the current velocity of the map panning in map units per second.
+    ///
+    /// Tiles in the direction of the movement are prefetched further than the ones behind, so
+    /// that they are ready by the time the view reaches them.
+    pub fn set_pan_velocity(&mut self, velocity: Vector2) {
+        self.tile_container.set_pan_velocity(velocity);
+    }
+
+    /// Sets the point in map coordinates the missing tiles are loaded closest-first from, e.g. the
+    /// center of the view. If `None`, the tiles are loaded in the order they are needed in.
+    pub fn set_focus(&mut self, focus: Option<Point2>) {
+        self.tile_container.set_focus(focus);
+    }
+
+    /// Sets the opacity the whole layer is drawn with. The fade in of the tiles is applied on top
+    /// of it.
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.tile_container.set_layer_opacity(opacity);
+        if let Some(messenger) = &self.messenger {
+            messenger.request_redraw();
+        }
+    }
+
+    /// If set, loaded tile images that do not have the tile size of the layer's tile schema are
+    /// rejected, so a misconfigured source is reported instead of being silently stretched.
+    ///
+    /// Note that this rejects high density (for example, `@2x`) tiles too.
+    pub fn set_validate_tile_size(&mut self, validate: bool) {
+        self.tile_container
+            .tile_provider()
+            .set_validate_tile_size(validate);
+    }
+
+    /// Sets the callback that is called every time the map view becomes completely covered by
+    /// loaded and fully opaque tiles of the layer.
+    pub fn set_on_fully_loaded(&mut self, callback: impl Fn() + MaybeSend + MaybeSync + 'static) {
+        self.tile_container
+            .set_on_fully_loaded(Some(Box::new(callback)));
+    }
+
+    /// Sets the callback that is called every time a newly loaded tile of the layer is displayed.
+    ///
+    /// The callback is called while the layer updates its tiles, so it must be cheap.
+    pub fn set_on_tile_loaded(
+        &mut self,
+        callback: impl Fn(WrappingTileIndex) + MaybeSend + MaybeSync + 'static,
+    ) {
+        self.tile_container
+            .set_on_tile_loaded(Some(Box::new(move |index, _| callback(index))));
+    }
+
+    /// Returns the instant at which fade-in animation of all displayed tiles completes, or `None`
+    /// if no tile is fading in. Can be used to schedule a single redraw instead of redrawing
+    /// every frame.
+    pub fn next_fade_deadline(&self) -> Option<web_time::Instant> {
+        self.tile_container.next_fade_deadline()
+    }
+
+    /// Returns true if any displayed tile is still fading in or out, so the render loop should
+    /// keep requesting frames.
+    pub fn is_animating(&self) -> bool {
+        self.tile_container.is_animating()
+    }
+
+    /// Keeps the tile in memory even when it's not displayed, so that it's shown without loading
+    /// when the map is navigated back to it.
+    pub fn pin_tile(&self, index: WrappingTileIndex) {
+        self.tile_container.pin(index, ());
+    }
+
+    /// Removes the protection set by [`RasterTileLayer::pin_tile`].
+    pub fn unpin_tile(&self, index: WrappingTileIndex) {
+        self.tile_container.unpin(index, ());
+    }
+
+    /// Drops all tiles loaded by the layer, so that they are loaded again from the tile loader
+    /// the next time they are needed. Returns true if the layer should be redrawn.
+    pub fn clear_tiles(&self) -> bool {
+        self.tile_container.tile_provider().clear();
+        self.tile_container.clear()
+    }
+
+    /// Drops the loaded tile, so that it is loaded again from the tile loader the next time it is
+    /// needed. Returns true if the layer should be redrawn.
+    pub fn invalidate_tile(&self, index: WrappingTileIndex) -> bool {
+        self.tile_container.tile_provider().remove(index.into());
+        self.tile_container.invalidate_index(index)
+    }
+
+    /// Returns approximate size in bytes of all tiles cached by the layer.
+    pub fn memory_usage(&self) -> u64 {
+        self.tile_container.memory_usage()
+    }
+
+    /// Returns the tiles that would have to be dropped from the layer cache, in the order of
+    /// dropping, for its [memory usage](RasterTileLayer::memory_usage) to become not larger than
+    /// `target_bytes`. Pinned tiles are never included.
+    pub fn eviction_plan(&self, target_bytes: u64) -> Vec<WrappingTileIndex> {
+        self.tile_container
+            .eviction_plan(target_bytes)
+            .into_iter()
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Returns the counters of how the tiles needed for the view were served, accumulated since
+    /// the layer was created.
+    pub fn tile_metrics(&self) -> TilesMetrics {
+        self.tile_container.metrics()
+    }
+
+    /// Returns the counts of the tiles currently displayed by the layer, e.g. for a debug overlay.
+    pub fn tile_cache_stats(&self) -> TileCacheStats {
+        self.tile_container.stats()
+    }
+
+    /// Replaces the displayed tiles of the layer with the given tiles and opacities, bypassing the
+    /// tile loader. Intended for snapshot tests of the rendering.
+    #[cfg(feature = "testing")]
+    pub fn inject_displayed_tiles(
+        &self,
+        tiles: Vec<(WrappingTileIndex, Arc<dyn crate::render::PackedBundle>, f32)>,
+    ) {
+        self.tile_container.inject_displayed(
+            tiles
+                .into_iter()
+                .map(|(index, bundle, opacity)| (index, (), bundle, opacity))
+                .collect(),
+        );
+    }
+
+    /// Returns the index and opacity of every visible tile of the layer in the order they are
+    /// drawn. Intended for tests of the layer logic.
+    #[cfg(feature = "testing")]
+    pub fn displayed_snapshot(&self) -> Vec<(WrappingTileIndex, f32)> {
+        self.tile_container
+            .displayed_snapshot()
+            .into_iter()
+            .map(|(index, _, opacity)| (index, opacity))
+            .collect()
+    }
+
+    /// Returns the tile at the center of the `view`, if it is currently displayed by the layer.
+    pub fn center_tile(&self, view: &MapView) -> Option<WrappingTileIndex> {
+        let position = view.projected_position()?;
+        let lod = self.tile_schema.select_lod(view.resolution())?;
+        self.tile_container
+            .center_tile(Point2::new(position.x(), position.y()), lod.z_index())
+    }
+
+    /// Enables or disables measuring the time spent in the phases of updating the displayed tiles
+    /// at every render. Disabled by default.
+    pub fn set_record_timing(&mut self, enabled: bool) {
+        self.tile_container.set_record_timing(enabled);
+    }
+
+    /// Returns the time spent in the phases of updating the displayed tiles at the last render, if
+    /// the timing is recorded.
+    pub fn last_update_timing(&self) -> Option<UpdateTiming> {
+        self.tile_container.last_update_timing()
+    }
+
+    /// Enables or disables recording of the tiles that appear and disappear at every render.
+    /// Disabled by default.
+    pub fn set_record_delta(&mut self, enabled: bool) {
+        self.tile_container.set_record_delta(enabled);
+    }
+
+    /// Returns the tiles that appeared and disappeared at the last render, if the delta is
+    /// recorded.
+    pub fn last_delta(&self) -> Option<TileDelta<()>> {
+        self.tile_container.last_delta()
+    }
+
+    /// Returns true if at the last render all tiles needed for the view were loaded and fully
+    /// opaque.
+    pub fn is_fully_loaded(&self) -> bool {
+        self.tile_container.is_fully_loaded()
+    }
+
+    pub(crate) fn tile_container(&self) -> &TilesContainer<(), RasterTileProvider> {
+        &self.tile_container
+    }
+
+    /// Packs the loaded tiles among the `needed` ones, so that the tile container can display
+    /// them.
+    pub(crate) fn pack_tiles(&self, needed: &[WrappingTileIndex], canvas: &dyn Canvas) {
+        let mut indices = needed.to_vec();
+        if self.tile_container.is_first_frame_pending() {
+            indices.extend(self.tile_container.coarsest_ancestors(needed));
+        }
+
+        let mut packed = HashSet::default();
+        let to_pack: Vec<TileIndex> = indices
+            .into_iter()
+            .filter_map(|index| self.tile_container.filter_request(index))
+            .map(TileIndex::from)
+            .filter(|index| packed.insert(*index))
+            .collect();
+
+        self.tile_container
+            .tile_provider()
             .pack_tiles(&to_pack, canvas);
+    }
+
+    fn update_displayed_tiles(&self, view: &MapView, canvas: &dyn Canvas) {
+        let Some(needed_indices) = self.tile_container.needed_indices(view) else {
+            return;
+        };
+
+        self.pack_tiles(&needed_indices, canvas);
         let requires_redraw = self
             .tile_container
             .update_displayed_tiles(needed_indices, ());
@@ -108,25 +524,34 @@ impl RasterTileLayer {
         tile_loader: Arc<dyn RasterTileLoader>,
         tiles: Arc<TilesContainer<(), RasterTileProvider>>,
         messenger: Option<Arc<dyn Messenger>>,
-    ) {
-        if tiles.tile_provider.set_loading(index) {
+    ) -> Option<u64> {
+        if tiles.tile_provider().set_loading(index) {
             // Already loading
-            return;
+            return None;
         }
 
         let load_result = tile_loader.load(index).await;
 
         match load_result {
             Ok(decoded_image) => {
-                tiles.tile_provider.set_loaded(index, decoded_image);
+                let byte_size = decoded_image.byte_size() as u64;
+                if let Err(err) = tiles.tile_provider().set_loaded(index, decoded_image) {
+                    log::warn!("Loaded tile {index:?} is rejected: {err}");
+                    return None;
+                }
+
+                tiles.invalidate_last_update();
 
                 if let Some(messenger) = messenger {
                     messenger.request_redraw();
                 }
+
+                Some(byte_size)
             }
             Err(err) => {
                 log::debug!("Failed to load tile: {err}");
-                tiles.tile_provider.set_error(index);
+                tiles.tile_provider().set_error(index);
+                None
             }
         }
     }
@@ -134,7 +559,11 @@ impl RasterTileLayer {
     /// Preload tiles for the given `view`.
     pub async fn load_tiles(&self, view: &MapView) {
         if let Some(iter) = self.tile_schema.iter_tiles(view) {
-            for index in iter {
+            let needed = self.tile_container.supported_indices(iter);
+            for index in needed
+                .into_iter()
+                .filter_map(|index| self.tile_container.filter_request(index))
+            {
                 let tile_provider = self.tile_loader.clone();
                 let messenger = self.messenger.clone();
                 Self::load_tile(
@@ -148,6 +577,52 @@ impl RasterTileLayer {
         }
     }
 
+    /// Loads all tiles covering `bbox` at the levels of `z_range`, e.g. to make the region
+    /// available offline.
+    ///
+    /// The returned stream drives the loading: tiles are requested one by one from the coarsest
+    /// level to the finest one, and an item is produced after each of them is processed. The last
+    /// item always has `completed == total`. Tiles that are already loaded are counted as
+    /// completed without adding to the downloaded bytes.
+    pub fn download_region(
+        &self,
+        bbox: Rect,
+        z_range: RangeInclusive<u32>,
+    ) -> impl Stream<Item = DownloadProgress> {
+        let indices: Vec<WrappingTileIndex> = self
+            .tile_schema
+            .tiles_covering_multi(bbox, z_range)
+            .filter_map(|index| self.tile_container.filter_request(index.into_wrapping()))
+            .collect();
+        let progress = DownloadProgress {
+            completed: 0,
+            total: indices.len(),
+            bytes: 0,
+        };
+
+        let tile_loader = self.tile_loader.clone();
+        let tiles = self.tile_container.clone();
+        let messenger = self.messenger.clone();
+
+        futures::stream::unfold(
+            (indices.into_iter(), progress),
+            move |(mut indices, mut progress)| {
+                let tile_loader = tile_loader.clone();
+                let tiles = tiles.clone();
+                let messenger = messenger.clone();
+                async move {
+                    let index = indices.next()?;
+                    let bytes = Self::load_tile(index.into(), tile_loader, tiles, messenger).await;
+
+                    progress.completed += 1;
+                    progress.bytes += bytes.unwrap_or(0);
+
+                    Some((progress, (indices, progress)))
+                }
+            },
+        )
+    }
+
     /// Returns tile schema of the layer.
     pub fn tile_schema(&self) -> &TileSchema {
         &self.tile_schema
@@ -158,14 +633,14 @@ impl Layer for RasterTileLayer {
     fn render(&self, view: &MapView, canvas: &mut dyn Canvas) {
         self.update_displayed_tiles(view, canvas);
 
-        let displayed_tiles = self.tile_container.tiles.lock();
-        let to_render: Vec<_> = displayed_tiles
-            .values()
-            .filter_map(|v| {
-                let tile_bbox = self.tile_schema.tile_bbox(v.index)?;
+        let renderables = self.tile_container.collect_renderables();
+        let to_render: Vec<_> = renderables
+            .iter()
+            .filter_map(|(index, bundle, opacity)| {
+                let tile_bbox = self.tile_container.tile_schema().tile_bbox(*index)?;
                 let offset = Vector2::new(tile_bbox.x_min() as f32, tile_bbox.y_max() as f32);
 
-                Some(BundleToDraw::new(&*v.bundle, v.opacity, offset))
+                Some(BundleToDraw::new(&**bundle, *opacity, offset))
             })
             .collect();
 
@@ -173,8 +648,47 @@ impl Layer for RasterTileLayer {
     }
 
     fn prepare(&self, view: &MapView) {
-        if let Some(iter) = self.tile_schema.iter_tiles(view) {
-            for index in iter {
+        if let Some(mut needed) = self.tile_container.needed_indices(view) {
+            let prefetch = self.tile_container.prefetch_indices(&needed);
+            let mut prefetch = self.tile_container.supported_indices(prefetch);
+
+            // The needed tiles are loaded before the prefetched ones, each closest to the focus
+            // first.
+            self.tile_container.sort_by_focus(&mut needed);
+            self.tile_container.sort_by_focus(&mut prefetch);
+
+            let new_requests = |indices: Vec<WrappingTileIndex>| {
+                let provider = self.tile_container.tile_provider();
+                indices
+                    .into_iter()
+                    .filter_map(|index| self.tile_container.filter_request(index))
+                    .filter(|index| !provider.contains((*index).into()))
+                    .collect::<Vec<_>>()
+            };
+
+            // While the loads are debounced, a tile is loaded only after it has been needed for
+            // the whole interval. Until then the view is not settled, so nothing is prefetched
+            // either, and the layer is redrawn to load the tiles once the interval is over.
+            // The coarsest tiles of the eager first frame are requested ahead of all others and
+            // are never debounced.
+            let mut requests = if self.tile_container.is_first_frame_pending() {
+                new_requests(self.tile_container.coarsest_ancestors(&needed))
+            } else {
+                vec![]
+            };
+
+            let mut needed = new_requests(needed);
+            let now = self.tile_container.now();
+            if self.tile_container.defer_requests(&mut needed, (), now) {
+                if let Some(messenger) = &self.messenger {
+                    messenger.request_redraw();
+                }
+            } else {
+                needed.extend(new_requests(prefetch));
+            }
+            requests.extend(needed);
+
+            for index in requests {
                 let tile_provider = self.tile_loader.clone();
                 let container = self.tile_container.clone();
                 let messenger = self.messenger.clone();
@@ -205,3 +719,196 @@ impl Layer for RasterTileLayer {
         self.attribution.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use galileo_types::cartesian::Size;
+    use parking_lot::Mutex;
+
+    use super::*;
+    use crate::decoded_image::DecodedImage;
+    use crate::error::GalileoError;
+    use crate::layer::tiles::ManualClock;
+    use crate::render::render_bundle::RenderBundle;
+    use crate::render::PackedBundle;
+
+    struct StubLoader;
+
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    impl RasterTileLoader for StubLoader {
+        async fn load(&self, _index: TileIndex) -> Result<DecodedImage, GalileoError> {
+            DecodedImage::from_raw(vec![0; 16], Size::new(2, 2))
+        }
+    }
+
+    /// Loader that records the indices of all the tiles it was asked to load.
+    #[derive(Clone, Default)]
+    struct RecordingLoader(Arc<Mutex<Vec<TileIndex>>>);
+
+    impl RecordingLoader {
+        fn loaded(&self) -> Vec<TileIndex> {
+            self.0.lock().clone()
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    impl RasterTileLoader for RecordingLoader {
+        async fn load(&self, index: TileIndex) -> Result<DecodedImage, GalileoError> {
+            self.0.lock().push(index);
+            DecodedImage::from_raw(vec![0; 16], Size::new(2, 2))
+        }
+    }
+
+    struct TestBundle;
+
+    impl PackedBundle for TestBundle {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    struct TestCanvas;
+
+    impl Canvas for TestCanvas {
+        fn size(&self) -> Size {
+            Size::new(512.0, 512.0)
+        }
+
+        fn pack_bundle(&self, _bundle: &RenderBundle) -> Box<dyn PackedBundle> {
+            Box::new(TestBundle)
+        }
+
+        fn draw_bundles(&mut self, _bundles: &[BundleToDraw], _options: RenderOptions) {}
+
+        fn draw_screen_sets(&mut self) -> bool {
+            false
+        }
+    }
+
+    fn recording_layer() -> (RasterTileLayer, RecordingLoader) {
+        let loader = RecordingLoader::default();
+        let layer = RasterTileLayer::new(TileSchema::web(18), loader.clone(), None);
+        (layer, loader)
+    }
+
+    /// View of 512x512 pixels at the center of the map at the resolution of the level `z`.
+    fn view(z: u32) -> MapView {
+        let resolution = TileSchema::web(18).lod_resolution(z).unwrap();
+        MapView::new_projected(&Point2::new(0.0, 0.0), resolution)
+            .with_size(Size::new(512.0, 512.0))
+    }
+
+    /// Lets the tile loads spawned by [`RasterTileLayer::prepare`] run on the test runtime.
+    async fn run_spawned_loads() {
+        for _ in 0..16 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[test]
+    fn download_region_reports_progress() {
+        let schema = TileSchema::web(18);
+        let bbox = Rect::new(-1.0, -1.0, 1.0, 1.0);
+        let tile_count = schema.tiles_covering_multi(bbox, 0..=2).count();
+        let layer = RasterTileLayer::new(schema, StubLoader, None);
+
+        let progress: Vec<DownloadProgress> =
+            tokio_test::block_on(layer.download_region(bbox, 0..=2).collect());
+
+        assert_eq!(progress.len(), tile_count);
+        assert!(progress.iter().all(|item| item.total == tile_count));
+        let last = progress.last().unwrap();
+        assert_eq!(last.completed, tile_count);
+        assert_eq!(last.bytes, 16 * tile_count as u64);
+        assert_eq!(last.percentage(), 100.0);
+
+        let again: Vec<DownloadProgress> =
+            tokio_test::block_on(layer.download_region(bbox, 0..=2).collect());
+        assert_eq!(again.last().unwrap().bytes, 0);
+    }
+
+    #[tokio::test]
+    async fn eager_first_frame_loads_coarsest_tile() {
+        let coarse = WrappingTileIndex::new(0, 0, 0);
+
+        let (layer, loader) = recording_layer();
+        layer.prepare(&view(4));
+        run_spawned_loads().await;
+        assert!(!loader.loaded().contains(&coarse.into()));
+
+        let (mut layer, loader) = recording_layer();
+        layer.set_eager_first_frame(true);
+        layer.prepare(&view(4));
+        run_spawned_loads().await;
+        assert_eq!(loader.loaded()[0], TileIndex::from(coarse));
+
+        layer.render(&view(4), &mut TestCanvas);
+        let displayed = layer.tile_container.displayed_tiles();
+        assert!(displayed.iter().any(|tile| tile.index == coarse));
+
+        // Only the first frame is loaded eagerly.
+        let loaded = loader.loaded().len();
+        layer.prepare(&view(5));
+        run_spawned_loads().await;
+        assert!(!loader.loaded()[loaded..].contains(&coarse.into()));
+    }
+
+    #[tokio::test]
+    async fn focus_orders_loads() {
+        let (layer, loader) = recording_layer();
+        layer.prepare(&view(3));
+        run_spawned_loads().await;
+        let loaded = loader.loaded();
+        assert!(loaded.len() > 1);
+        let last = *loaded.last().unwrap();
+
+        let (mut layer, loader) = recording_layer();
+        layer.set_focus(TileSchema::web(18).tile_center(last));
+        layer.prepare(&view(3));
+        run_spawned_loads().await;
+        let loaded = loader.loaded();
+        assert_eq!(loaded[0], last);
+    }
+
+    #[tokio::test]
+    async fn request_debounce_defers_loads() {
+        let (mut layer, loader) = recording_layer();
+        let clock = Arc::new(ManualClock::new());
+        layer.set_clock(clock.clone());
+        layer.set_request_debounce(Some(Duration::from_millis(100)));
+
+        // Three rapid frames within the debounce interval.
+        for z in [3, 4, 5] {
+            layer.prepare(&view(z));
+            run_spawned_loads().await;
+            clock.advance(Duration::from_millis(30));
+        }
+        assert!(loader.loaded().is_empty());
+
+        // Only the tiles of the final view are loaded once they have been needed for the whole
+        // interval.
+        clock.advance(Duration::from_millis(80));
+        layer.prepare(&view(5));
+        run_spawned_loads().await;
+        let mut loaded = loader.loaded();
+        let mut needed: Vec<TileIndex> = layer
+            .tile_container
+            .needed_indices(&view(5))
+            .unwrap()
+            .into_iter()
+            .map(TileIndex::from)
+            .collect();
+        loaded.sort_by_key(|index| (index.z, index.x, index.y));
+        needed.sort_by_key(|index| (index.z, index.x, index.y));
+        assert!(!needed.is_empty());
+        assert_eq!(loaded, needed);
+
+        // The loaded tiles are not deferred or loaded again.
+        layer.prepare(&view(5));
+        run_spawned_loads().await;
+        assert_eq!(loader.loaded().len(), needed.len());
+    }
+}
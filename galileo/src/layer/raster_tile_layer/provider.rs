@@ -1,3 +1,5 @@
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use bytes::Bytes;
@@ -64,10 +66,15 @@ pub trait RasterTileLoader: MaybeSend + MaybeSync {
 /// ```
 pub struct RestTileLoader {
     url_source: Box<dyn UrlSource<TileIndex>>,
+    density_fallback: Option<Box<dyn UrlSource<TileIndex>>>,
     cache: Option<Box<dyn PersistentCacheController<str, Bytes>>>,
     offline_mode: bool,
+    tile_validator: Option<Box<TileValidator>>,
 }
 
+/// Predicate that checks if the raw data returned by a tile server is a real tile.
+pub type TileValidator = dyn Fn(&[u8]) -> bool + MaybeSend + MaybeSync;
+
 impl RestTileLoader {
     /// Creates a new instance of the provider.
     pub fn new(
@@ -77,17 +84,63 @@ impl RestTileLoader {
     ) -> Self {
         Self {
             url_source: Box::new(url_source),
+            density_fallback: None,
             cache,
             offline_mode,
+            tile_validator: None,
         }
     }
 
+    /// Sets the url source of the same tiles published with another pixel density (for example,
+    /// `@1x` tiles for a `@2x` source).
+    ///
+    /// If a tile cannot be loaded from the main source, the loader tries the fallback source
+    /// before reporting an error. The image is stretched over the tile area whatever its pixel
+    /// size is, so the tile of another density is displayed in place of the missing one.
+    pub fn with_density_fallback(
+        mut self,
+        fallback_source: impl UrlSource<TileIndex> + 'static,
+    ) -> Self {
+        self.density_fallback = Some(Box::new(fallback_source));
+        self
+    }
+
+    /// Sets a predicate that checks the raw data of every loaded tile.
+    ///
+    /// Some servers respond with a blank placeholder image instead of an error for the tiles they
+    /// don't have. Data rejected by the `validator` is treated as a missing tile: it is not stored
+    /// in the cache, and the layer keeps displaying substitute tiles in its place.
+    pub fn with_tile_validator(
+        mut self,
+        validator: impl Fn(&[u8]) -> bool + MaybeSend + MaybeSync + 'static,
+    ) -> Self {
+        self.tile_validator = Some(Box::new(validator));
+        self
+    }
+
+    fn is_valid_tile(&self, data: &[u8]) -> bool {
+        self.tile_validator
+            .as_ref()
+            .is_none_or(|validator| validator(data))
+    }
+
     async fn download_tile(&self, index: TileIndex) -> Result<Bytes, GalileoError> {
-        let url = (self.url_source)(&index);
+        let result = self.download_url((self.url_source)(&index)).await;
+        match (&self.density_fallback, result) {
+            (Some(fallback_source), Err(error)) => {
+                log::debug!("Failed to load tile {index:?}, trying fallback density: {error:?}");
+                self.download_url(fallback_source(&index)).await
+            }
+            (_, result) => result,
+        }
+    }
 
+    async fn download_url(&self, url: String) -> Result<Bytes, GalileoError> {
         if let Some(cache) = &self.cache {
             if let Some(data) = cache.get(&url) {
-                return Ok(data);
+                if self.is_valid_tile(&data) {
+                    return Ok(data);
+                }
             }
         }
 
@@ -100,6 +153,11 @@ impl RestTileLoader {
             .load_bytes_from_url(&url)
             .await?;
 
+        if !self.is_valid_tile(&data) {
+            log::debug!("Tile data from {url} is rejected by the validator");
+            return Err(GalileoError::NotFound);
+        }
+
         if let Some(cache) = &self.cache {
             if let Err(error) = cache.insert(&url, &data) {
                 log::warn!("Failed to write persistent cache entry: {error:?}");
@@ -131,15 +189,23 @@ enum TileState {
 pub(crate) struct RasterTileProvider {
     tiles: Mutex<Cache<TileIndex, TileState>>,
     tile_schema: TileSchema,
+    zoom_range: Option<RangeInclusive<u32>>,
+    validate_tile_size: AtomicBool,
 }
 
 impl RasterTileProvider {
-    pub(crate) fn new(tile_schema: TileSchema) -> Self {
+    pub(crate) fn new(tile_schema: TileSchema, zoom_range: Option<RangeInclusive<u32>>) -> Self {
         Self {
             tile_schema,
             tiles: Mutex::new(Cache::new(5000)),
+            zoom_range,
+            validate_tile_size: AtomicBool::new(false),
         }
     }
+
+    pub(crate) fn set_validate_tile_size(&self, validate: bool) {
+        self.validate_tile_size.store(validate, Ordering::Relaxed);
+    }
 }
 
 impl RasterTileProvider {
@@ -154,16 +220,47 @@ impl RasterTileProvider {
         }
     }
 
-    pub(crate) fn set_loaded(&self, index: TileIndex, image: DecodedImage) {
+    /// Stores the loaded image of the tile. If the tile size validation is on and the image has
+    /// a wrong size, the tile is marked as failed instead.
+    pub(crate) fn set_loaded(
+        &self,
+        index: TileIndex,
+        image: DecodedImage,
+    ) -> Result<(), GalileoError> {
+        if self.validate_tile_size.load(Ordering::Relaxed)
+            && (image.width() != self.tile_schema.tile_width_at(index.z)
+                || image.height() != self.tile_schema.tile_height_at(index.z))
+        {
+            self.set_error(index);
+            return Err(GalileoError::UnexpectedTileSize {
+                width: image.width(),
+                height: image.height(),
+            });
+        }
+
         self.tiles
             .lock()
             .insert(index, TileState::Loaded(Arc::new(image)));
+        Ok(())
+    }
+
+    /// Returns true if the tile is loading, loaded or failed to load.
+    pub(crate) fn contains(&self, index: TileIndex) -> bool {
+        self.tiles.lock().get(&index).is_some()
     }
 
     pub(crate) fn set_error(&self, index: TileIndex) {
         self.tiles.lock().insert(index, TileState::Error);
     }
 
+    pub(crate) fn remove(&self, index: TileIndex) {
+        self.tiles.lock().remove(&index);
+    }
+
+    pub(crate) fn clear(&self) {
+        self.tiles.lock().clear();
+    }
+
     pub(crate) fn pack_tiles(&self, indices: &[TileIndex], canvas: &dyn Canvas) {
         let tiles = self.tiles.lock();
         for index in indices {
@@ -171,9 +268,16 @@ impl RasterTileProvider {
                 let Some(resolution) = self.tile_schema.lod_resolution(index.z) else {
                     continue;
                 };
-                let width = self.tile_schema.tile_width() as f64;
-                let height = self.tile_schema.tile_height() as f64;
-                let tile_bbox = Rect::new(0.0, 0.0, width * resolution, -height * resolution);
+                let width = self.tile_schema.tile_width_at(index.z) as f64;
+                let height = self.tile_schema.tile_height_at(index.z) as f64;
+                // Tiles are expanded by the inset of the schema to overlap with the neighbours.
+                let inset = self.tile_schema.tile_inset() as f64 * resolution;
+                let tile_bbox = Rect::new(
+                    -inset,
+                    inset,
+                    width * resolution + inset,
+                    -height * resolution - inset,
+                );
 
                 let mut bundle = RenderBundle::default();
                 bundle.add_image(
@@ -195,4 +299,133 @@ impl TileProvider<()> for RasterTileProvider {
             _ => None,
         }
     }
+
+    fn is_failed(&self, index: TileIndex, _style_id: ()) -> bool {
+        matches!(self.tiles.lock().get(&index), Some(TileState::Error))
+    }
+
+    fn retry(&self, index: TileIndex, _style_id: ()) {
+        let tiles = self.tiles.lock();
+        if matches!(tiles.get(&index), Some(TileState::Error)) {
+            tiles.remove(&index);
+        }
+    }
+
+    fn supported_zoom_range(&self) -> Option<RangeInclusive<u32>> {
+        self.zoom_range.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ahash::HashMap;
+    use galileo_types::cartesian::Size;
+
+    use super::*;
+    use crate::layer::tiles::TilesContainer;
+    use crate::layer::RasterTileLayer;
+
+    #[derive(Default)]
+    struct TestCache(Mutex<HashMap<String, Bytes>>);
+
+    impl PersistentCacheController<str, Bytes> for TestCache {
+        fn get(&self, key: &str) -> Option<Bytes> {
+            self.0.lock().get(key).cloned()
+        }
+
+        fn insert(&self, key: &str, data: &Bytes) -> Result<(), GalileoError> {
+            self.0.lock().insert(key.to_string(), data.clone());
+            Ok(())
+        }
+    }
+
+    fn url_source(density: &'static str) -> impl UrlSource<TileIndex> {
+        move |index: &TileIndex| format!("{}/{}/{}{density}.png", index.z, index.x, index.y)
+    }
+
+    #[test]
+    fn tile_size_validation() {
+        let provider = RasterTileProvider::new(TileSchema::web(18), None);
+        let image = |size: u32| {
+            DecodedImage::from_raw(
+                vec![0; 4 * size as usize * size as usize],
+                Size::new(size, size),
+            )
+            .unwrap()
+        };
+        let index = TileIndex::new(0, 0, 1);
+
+        assert!(provider.set_loaded(index, image(512)).is_ok());
+
+        provider.set_validate_tile_size(true);
+        let result = provider.set_loaded(index, image(512));
+        assert!(matches!(
+            result,
+            Err(GalileoError::UnexpectedTileSize {
+                width: 512,
+                height: 512
+            })
+        ));
+        assert!(matches!(
+            provider.tiles.lock().get(&index),
+            Some(TileState::Error)
+        ));
+
+        assert!(provider.set_loaded(index, image(256)).is_ok());
+    }
+
+    #[test]
+    fn tile_validator_rejects_placeholders() {
+        let cache = TestCache::default();
+        cache
+            .insert("3/1/2.png", &Bytes::from_static(b"blank"))
+            .unwrap();
+        let index = TileIndex::new(1, 2, 3);
+
+        let loader = RestTileLoader::new(url_source(""), Some(Box::new(cache)), true);
+        let result = tokio_test::block_on(loader.download_tile(index));
+        assert_eq!(result.unwrap(), Bytes::from_static(b"blank"));
+
+        let loader = loader.with_tile_validator(|data| data != b"blank");
+        let result = tokio_test::block_on(loader.download_tile(index));
+        assert!(matches!(result, Err(GalileoError::NotFound)));
+
+        // The rejected tile is marked as failed, so the layer keeps substituting it.
+        let container = Arc::new(TilesContainer::new(
+            TileSchema::web(18),
+            RasterTileProvider::new(TileSchema::web(18), None),
+        ));
+        let loaded = tokio_test::block_on(RasterTileLayer::load_tile(
+            index,
+            Arc::new(loader),
+            container.clone(),
+            None,
+        ));
+        assert!(loaded.is_none());
+
+        let provider = container.tile_provider();
+        assert!(provider.get_tile(index, ()).is_none());
+        assert!(provider.is_failed(index, ()));
+        assert!(matches!(
+            provider.tiles.lock().get(&index),
+            Some(TileState::Error)
+        ));
+    }
+
+    #[test]
+    fn density_fallback() {
+        let cache = TestCache::default();
+        cache
+            .insert("3/1/2@1x.png", &Bytes::from_static(b"1x"))
+            .unwrap();
+        let index = TileIndex::new(1, 2, 3);
+
+        let loader = RestTileLoader::new(url_source("@2x"), Some(Box::new(cache)), true);
+        let result = tokio_test::block_on(loader.download_tile(index));
+        assert!(matches!(result, Err(GalileoError::NotFound)));
+
+        let loader = loader.with_density_fallback(url_source("@1x"));
+        let result = tokio_test::block_on(loader.download_tile(index));
+        assert_eq!(result.unwrap(), Bytes::from_static(b"1x"));
+    }
 }
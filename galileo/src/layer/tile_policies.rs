@@ -0,0 +1,615 @@
+//! Optional policies applied by the [`TilesContainer`](super::tiles::TilesContainer) update.
+//!
+//! Every policy keeps its own settings and state, and the update calls into it at the step it
+//! affects. All of them are disabled by default.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use ahash::{HashMap, HashSet};
+use galileo_types::cartesian::{Rect, Vector2};
+use ordered_hash_map::OrderedHashMap;
+use parking_lot::{Mutex, MutexGuard};
+
+use super::tiles::{DisplayedTile, TileKey, TileState};
+use crate::tile_schema::{VerticalDirection, WrappingTileIndex};
+use crate::TileSchema;
+
+/// Maximum number of failed tiles that are remembered to retry them with a backoff.
+const MAX_TRACKED_FAILURES: usize = 4096;
+
+const DEFAULT_MAX_RETRY_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Limit of the newly loaded tiles added to the displayed set in one update.
+pub(crate) struct PromotionCap {
+    max_per_update: AtomicUsize,
+}
+
+impl Default for PromotionCap {
+    fn default() -> Self {
+        Self {
+            max_per_update: AtomicUsize::new(usize::MAX),
+        }
+    }
+}
+
+impl PromotionCap {
+    pub(crate) fn max_per_update(&self) -> usize {
+        self.max_per_update.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_max_per_update(&self, max_promotions: usize) {
+        self.max_per_update.store(max_promotions, Ordering::Relaxed);
+    }
+
+    /// Starts counting the tiles promoted in one update.
+    pub(crate) fn budget(&self) -> PromotionBudget {
+        PromotionBudget {
+            max: self.max_per_update(),
+            promoted: 0,
+        }
+    }
+}
+
+/// Tiles promoted in the current update.
+pub(crate) struct PromotionBudget {
+    max: usize,
+    pub(crate) promoted: usize,
+}
+
+impl PromotionBudget {
+    /// Returns true if no more tiles can be promoted in this update.
+    pub(crate) fn is_exhausted(&self) -> bool {
+        self.promoted >= self.max
+    }
+
+    pub(crate) fn promote(&mut self) {
+        self.promoted += 1;
+    }
+}
+
+/// Rules for keeping the tiles that are no longer displayed.
+pub(crate) struct Retention<StyleId> {
+    min_retention: AtomicU64,
+    pinned: Mutex<HashSet<TileKey<StyleId>>>,
+}
+
+impl<StyleId> Default for Retention<StyleId> {
+    fn default() -> Self {
+        Self {
+            min_retention: AtomicU64::new(0),
+            pinned: Mutex::default(),
+        }
+    }
+}
+
+impl<StyleId: Copy + std::hash::Hash + Eq> Retention<StyleId> {
+    pub(crate) fn min_retention(&self) -> Duration {
+        Duration::from_millis(self.min_retention.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn set_min_retention(&self, duration: Duration) {
+        self.min_retention
+            .store(duration.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn pin(&self, key: TileKey<StyleId>) {
+        self.pinned.lock().insert(key);
+    }
+
+    pub(crate) fn unpin(&self, key: TileKey<StyleId>) {
+        self.pinned.lock().remove(&key);
+    }
+
+    /// Locks the pinned tiles and takes the current settings, for the duration of an update.
+    pub(crate) fn lock(&self) -> RetentionGuard<'_, StyleId> {
+        RetentionGuard {
+            min_retention: self.min_retention(),
+            pinned: self.pinned.lock(),
+        }
+    }
+}
+
+/// Retention rules locked for an update.
+pub(crate) struct RetentionGuard<'a, StyleId> {
+    min_retention: Duration,
+    pinned: MutexGuard<'a, HashSet<TileKey<StyleId>>>,
+}
+
+impl<StyleId: Copy + std::hash::Hash + Eq> RetentionGuard<'_, StyleId> {
+    pub(crate) fn is_pinned(&self, key: &TileKey<StyleId>) -> bool {
+        self.pinned.contains(key)
+    }
+
+    /// Returns true if the tile hidden at `hidden_since` must stay in the container.
+    pub(crate) fn keeps(
+        &self,
+        key: &TileKey<StyleId>,
+        hidden_since: web_time::Instant,
+        now: web_time::Instant,
+    ) -> bool {
+        self.is_pinned(key) || now.duration_since(hidden_since) < self.min_retention
+    }
+}
+
+/// Failed loads of a tile, tracked to retry it with an exponential backoff.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct TileFailure {
+    pub(crate) count: u32,
+    /// Time when the tile is requested again. `None` while the retry is in progress.
+    pub(crate) retry_at: Option<web_time::Instant>,
+}
+
+/// Retries of the tiles that failed to load, with the delay doubled after every failure.
+pub(crate) struct RetryBackoff<StyleId> {
+    backoff: AtomicU64,
+    max_backoff: AtomicU64,
+    pub(crate) failures: Mutex<HashMap<TileKey<StyleId>, TileFailure>>,
+}
+
+impl<StyleId> Default for RetryBackoff<StyleId> {
+    fn default() -> Self {
+        Self {
+            backoff: AtomicU64::new(0),
+            max_backoff: AtomicU64::new(DEFAULT_MAX_RETRY_BACKOFF.as_millis() as u64),
+            failures: Mutex::default(),
+        }
+    }
+}
+
+impl<StyleId: Copy + std::hash::Hash + Eq> RetryBackoff<StyleId> {
+    pub(crate) fn backoff(&self) -> Duration {
+        Duration::from_millis(self.backoff.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn set_backoff(&self, backoff: Duration) {
+        self.backoff
+            .store(backoff.as_millis() as u64, Ordering::Relaxed);
+        if backoff.is_zero() {
+            self.failures.lock().clear();
+        }
+    }
+
+    pub(crate) fn max_backoff(&self) -> Duration {
+        Duration::from_millis(self.max_backoff.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn set_max_backoff(&self, backoff: Duration) {
+        self.max_backoff
+            .store(backoff.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Delay before the next retry of a tile that failed to load `failures` times in a row.
+    pub(crate) fn delay(&self, failures: u32) -> Duration {
+        let factor = 1u32 << failures.saturating_sub(1).min(31);
+        self.backoff()
+            .saturating_mul(factor)
+            .min(self.max_backoff())
+    }
+
+    /// Returns the `needed` tiles that are waiting for their retry and must not be requested.
+    ///
+    /// `retry` is called for the tiles whose backoff is over, so that the provider forgets the
+    /// failure and loads the tile again.
+    pub(crate) fn backing_off(
+        &self,
+        needed: &[WrappingTileIndex],
+        style_id: StyleId,
+        now: web_time::Instant,
+        mut retry: impl FnMut(WrappingTileIndex),
+    ) -> HashSet<WrappingTileIndex> {
+        let mut backing_off = HashSet::default();
+        if self.backoff().is_zero() {
+            return backing_off;
+        }
+
+        let mut failures = self.failures.lock();
+        for index in needed {
+            let Some(failure) = failures.get_mut(&(*index, style_id)) else {
+                continue;
+            };
+
+            match failure.retry_at {
+                Some(retry_at) if now < retry_at => {
+                    backing_off.insert(*index);
+                }
+                Some(_) => {
+                    failure.retry_at = None;
+                    retry(*index);
+                }
+                None => {}
+            }
+        }
+
+        backing_off
+    }
+
+    /// Records the state of a needed tile that is not displayed: a loaded tile resets its backoff,
+    /// and a failed one schedules the next retry.
+    pub(crate) fn record(
+        &self,
+        key: TileKey<StyleId>,
+        state: &TileState,
+        is_backing_off: bool,
+        now: web_time::Instant,
+    ) {
+        if self.backoff().is_zero() {
+            return;
+        }
+
+        let mut failures = self.failures.lock();
+        match state {
+            TileState::Present(_) => {
+                failures.remove(&key);
+            }
+            TileState::Missing if !is_backing_off => {
+                if failures.len() >= MAX_TRACKED_FAILURES {
+                    failures.clear();
+                }
+
+                let failure = failures.entry(key).or_default();
+                failure.count += 1;
+                failure.retry_at = Some(now + self.delay(failure.count));
+            }
+            _ => {}
+        }
+    }
+
+    /// Forgets the failures of the tiles with matching keys.
+    pub(crate) fn forget(&self, matches: impl Fn(&TileKey<StyleId>) -> bool) {
+        self.failures.lock().retain(|key, _| !matches(key));
+    }
+}
+
+/// Deferral of the tile loads until the tiles stay needed for some time.
+pub(crate) struct RequestDebounce<StyleId> {
+    interval: Mutex<Option<Duration>>,
+    /// Time since which each tile is waiting to be loaded, while the loads are debounced.
+    pub(crate) deferred: Mutex<HashMap<TileKey<StyleId>, web_time::Instant>>,
+}
+
+impl<StyleId> Default for RequestDebounce<StyleId> {
+    fn default() -> Self {
+        Self {
+            interval: Mutex::new(None),
+            deferred: Mutex::default(),
+        }
+    }
+}
+
+impl<StyleId: Copy + std::hash::Hash + Eq> RequestDebounce<StyleId> {
+    pub(crate) fn interval(&self) -> Option<Duration> {
+        *self.interval.lock()
+    }
+
+    pub(crate) fn set_interval(&self, interval: Option<Duration>) {
+        *self.interval.lock() = interval;
+        if interval.is_none() {
+            self.deferred.lock().clear();
+        }
+    }
+
+    /// Removes from the `requests` the tiles that have not been requested for the whole interval
+    /// yet, and returns true if any tile was removed. The tiles that are not requested anymore are
+    /// forgotten.
+    pub(crate) fn defer(
+        &self,
+        requests: &mut Vec<WrappingTileIndex>,
+        style_id: StyleId,
+        now: web_time::Instant,
+    ) -> bool {
+        let Some(interval) = self.interval() else {
+            return false;
+        };
+
+        let mut deferred_requests = self.deferred.lock();
+        let mut still_requested = HashMap::default();
+        let mut deferred = false;
+        requests.retain(|index| {
+            let key = (*index, style_id);
+            let requested_since = deferred_requests.get(&key).copied().unwrap_or(now);
+            still_requested.insert(key, requested_since);
+
+            let settled = now.duration_since(requested_since) >= interval;
+            deferred |= !settled;
+            settled
+        });
+        *deferred_requests = still_requested;
+
+        deferred
+    }
+}
+
+/// Loading of the tiles around the needed ones in advance.
+#[derive(Default)]
+pub(crate) struct Prefetch {
+    ring: AtomicUsize,
+    pan_velocity: Mutex<Vector2>,
+}
+
+impl Prefetch {
+    pub(crate) fn ring(&self) -> usize {
+        self.ring.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_ring(&self, ring: usize) {
+        self.ring.store(ring, Ordering::Relaxed);
+    }
+
+    pub(crate) fn pan_velocity(&self) -> Vector2 {
+        *self.pan_velocity.lock()
+    }
+
+    pub(crate) fn set_pan_velocity(&self, velocity: Vector2) {
+        *self.pan_velocity.lock() = velocity;
+    }
+
+    /// Returns the tiles around the `needed` ones that should be loaded in advance.
+    ///
+    /// For every z-level, the ring of tiles is added around the range of the needed tiles of that
+    /// level, extended in the direction of the panning. The needed tiles themselves are not
+    /// returned.
+    pub(crate) fn indices(
+        &self,
+        tile_schema: &TileSchema,
+        needed: &[WrappingTileIndex],
+    ) -> Vec<WrappingTileIndex> {
+        let ring = self.ring() as i32;
+        if ring == 0 {
+            return vec![];
+        }
+
+        let velocity = self.pan_velocity();
+        let speed = velocity.magnitude();
+        let (dir_x, dir_y) = if speed > 0.0 {
+            let dir_y = match tile_schema.y_direction() {
+                VerticalDirection::TopToBottom => -velocity.dy() / speed,
+                VerticalDirection::BottomToTop => velocity.dy() / speed,
+            };
+            (velocity.dx() / speed, dir_y)
+        } else {
+            (0.0, 0.0)
+        };
+        let extension = |direction: f64| ring + (ring as f64 * direction.max(0.0)).round() as i32;
+
+        let mut levels: Vec<u32> = needed.iter().map(|index| index.z).collect();
+        levels.sort();
+        levels.dedup();
+
+        let mut prefetch = vec![];
+        let mut found = HashSet::default();
+        for z in levels {
+            let level = needed.iter().filter(|index| index.z == z);
+            let (Some(x_min), Some(x_max), Some(y_min), Some(y_max)) = (
+                level.clone().map(|index| index.display_x).min(),
+                level.clone().map(|index| index.display_x).max(),
+                level.clone().map(|index| index.y).min(),
+                level.map(|index| index.y).max(),
+            ) else {
+                continue;
+            };
+
+            for display_x in (x_min - extension(-dir_x))..=(x_max + extension(dir_x)) {
+                for y in (y_min - extension(-dir_y))..=(y_max + extension(dir_y)) {
+                    if (x_min..=x_max).contains(&display_x) && (y_min..=y_max).contains(&y) {
+                        continue;
+                    }
+
+                    // The index is wrapped and validated through the tile position.
+                    let Some(index) = tile_schema
+                        .tile_bbox(WrappingTileIndex::new(display_x, y, z))
+                        .and_then(|bbox: Rect| tile_schema.tile_at_point(bbox.center(), z))
+                    else {
+                        continue;
+                    };
+
+                    if found.insert(index) {
+                        prefetch.push(index);
+                    }
+                }
+            }
+        }
+
+        prefetch
+    }
+}
+
+/// Limits of the number and the size of the tiles kept in the container.
+pub(crate) struct Eviction {
+    max_tiles: AtomicUsize,
+    max_bytes: AtomicU64,
+}
+
+impl Default for Eviction {
+    fn default() -> Self {
+        Self {
+            max_tiles: AtomicUsize::new(usize::MAX),
+            max_bytes: AtomicU64::new(u64::MAX),
+        }
+    }
+}
+
+impl Eviction {
+    pub(crate) fn max_tiles(&self) -> usize {
+        self.max_tiles.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_max_tiles(&self, max_tiles: usize) {
+        self.max_tiles.store(max_tiles, Ordering::Relaxed);
+    }
+
+    pub(crate) fn max_bytes(&self) -> u64 {
+        self.max_bytes.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_max_bytes(&self, max_bytes: u64) {
+        self.max_bytes.store(max_bytes, Ordering::Relaxed);
+    }
+
+    /// Drops the tiles of `cached` over the limits, the ones hidden for the longest time first.
+    /// The `needed` tiles are counted, but never dropped, and neither are the pinned ones.
+    /// `evicted` is called for every dropped tile.
+    pub(crate) fn enforce<StyleId: Copy + std::hash::Hash + Eq>(
+        &self,
+        cached: &mut OrderedHashMap<TileKey<StyleId>, DisplayedTile<StyleId>>,
+        needed: &[DisplayedTile<StyleId>],
+        retention: &RetentionGuard<StyleId>,
+        mut evicted: impl FnMut(TileKey<StyleId>),
+    ) {
+        let max_tiles = self.max_tiles();
+        let max_bytes = self.max_bytes();
+        let mut cached_count = cached.len() + needed.len();
+        let mut usage = if max_bytes < u64::MAX {
+            BundleUsage::new(cached.values().chain(needed.iter()))
+        } else {
+            BundleUsage::default()
+        };
+
+        if cached_count <= max_tiles && usage.bytes <= max_bytes {
+            return;
+        }
+
+        let mut hidden: Vec<_> = cached
+            .iter()
+            .filter(|(key, _)| !retention.is_pinned(key))
+            .filter_map(|(key, tile)| Some((*key, tile.hidden_since?)))
+            .collect();
+        hidden.sort_by_key(|(_, hidden_since)| *hidden_since);
+
+        for (key, _) in hidden {
+            if cached_count <= max_tiles && usage.bytes <= max_bytes {
+                break;
+            }
+
+            let Some(tile) = cached.remove(&key) else {
+                continue;
+            };
+
+            cached_count -= 1;
+            usage.remove(&tile);
+            evicted(key);
+        }
+    }
+
+    /// Returns the tiles that would have to be dropped from the `tiles` for the size of their
+    /// bundles to become not larger than `target_bytes`, in the order of dropping.
+    ///
+    /// Hidden tiles are dropped first, starting from the ones hidden the longest time ago, and
+    /// then the displayed ones. Pinned tiles are never included.
+    pub(crate) fn plan<StyleId: Copy + std::hash::Hash + Eq>(
+        tiles: &OrderedHashMap<TileKey<StyleId>, DisplayedTile<StyleId>>,
+        retention: &RetentionGuard<StyleId>,
+        target_bytes: u64,
+    ) -> Vec<TileKey<StyleId>> {
+        let mut usage = BundleUsage::new(tiles.values());
+
+        let mut candidates: Vec<_> = tiles
+            .iter()
+            .filter(|(key, _)| !retention.is_pinned(key))
+            .collect();
+        candidates.sort_by_key(|(_, tile)| (tile.hidden_since.is_none(), tile.hidden_since));
+
+        let mut plan = vec![];
+        for (key, tile) in candidates {
+            if usage.bytes <= target_bytes {
+                break;
+            }
+
+            plan.push(*key);
+            usage.remove(tile);
+        }
+
+        plan
+    }
+}
+
+/// Size of the bundles of a set of tiles. Bundles shared between wrapped copies are counted once
+/// and only freed with the last copy.
+#[derive(Default)]
+struct BundleUsage {
+    copies: HashMap<usize, usize>,
+    bytes: u64,
+}
+
+impl BundleUsage {
+    fn new<'a, StyleId: Copy + 'a>(
+        tiles: impl Iterator<Item = &'a DisplayedTile<StyleId>>,
+    ) -> Self {
+        let mut usage = Self::default();
+        for tile in tiles {
+            let count = usage.copies.entry(bundle_id(tile)).or_default();
+            if *count == 0 {
+                usage.bytes += tile.bundle.size_bytes();
+            }
+            *count += 1;
+        }
+
+        usage
+    }
+
+    fn remove<StyleId: Copy>(&mut self, tile: &DisplayedTile<StyleId>) {
+        if let Some(count) = self.copies.get_mut(&bundle_id(tile)) {
+            *count -= 1;
+            if *count == 0 {
+                self.bytes = self.bytes.saturating_sub(tile.bundle.size_bytes());
+            }
+        }
+    }
+}
+
+fn bundle_id<StyleId: Copy>(tile: &DisplayedTile<StyleId>) -> usize {
+    Arc::as_ptr(&tile.bundle) as *const () as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn promotion_budget() {
+        let cap = PromotionCap::default();
+        assert!(!cap.budget().is_exhausted());
+
+        cap.set_max_per_update(2);
+        let mut budget = cap.budget();
+        budget.promote();
+        assert!(!budget.is_exhausted());
+        budget.promote();
+        assert!(budget.is_exhausted());
+
+        // Every update starts with the full budget.
+        assert!(!cap.budget().is_exhausted());
+    }
+
+    #[test]
+    fn retention_keeps_pinned_and_recent_tiles() {
+        let retention = Retention::default();
+        retention.set_min_retention(Duration::from_secs(1));
+        let pinned = (WrappingTileIndex::new(0, 0, 1), ());
+        let other = (WrappingTileIndex::new(1, 0, 1), ());
+        retention.pin(pinned);
+
+        let hidden_since = web_time::Instant::now();
+        let later = hidden_since + Duration::from_secs(1);
+        let guard = retention.lock();
+        assert!(guard.keeps(&other, hidden_since, hidden_since));
+        assert!(!guard.keeps(&other, hidden_since, later));
+        assert!(guard.keeps(&pinned, hidden_since, later));
+        drop(guard);
+
+        retention.unpin(pinned);
+        assert!(!retention.lock().keeps(&pinned, hidden_since, later));
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_maximum() {
+        let retry = RetryBackoff::<()>::default();
+        retry.set_backoff(Duration::from_secs(10));
+        retry.set_max_backoff(Duration::from_secs(30));
+        assert_eq!(retry.delay(1), Duration::from_secs(10));
+        assert_eq!(retry.delay(2), Duration::from_secs(20));
+        assert_eq!(retry.delay(3), Duration::from_secs(30));
+        assert_eq!(retry.delay(100), Duration::from_secs(30));
+    }
+}
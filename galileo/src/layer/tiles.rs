@@ -1,18 +1,252 @@
-use std::hash::Hash;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
-use ahash::HashSet;
+use ahash::{HashMap, HashSet};
+use galileo_types::cartesian::{CartesianPoint2d, Point2, Rect, Vector2};
+use maybe_sync::{MaybeSend, MaybeSync};
 use ordered_hash_map::OrderedHashMap;
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock, RwLockReadGuard};
 
-use crate::render::PackedBundle;
-use crate::tile_schema::{TileIndex, WrappingTileIndex};
+use crate::layer::tile_policies::{
+    Eviction, Prefetch, PromotionCap, RequestDebounce, Retention, RetryBackoff,
+};
+use crate::layer::{RasterTileLayer, VectorTileLayer};
+use crate::render::{Canvas, PackedBundle};
+use crate::tile_schema::{TileIndex, TileRange, WrappingTileIndex};
+use crate::view::MapView;
 use crate::TileSchema;
 
 const DEFAULT_FADE_IN_DURATION: Duration = Duration::from_millis(300);
 
+/// Identical updates that are made within this interval are considered to be done in the same
+/// frame, so only the first of them is actually executed.
+const SAME_FRAME_INTERVAL: Duration = Duration::from_millis(2);
+
+/// Maximum number of dropped tiles that are remembered to detect when they are restored.
+const MAX_TRACKED_EVICTED: usize = 4096;
+
+type FullyLoadedCallback = Box<dyn Fn() + MaybeSend + MaybeSync>;
+type TileLoadedCallback<StyleId> = Box<dyn Fn(WrappingTileIndex, StyleId) + MaybeSend + MaybeSync>;
+type RequestFilter =
+    Box<dyn Fn(WrappingTileIndex) -> Option<WrappingTileIndex> + MaybeSend + MaybeSync>;
+
+/// Source of the current time for the tile animations.
+///
+/// By default the real time is used. A custom clock can be set to drive the animations from a
+/// fixed time or a frame counter, e.g. to render repeatable frames on a server.
+pub trait Clock: MaybeSend + MaybeSync {
+    /// Returns the current instant.
+    fn now(&self) -> web_time::Instant;
+}
+
+/// Clock that returns the real time.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> web_time::Instant {
+        web_time::Instant::now()
+    }
+}
+
+/// Clock that only moves when it is advanced, so that the tests control the time.
+#[cfg(test)]
+pub(crate) struct ManualClock(Mutex<web_time::Instant>);
+
+#[cfg(test)]
+impl ManualClock {
+    pub(crate) fn new() -> Self {
+        Self(Mutex::new(web_time::Instant::now()))
+    }
+
+    pub(crate) fn advance(&self, duration: Duration) {
+        *self.0.lock() += duration;
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> web_time::Instant {
+        *self.0.lock()
+    }
+}
+
+/// Coarse tiles that are kept under the finer ones while they are loading.
+struct Underlay<StyleId> {
+    tiles: HashSet<(WrappingTileIndex, StyleId)>,
+    /// Time when all the needed tiles became opaque and the underlay started fading out.
+    fade_out_since: Option<web_time::Instant>,
+}
+
+impl<StyleId> Default for Underlay<StyleId> {
+    fn default() -> Self {
+        Self {
+            tiles: HashSet::default(),
+            fade_out_since: None,
+        }
+    }
+}
+
+/// Cross-fade from the tiles of one style to the tiles of another.
+struct StyleTransition<StyleId> {
+    from: StyleId,
+    to: StyleId,
+    /// Time of the first update with the new style, when the tiles of the old one start fading
+    /// out. `None` until then.
+    started_at: Option<web_time::Instant>,
+}
+
+/// Needed tiles computed for the last view, reused until the view crosses a tile boundary.
+struct PanTracker {
+    range: TileRange,
+    needed: Vec<WrappingTileIndex>,
+}
+
+/// Result of the last update of the displayed tiles.
+struct LastUpdate {
+    needed_hash: u64,
+    updated_at: web_time::Instant,
+    requires_redraw: bool,
+    /// If set, the next update for the same needed tiles reuses this one whenever it happens.
+    held: bool,
+}
+
+/// Snapshot of the counters of how the tiles needed by a tile layer were served.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TilesMetrics {
+    /// Number of needed tiles that were displayed with their own bundle.
+    pub direct_hits: u64,
+    /// Number of needed tiles that were missing but covered by some other displayed tile.
+    pub substituted: u64,
+    /// Number of needed tiles that were missing and had no substitute to display instead.
+    pub blanks: u64,
+    /// Number of times the tile provider was asked for a tile that was not in the container.
+    pub requests_issued: u64,
+}
+
+/// Snapshot of the state of the tiles stored in a tile layer.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TileCacheStats {
+    /// Number of tiles that are currently displayed.
+    pub displayed: usize,
+    /// Number of displayed tiles that are not fully opaque, e.g. because they are fading in or out.
+    pub fading: usize,
+    /// Number of distinct styles of the displayed tiles.
+    pub styles: usize,
+    /// Number of needed tiles that were replaced by substitutes at the last update.
+    pub substituted: u64,
+}
+
+/// Time spent in the phases of an update of the displayed tiles.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateTiming {
+    /// Collecting the needed tiles from the container and the tile provider, including updating
+    /// the opacity of the fading in tiles.
+    pub gather: Duration,
+    /// Selecting the substitutes for the missing tiles and dropping the occluded ones.
+    pub substitution: Duration,
+    /// Updating the coarse underlay tiles and their fade out.
+    pub fade: Duration,
+    /// Retaining the hidden tiles and building the new set of displayed tiles.
+    pub rebuild: Duration,
+}
+
+/// Tiles that appeared in and disappeared from the displayed set at an update.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileDelta<StyleId> {
+    /// Tiles that are displayed after the update, but were not displayed before it.
+    pub added: Vec<(WrappingTileIndex, StyleId)>,
+    /// Tiles that were displayed before the update, but are not anymore, including the ones that
+    /// were hidden or dropped from the container.
+    pub removed: Vec<(WrappingTileIndex, StyleId)>,
+}
+
+/// Curve of the tile opacity change during the fade in.
+#[derive(Debug, Default, Clone, Copy)]
+pub enum Easing {
+    /// Opacity changes at a constant rate.
+    #[default]
+    Linear,
+    /// Opacity changes slowly at the start and fast at the end (cubic curve).
+    EaseIn,
+    /// Opacity changes fast at the start and slowly at the end (cubic curve).
+    EaseOut,
+    /// Opacity changes slowly at the start and at the end (cubic curve).
+    EaseInOut,
+    /// Custom curve mapping the fade progress from 0 to 1 to the opacity from 0 to 1.
+    Custom(fn(f32) -> f32),
+}
+
+/// Encodes a linear light value with the sRGB transfer function.
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl Easing {
+    /// Returns the opacity at the given `progress` of the fade. The result is clamped to `0..=1`,
+    /// and is exactly `1` once the fade is complete.
+    pub fn apply(&self, progress: f32) -> f32 {
+        let t = progress.clamp(0.0, 1.0);
+        if t >= 1.0 {
+            return 1.0;
+        }
+
+        let value = match self {
+            Self::Linear => t,
+            Self::EaseIn => t * t * t,
+            Self::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Self::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (2.0 - 2.0 * t).powi(3) / 2.0
+                }
+            }
+            Self::Custom(curve) => curve(t),
+        };
+
+        value.clamp(0.0, 1.0)
+    }
+}
+
+/// What a tile layer displays in place of a tile that failed to load.
+#[derive(Clone, Default)]
+pub enum ErrorPolicy {
+    /// Keep displaying the substitutes of the tile, as if it was still loading.
+    #[default]
+    KeepSubstitute,
+    /// Display the given bundle in place of the tile.
+    ErrorTile(Arc<dyn PackedBundle>),
+    /// Leave the area of the tile empty.
+    Blank,
+}
+
+impl std::fmt::Debug for ErrorPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::KeepSubstitute => write!(f, "KeepSubstitute"),
+            Self::ErrorTile(_) => write!(f, "ErrorTile"),
+            Self::Blank => write!(f, "Blank"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct MetricsCounters {
+    direct_hits: AtomicU64,
+    substituted: AtomicU64,
+    blanks: AtomicU64,
+    requests_issued: AtomicU64,
+}
+
 #[derive(Clone)]
 pub(crate) struct DisplayedTile<StyleId: Copy> {
     pub(crate) index: WrappingTileIndex,
@@ -20,16 +254,138 @@ pub(crate) struct DisplayedTile<StyleId: Copy> {
     style_id: StyleId,
     pub(crate) opacity: f32,
     displayed_at: web_time::Instant,
+    /// Time when the tile was removed from the displayed set while still being kept in the
+    /// container. `None` if the tile is displayed.
+    pub(crate) hidden_since: Option<web_time::Instant>,
+    /// Time when the tile started fading out after it was removed from the displayed set. `None`
+    /// if the tile is not fading out.
+    removing_at: Option<web_time::Instant>,
 }
 
 impl<StyleId: Copy> DisplayedTile<StyleId> {
     pub(crate) fn is_opaque(&self) -> bool {
         self.opacity >= 0.999
     }
+
+    pub(crate) fn is_hidden(&self) -> bool {
+        self.hidden_since.is_some()
+    }
+}
+
+/// State of a tile in a [`TileProvider`].
+#[derive(Clone)]
+pub(crate) enum TileState {
+    /// The tile is not loaded yet. It is requested again on the next updates and substituted
+    /// until it is loaded.
+    Loading,
+    /// The tile is loaded and can be displayed.
+    Present(Arc<dyn PackedBundle>),
+    /// The tile does not exist or failed to load. It is displayed according to the
+    /// [`ErrorPolicy`] of the container and retried only after the retry backoff, if one is set.
+    Missing,
+}
+
+impl From<Option<Arc<dyn PackedBundle>>> for TileState {
+    /// Tiles the provider does not have are considered loading.
+    fn from(bundle: Option<Arc<dyn PackedBundle>>) -> Self {
+        bundle.map_or(Self::Loading, Self::Present)
+    }
 }
 
 pub(crate) trait TileProvider<StyleId> {
     fn get_tile(&self, index: TileIndex, style_id: StyleId) -> Option<Arc<dyn PackedBundle>>;
+
+    /// Returns the state of the tile.
+    ///
+    /// By default the tile is looked up with [`get_tile`](Self::get_tile), and a tile the
+    /// provider does not have is missing if [`is_failed`](Self::is_failed) returns true.
+    fn tile_state(&self, index: TileIndex, style_id: StyleId) -> TileState
+    where
+        StyleId: Copy,
+    {
+        match TileState::from(self.get_tile(index, style_id)) {
+            TileState::Loading if self.is_failed(index, style_id) => TileState::Missing,
+            state => state,
+        }
+    }
+
+    /// Returns the states of the tiles with the given indices in the same order as the indices.
+    ///
+    /// By default [`tile_state`](Self::tile_state) is called for each index. Providers that
+    /// override [`get_tiles`](Self::get_tiles) can use [`batch_tile_states`] to look up the
+    /// states with a single call too.
+    fn tile_states(&self, indices: &[TileIndex], style_id: StyleId) -> Vec<TileState>
+    where
+        StyleId: Copy,
+    {
+        indices
+            .iter()
+            .map(|index| self.tile_state(*index, style_id))
+            .collect()
+    }
+
+    /// Returns the tiles with the given indices in the same order as the indices.
+    ///
+    /// Providers that can look up several tiles at once more efficiently than one by one (e.g.
+    /// a tile archive doing a single range read) should override this method. By default
+    /// [`get_tile`](Self::get_tile) is called for each index.
+    fn get_tiles(
+        &self,
+        indices: &[TileIndex],
+        style_id: StyleId,
+    ) -> Vec<Option<Arc<dyn PackedBundle>>>
+    where
+        StyleId: Copy,
+    {
+        indices
+            .iter()
+            .map(|index| self.get_tile(*index, style_id))
+            .collect()
+    }
+
+    /// Returns the tile, loading it synchronously if the provider is able to.
+    ///
+    /// Used only until the first frame of a container with the eager first frame enabled is
+    /// displayed, so that something is displayed right away. By default no loading is done, and
+    /// only the tiles the provider already has are returned. Providers that load tiles
+    /// asynchronously should rather start loading the tiles returned by
+    /// [`TilesContainer::coarsest_ancestors`] ahead of the others.
+    fn fetch_tile_now(&self, index: TileIndex, style_id: StyleId) -> Option<Arc<dyn PackedBundle>> {
+        self.get_tile(index, style_id)
+    }
+
+    /// Returns true if the provider failed to load the tile and will not provide it.
+    fn is_failed(&self, _index: TileIndex, _style_id: StyleId) -> bool {
+        false
+    }
+
+    /// Forgets that the tile failed to load, so that it is loaded again the next time it is
+    /// needed. Called by the container when the retry backoff of the tile is over.
+    ///
+    /// Does nothing by default, so the failed tiles are never retried.
+    fn retry(&self, _index: TileIndex, _style_id: StyleId) {}
+
+    /// Range of z-levels the source of the provider has tiles for. `None` if unrestricted.
+    fn supported_zoom_range(&self) -> Option<RangeInclusive<u32>> {
+        None
+    }
+}
+
+/// Returns the states of the tiles looked up with a single [`TileProvider::get_tiles`] call. The
+/// tiles the provider does not have are checked with [`TileProvider::is_failed`].
+pub(crate) fn batch_tile_states<StyleId: Copy>(
+    provider: &(impl TileProvider<StyleId> + ?Sized),
+    indices: &[TileIndex],
+    style_id: StyleId,
+) -> Vec<TileState> {
+    indices
+        .iter()
+        .zip(provider.get_tiles(indices, style_id))
+        .map(|(index, bundle)| match TileState::from(bundle) {
+            TileState::Loading if provider.is_failed(*index, style_id) => TileState::Missing,
+            state => state,
+        })
+        .collect()
 }
 
 pub(crate) struct TilesContainer<StyleId, Provider>
@@ -37,10 +393,57 @@ where
     StyleId: Copy + Hash + Eq,
     Provider: TileProvider<StyleId>,
 {
-    pub(crate) tiles: Mutex<OrderedHashMap<(WrappingTileIndex, StyleId), DisplayedTile<StyleId>>>,
+    pub(crate) tiles: RwLock<OrderedHashMap<(WrappingTileIndex, StyleId), DisplayedTile<StyleId>>>,
     tile_schema: TileSchema,
-    pub(crate) tile_provider: Provider,
+    tile_provider: RwLock<Provider>,
     pub fade_in_duration: AtomicU64,
+    fade_out_duration: AtomicU64,
+    animations_enabled: AtomicBool,
+    animations_paused_at: Mutex<Option<web_time::Instant>>,
+    fade_easing: Mutex<Easing>,
+    fade_in_linear_space: AtomicBool,
+    promotion_cap: PromotionCap,
+    retention: Retention<StyleId>,
+    max_substitution_scale: AtomicU64,
+    substitution_parents: AtomicU32,
+    substitution_children: AtomicU32,
+    max_displayed_levels: AtomicUsize,
+    eviction: Eviction,
+    substitute_opacity_falloff: AtomicU32,
+    layer_opacity: AtomicU32,
+    early_substitution_termination: AtomicBool,
+    ancestor_fallback: AtomicBool,
+    prefetch: Prefetch,
+    focus: Mutex<Option<Point2>>,
+    eager_first_frame: AtomicBool,
+    first_frame_done: AtomicBool,
+    coarse_underlay_until_loaded: AtomicBool,
+    overzoom: AtomicBool,
+    min_zoom: AtomicU32,
+    max_zoom: AtomicU32,
+    underlay: Mutex<Underlay<StyleId>>,
+    style_transition: Mutex<Option<StyleTransition<StyleId>>>,
+    fully_loaded: AtomicBool,
+    on_fully_loaded: Mutex<Option<FullyLoadedCallback>>,
+    on_tile_loaded: Mutex<Option<TileLoadedCallback<StyleId>>>,
+    request_filter: Mutex<Option<RequestFilter>>,
+    on_tile_error: Mutex<ErrorPolicy>,
+    zoom_offset: AtomicI32,
+    last_update: Mutex<Option<LastUpdate>>,
+    track_pan: AtomicBool,
+    pan_tracker: Mutex<Option<PanTracker>>,
+    evicted: Mutex<HashSet<(WrappingTileIndex, StyleId)>>,
+    retry: RetryBackoff<StyleId>,
+    debounce: RequestDebounce<StyleId>,
+    metrics: MetricsCounters,
+    record_timing: AtomicBool,
+    last_update_timing: Mutex<Option<UpdateTiming>>,
+    record_delta: AtomicBool,
+    /// Tiles displayed after the last update, while the delta is recorded.
+    delta_displayed: Mutex<HashSet<(WrappingTileIndex, StyleId)>>,
+    last_delta: Mutex<Option<TileDelta<StyleId>>>,
+    last_substituted: AtomicU64,
+    clock: Mutex<Arc<dyn Clock>>,
 }
 
 impl<StyleId, Provider> TilesContainer<StyleId, Provider>
@@ -52,9 +455,84 @@ where
         Self {
             tiles: Default::default(),
             tile_schema,
-            tile_provider,
+            tile_provider: RwLock::new(tile_provider),
             fade_in_duration: AtomicU64::new(DEFAULT_FADE_IN_DURATION.as_millis() as u64),
+            fade_out_duration: AtomicU64::new(0),
+            animations_enabled: AtomicBool::new(true),
+            animations_paused_at: Mutex::new(None),
+            fade_easing: Mutex::default(),
+            fade_in_linear_space: AtomicBool::new(false),
+            promotion_cap: PromotionCap::default(),
+            retention: Retention::default(),
+            max_substitution_scale: AtomicU64::new(f64::INFINITY.to_bits()),
+            substitution_parents: AtomicU32::new(u32::MAX),
+            substitution_children: AtomicU32::new(u32::MAX),
+            max_displayed_levels: AtomicUsize::new(usize::MAX),
+            eviction: Eviction::default(),
+            substitute_opacity_falloff: AtomicU32::new(0f32.to_bits()),
+            layer_opacity: AtomicU32::new(1f32.to_bits()),
+            early_substitution_termination: AtomicBool::new(false),
+            ancestor_fallback: AtomicBool::new(false),
+            prefetch: Prefetch::default(),
+            focus: Mutex::new(None),
+            eager_first_frame: AtomicBool::new(false),
+            first_frame_done: AtomicBool::new(false),
+            coarse_underlay_until_loaded: AtomicBool::new(false),
+            overzoom: AtomicBool::new(false),
+            min_zoom: AtomicU32::new(0),
+            max_zoom: AtomicU32::new(u32::MAX),
+            underlay: Mutex::default(),
+            style_transition: Mutex::new(None),
+            fully_loaded: AtomicBool::new(false),
+            on_fully_loaded: Mutex::new(None),
+            on_tile_loaded: Mutex::new(None),
+            request_filter: Mutex::new(None),
+            on_tile_error: Mutex::default(),
+            zoom_offset: AtomicI32::new(0),
+            last_update: Mutex::new(None),
+            track_pan: AtomicBool::new(true),
+            pan_tracker: Mutex::new(None),
+            evicted: Mutex::default(),
+            retry: RetryBackoff::default(),
+            debounce: RequestDebounce::default(),
+            metrics: MetricsCounters::default(),
+            record_timing: AtomicBool::new(false),
+            record_delta: AtomicBool::new(false),
+            delta_displayed: Mutex::default(),
+            last_delta: Mutex::new(None),
+            last_update_timing: Mutex::new(None),
+            last_substituted: AtomicU64::new(0),
+            clock: Mutex::new(Arc::new(SystemClock)),
+        }
+    }
+
+    pub(crate) fn tile_schema(&self) -> &TileSchema {
+        &self.tile_schema
+    }
+
+    /// Provider the tiles are taken from.
+    ///
+    /// The provider is locked for reading while the guard is held, so it should not be kept
+    /// longer than needed.
+    pub(crate) fn tile_provider(&self) -> RwLockReadGuard<'_, Provider> {
+        self.tile_provider.read()
+    }
+
+    /// Replaces the provider the tiles are taken from, e.g. to switch from a local cache to a
+    /// remote server.
+    ///
+    /// If `clear_cache` is set, all the tiles taken from the old provider are removed from the
+    /// container. Otherwise they stay displayed until they are replaced by the tiles of the new
+    /// provider.
+    pub fn set_tile_provider(&self, provider: Provider, clear_cache: bool) {
+        *self.tile_provider.write() = provider;
+        if clear_cache {
+            self.clear();
         }
+
+        // The new provider may support other z-levels.
+        self.reset_pan_tracker();
+        self.invalidate_last_update();
     }
 
     pub(crate) fn update_displayed_tiles(
@@ -62,55 +540,282 @@ where
         needed_indices: impl IntoIterator<Item = WrappingTileIndex>,
         style_id: StyleId,
     ) -> bool {
-        let mut displayed_tiles = self.tiles.lock();
+        self.update_displayed_tiles_at(needed_indices, style_id, self.now())
+    }
+
+    /// Same as [`TilesContainer::update_displayed_tiles`], but the update is done as if at the
+    /// given instant, so that several containers can be updated for the same moment.
+    pub(crate) fn update_displayed_tiles_at(
+        &self,
+        needed_indices: impl IntoIterator<Item = WrappingTileIndex>,
+        style_id: StyleId,
+        now: web_time::Instant,
+    ) -> bool {
+        let needed_indices = self.supported_indices(needed_indices);
+
+        let mut hasher = DefaultHasher::new();
+        needed_indices.hash(&mut hasher);
+        style_id.hash(&mut hasher);
+        let needed_hash = hasher.finish();
+
+        if let Some(last_update) = &mut *self.last_update.lock() {
+            if last_update.needed_hash == needed_hash
+                && (last_update.held
+                    || now.duration_since(last_update.updated_at) < SAME_FRAME_INTERVAL)
+            {
+                last_update.held = false;
+                return last_update.requires_redraw;
+            }
+        }
+
+        let requires_redraw = self.do_update_displayed_tiles(needed_indices, style_id, now);
+        *self.last_update.lock() = Some(LastUpdate {
+            needed_hash,
+            updated_at: now,
+            requires_redraw,
+            held: false,
+        });
+
+        requires_redraw
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "update_displayed_tiles",
+            level = "debug",
+            skip_all,
+            fields(needed = needed_indices.len())
+        )
+    )]
+    fn do_update_displayed_tiles(
+        &self,
+        needed_indices: Vec<WrappingTileIndex>,
+        style_id: StyleId,
+        now: web_time::Instant,
+    ) -> bool {
+        let record_timing = self.record_timing();
+        let mark = || record_timing.then(web_time::Instant::now);
+        let started = mark();
+
+        let mut displayed_tiles = self.tiles.write();
+        let mut evicted = self.evicted.lock();
+
+        // With the eager first frame, the first frame lasts until the coarsest tiles are loaded.
+        if !self.first_frame_done.load(Ordering::Relaxed)
+            && (!self.eager_first_frame()
+                || self.fetch_coarsest(&needed_indices, style_id, &mut displayed_tiles, now))
+        {
+            self.first_frame_done.store(true, Ordering::Relaxed);
+        }
 
+        let mut underlay = self.underlay.lock();
+        if self.coarse_underlay_until_loaded() && underlay.tiles.is_empty() {
+            if let Some(finest_z) = needed_indices.iter().map(|index| index.z).max() {
+                underlay.tiles = displayed_tiles
+                    .iter()
+                    .filter(|(key, tile)| {
+                        key.0.z < finest_z && !tile.is_hidden() && tile.is_opaque()
+                    })
+                    .map(|(key, _)| *key)
+                    .collect();
+                underlay.fade_out_since = None;
+            }
+        }
+
+        let mut bboxes = BboxCache::new(&self.tile_schema);
         let mut needed_tiles = vec![];
         let mut tile_indices = HashSet::default();
         let mut to_substitute = vec![];
 
-        let now = web_time::Instant::now();
-        let fade_in_time = self.fade_in_duration();
+        let animations_enabled = self.animations_enabled();
+        // While the animations are paused, their progress is calculated for the moment of the
+        // pause, and the fades alone do not require redraws.
+        let paused_at = *self.animations_paused_at.lock();
+        let paused = paused_at.is_some();
+        let fade_now = paused_at.map_or(now, |paused_at| paused_at.min(now));
+        let fade_in_time = if animations_enabled {
+            self.fade_in_duration()
+        } else {
+            Duration::ZERO
+        };
+        let fade_easing = self.fade_easing();
+        let fade_in_linear_space = self.fade_in_linear_space();
+        let max_substitution_scale = self.max_substitution_scale();
+        let substitute_opacity_falloff = self.substitute_opacity_falloff();
+        let on_tile_error = self.on_tile_error();
+        let on_tile_loaded = self.on_tile_loaded.lock();
+        let mut promotions = self.promotion_cap.budget();
         let mut requires_redraw = false;
+        let mut needed_count = 0;
+        let mut ready_count = 0;
+        let mut direct_hits = 0;
+        let mut requests_issued = 0;
+        let mut blanks = 0;
+
+        let prefetch = self.supported_indices(self.prefetch_indices(&needed_indices));
+        let needed_z = needed_indices.iter().map(|index| index.z).max();
+
+        // Failed tiles are not requested until their backoff is over. Then the provider is asked
+        // to forget the failure, so that the tile is loaded again.
+        let backing_off = self
+            .retry
+            .backing_off(&needed_indices, style_id, now, |index| {
+                if let Some(request_index) = self.filter_request(index) {
+                    self.tile_provider().retry(request_index.into(), style_id);
+                }
+            });
+
+        // All the missing tiles are requested from the provider with a single batch call.
+        let mut missing: Vec<(WrappingTileIndex, WrappingTileIndex)> = needed_indices
+            .iter()
+            .filter(|index| !displayed_tiles.contains_key(&(**index, style_id)))
+            .filter(|index| !backing_off.contains(*index))
+            .filter_map(|index| Some((*index, self.filter_request(*index)?)))
+            .collect();
+
+        // The tiles closest to the focus are requested first, so that they appear first.
+        if let Some(focus) = self.focus() {
+            sort_by_focus(&mut missing, focus, |(index, _)| *index, &mut bboxes);
+        }
+        let mut fetched: HashMap<WrappingTileIndex, TileState> = HashMap::default();
+        if !missing.is_empty() {
+            let request_indices: Vec<TileIndex> = missing
+                .iter()
+                .map(|(_, request_index)| (*request_index).into())
+                .collect();
+            requests_issued += request_indices.len() as u64;
+            let states = self.tile_provider().tile_states(&request_indices, style_id);
+            fetched = missing
+                .into_iter()
+                .map(|(index, _)| index)
+                .zip(states)
+                .collect();
+        }
 
         for index in needed_indices {
+            needed_count += 1;
+
             if let Some(mut displayed) = displayed_tiles.remove(&(index, style_id)) {
+                direct_hits += 1;
+                displayed.hidden_since = None;
+                if displayed.removing_at.take().is_some() {
+                    // Fading in again from the current opacity.
+                    let fade_in = fade_in_time.mul_f32(displayed.opacity);
+                    displayed.displayed_at = fade_now.checked_sub(fade_in).unwrap_or(fade_now);
+                }
                 if !displayed.is_opaque() {
-                    if let Some(bbox) = self.tile_schema.tile_bbox(index) {
-                        to_substitute.push(bbox);
+                    if let Some(bbox) = bboxes.get(index) {
+                        to_substitute.push((bbox, index, false));
                     }
 
+                    // Once the whole fade duration has passed, the tile is opaque regardless of
+                    // the rounding of the fade progress.
+                    let elapsed = fade_now.duration_since(displayed.displayed_at);
                     let fade_in_secs = fade_in_time.as_secs_f64();
-                    displayed.opacity = if fade_in_secs > 0.001 {
-                        ((now.duration_since(displayed.displayed_at)).as_secs_f64() / fade_in_secs)
-                            .min(1.0) as f32
+                    displayed.opacity = if fade_in_secs > 0.001 && elapsed < fade_in_time {
+                        let opacity =
+                            fade_easing.apply((elapsed.as_secs_f64() / fade_in_secs) as f32);
+                        if fade_in_linear_space {
+                            linear_to_srgb(opacity)
+                        } else {
+                            opacity
+                        }
                     } else {
                         1.0
                     };
-                    requires_redraw = true;
+
+                    // The tile is drawn with its final opacity in this frame, so it needs no more
+                    // redraws once it is opaque.
+                    requires_redraw |= !paused && !displayed.is_opaque();
+                }
+
+                if displayed.is_opaque() {
+                    ready_count += 1;
                 }
 
                 needed_tiles.push(displayed.clone());
                 tile_indices.insert((index, style_id));
             } else {
-                match self.tile_provider.get_tile(index.into(), style_id) {
-                    None => {
-                        if let Some(bbox) = self.tile_schema.tile_bbox(index) {
-                            to_substitute.push(bbox);
+                // The tiles that are not requested in this update are loading, unless they are
+                // waiting for a retry after a failure.
+                let is_backing_off = backing_off.contains(&index);
+                let state = match fetched.remove(&index) {
+                    _ if is_backing_off => TileState::Missing,
+                    Some(state) => state,
+                    None => TileState::Loading,
+                };
+                self.retry
+                    .record((index, style_id), &state, is_backing_off, now);
+
+                match state {
+                    TileState::Missing if !matches!(on_tile_error, ErrorPolicy::KeepSubstitute) => {
+                        // The tile will never be loaded, so it's as ready as it can be.
+                        ready_count += 1;
+                        match &on_tile_error {
+                            ErrorPolicy::ErrorTile(bundle) => {
+                                needed_tiles.push(DisplayedTile {
+                                    index,
+                                    bundle: bundle.clone(),
+                                    style_id,
+                                    opacity: 1.0,
+                                    displayed_at: now,
+                                    hidden_since: None,
+                                    removing_at: None,
+                                });
+                                tile_indices.insert((index, style_id));
+                                requires_redraw = true;
+                            }
+                            _ => blanks += 1,
                         }
                     }
-                    Some(bundle) => {
-                        let opacity = if self.requires_animation() { 0.0 } else { 1.0 };
+                    TileState::Loading | TileState::Missing => {
+                        if let Some(bbox) = bboxes.get(index) {
+                            to_substitute.push((bbox, index, true));
+                        }
+                    }
+                    TileState::Present(_) if promotions.is_exhausted() => {
+                        // The tile is ready, but is left pending until one of the next updates
+                        if let Some(bbox) = bboxes.get(index) {
+                            to_substitute.push((bbox, index, true));
+                        }
+
+                        requires_redraw = true;
+                    }
+                    TileState::Present(bundle) => {
+                        // A tile restored after it was dropped is faded in only if it replaces
+                        // some opaque tile, otherwise it would flicker on the cache bounce.
+                        let restored = evicted.remove(&(index, style_id));
+                        let fade_in = self.requires_animation()
+                            && (!restored
+                                || self.is_covered(
+                                    index,
+                                    displayed_tiles.values().chain(needed_tiles.iter()),
+                                ));
+                        let opacity = if fade_in { 0.0 } else { 1.0 };
+                        if opacity >= 1.0 {
+                            ready_count += 1;
+                        }
+
                         needed_tiles.push(DisplayedTile {
                             index,
                             bundle,
                             style_id,
                             opacity,
                             displayed_at: now,
+                            hidden_since: None,
+                            removing_at: None,
                         });
                         tile_indices.insert((index, style_id));
+                        promotions.promote();
+                        direct_hits += 1;
 
-                        if let Some(bbox) = self.tile_schema.tile_bbox(index) {
-                            to_substitute.push(bbox);
+                        if let Some(callback) = &*on_tile_loaded {
+                            callback(index, style_id);
+                        }
+
+                        if let Some(bbox) = bboxes.get(index) {
+                            to_substitute.push((bbox, index, false));
                         }
 
                         requires_redraw = true;
@@ -119,39 +824,1123 @@ where
             }
         }
 
+        // Tiles around the needed ones are requested so that the provider can prepare them in
+        // advance, but they are not displayed until they are needed.
+        for index in prefetch {
+            if displayed_tiles.contains_key(&(index, style_id)) {
+                continue;
+            }
+
+            if let Some(request_index) = self.filter_request(index) {
+                let _ = self
+                    .tile_provider()
+                    .tile_state(request_index.into(), style_id);
+            }
+        }
+
+        let gather_done = mark();
+        #[cfg(feature = "tracing")]
+        let substitution_started = web_time::Instant::now();
+        #[cfg(feature = "tracing")]
+        let mut intersection_checks = 0u64;
+
         let mut new_displayed = OrderedHashMap::new();
         let mut selected = Vec::with_capacity(displayed_tiles.len());
+        let mut lookup = TileLookup::new(&self.tile_schema, displayed_tiles.keys().copied());
+        let mut substituted = 0;
+
+        // Substitutes of full opacity are not made transparent later, so a single one of them
+        // covering the whole missing tile is enough to stop looking for others.
+        let early_termination =
+            self.early_substitution_termination() && substitute_opacity_falloff <= 0.0;
+        let mut opaque_covers: Vec<Rect> = vec![];
+
+        let max_parents = self.substitution_parents();
+        let ancestor_fallback = self.ancestor_fallback();
+        let max_children = self.substitution_children();
+        let within_depth = |displayed_z: u32, target_z: u32| {
+            if displayed_z < target_z {
+                target_z - displayed_z <= max_parents
+            } else {
+                displayed_z - target_z <= max_children
+            }
+        };
+
+        // Adjacent tiles share most of their candidates, so they are looked up together.
+        let tiles: Vec<_> = to_substitute
+            .iter()
+            .map(|(bbox, index, _)| (*bbox, *index))
+            .collect();
+        let candidates =
+            SubstitutionCandidates::new(&self.tile_schema, &lookup, &tiles, |z, subst_z| {
+                self.can_substitute(z, subst_z, max_substitution_scale) && within_depth(z, subst_z)
+            });
+
+        for (position, (subst_bbox, subst_index, is_missing)) in to_substitute.iter().enumerate() {
+            let subst_z = &subst_index.z;
+            let is_covered = |covers: &[Rect]| {
+                covers
+                    .iter()
+                    .any(|cover| is_occluded(*subst_bbox, std::slice::from_ref(cover)))
+            };
+
+            if early_termination && is_covered(&opaque_covers) {
+                if *is_missing {
+                    substituted += 1;
+                }
 
-        for subst_bbox in &to_substitute {
-            for key in displayed_tiles.keys() {
-                let Some(displayed_bbox) = self.tile_schema.tile_bbox(key.0) else {
+                continue;
+            }
+
+            let mut child_covers = vec![];
+            for key in candidates.get(position, *subst_bbox, &lookup, &mut bboxes) {
+                let Some(tile) = displayed_tiles
+                    .get(&key)
+                    .or_else(|| new_displayed.get(&key))
+                else {
+                    continue;
+                };
+
+                let Some(displayed_bbox) = bboxes.get(key.0) else {
                     continue;
                 };
 
-                if displayed_bbox.intersects(*subst_bbox) {
-                    selected.push(*key);
+                #[cfg(feature = "tracing")]
+                {
+                    intersection_checks += 1;
+                }
+
+                if overlaps(displayed_bbox, *subst_bbox) {
+                    selected.push(key);
+                    if key.0.z > *subst_z && tile.is_opaque() {
+                        child_covers.push(displayed_bbox);
+                    }
+
+                    if early_termination && tile.is_opaque() {
+                        opaque_covers.push(displayed_bbox);
+                        if is_covered(&opaque_covers[opaque_covers.len() - 1..]) {
+                            break;
+                        }
+                    }
+                }
+            }
+
+            // Finer tiles look better, so parents are not needed if the children cover the tile.
+            if !child_covers.is_empty() && is_occluded(*subst_bbox, &child_covers) {
+                selected.retain(|key| key.0.z >= *subst_z);
+            }
+
+            // Rather than leaving a hole, the deepest cached ancestor is displayed even if it is
+            // beyond the substitution limits. Finer tiles are drawn over it, so only the part
+            // covering the missing tile stays visible.
+            if *is_missing && selected.is_empty() && ancestor_fallback {
+                selected.extend(
+                    (0..*subst_z)
+                        .rev()
+                        .map(|z| (self.ancestor(*subst_index, z), style_id))
+                        .find(|key| {
+                            displayed_tiles.contains_key(key) || new_displayed.contains_key(key)
+                        }),
+                );
+            }
+
+            if *is_missing {
+                if selected.is_empty() {
+                    blanks += 1;
+                } else {
+                    substituted += 1;
                 }
             }
 
             for key in &selected {
-                let Some(tile) = displayed_tiles.remove(key) else {
+                let Some(mut tile) = displayed_tiles.remove(key) else {
                     continue;
                 };
 
+                tile.hidden_since = None;
+                if tile.removing_at.take().is_some() {
+                    tile.opacity = 1.0;
+                }
+
+                if substitute_opacity_falloff > 0.0 {
+                    let levels = subst_z.saturating_sub(key.0.z);
+                    tile.opacity = tile.opacity.min(self.substitute_opacity(levels));
+                }
+
                 new_displayed.insert(*key, tile);
+                lookup.moved(*key);
             }
 
             selected.clear();
         }
 
+        // After zooming in, coarse substitutes can be completely hidden under the opaque finer
+        // tiles while still intersecting some missing neighbour, so they are dropped explicitly.
+        let opaque_bboxes: Vec<Rect> = needed_tiles
+            .iter()
+            .filter(|tile| tile.is_opaque())
+            .filter_map(|tile| bboxes.get(tile.index))
+            .collect();
+        let redundant: Vec<_> = new_displayed
+            .keys()
+            .filter(|key| {
+                bboxes
+                    .get(key.0)
+                    .is_some_and(|bbox| is_occluded(bbox, &opaque_bboxes))
+            })
+            .copied()
+            .collect();
+        for key in redundant {
+            if let Some(tile) = new_displayed.remove(&key) {
+                displayed_tiles.insert(key, tile);
+            }
+        }
+
+        let substitution_done = mark();
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            intersection_checks,
+            elapsed_us = substitution_started.elapsed().as_micros() as u64,
+            "tile substitution"
+        );
+
+        // Underlay tiles stay visible until all the needed tiles are opaque, and then fade out.
+        if !underlay.tiles.is_empty() {
+            if needed_count > 0 && ready_count == needed_count && underlay.fade_out_since.is_none()
+            {
+                underlay.fade_out_since = Some(now);
+            }
+
+            let opacity = match underlay.fade_out_since {
+                None => 1.0,
+                Some(since) => {
+                    let fade_secs = fade_in_time.as_secs_f64();
+                    if fade_secs > 0.001 {
+                        1.0 - (fade_now.duration_since(since).as_secs_f64() / fade_secs).min(1.0)
+                            as f32
+                    } else {
+                        0.0
+                    }
+                }
+            };
+
+            for key in &underlay.tiles {
+                if let Some(mut tile) = displayed_tiles.remove(key) {
+                    tile.hidden_since = None;
+                    new_displayed.insert(*key, tile);
+                }
+            }
+
+            if opacity > 0.0 {
+                for key in &underlay.tiles {
+                    if let Some(tile) = new_displayed.get_mut(key) {
+                        tile.opacity = opacity;
+                    }
+                }
+
+                requires_redraw |= underlay.fade_out_since.is_some() && !paused;
+            } else {
+                for key in underlay.tiles.drain() {
+                    if let Some(tile) = new_displayed.remove(&key) {
+                        displayed_tiles.insert(key, tile);
+                    }
+                }
+
+                underlay.fade_out_since = None;
+            }
+        }
+
+        drop(underlay);
+
+        // During a style transition the tiles of the previous style stay under the new ones,
+        // fading out at the same rate the new tiles fade in.
+        let mut style_transition = self.style_transition.lock();
+        if style_transition
+            .as_ref()
+            .is_some_and(|transition| transition.to != style_id)
+        {
+            *style_transition = None;
+        }
+
+        if let Some(transition) = style_transition.as_mut() {
+            let from = transition.from;
+            let started_at = *transition.started_at.get_or_insert(now);
+            let fade_secs = fade_in_time.as_secs_f64();
+            let opacity = if fade_secs > 0.001 {
+                1.0 - fade_easing
+                    .apply((fade_now.duration_since(started_at).as_secs_f64() / fade_secs) as f32)
+            } else {
+                0.0
+            };
+
+            if opacity > 0.0 {
+                let fading: Vec<_> = displayed_tiles
+                    .iter()
+                    .filter(|(key, tile)| key.1 == from && !tile.is_hidden())
+                    .map(|(key, _)| *key)
+                    .collect();
+                for key in fading {
+                    if let Some(mut tile) = displayed_tiles.remove(&key) {
+                        tile.removing_at = None;
+                        new_displayed.insert(key, tile);
+                    }
+                }
+
+                for (key, tile) in new_displayed.iter_mut() {
+                    if key.1 == from {
+                        tile.opacity = tile.opacity.min(opacity);
+                    }
+                }
+
+                requires_redraw |= !paused;
+            } else {
+                // The old tiles are fully transparent now, so they are hidden without fading out
+                // again, even if they were selected as substitutes.
+                let faded: Vec<_> = new_displayed
+                    .keys()
+                    .filter(|key| key.1 == from)
+                    .copied()
+                    .collect();
+                for key in faded {
+                    if let Some(tile) = new_displayed.remove(&key) {
+                        displayed_tiles.insert(key, tile);
+                    }
+                }
+
+                for (key, tile) in displayed_tiles.iter_mut() {
+                    if key.1 == from && !tile.is_hidden() {
+                        tile.opacity = 1.0;
+                        tile.hidden_since = Some(now);
+                    }
+                }
+
+                *style_transition = None;
+            }
+        }
+
+        drop(style_transition);
+
+        let max_levels = self.max_displayed_levels();
+        if let Some(target_z) = needed_z.filter(|_| max_levels < usize::MAX) {
+            let mut levels: Vec<u32> = needed_tiles
+                .iter()
+                .map(|tile| tile.index.z)
+                .chain(new_displayed.keys().map(|key| key.0.z))
+                .collect();
+            levels.sort_unstable_by_key(|z| (z.abs_diff(target_z), *z));
+            levels.dedup();
+
+            if levels.len() > max_levels {
+                let dropped = &levels[max_levels..];
+                let to_drop: Vec<_> = new_displayed
+                    .keys()
+                    .filter(|key| dropped.contains(&key.0.z))
+                    .copied()
+                    .collect();
+                for key in to_drop {
+                    if let Some(tile) = new_displayed.remove(&key) {
+                        displayed_tiles.insert(key, tile);
+                    }
+                }
+            }
+        }
+
+        let fade_done = mark();
+
+        // Tiles that recently left the displayed set are kept hidden for some time, so that they
+        // can be reused without loading if the view returns back quickly. Pinned tiles are kept
+        // hidden until they are unpinned.
+        let fade_out_time = if animations_enabled {
+            self.fade_out_duration()
+        } else {
+            Duration::ZERO
+        };
+        let retention = self.retention.lock();
+        #[cfg(feature = "tracing")]
+        let mut evicted_count = 0u64;
+        let mut track_evicted = |key| {
+            if evicted.len() >= MAX_TRACKED_EVICTED {
+                evicted.clear();
+            }
+
+            evicted.insert(key);
+            #[cfg(feature = "tracing")]
+            {
+                evicted_count += 1;
+            }
+        };
+        for (key, mut tile) in displayed_tiles.drain() {
+            // Removed tiles are faded out first, staying behind the needed tiles.
+            if !fade_out_time.is_zero() && !tile.is_hidden() {
+                let removing_at = *tile.removing_at.get_or_insert(now);
+                let progress = fade_now.duration_since(removing_at).as_secs_f64()
+                    / fade_out_time.as_secs_f64();
+                let opacity = tile.opacity.min(1.0 - progress.min(1.0) as f32);
+                if opacity > 0.0 {
+                    tile.opacity = opacity;
+                    new_displayed.insert(key, tile);
+                    requires_redraw |= !paused;
+                    continue;
+                }
+
+                tile.removing_at = None;
+                tile.opacity = 1.0;
+            }
+
+            let hidden_since = *tile.hidden_since.get_or_insert(now);
+            if retention.keeps(&key, hidden_since, now) {
+                new_displayed.insert(key, tile);
+            } else {
+                track_evicted(key);
+            }
+        }
+
+        // Over the caps, the tiles that have been hidden for the longest time are dropped first.
+        self.eviction.enforce(
+            &mut new_displayed,
+            &needed_tiles,
+            &retention,
+            &mut track_evicted,
+        );
+
+        drop(retention);
+
         for tile in needed_tiles {
             new_displayed.insert((tile.index, tile.style_id), tile);
         }
+        if self.record_delta() {
+            self.update_delta(&new_displayed);
+        }
         *displayed_tiles = new_displayed;
+        drop(evicted);
+        drop(displayed_tiles);
+
+        if let (Some(started), Some(gather_done), Some(substitution_done), Some(fade_done)) =
+            (started, gather_done, substitution_done, fade_done)
+        {
+            *self.last_update_timing.lock() = Some(UpdateTiming {
+                gather: gather_done.duration_since(started),
+                substitution: substitution_done.duration_since(gather_done),
+                fade: fade_done.duration_since(substitution_done),
+                rebuild: web_time::Instant::now().duration_since(fade_done),
+            });
+        }
+
+        let metrics = &self.metrics;
+        metrics
+            .direct_hits
+            .fetch_add(direct_hits, Ordering::Relaxed);
+        metrics
+            .substituted
+            .fetch_add(substituted, Ordering::Relaxed);
+        self.last_substituted.store(substituted, Ordering::Relaxed);
+        metrics.blanks.fetch_add(blanks, Ordering::Relaxed);
+        metrics
+            .requests_issued
+            .fetch_add(requests_issued, Ordering::Relaxed);
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            loaded = promotions.promoted,
+            substituted,
+            blanks,
+            evicted = evicted_count,
+            "displayed tiles updated"
+        );
+
+        let is_fully_loaded = needed_count > 0 && ready_count == needed_count;
+        let was_fully_loaded = self.fully_loaded.swap(is_fully_loaded, Ordering::Relaxed);
+        if is_fully_loaded && !was_fully_loaded {
+            if let Some(callback) = &*self.on_fully_loaded.lock() {
+                callback();
+            }
+        }
 
         requires_redraw
     }
 
+    /// Returns the tiles needed to display the given view, as supported by the tile provider.
+    ///
+    /// If pan tracking is enabled, the position of the view in the tile grid is remembered, and
+    /// while the view moves within the same tiles the previously computed set is returned as is.
+    /// This way slow continuous pans do not rebuild the needed set every frame.
+    pub(crate) fn needed_indices(&self, view: &MapView) -> Option<Vec<WrappingTileIndex>> {
+        let range = self.tile_schema.tile_range(view)?;
+        Some(self.needed_in_range(range, || self.tile_schema.iter_tile_range(range)))
+    }
+
+    /// Returns the needed tiles for the tile `range` of a view. `tiles` gives all the tiles of the
+    /// range, and is called only if the needed tiles are not kept from the previous call.
+    fn needed_in_range<I>(
+        &self,
+        range: TileRange,
+        tiles: impl FnOnce() -> I,
+    ) -> Vec<WrappingTileIndex>
+    where
+        I: IntoIterator<Item = WrappingTileIndex>,
+    {
+        let track_pan = self.track_pan();
+        if track_pan {
+            if let Some(tracker) = &*self.pan_tracker.lock() {
+                if tracker.range == range {
+                    return tracker.needed.clone();
+                }
+            }
+        }
+
+        let needed = self.supported_indices(tiles());
+        if track_pan {
+            *self.pan_tracker.lock() = Some(PanTracker {
+                range,
+                needed: needed.clone(),
+            });
+        }
+
+        needed
+    }
+
+    /// Makes the next update for the same needed tiles reuse the result of the last update
+    /// whenever it happens, so that the tiles updated ahead of the rendering are rendered as they
+    /// were updated.
+    fn hold_last_update(&self) {
+        if let Some(last_update) = &mut *self.last_update.lock() {
+            last_update.held = true;
+        }
+    }
+
+    /// Drops the needed tiles kept while the view stays within the same tiles. Must be called
+    /// every time a setting that changes the needed tiles for a view is changed.
+    fn reset_pan_tracker(&self) {
+        *self.pan_tracker.lock() = None;
+    }
+
+    /// Replaces the indices with the ones the tile provider has tiles for, removing duplicates.
+    ///
+    /// Indices of the levels finer than the provider supports are replaced by their ancestors at
+    /// the finest supported level. Indices of the levels coarser than the provider supports are
+    /// skipped. If overzoom is enabled, indices of the levels finer than the schema has are
+    /// replaced by their ancestors at the finest level of the schema.
+    pub(crate) fn supported_indices(
+        &self,
+        indices: impl IntoIterator<Item = WrappingTileIndex>,
+    ) -> Vec<WrappingTileIndex> {
+        let displayed_zooms = self.min_zoom()..=self.max_zoom();
+        let indices = indices
+            .into_iter()
+            .filter(move |index| displayed_zooms.contains(&index.z));
+
+        let range = self.tile_provider().supported_zoom_range();
+        let overzoom = self.overzoom();
+        if range.is_none() && !overzoom {
+            return indices.collect();
+        }
+
+        let mut found = HashSet::default();
+        indices
+            .map(|index| {
+                if overzoom {
+                    self.overzoom_index(index)
+                } else {
+                    index
+                }
+            })
+            .filter_map(|index| match &range {
+                Some(range) => self.clamp_to_range(index, range),
+                None => Some(index),
+            })
+            .filter(|index| found.insert(*index))
+            .collect()
+    }
+
+    fn overzoom_index(&self, index: WrappingTileIndex) -> WrappingTileIndex {
+        let target_z = self.tile_schema.clamp_zoom_for_display(index.z);
+        if target_z < index.z {
+            self.ancestor(index, target_z)
+        } else {
+            index
+        }
+    }
+
+    fn clamp_to_range(
+        &self,
+        index: WrappingTileIndex,
+        range: &RangeInclusive<u32>,
+    ) -> Option<WrappingTileIndex> {
+        if index.z < *range.start() {
+            return None;
+        }
+
+        if index.z <= *range.end() {
+            return Some(index);
+        }
+
+        let target_z = *range.end();
+        self.tile_schema.lod_resolution(target_z)?;
+
+        Some(self.ancestor(index, target_z))
+    }
+
+    /// Returns the tile of the coarser level `target_z` that contains the tile `index`, keeping
+    /// the wrapping of the index.
+    fn ancestor(&self, index: WrappingTileIndex, target_z: u32) -> WrappingTileIndex {
+        let actual = self.tile_schema.index_at_level(index.into(), target_z);
+        let display = self
+            .tile_schema
+            .index_at_level(TileIndex::new(index.display_x, index.y, index.z), target_z);
+
+        WrappingTileIndex {
+            x: actual.x,
+            y: actual.y,
+            z: target_z,
+            display_x: display.x,
+        }
+    }
+
+    /// Adds the tiles of the coarsest level covering the `needed` ones to the displayed set, so
+    /// they are used as substitutes while the needed tiles are loading. Returns true if all of
+    /// them are displayed.
+    fn fetch_coarsest(
+        &self,
+        needed: &[WrappingTileIndex],
+        style_id: StyleId,
+        displayed_tiles: &mut OrderedHashMap<(WrappingTileIndex, StyleId), DisplayedTile<StyleId>>,
+        now: web_time::Instant,
+    ) -> bool {
+        let mut all_displayed = true;
+        for coarse in self.coarsest_ancestors(needed) {
+            if displayed_tiles.contains_key(&(coarse, style_id)) {
+                continue;
+            }
+
+            let bundle = self.filter_request(coarse).and_then(|request| {
+                self.tile_provider()
+                    .fetch_tile_now(request.into(), style_id)
+            });
+            if let Some(bundle) = bundle {
+                displayed_tiles.insert(
+                    (coarse, style_id),
+                    DisplayedTile {
+                        index: coarse,
+                        bundle,
+                        style_id,
+                        opacity: 1.0,
+                        displayed_at: now,
+                        hidden_since: None,
+                        removing_at: None,
+                    },
+                );
+            } else {
+                all_displayed = false;
+            }
+        }
+
+        all_displayed
+    }
+
+    /// Returns the tiles of the coarsest level of the schema covering the `needed` tiles, without
+    /// duplicates.
+    pub(crate) fn coarsest_ancestors(
+        &self,
+        needed: &[WrappingTileIndex],
+    ) -> Vec<WrappingTileIndex> {
+        let Some(coarsest_z) = self.tile_schema.min_z() else {
+            return vec![];
+        };
+
+        let mut ancestors = vec![];
+        for index in needed {
+            let coarse = self.ancestor(*index, coarsest_z);
+            if !ancestors.contains(&coarse) {
+                ancestors.push(coarse);
+            }
+        }
+
+        ancestors
+    }
+
+    /// Returns true if the eager first frame is enabled and the coarsest tiles have not been
+    /// displayed yet.
+    pub(crate) fn is_first_frame_pending(&self) -> bool {
+        self.eager_first_frame() && !self.first_frame_done.load(Ordering::Relaxed)
+    }
+
+    /// Returns all the tiles stored in the container, including the hidden ones.
+    ///
+    /// The returned guard only blocks the updates of the container, so several readers (e.g.
+    /// render threads drawing the tiles) can hold it at the same time.
+    pub(crate) fn tiles(
+        &self,
+    ) -> RwLockReadGuard<'_, OrderedHashMap<(WrappingTileIndex, StyleId), DisplayedTile<StyleId>>>
+    {
+        self.tiles.read()
+    }
+
+    /// Returns the tiles that should be drawn, in the drawing order.
+    ///
+    /// Tiles that are not visible (hidden ones and the ones with zero opacity) are skipped, but are
+    /// kept in the container.
+    pub(crate) fn displayed_tiles(&self) -> Vec<DisplayedTile<StyleId>> {
+        self.tiles()
+            .values()
+            .filter(|tile| !tile.is_hidden() && tile.opacity > 0.0)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the visible tiles in the order they should be drawn: from the coarsest level to the
+    /// finest one, so that the substitutes are always behind the more detailed tiles. Tiles of the
+    /// same level are ordered by column and then by row.
+    pub(crate) fn tiles_to_draw(&self) -> Vec<DisplayedTile<StyleId>> {
+        let mut tiles = self.displayed_tiles();
+        tiles.sort_by_key(|tile| (tile.index.z, tile.index.display_x, tile.index.y));
+        tiles
+    }
+
+    /// Returns the visible tiles with the opacity they should be drawn with (including the layer
+    /// opacity), in the draw order of [`TilesContainer::tiles_to_draw`].
+    ///
+    /// The tiles are copied out of the container under a short lock, so the returned list can be
+    /// rendered without blocking the updates of the container.
+    pub fn collect_renderables(&self) -> Vec<(WrappingTileIndex, Arc<dyn PackedBundle>, f32)> {
+        self.tiles_to_draw()
+            .into_iter()
+            .map(|tile| (tile.index, tile.bundle.clone(), self.draw_opacity(&tile)))
+            .collect()
+    }
+
+    /// Returns the tile of the level `z` at the center of the view, if it is currently displayed.
+    pub fn center_tile(&self, view_center: Point2, z: u32) -> Option<WrappingTileIndex> {
+        let index = self.tile_schema.tile_at_point(view_center, z)?;
+        self.tiles()
+            .values()
+            .any(|tile| tile.index == index && !tile.is_hidden() && tile.opacity > 0.0)
+            .then_some(index)
+    }
+
+    /// Returns the instant at which the last of the currently running fade-in animations
+    /// completes, or `None` if no displayed tile is fading in.
+    pub fn next_fade_deadline(&self) -> Option<web_time::Instant> {
+        if self.animations_paused() {
+            return None;
+        }
+
+        let fade_in_time = self.fade_in_duration();
+        self.tiles()
+            .values()
+            .filter(|tile| !tile.is_hidden() && !tile.is_opaque())
+            .map(|tile| tile.displayed_at + fade_in_time)
+            .max()
+    }
+
+    /// Returns true if any displayed tile is still fading in or out at the current time of the
+    /// clock, so the layer has to be redrawn to advance the animation.
+    ///
+    /// Unlike [`TilesContainer::update_displayed_tiles`] this does not change the container, so a
+    /// render loop can check it to decide whether to request more frames. Always false while the
+    /// animations are paused.
+    pub fn is_animating(&self) -> bool {
+        if self.animations_paused() {
+            return false;
+        }
+
+        let now = self.now();
+        let fade_in_time = self.fade_in_duration();
+        let fade_out_time = self.fade_out_duration();
+        self.tiles()
+            .values()
+            .filter(|tile| !tile.is_hidden())
+            .any(|tile| match tile.removing_at {
+                Some(removing_at) => now < removing_at + fade_out_time,
+                None => !tile.is_opaque() && now < tile.displayed_at + fade_in_time,
+            })
+    }
+
+    /// Returns the total size in bytes of all the bundles stored in the container, including the
+    /// hidden ones. Bundles shared between several wrapped copies of a tile are counted once.
+    pub fn memory_usage(&self) -> u64 {
+        let mut counted = HashSet::default();
+        self.tiles()
+            .values()
+            .filter(|tile| counted.insert(Arc::as_ptr(&tile.bundle) as *const () as usize))
+            .map(|tile| tile.bundle.size_bytes())
+            .sum()
+    }
+
+    /// Returns the tiles that would have to be dropped from the container, in the order of
+    /// dropping, for its [memory usage](TilesContainer::memory_usage) to become not larger than
+    /// `target_bytes`. The container itself is not changed.
+    ///
+    /// Hidden tiles are dropped first, starting from the ones hidden the longest time ago, and
+    /// then the displayed ones. Pinned tiles are never included, so the returned plan may be not
+    /// enough to reach the target.
+    pub fn eviction_plan(&self, target_bytes: u64) -> Vec<(WrappingTileIndex, StyleId)> {
+        Eviction::plan(&self.tiles(), &self.retention.lock(), target_bytes)
+    }
+
+    /// Returns the counters of how the needed tiles were served, accumulated over all updates.
+    ///
+    /// Updates skipped as identical to the previous one in the same frame are not counted.
+    pub fn metrics(&self) -> TilesMetrics {
+        let metrics = &self.metrics;
+        TilesMetrics {
+            direct_hits: metrics.direct_hits.load(Ordering::Relaxed),
+            substituted: metrics.substituted.load(Ordering::Relaxed),
+            blanks: metrics.blanks.load(Ordering::Relaxed),
+            requests_issued: metrics.requests_issued.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the counts of the displayed tiles. Cheap enough to be called every frame.
+    pub fn stats(&self) -> TileCacheStats {
+        let tiles = self.tiles();
+        let mut styles = HashSet::default();
+        let mut stats = TileCacheStats {
+            substituted: self.last_substituted.load(Ordering::Relaxed),
+            ..Default::default()
+        };
+
+        for tile in tiles
+            .values()
+            .filter(|tile| !tile.is_hidden() && tile.opacity > 0.0)
+        {
+            stats.displayed += 1;
+            if !tile.is_opaque() {
+                stats.fading += 1;
+            }
+            styles.insert(tile.style_id);
+        }
+
+        stats.styles = styles.len();
+        stats
+    }
+
+    /// Returns the index, style and opacity of every visible tile in the order they are drawn.
+    ///
+    /// The order does not depend on hashing, so the snapshot can be compared to the expected
+    /// value in tests.
+    #[cfg(feature = "testing")]
+    pub fn displayed_snapshot(&self) -> Vec<(WrappingTileIndex, StyleId, f32)> {
+        self.tiles_to_draw()
+            .into_iter()
+            .map(|tile| (tile.index, tile.style_id, tile.opacity))
+            .collect()
+    }
+
+    /// Replaces the content of the container with the given tiles, bypassing the tile provider.
+    ///
+    /// Every tile is set as displayed with the given opacity. Tiles that are not fully opaque
+    /// continue fading in from that opacity at the next updates.
+    #[cfg(feature = "testing")]
+    pub fn inject_displayed(
+        &self,
+        tiles: Vec<(WrappingTileIndex, StyleId, Arc<dyn PackedBundle>, f32)>,
+    ) {
+        let now = self.now();
+        let fade_in_time = self.fade_in_duration();
+        let mut displayed_tiles = self.tiles.write();
+        displayed_tiles.clear();
+
+        for (index, style_id, bundle, opacity) in tiles {
+            let opacity = opacity.clamp(0.0, 1.0);
+            let elapsed = fade_in_time.mul_f32(opacity);
+            displayed_tiles.insert(
+                (index, style_id),
+                DisplayedTile {
+                    index,
+                    bundle,
+                    style_id,
+                    opacity,
+                    displayed_at: now.checked_sub(elapsed).unwrap_or(now),
+                    hidden_since: None,
+                    removing_at: None,
+                },
+            );
+        }
+
+        drop(displayed_tiles);
+        self.invalidate_last_update();
+    }
+
+    /// Removes all tiles from the container, including the hidden and fading out ones, so that
+    /// they are requested from the tile provider again at the next update.
+    ///
+    /// Returns true if any tile was removed and the layer should be redrawn.
+    pub fn clear(&self) -> bool {
+        self.remove_tiles(|_| true)
+    }
+
+    /// Removes the tiles of the given style from the container. Tiles of other styles are kept.
+    ///
+    /// Returns true if any tile was removed and the layer should be redrawn.
+    pub fn invalidate_style(&self, style_id: StyleId) -> bool {
+        self.remove_tiles(|key| key.1 == style_id)
+    }
+
+    /// Removes the tile with the given index of all styles from the container, so that it is
+    /// requested from the tile provider again at the next update.
+    ///
+    /// Returns true if any tile was removed and the layer should be redrawn.
+    pub fn invalidate_index(&self, index: WrappingTileIndex) -> bool {
+        self.remove_tiles(|key| key.0 == index)
+    }
+
+    /// Removes the tiles with matching keys without fading them out.
+    fn remove_tiles(&self, matches: impl Fn(&(WrappingTileIndex, StyleId)) -> bool) -> bool {
+        let mut tiles = self.tiles.write();
+        let to_remove: Vec<_> = tiles.keys().filter(|key| matches(key)).copied().collect();
+        for key in &to_remove {
+            tiles.remove(key);
+        }
+        drop(tiles);
+
+        // Removed tiles are not restored from a cache bounce, and must not be kept as underlay.
+        self.evicted.lock().retain(|key| !matches(key));
+        self.retry.forget(&matches);
+        self.underlay.lock().tiles.retain(|key| !matches(key));
+
+        self.invalidate_last_update();
+        !to_remove.is_empty()
+    }
+
+    /// Protects the tile from being dropped from the container when it's no longer displayed.
+    ///
+    /// A tile that is not loaded yet is kept after it's loaded and displayed for the first time.
+    pub fn pin(&self, index: WrappingTileIndex, style_id: StyleId) {
+        self.retention.pin((index, style_id));
+    }
+
+    /// Removes the protection set by [`TilesContainer::pin`]. The tile is dropped at one of the
+    /// next updates if it's not displayed.
+    pub fn unpin(&self, index: WrappingTileIndex, style_id: StyleId) {
+        self.retention.unpin((index, style_id));
+    }
+
+    /// Starts a cross-fade from the tiles of the `from` style to the tiles of the `to` style.
+    ///
+    /// While the tiles of the `to` style are displayed, the tiles of the `from` style are kept
+    /// under them and fade out over the fade in duration. The transition is cancelled if the
+    /// tiles are updated with any other style.
+    pub fn begin_style_transition(&self, from: StyleId, to: StyleId) {
+        *self.style_transition.lock() = Some(StyleTransition {
+            from,
+            to,
+            started_at: None,
+        });
+        self.invalidate_last_update();
+    }
+
+    /// Returns true if at the last update all needed tiles were loaded and fully opaque.
+    pub fn is_fully_loaded(&self) -> bool {
+        self.fully_loaded.load(Ordering::Relaxed)
+    }
+
+    /// Sets the callback that is called every time all the needed tiles become loaded and fully
+    /// opaque after the view was only partially loaded.
+    pub fn set_on_fully_loaded(&self, callback: Option<FullyLoadedCallback>) {
+        *self.on_fully_loaded.lock() = callback;
+    }
+
+    /// Returns the current time of the clock of the container.
+    pub(crate) fn now(&self) -> web_time::Instant {
+        self.clock.lock().now()
+    }
+
+    /// Sets the clock the fade animations of the tiles are driven by. [`SystemClock`] is used by
+    /// default.
+    pub fn set_clock(&self, clock: Arc<dyn Clock>) {
+        *self.clock.lock() = clock;
+        self.invalidate_last_update();
+    }
+
+    /// Sets the callback that is called every time a newly loaded tile is added to the displayed
+    /// tiles. It is not called for the tiles that were already displayed or are used as substitutes.
+    ///
+    /// The callback is called during the update while the tiles lock is held, so it must be cheap
+    /// and must not access the container.
+    pub fn set_on_tile_loaded(&self, callback: Option<TileLoadedCallback<StyleId>>) {
+        *self.on_tile_loaded.lock() = callback;
+    }
+
+    /// Sets the function that is applied to every tile index before the tile is requested from the
+    /// tile provider.
+    ///
+    /// If the function returns `None`, the tile is not requested and is treated as missing.
+    /// If it returns a different index, the tile of that index is requested and displayed in place
+    /// of the original one.
+    pub fn set_request_filter(&self, filter: Option<RequestFilter>) {
+        *self.request_filter.lock() = filter;
+        self.invalidate_last_update();
+    }
+
+    /// Sets what is displayed in place of the tiles the tile provider failed to load.
+    pub fn set_on_tile_error(&self, policy: ErrorPolicy) {
+        *self.on_tile_error.lock() = policy;
+        self.invalidate_last_update();
+    }
+
+    /// Returns what is displayed in place of the tiles the tile provider failed to load.
+    pub fn on_tile_error(&self) -> ErrorPolicy {
+        self.on_tile_error.lock().clone()
+    }
+
+    /// Sets the delay before the first retry of a tile that failed to load. The delay is doubled
+    /// after every next failure of the same tile up to the
+    /// [maximum](TilesContainer::set_max_retry_backoff). Zero disables retries, which is the
+    /// default.
+    ///
+    /// While a tile is waiting for the retry it is not requested from the tile provider and is
+    /// treated as failed. A successful load resets the backoff of the tile.
+    pub fn set_retry_backoff(&self, backoff: Duration) {
+        self.retry.set_backoff(backoff);
+        self.invalidate_last_update();
+    }
+
+    /// Sets the interval a missing tile must stay needed for before it is loaded. `None` by
+    /// default, in which case the tiles are loaded immediately.
+    ///
+    /// During fast panning or pinch-zooming the needed tiles change every frame, and most of them
+    /// are not needed anymore by the time they are loaded. With the debounce such tiles are never
+    /// loaded. Only the loads are deferred, see [`TilesContainer::defer_requests`]: the tiles
+    /// the provider already has are displayed without delay.
+    pub fn set_request_debounce(&self, debounce: Option<Duration>) {
+        self.debounce.set_interval(debounce);
+        self.invalidate_last_update();
+    }
+
+    /// Removes from the `requests` the tiles that have not been requested for the whole
+    /// [debounce](TilesContainer::set_request_debounce) interval yet, and returns true if any
+    /// tile was removed.
+    ///
+    /// Must be called with all the tiles to load every time the tiles are loaded, before starting
+    /// the loads. The tiles that are not requested anymore are forgotten, so the tiles passed by
+    /// during fast panning are never loaded.
+    pub(crate) fn defer_requests(
+        &self,
+        requests: &mut Vec<WrappingTileIndex>,
+        style_id: StyleId,
+        now: web_time::Instant,
+    ) -> bool {
+        self.debounce.defer(requests, style_id, now)
+    }
+
+    /// Sets the maximum delay between retries of a tile that failed to load. Defaults to 5
+    /// minutes.
+    pub fn set_max_retry_backoff(&self, backoff: Duration) {
+        self.retry.set_max_backoff(backoff);
+    }
+
+    /// Returns the index that should be requested from the tile provider for the tile `index`, or
+    /// `None` if the tile should not be requested.
+    pub(crate) fn filter_request(&self, index: WrappingTileIndex) -> Option<WrappingTileIndex> {
+        let index = match &*self.request_filter.lock() {
+            Some(filter) => filter(index)?,
+            None => index,
+        };
+
+        let z = u32::try_from(index.z as i64 + self.zoom_offset() as i64).ok()?;
+        Some(WrappingTileIndex { z, ..index })
+    }
+
+    /// Difference between the z-levels of the tiles requested from the tile provider and the
+    /// z-levels of the schema.
+    pub fn zoom_offset(&self) -> i32 {
+        self.zoom_offset.load(Ordering::Relaxed)
+    }
+
+    /// Changes the difference between the z-levels of the tiles requested from the tile provider
+    /// and the z-levels of the schema.
+    ///
+    /// Tiles in the container are kept by their schema indices, so they stay valid and are not
+    /// reloaded. Only the tiles requested after this call use the new offset. Requests for the
+    /// levels that would become negative are skipped.
+    pub fn rekey_for_offset(&self, offset: i32) {
+        self.zoom_offset.store(offset, Ordering::Relaxed);
+        self.reset_pan_tracker();
+        self.invalidate_last_update();
+    }
+
+    /// Whether the time spent in the phases of every update is measured.
+    pub fn record_timing(&self) -> bool {
+        self.record_timing.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables measuring the time spent in the phases of every update. Disabled by
+    /// default, in which case no time is measured at all.
+    pub fn set_record_timing(&self, enabled: bool) {
+        self.record_timing.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            *self.last_update_timing.lock() = None;
+        }
+    }
+
+    /// Returns the time spent in the phases of the last update, if recording of the timing is
+    /// enabled and there was an update since then.
+    pub fn last_update_timing(&self) -> Option<UpdateTiming> {
+        *self.last_update_timing.lock()
+    }
+
+    /// Whether the changes of the displayed set are recorded at every update.
+    pub fn record_delta(&self) -> bool {
+        self.record_delta.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables recording of the tiles added to and removed from the displayed set at
+    /// every update. Disabled by default. The first recorded delta is relative to the tiles
+    /// displayed at the moment the recording is enabled.
+    pub fn set_record_delta(&self, enabled: bool) {
+        self.record_delta.store(enabled, Ordering::Relaxed);
+        let mut delta_displayed = self.delta_displayed.lock();
+        *delta_displayed = if enabled {
+            self.tiles()
+                .iter()
+                .filter(|(_, tile)| !tile.is_hidden())
+                .map(|(key, _)| *key)
+                .collect()
+        } else {
+            HashSet::default()
+        };
+        *self.last_delta.lock() = None;
+    }
+
+    /// Returns the tiles added to and removed from the displayed set at the last update, if
+    /// recording of the delta is enabled and there was an update since then.
+    ///
+    /// Both lists are ordered by z-level, column and row.
+    pub fn last_delta(&self) -> Option<TileDelta<StyleId>> {
+        self.last_delta.lock().clone()
+    }
+
+    /// Compares the displayed tiles of the new set with the ones displayed after the previous
+    /// update and stores the difference as the last delta.
+    fn update_delta(
+        &self,
+        tiles: &OrderedHashMap<(WrappingTileIndex, StyleId), DisplayedTile<StyleId>>,
+    ) {
+        let mut previous = self.delta_displayed.lock();
+        let displayed: HashSet<_> = tiles
+            .iter()
+            .filter(|(_, tile)| !tile.is_hidden())
+            .map(|(key, _)| *key)
+            .collect();
+
+        let order = |key: &(WrappingTileIndex, StyleId)| (key.0.z, key.0.display_x, key.0.y);
+        let mut added: Vec<_> = displayed
+            .iter()
+            .filter(|key| !previous.contains(key))
+            .copied()
+            .collect();
+        added.sort_by_key(order);
+        let mut removed: Vec<_> = previous
+            .iter()
+            .filter(|key| !displayed.contains(key))
+            .copied()
+            .collect();
+        removed.sort_by_key(order);
+
+        *previous = displayed;
+        *self.last_delta.lock() = Some(TileDelta { added, removed });
+    }
+
     pub fn fade_in_duration(&self) -> Duration {
         Duration::from_millis(self.fade_in_duration.load(Ordering::Relaxed))
     }
@@ -159,9 +1948,3425 @@ where
     pub fn set_fade_in_duration(&self, duration: Duration) {
         self.fade_in_duration
             .store(duration.as_millis() as u64, Ordering::Relaxed);
+        self.invalidate_last_update();
     }
 
-    fn requires_animation(&self) -> bool {
-        self.fade_in_duration.load(Ordering::Relaxed) > 1
+    /// Curve of the opacity change during the fade in of the tiles. Linear by default.
+    pub fn fade_easing(&self) -> Easing {
+        *self.fade_easing.lock()
+    }
+
+    pub fn set_fade_easing(&self, easing: Easing) {
+        *self.fade_easing.lock() = easing;
+        self.invalidate_last_update();
+    }
+
+    /// If true, the fade in progress is treated as the coverage in linear light, and converted to
+    /// the opacity of blending in sRGB space, which is how the canvases blend the bundles.
+    ///
+    /// Without this the tiles fading in over a dark background look too dark in the middle of the
+    /// fade. The conversion is exact for blending over black and approximate for other colors.
+    /// Disabled by default.
+    pub fn fade_in_linear_space(&self) -> bool {
+        self.fade_in_linear_space.load(Ordering::Relaxed)
+    }
+
+    pub fn set_fade_in_linear_space(&self, enabled: bool) {
+        self.fade_in_linear_space.store(enabled, Ordering::Relaxed);
+        self.invalidate_last_update();
+    }
+
+    /// Time during which the tiles removed from the displayed set are faded out. Zero by default,
+    /// so the tiles disappear immediately.
+    pub fn fade_out_duration(&self) -> Duration {
+        Duration::from_millis(self.fade_out_duration.load(Ordering::Relaxed))
+    }
+
+    pub fn set_fade_out_duration(&self, duration: Duration) {
+        self.fade_out_duration
+            .store(duration.as_millis() as u64, Ordering::Relaxed);
+        self.invalidate_last_update();
+    }
+
+    /// If false, fade-in and fade-out animations are skipped: new tiles are fully opaque as soon
+    /// as they are displayed, and removed tiles disappear immediately. Useful for rendering
+    /// snapshots that must not depend on the timing of the updates. True by default.
+    pub fn animations_enabled(&self) -> bool {
+        self.animations_enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_animations_enabled(&self, enabled: bool) {
+        self.animations_enabled.store(enabled, Ordering::Relaxed);
+        self.invalidate_last_update();
+    }
+
+    /// Returns true if the fade animations are paused.
+    pub fn animations_paused(&self) -> bool {
+        self.animations_paused_at.lock().is_some()
+    }
+
+    /// Pauses or resumes the fade animations, e.g. while the application is in the background.
+    ///
+    /// While paused, the tiles keep their current opacity and the updates do not request redraws
+    /// only to advance the fades. Tiles loaded during the pause stay transparent. After resuming,
+    /// the fades continue from where they were paused.
+    pub fn set_animations_paused(&self, paused: bool) {
+        let now = self.now();
+        let mut paused_at = self.animations_paused_at.lock();
+        match (*paused_at, paused) {
+            (None, true) => *paused_at = Some(now),
+            (Some(since), false) => {
+                *paused_at = None;
+                drop(paused_at);
+                self.resume_animations(since, now);
+            }
+            _ => return,
+        }
+
+        self.invalidate_last_update();
+    }
+
+    /// Shifts the start times of the fades by the duration of the pause, so that their progress
+    /// stays the same as at the moment of the pause.
+    fn resume_animations(&self, paused_at: web_time::Instant, now: web_time::Instant) {
+        let pause = now.duration_since(paused_at);
+        let shift = |time: web_time::Instant| {
+            if time < paused_at {
+                time + pause
+            } else {
+                now
+            }
+        };
+
+        for (_, tile) in self.tiles.write().iter_mut() {
+            tile.displayed_at = shift(tile.displayed_at);
+            tile.removing_at = tile.removing_at.map(shift);
+        }
+
+        let mut underlay = self.underlay.lock();
+        underlay.fade_out_since = underlay.fade_out_since.map(shift);
+        drop(underlay);
+
+        if let Some(transition) = self.style_transition.lock().as_mut() {
+            transition.started_at = transition.started_at.map(shift);
+        }
+    }
+
+    /// Sets the maximum number of newly loaded tiles that are added to the displayed set in one
+    /// update.
+    ///
+    /// Tiles over the limit are left pending and are added during the next updates, which
+    /// smooths out spikes of GPU uploads when many tiles become ready at once.
+    pub fn set_max_promotions_per_frame(&self, max_promotions: usize) {
+        self.promotion_cap.set_max_per_update(max_promotions);
+        self.invalidate_last_update();
+    }
+
+    /// Sets the minimum time a tile is kept in the container after it's no longer displayed.
+    pub fn set_min_retention(&self, duration: Duration) {
+        self.retention.set_min_retention(duration);
+        self.invalidate_last_update();
+    }
+
+    /// Maximum ratio between resolutions of a displayed tile and a missing tile, for the displayed
+    /// tile to be kept as a substitute for the missing one.
+    ///
+    /// Substituting with tiles of very different resolution (for example, in schemas with
+    /// irregular level spacing) looks bad, so such tiles are dropped instead. Unlimited by default.
+    pub fn max_substitution_scale(&self) -> f64 {
+        f64::from_bits(self.max_substitution_scale.load(Ordering::Relaxed))
+    }
+
+    pub fn set_max_substitution_scale(&self, scale: f64) {
+        self.max_substitution_scale
+            .store(scale.to_bits(), Ordering::Relaxed);
+        self.invalidate_last_update();
+    }
+
+    /// Sets the maximum number of tiles kept in the container.
+    ///
+    /// When the limit is exceeded, the tiles that were not displayed for the longest time are
+    /// dropped. The needed tiles, their substitutes and the pinned tiles are never dropped, so the
+    /// number of tiles can still be greater than the limit. Unlimited by default.
+    pub fn set_max_cached_tiles(&self, max_tiles: usize) {
+        self.eviction.set_max_tiles(max_tiles);
+        self.invalidate_last_update();
+    }
+
+    /// Sets the maximum total size in bytes of the bundles kept in the container, as reported by
+    /// [`PackedBundle::size_bytes`].
+    ///
+    /// When the limit is exceeded, the tiles that were not displayed for the longest time are
+    /// dropped, same as with [`TilesContainer::set_max_cached_tiles`]. Bundles that don't report
+    /// their size are counted as free, so the limit has no effect with the rendering backends
+    /// that don't implement the size estimation. Unlimited by default.
+    pub fn set_max_cache_bytes(&self, max_bytes: u64) {
+        self.eviction.set_max_bytes(max_bytes);
+        self.invalidate_last_update();
+    }
+
+    /// Maximum number of z-levels displayed at the same time.
+    ///
+    /// When substitutes of many levels are displayed (for example, during fast zooming), only the
+    /// levels closest to the level of the needed tiles are kept. Unlimited by default.
+    pub fn max_displayed_levels(&self) -> usize {
+        self.max_displayed_levels.load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_displayed_levels(&self, max_levels: usize) {
+        self.max_displayed_levels
+            .store(max_levels, Ordering::Relaxed);
+        self.invalidate_last_update();
+    }
+
+    /// Maximum number of levels a substitute tile can be coarser than the missing one.
+    ///
+    /// Unlimited by default.
+    pub fn substitution_parents(&self) -> u32 {
+        self.substitution_parents.load(Ordering::Relaxed)
+    }
+
+    pub fn set_substitution_parents(&self, levels: u32) {
+        self.substitution_parents.store(levels, Ordering::Relaxed);
+        self.invalidate_last_update();
+    }
+
+    /// Maximum number of levels a substitute tile can be finer than the missing one.
+    ///
+    /// If the finer substitutes cover the whole missing tile, the coarser ones are not used for
+    /// it. Unlimited by default.
+    pub fn substitution_children(&self) -> u32 {
+        self.substitution_children.load(Ordering::Relaxed)
+    }
+
+    pub fn set_substitution_children(&self, levels: u32) {
+        self.substitution_children.store(levels, Ordering::Relaxed);
+        self.invalidate_last_update();
+    }
+
+    /// If true, looking for substitutes of a missing tile stops as soon as a single opaque tile
+    /// covering the whole missing tile is found, and missing tiles already covered by a selected
+    /// substitute are not looked up at all.
+    ///
+    /// This makes updates faster when there are many substitutes available, but some of the
+    /// partially overlapping substitutes (possibly finer ones) are not kept. Disabled by default.
+    pub fn early_substitution_termination(&self) -> bool {
+        self.early_substitution_termination.load(Ordering::Relaxed)
+    }
+
+    pub fn set_early_substitution_termination(&self, enabled: bool) {
+        self.early_substitution_termination
+            .store(enabled, Ordering::Relaxed);
+        self.invalidate_last_update();
+    }
+
+    /// If true, a missing tile that has no substitutes within the substitution limits is replaced
+    /// by its deepest cached ancestor, however coarse it is.
+    ///
+    /// This prevents holes when the view jumps over many levels at once. The ancestor is removed
+    /// as soon as the tile is loaded and opaque. Disabled by default.
+    pub fn ancestor_fallback(&self) -> bool {
+        self.ancestor_fallback.load(Ordering::Relaxed)
+    }
+
+    pub fn set_ancestor_fallback(&self, enabled: bool) {
+        self.ancestor_fallback.store(enabled, Ordering::Relaxed);
+        self.invalidate_last_update();
+    }
+
+    /// Opacity lost by a substitute tile for every level it is coarser than the tile it replaces.
+    ///
+    /// Coarse substitutes look blurry, so they can be made more transparent the more they are
+    /// stretched. `0` by default, which means substitutes are displayed with full opacity.
+    pub fn substitute_opacity_falloff(&self) -> f32 {
+        f32::from_bits(self.substitute_opacity_falloff.load(Ordering::Relaxed))
+    }
+
+    pub fn set_substitute_opacity_falloff(&self, falloff: f32) {
+        self.substitute_opacity_falloff
+            .store(falloff.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+        self.invalidate_last_update();
+    }
+
+    /// Opacity of a substitute tile that is `levels` levels coarser than the tile it replaces.
+    pub(crate) fn substitute_opacity(&self, levels: u32) -> f32 {
+        (1.0 - self.substitute_opacity_falloff() * levels as f32).max(0.0)
+    }
+
+    /// Opacity the whole layer is drawn with, multiplied with the fade opacity of every tile.
+    ///
+    /// `1` by default. Unlike the tile opacities it is not animated, so changing it does not
+    /// restart or skip the fade in of the loading tiles.
+    pub fn layer_opacity(&self) -> f32 {
+        f32::from_bits(self.layer_opacity.load(Ordering::Relaxed))
+    }
+
+    pub fn set_layer_opacity(&self, opacity: f32) {
+        self.layer_opacity
+            .store(opacity.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+        self.invalidate_last_update();
+    }
+
+    /// Opacity the `tile` should be drawn with, taking the layer opacity into account.
+    pub(crate) fn draw_opacity(&self, tile: &DisplayedTile<StyleId>) -> f32 {
+        tile.opacity * self.layer_opacity()
+    }
+
+    /// If true, the tiles of the coarsest level covering the view are displayed as soon as they
+    /// are available, so that the view is not blank while the needed tiles are loading.
+    ///
+    /// Until all of them are displayed, every update fetches them from the tile provider (see
+    /// [`TileProvider::fetch_tile_now`]), and [`TilesContainer::is_first_frame_pending`] is true,
+    /// so that the layer can request them before any other tiles.
+    pub fn eager_first_frame(&self) -> bool {
+        self.eager_first_frame.load(Ordering::Relaxed)
+    }
+
+    pub fn set_eager_first_frame(&self, eager: bool) {
+        self.eager_first_frame.store(eager, Ordering::Relaxed);
+        self.invalidate_last_update();
+    }
+
+    /// If true, the coarse tiles displayed when the view moves to a finer level are kept under the
+    /// new tiles until all of them are loaded and opaque, and then faded out.
+    pub fn coarse_underlay_until_loaded(&self) -> bool {
+        self.coarse_underlay_until_loaded.load(Ordering::Relaxed)
+    }
+
+    /// If true, the tiles of the levels finer than the finest level of the tile schema are replaced
+    /// by the tiles of the finest level, which are displayed scaled up.
+    pub fn overzoom(&self) -> bool {
+        self.overzoom.load(Ordering::Relaxed)
+    }
+
+    pub fn set_overzoom(&self, enabled: bool) {
+        self.overzoom.store(enabled, Ordering::Relaxed);
+        self.reset_pan_tracker();
+        self.invalidate_last_update();
+    }
+
+    /// The coarsest z-level the tiles are displayed at. Tiles of the coarser levels are treated
+    /// as not needed, so they are neither requested nor displayed.
+    ///
+    /// Defaults to the coarsest valid level of the tile schema.
+    pub fn min_zoom(&self) -> u32 {
+        let schema_min = self.tile_schema.min_z().unwrap_or(0);
+        self.min_zoom.load(Ordering::Relaxed).max(schema_min)
+    }
+
+    pub fn set_min_zoom(&self, z: u32) {
+        self.min_zoom.store(z, Ordering::Relaxed);
+        self.reset_pan_tracker();
+        self.invalidate_last_update();
+    }
+
+    /// The finest z-level the tiles are displayed at. Tiles of the finer levels are treated as
+    /// not needed, so they are neither requested nor displayed.
+    ///
+    /// Defaults to the finest valid level of the tile schema, or to no limit in the overzoom mode.
+    pub fn max_zoom(&self) -> u32 {
+        let max_zoom = self.max_zoom.load(Ordering::Relaxed);
+        if self.overzoom() {
+            return max_zoom;
+        }
+
+        let schema_max = self.tile_schema.valid_lods().last().map_or(0, |(z, _)| z);
+        max_zoom.min(schema_max)
+    }
+
+    pub fn set_max_zoom(&self, z: u32) {
+        self.max_zoom.store(z, Ordering::Relaxed);
+        self.reset_pan_tracker();
+        self.invalidate_last_update();
+    }
+
+    pub fn set_coarse_underlay_until_loaded(&self, enabled: bool) {
+        self.coarse_underlay_until_loaded
+            .store(enabled, Ordering::Relaxed);
+        if !enabled {
+            *self.underlay.lock() = Underlay::default();
+        }
+
+        self.invalidate_last_update();
+    }
+
+    /// Whether the needed tiles are only recomputed when the view crosses a tile boundary.
+    pub fn track_pan(&self) -> bool {
+        self.track_pan.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables reusing the needed tiles while the view moves within the same tiles.
+    /// Enabled by default.
+    pub fn set_track_pan(&self, enabled: bool) {
+        self.track_pan.store(enabled, Ordering::Relaxed);
+        self.reset_pan_tracker();
+    }
+
+    /// Sets the number of rows and columns of tiles around the needed ones that are loaded in
+    /// advance.
+    pub fn set_prefetch_ring(&self, ring: usize) {
+        self.prefetch.set_ring(ring);
+        self.invalidate_last_update();
+    }
+
+    /// Sets the velocity of the map panning in map units per second, as reported by the
+    /// application. The prefetch ring is extended up to twice its size
+    /// in the direction of the movement, since that is where the view is going to be next.
+    pub fn set_pan_velocity(&self, velocity: Vector2) {
+        self.prefetch.set_pan_velocity(velocity);
+        self.invalidate_last_update();
+    }
+
+    /// Point in map coordinates the missing tiles are requested closest-first from.
+    ///
+    /// If not set, the tiles are requested in the order they are needed in.
+    pub fn focus(&self) -> Option<Point2> {
+        *self.focus.lock()
+    }
+
+    pub fn set_focus(&self, focus: Option<Point2>) {
+        *self.focus.lock() = focus;
+        self.invalidate_last_update();
+    }
+
+    /// Orders the `indices` closest to the [`TilesContainer::focus`] first. Does nothing if the
+    /// focus is not set.
+    pub(crate) fn sort_by_focus(&self, indices: &mut [WrappingTileIndex]) {
+        if let Some(focus) = self.focus() {
+            let mut bboxes = BboxCache::new(&self.tile_schema);
+            sort_by_focus(indices, focus, |index| *index, &mut bboxes);
+        }
+    }
+
+    /// Returns the tiles around the `needed` ones that should be loaded in advance.
+    ///
+    /// For every z-level, the [prefetch ring](TilesContainer::set_prefetch_ring) of tiles is added
+    /// around the range of the needed tiles of that level. The needed tiles themselves are not
+    /// returned.
+    pub(crate) fn prefetch_indices(&self, needed: &[WrappingTileIndex]) -> Vec<WrappingTileIndex> {
+        self.prefetch.indices(&self.tile_schema, needed)
+    }
+
+    fn can_substitute(&self, displayed_z: u32, target_z: u32, max_scale: f64) -> bool {
+        if max_scale.is_infinite() {
+            return true;
+        }
+
+        let (Some(displayed), Some(target)) = (
+            self.tile_schema.lod_resolution(displayed_z),
+            self.tile_schema.lod_resolution(target_z),
+        ) else {
+            return false;
+        };
+
+        let ratio = if displayed > target {
+            displayed / target
+        } else {
+            target / displayed
+        };
+
+        ratio <= max_scale
+    }
+
+    /// Makes the next update to be executed even if it is identical to the previous one.
+    pub(crate) fn invalidate_last_update(&self) {
+        *self.last_update.lock() = None;
+    }
+
+    /// Returns true if the center of the tile `index` is covered by one of the displayed opaque
+    /// `tiles`.
+    fn is_covered<'a>(
+        &self,
+        index: WrappingTileIndex,
+        tiles: impl Iterator<Item = &'a DisplayedTile<StyleId>>,
+    ) -> bool
+    where
+        StyleId: 'a,
+    {
+        let Some(center) = self.tile_schema.tile_bbox(index).map(|bbox| bbox.center()) else {
+            return false;
+        };
+
+        tiles
+            .filter(|tile| tile.index != index && !tile.is_hidden() && tile.is_opaque())
+            .filter_map(|tile| self.tile_schema.tile_bbox(tile.index))
+            .any(|bbox| bbox.contains(&center))
+    }
+
+    fn requires_animation(&self) -> bool {
+        self.animations_enabled() && self.fade_in_duration.load(Ordering::Relaxed) > 1
+    }
+}
+
+/// Bounding boxes of the tiles calculated during a single update, so that every bounding box is
+/// calculated only once however many times the tile is checked.
+struct BboxCache<'a> {
+    tile_schema: &'a TileSchema,
+    bboxes: HashMap<WrappingTileIndex, Option<Rect>>,
+}
+
+impl<'a> BboxCache<'a> {
+    fn new(tile_schema: &'a TileSchema) -> Self {
+        Self {
+            tile_schema,
+            bboxes: HashMap::default(),
+        }
+    }
+
+    fn get(&mut self, index: WrappingTileIndex) -> Option<Rect> {
+        *self
+            .bboxes
+            .entry(index)
+            .or_insert_with(|| self.tile_schema.tile_bbox(index))
+    }
+
+    /// Number of bounding boxes that were calculated.
+    #[cfg(test)]
+    fn calculated(&self) -> usize {
+        self.bboxes.len()
+    }
+}
+
+/// Sorts the `items` by the distance from the center of their tile to the `focus`. The items of
+/// the tiles without a bounding box go last.
+fn sort_by_focus<T: Copy>(
+    items: &mut [T],
+    focus: Point2,
+    index_of: impl Fn(&T) -> WrappingTileIndex,
+    bboxes: &mut BboxCache,
+) {
+    let mut by_distance: Vec<_> = items
+        .iter()
+        .map(|item| {
+            let distance = bboxes
+                .get(index_of(item))
+                .map_or(f64::INFINITY, |bbox| bbox.center().distance_sq(&focus));
+            (distance, *item)
+        })
+        .collect();
+    by_distance.sort_by(|a, b| a.0.total_cmp(&b.0));
+    for (item, (_, sorted)) in items.iter_mut().zip(by_distance) {
+        *item = sorted;
+    }
+}
+
+pub(crate) type TileKey<StyleId> = (WrappingTileIndex, StyleId);
+
+/// Tiles of one z-level by their position.
+type TileCells<StyleId> = HashMap<(i32, i32), Vec<TileKey<StyleId>>>;
+
+/// Tiles of a container grouped by z-level and position, so that the tiles intersecting some area
+/// can be found without testing every stored tile.
+///
+/// The tiles are returned in the same order as they are iterated at the substitution step: first
+/// the tiles that are still in the old displayed set in their original order, and then the tiles
+/// moved to the new displayed set in the order they were moved.
+struct TileLookup<StyleId> {
+    levels: BTreeMap<u32, TileCells<StyleId>>,
+    order: HashMap<TileKey<StyleId>, usize>,
+    next_order: usize,
+}
+
+impl<StyleId: Copy + Hash + Eq> TileLookup<StyleId> {
+    fn new(tile_schema: &TileSchema, keys: impl Iterator<Item = TileKey<StyleId>>) -> Self {
+        let base = tile_schema.index_base() as i32;
+        let mut lookup = Self {
+            levels: BTreeMap::new(),
+            order: HashMap::default(),
+            next_order: 0,
+        };
+
+        for key in keys {
+            lookup
+                .levels
+                .entry(key.0.z)
+                .or_default()
+                .entry((key.0.display_x - base, key.0.y - base))
+                .or_default()
+                .push(key);
+            lookup.moved(key);
+        }
+
+        lookup
+    }
+
+    /// Moves the tile to the end of the iteration order.
+    fn moved(&mut self, key: TileKey<StyleId>) {
+        self.order.insert(key, self.next_order);
+        self.next_order += 1;
+    }
+
+    /// Returns the tiles of the levels accepted by `level_filter` that may intersect the `bbox`.
+    /// Some of the returned tiles may not intersect it, so the caller must check them.
+    fn candidates(
+        &self,
+        tile_schema: &TileSchema,
+        bbox: Rect,
+        level_filter: impl Fn(u32) -> bool,
+    ) -> Vec<TileKey<StyleId>> {
+        let mut candidates = vec![];
+        for (z, cells) in &self.levels {
+            if !level_filter(*z) {
+                continue;
+            }
+
+            let Some(range) = tile_schema.touching_tile_range(bbox, *z) else {
+                continue;
+            };
+
+            let in_range = |(x, y): (i32, i32)| {
+                (range.x_min..=range.x_max).contains(&x) && (range.y_min..=range.y_max).contains(&y)
+            };
+            let range_size = (range.x_max as i64 - range.x_min as i64 + 1)
+                * (range.y_max as i64 - range.y_min as i64 + 1);
+
+            // For a coarse tile a range of a fine level can be much larger than the number of the
+            // stored tiles of that level, so they are scanned instead.
+            if range_size > cells.len() as i64 {
+                candidates.extend(
+                    cells
+                        .iter()
+                        .filter(|(cell, _)| in_range(**cell))
+                        .flat_map(|(_, keys)| keys.iter().copied()),
+                );
+            } else {
+                for x in range.x_min..=range.x_max {
+                    for y in range.y_min..=range.y_max {
+                        if let Some(keys) = cells.get(&(x, y)) {
+                            candidates.extend(keys.iter().copied());
+                        }
+                    }
+                }
+            }
+        }
+
+        self.sort(&mut candidates);
+        candidates
+    }
+
+    /// Sorts the `keys` in the current iteration order.
+    fn sort(&self, keys: &mut [TileKey<StyleId>]) {
+        keys.sort_unstable_by_key(|key| self.order.get(key).copied().unwrap_or(usize::MAX));
+    }
+}
+
+/// Rectangle of adjacent tiles of the same level.
+struct TileBlock {
+    z: u32,
+    x_min: i32,
+    x_max: i32,
+    y_min: i32,
+    y_max: i32,
+    bbox: Rect,
+    /// Positions of the tiles of the block in the merged list.
+    tiles: Vec<usize>,
+}
+
+/// Merges the adjacent tiles of the same level into rectangular blocks: first the tiles of a row
+/// into strips, and then the strips spanning the same columns in adjacent rows.
+fn merge_tiles(tiles: &[(Rect, WrappingTileIndex)]) -> Vec<TileBlock> {
+    let mut order: Vec<usize> = (0..tiles.len()).collect();
+    order.sort_unstable_by_key(|&i| {
+        let index = tiles[i].1;
+        (index.z, index.y, index.display_x)
+    });
+
+    let mut strips: Vec<TileBlock> = vec![];
+    for i in order {
+        let (bbox, index) = tiles[i];
+        match strips.last_mut() {
+            Some(strip)
+                if strip.z == index.z
+                    && strip.y_min == index.y
+                    && strip.x_max + 1 >= index.display_x =>
+            {
+                strip.x_max = index.display_x;
+                strip.bbox = strip.bbox.merge(bbox);
+                strip.tiles.push(i);
+            }
+            _ => strips.push(TileBlock {
+                z: index.z,
+                x_min: index.display_x,
+                x_max: index.display_x,
+                y_min: index.y,
+                y_max: index.y,
+                bbox,
+                tiles: vec![i],
+            }),
+        }
+    }
+
+    strips.sort_unstable_by_key(|strip| (strip.z, strip.x_min, strip.x_max, strip.y_min));
+    let mut blocks: Vec<TileBlock> = vec![];
+    for strip in strips {
+        match blocks.last_mut() {
+            Some(block)
+                if block.z == strip.z
+                    && block.x_min == strip.x_min
+                    && block.x_max == strip.x_max
+                    && block.y_max + 1 == strip.y_min =>
+            {
+                block.y_max = strip.y_max;
+                block.bbox = block.bbox.merge(strip.bbox);
+                block.tiles.extend(strip.tiles);
+            }
+            _ => blocks.push(strip),
+        }
+    }
+
+    blocks
+}
+
+/// Substitution candidates of the tiles to substitute.
+///
+/// The displayed tiles are looked up once for every block of adjacent tiles rather than once for
+/// every tile. The candidates of a block are a superset of the candidates of each of its tiles,
+/// so filtering them by the tile bounding box gives the same tiles as a lookup for the tile.
+struct SubstitutionCandidates<StyleId> {
+    blocks: Vec<Vec<TileKey<StyleId>>>,
+    block_of: Vec<usize>,
+}
+
+impl<StyleId: Copy + Hash + Eq> SubstitutionCandidates<StyleId> {
+    /// Looks up the candidates of the `tiles`. `level_filter` is called with the z-level of the
+    /// displayed tiles and the z-level of the tile to substitute.
+    fn new(
+        tile_schema: &TileSchema,
+        lookup: &TileLookup<StyleId>,
+        tiles: &[(Rect, WrappingTileIndex)],
+        level_filter: impl Fn(u32, u32) -> bool,
+    ) -> Self {
+        let mut blocks = vec![];
+        let mut block_of = vec![0; tiles.len()];
+        for block in merge_tiles(tiles) {
+            for tile in &block.tiles {
+                block_of[*tile] = blocks.len();
+            }
+
+            blocks.push(lookup.candidates(tile_schema, block.bbox, |z| level_filter(z, block.z)));
+        }
+
+        Self { blocks, block_of }
+    }
+
+    /// Returns the displayed tiles intersecting the tile at the position `tile` with the bounding
+    /// box `bbox`, in the current iteration order of the `lookup`.
+    fn get(
+        &self,
+        tile: usize,
+        bbox: Rect,
+        lookup: &TileLookup<StyleId>,
+        bboxes: &mut BboxCache,
+    ) -> Vec<TileKey<StyleId>> {
+        let mut candidates: Vec<_> = self.blocks[self.block_of[tile]]
+            .iter()
+            .filter(|key| {
+                bboxes
+                    .get(key.0)
+                    .is_some_and(|candidate| candidate.intersects(bbox))
+            })
+            .copied()
+            .collect();
+        lookup.sort(&mut candidates);
+        candidates
+    }
+}
+
+/// Returns true if the rectangles share some area. Tiles that only touch each other at the edges,
+/// like the neighbouring copies of a tile wrapped around the antimeridian, do not overlap.
+fn overlaps(a: Rect, b: Rect) -> bool {
+    let overlap = a.limit(b);
+    overlap.width() > a.width() * 1e-9 && overlap.height() > a.height() * 1e-9
+}
+
+/// Returns true if the `bbox` is completely covered by the non-overlapping `opaque` rectangles.
+fn is_occluded(bbox: Rect, opaque: &[Rect]) -> bool {
+    let area = bbox.width() * bbox.height();
+    let covered: f64 = opaque
+        .iter()
+        .map(|other| bbox.limit(*other))
+        .filter(|overlap| overlap.width() > 0.0 && overlap.height() > 0.0)
+        .map(|overlap| overlap.width() * overlap.height())
+        .sum();
+
+    area > 0.0 && covered >= area * (1.0 - 1e-9)
+}
+
+/// Tile layer added to a [`MultiLayerUpdater`].
+trait LayerUpdate {
+    fn tile_schema(&self) -> &TileSchema;
+
+    fn now(&self) -> web_time::Instant;
+
+    /// Updates the displayed tiles for the tile `range` of the view, `tiles` being all the tiles
+    /// of the range. Returns true if the layer requires a redraw.
+    fn update(
+        &self,
+        range: TileRange,
+        tiles: &[WrappingTileIndex],
+        canvas: &dyn Canvas,
+        now: web_time::Instant,
+    ) -> bool;
+}
+
+struct ContainerUpdate<'a, StyleId, Provider, Pack>
+where
+    StyleId: Copy + Hash + Eq,
+    Provider: TileProvider<StyleId>,
+{
+    container: &'a TilesContainer<StyleId, Provider>,
+    style_id: StyleId,
+    /// Prepares the loaded tiles among the needed ones to be displayed by the container.
+    pack: Pack,
+}
+
+impl<StyleId, Provider, Pack> LayerUpdate for ContainerUpdate<'_, StyleId, Provider, Pack>
+where
+    StyleId: Copy + Hash + Eq,
+    Provider: TileProvider<StyleId>,
+    Pack: Fn(&[WrappingTileIndex], &dyn Canvas),
+{
+    fn tile_schema(&self) -> &TileSchema {
+        self.container.tile_schema()
+    }
+
+    fn now(&self) -> web_time::Instant {
+        self.container.now()
+    }
+
+    fn update(
+        &self,
+        range: TileRange,
+        tiles: &[WrappingTileIndex],
+        canvas: &dyn Canvas,
+        now: web_time::Instant,
+    ) -> bool {
+        let needed = self
+            .container
+            .needed_in_range(range, || tiles.iter().copied());
+        (self.pack)(&needed, canvas);
+        let requires_redraw = self
+            .container
+            .update_displayed_tiles_at(needed, self.style_id, now);
+        self.container.hold_last_update();
+
+        requires_redraw
+    }
+}
+
+/// Updates the displayed tiles of several tile layers for the same view at once.
+///
+/// The tiles covering the view are calculated only once for every distinct tile schema, and the
+/// time of the update is taken from the clocks of all the layers before any of them is updated,
+/// so the layers sharing a clock are updated for the same instant and their fade animations stay
+/// in sync. When the layers are then rendered for the same view, they display the result of this
+/// update instead of updating again, so the rendered frame is consistent across the layers.
+///
+/// The renderers do not use the updater by themselves. A renderer opts in by adding the visible
+/// tile layers and calling [`MultiLayerUpdater::update`] with its canvas before rendering them.
+#[derive(Default)]
+pub struct MultiLayerUpdater<'a> {
+    layers: Vec<Box<dyn LayerUpdate + 'a>>,
+}
+
+impl<'a> MultiLayerUpdater<'a> {
+    /// Creates an updater without layers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the raster tile layer to be updated.
+    pub fn add_raster_layer(&mut self, layer: &'a RasterTileLayer) {
+        self.add(layer.tile_container(), (), move |needed, canvas| {
+            layer.pack_tiles(needed, canvas)
+        });
+    }
+
+    /// Adds the vector tile layer to be updated with its current style.
+    pub fn add_vector_layer(&mut self, layer: &'a VectorTileLayer) {
+        self.add(
+            layer.tile_container(),
+            layer.style_id(),
+            move |needed, canvas| layer.pack_tiles(needed, canvas),
+        );
+    }
+
+    fn add<StyleId, Provider>(
+        &mut self,
+        container: &'a TilesContainer<StyleId, Provider>,
+        style_id: StyleId,
+        pack: impl Fn(&[WrappingTileIndex], &dyn Canvas) + 'a,
+    ) where
+        StyleId: Copy + Hash + Eq + 'a,
+        Provider: TileProvider<StyleId> + 'a,
+    {
+        self.layers.push(Box::new(ContainerUpdate {
+            container,
+            style_id,
+            pack,
+        }));
+    }
+
+    /// Updates the displayed tiles of all the layers for the given view. Returns true if any of
+    /// them requires a redraw.
+    #[must_use]
+    pub fn update(&self, view: &MapView, canvas: &dyn Canvas) -> bool {
+        let now: Vec<_> = self.layers.iter().map(|layer| layer.now()).collect();
+        let mut tiles_by_schema: Vec<(&TileSchema, Option<_>)> = vec![];
+        let mut requires_redraw = false;
+
+        for (layer, now) in self.layers.iter().zip(now) {
+            let schema = layer.tile_schema();
+            let position = match tiles_by_schema.iter().position(|(s, _)| *s == schema) {
+                Some(position) => position,
+                None => {
+                    let tiles = schema
+                        .tile_range(view)
+                        .map(|range| (range, schema.iter_tile_range(range).collect::<Vec<_>>()));
+                    tiles_by_schema.push((schema, tiles));
+                    tiles_by_schema.len() - 1
+                }
+            };
+
+            if let Some((range, tiles)) = &tiles_by_schema[position].1 {
+                requires_redraw |= layer.update(*range, tiles, canvas, now);
+            }
+        }
+
+        requires_redraw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+
+    use approx::assert_abs_diff_eq;
+    use galileo_types::cartesian::Size;
+
+    use super::*;
+
+    struct TestBundle(u64);
+
+    impl PackedBundle for TestBundle {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn size_bytes(&self) -> u64 {
+            self.0
+        }
+    }
+
+    #[derive(Default)]
+    struct TestProvider {
+        tiles: Mutex<HashMap<TileIndex, Arc<dyn PackedBundle>>>,
+        requested: Mutex<Vec<TileIndex>>,
+        failed: Mutex<HashSet<TileIndex>>,
+        zoom_range: Option<RangeInclusive<u32>>,
+    }
+
+    impl TestProvider {
+        fn add_tile(&self, index: TileIndex) {
+            self.add_tile_with_size(index, 0);
+        }
+
+        fn add_tile_with_size(&self, index: TileIndex, size: u64) {
+            self.tiles.lock().insert(index, Arc::new(TestBundle(size)));
+        }
+
+        fn fail_tile(&self, index: TileIndex) {
+            self.failed.lock().insert(index);
+        }
+
+        fn request_count(&self, index: TileIndex) -> usize {
+            self.requested
+                .lock()
+                .iter()
+                .filter(|requested| **requested == index)
+                .count()
+        }
+    }
+
+    impl TileProvider<()> for TestProvider {
+        fn get_tile(&self, index: TileIndex, _style_id: ()) -> Option<Arc<dyn PackedBundle>> {
+            self.requested.lock().push(index);
+            self.tiles.lock().get(&index).cloned()
+        }
+
+        fn is_failed(&self, index: TileIndex, _style_id: ()) -> bool {
+            self.failed.lock().contains(&index)
+        }
+
+        fn retry(&self, index: TileIndex, _style_id: ()) {
+            self.failed.lock().remove(&index);
+        }
+
+        fn supported_zoom_range(&self) -> Option<RangeInclusive<u32>> {
+            self.zoom_range.clone()
+        }
+    }
+
+    fn test_container() -> TilesContainer<(), TestProvider> {
+        TilesContainer::new(TileSchema::web(18), TestProvider::default())
+    }
+
+    fn indices(z: u32, count: i32) -> Vec<WrappingTileIndex> {
+        (0..count)
+            .map(|x| WrappingTileIndex::new(x, 0, z))
+            .collect()
+    }
+
+    #[test]
+    fn tile_schema_accessor() {
+        let container = test_container();
+        assert_eq!(*container.tile_schema(), TileSchema::web(18));
+    }
+
+    #[test]
+    fn max_promotions_per_frame() {
+        let container = test_container();
+        container.set_max_promotions_per_frame(2);
+        let needed = indices(3, 5);
+        for index in &needed {
+            container.tile_provider().add_tile((*index).into());
+        }
+
+        let requires_redraw = container.update_displayed_tiles(needed.clone(), ());
+        assert!(requires_redraw);
+        assert_eq!(container.tiles().len(), 2);
+
+        container.invalidate_last_update();
+        container.update_displayed_tiles(needed.clone(), ());
+        assert_eq!(container.tiles().len(), 4);
+
+        container.invalidate_last_update();
+        container.update_displayed_tiles(needed.clone(), ());
+        assert_eq!(container.tiles().len(), 5);
+    }
+
+    #[test]
+    fn min_retention_keeps_hidden_tiles() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        container.set_min_retention(Duration::from_secs(60));
+
+        let first = WrappingTileIndex::new(0, 0, 3);
+        let second = WrappingTileIndex::new(5, 5, 3);
+        container.tile_provider().add_tile(first.into());
+        container.tile_provider().add_tile(second.into());
+
+        container.update_displayed_tiles([first], ());
+        container.update_displayed_tiles([second], ());
+        {
+            let tiles = container.tiles();
+            assert_eq!(tiles.len(), 2);
+            assert!(tiles[&(first, ())].is_hidden());
+            assert!(!tiles[&(second, ())].is_hidden());
+        }
+
+        container.update_displayed_tiles([first], ());
+        assert_eq!(container.tile_provider().request_count(first.into()), 1);
+        assert!(!container.tiles()[&(first, ())].is_hidden());
+    }
+
+    #[test]
+    fn tiles_are_dropped_without_retention() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+
+        let first = WrappingTileIndex::new(0, 0, 3);
+        let second = WrappingTileIndex::new(5, 5, 3);
+        container.tile_provider().add_tile(first.into());
+        container.tile_provider().add_tile(second.into());
+
+        container.update_displayed_tiles([first], ());
+        container.update_displayed_tiles([second], ());
+        assert_eq!(container.tiles().len(), 1);
+
+        container.update_displayed_tiles([first], ());
+        assert_eq!(container.tile_provider().request_count(first.into()), 2);
+    }
+
+    #[test]
+    fn animations_disabled() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::from_secs(1));
+        container.set_fade_out_duration(Duration::from_secs(1));
+        container.set_animations_enabled(false);
+
+        let first = indices(3, 2);
+        for index in &first {
+            container.tile_provider().add_tile((*index).into());
+        }
+        assert!(container.update_displayed_tiles(first.clone(), ()));
+        let displayed = container.displayed_tiles();
+        assert_eq!(displayed.len(), 2);
+        assert!(displayed.iter().all(|tile| tile.opacity == 1.0));
+        assert_eq!(container.next_fade_deadline(), None);
+
+        container.invalidate_last_update();
+        assert!(!container.update_displayed_tiles(first.clone(), ()));
+
+        // Removed tiles disappear without fading out.
+        container.update_displayed_tiles(indices(3, 1), ());
+        assert_eq!(container.displayed_tiles().len(), 1);
+    }
+
+    #[test]
+    fn overzoom() {
+        let container = TilesContainer::new(TileSchema::web(19), TestProvider::default());
+        container.set_fade_in_duration(Duration::ZERO);
+        let parent = WrappingTileIndex::new(1, 2, 18);
+        container.tile_provider().add_tile(parent.into());
+
+        let needed = [
+            WrappingTileIndex::new(4, 8, 20),
+            WrappingTileIndex::new(5, 9, 20),
+            WrappingTileIndex::new(7, 11, 20),
+        ];
+        // The levels beyond the schema are not displayed without the overzoom.
+        assert!(container.supported_indices(needed).is_empty());
+        container.update_displayed_tiles(needed, ());
+        assert!(container.displayed_tiles().is_empty());
+
+        container.set_overzoom(true);
+        assert_eq!(container.supported_indices(needed), vec![parent]);
+        container.update_displayed_tiles(needed, ());
+        let displayed: Vec<_> = container
+            .displayed_tiles()
+            .iter()
+            .map(|tile| tile.index)
+            .collect();
+        assert_eq!(displayed, vec![parent]);
+    }
+
+    #[test]
+    fn tile_loaded_callback() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+
+        let loaded = Arc::new(Mutex::new(vec![]));
+        let loaded_clone = loaded.clone();
+        container.set_on_tile_loaded(Some(Box::new(move |index, _| {
+            loaded_clone.lock().push(index);
+        })));
+
+        let needed = indices(3, 3);
+        container.tile_provider().add_tile(needed[0].into());
+        container.tile_provider().add_tile(needed[1].into());
+        container.update_displayed_tiles(needed.clone(), ());
+        assert_eq!(*loaded.lock(), vec![needed[0], needed[1]]);
+
+        // Already displayed tiles do not fire the callback again.
+        container.tile_provider().add_tile(needed[2].into());
+        container.invalidate_last_update();
+        container.update_displayed_tiles(needed.clone(), ());
+        assert_eq!(*loaded.lock(), vec![needed[0], needed[1], needed[2]]);
+    }
+
+    #[test]
+    fn fully_loaded_callback_fires_once() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_clone = calls.clone();
+        container.set_on_fully_loaded(Some(Box::new(move || {
+            calls_clone.fetch_add(1, Ordering::Relaxed);
+        })));
+
+        let needed = indices(3, 2);
+        container.tile_provider().add_tile(needed[0].into());
+
+        container.update_displayed_tiles(needed.clone(), ());
+        assert!(!container.is_fully_loaded());
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+
+        container.tile_provider().add_tile(needed[1].into());
+        container.invalidate_last_update();
+        container.update_displayed_tiles(needed.clone(), ());
+        assert!(container.is_fully_loaded());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        container.update_displayed_tiles(needed.clone(), ());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        let other = indices(4, 1);
+        container.update_displayed_tiles(other.clone(), ());
+        assert!(!container.is_fully_loaded());
+        container.tile_provider().add_tile(other[0].into());
+        container.invalidate_last_update();
+        container.update_displayed_tiles(other, ());
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn transparent_tiles_are_not_displayed() {
+        let container = test_container();
+        let index = WrappingTileIndex::new(0, 0, 3);
+        container.tile_provider().add_tile(index.into());
+
+        container.update_displayed_tiles([index], ());
+        assert_eq!(container.tiles()[&(index, ())].opacity, 0.0);
+        assert!(container.displayed_tiles().is_empty());
+
+        container.set_fade_in_duration(Duration::ZERO);
+        container.update_displayed_tiles([index], ());
+        assert_eq!(container.displayed_tiles().len(), 1);
+    }
+
+    #[test]
+    fn next_fade_deadline() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::from_secs(10));
+        assert_eq!(container.next_fade_deadline(), None);
+
+        let index = WrappingTileIndex::new(0, 0, 3);
+        container.tile_provider().add_tile(index.into());
+        container.update_displayed_tiles([index], ());
+
+        let displayed_at = container.tiles()[&(index, ())].displayed_at;
+        assert_eq!(
+            container.next_fade_deadline(),
+            Some(displayed_at + Duration::from_secs(10))
+        );
+
+        container
+            .tiles
+            .write()
+            .get_mut(&(index, ()))
+            .unwrap()
+            .opacity = 1.0;
+        assert_eq!(container.next_fade_deadline(), None);
+    }
+
+    #[test]
+    fn max_substitution_scale() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+
+        let coarse = WrappingTileIndex::new(0, 0, 2);
+        let target = WrappingTileIndex::new(0, 0, 5);
+        container.tile_provider().add_tile(coarse.into());
+
+        container.update_displayed_tiles([coarse], ());
+        container.update_displayed_tiles([target], ());
+        assert!(container.tiles().contains_key(&(coarse, ())));
+
+        container.set_max_substitution_scale(4.0);
+        container.update_displayed_tiles([coarse], ());
+        container.update_displayed_tiles([target], ());
+        assert!(container.tiles().is_empty());
+    }
+
+    #[test]
+    fn substitute_opacity_falloff() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        assert_eq!(container.substitute_opacity(3), 1.0);
+
+        container.set_substitute_opacity_falloff(0.25);
+        assert_eq!(container.substitute_opacity(0), 1.0);
+        assert_eq!(container.substitute_opacity(3), 0.25);
+        assert_eq!(container.substitute_opacity(5), 0.0);
+
+        let coarse = WrappingTileIndex::new(0, 0, 2);
+        let target = WrappingTileIndex::new(0, 0, 5);
+        container.tile_provider().add_tile(coarse.into());
+
+        container.update_displayed_tiles([coarse], ());
+        container.update_displayed_tiles([target], ());
+        assert_eq!(container.tiles()[&(coarse, ())].opacity, 0.25);
+
+        container.update_displayed_tiles([coarse], ());
+        assert_eq!(container.tiles()[&(coarse, ())].opacity, 1.0);
+    }
+
+    #[test]
+    fn early_substitution_termination() {
+        let parent = WrappingTileIndex::new(1, 1, 2);
+        let grandparent = WrappingTileIndex::new(0, 0, 1);
+        let children = [
+            WrappingTileIndex::new(2, 2, 3),
+            WrappingTileIndex::new(3, 2, 3),
+            WrappingTileIndex::new(2, 3, 3),
+            WrappingTileIndex::new(3, 3, 3),
+        ];
+
+        let run = |early: bool| {
+            let container = test_container();
+            container.set_fade_in_duration(Duration::ZERO);
+            container.set_early_substitution_termination(early);
+            container.tile_provider().add_tile(parent.into());
+            container.tile_provider().add_tile(grandparent.into());
+
+            container.update_displayed_tiles([parent, grandparent], ());
+            container.update_displayed_tiles(children, ());
+            container
+        };
+
+        let full_scan = run(false);
+        assert!(full_scan.tiles().contains_key(&(parent, ())));
+        assert!(full_scan.tiles().contains_key(&(grandparent, ())));
+        assert_eq!(full_scan.metrics().substituted, 4);
+
+        let early = run(true);
+        assert!(early.tiles().contains_key(&(parent, ())));
+        assert!(!early.tiles().contains_key(&(grandparent, ())));
+        assert_eq!(early.metrics().substituted, 4);
+        assert_eq!(early.metrics().blanks, 0);
+    }
+
+    #[test]
+    fn ancestor_fallback() {
+        let ancestors: Vec<_> = (0..2)
+            .flat_map(|x| (0..2).map(move |y| WrappingTileIndex::new(x, y, 5)))
+            .collect();
+        let needed: Vec<_> = [0, 31, 32, 63]
+            .into_iter()
+            .flat_map(|x| [0, 31, 32, 63].map(|y| WrappingTileIndex::new(x, y, 10)))
+            .collect();
+
+        let run = |fallback: bool| {
+            let container = test_container();
+            container.set_fade_in_duration(Duration::ZERO);
+            container.set_substitution_parents(2);
+            container.set_ancestor_fallback(fallback);
+            for index in &ancestors {
+                container.tile_provider().add_tile((*index).into());
+            }
+
+            container.update_displayed_tiles(ancestors.iter().copied(), ());
+            container.update_displayed_tiles(needed.iter().copied(), ());
+            container
+        };
+
+        let without_fallback = run(false);
+        assert!(without_fallback.tiles_to_draw().is_empty());
+        assert_eq!(without_fallback.metrics().blanks, 16);
+
+        let container = run(true);
+        let drawn: Vec<_> = container
+            .tiles_to_draw()
+            .iter()
+            .map(|tile| tile.index)
+            .collect();
+        assert_eq!(drawn, ancestors);
+        assert_eq!(container.metrics().blanks, 0);
+        assert_eq!(container.metrics().substituted, 16);
+
+        for index in &needed {
+            container.tile_provider().add_tile((*index).into());
+        }
+        container.invalidate_last_update();
+        container.update_displayed_tiles(needed.iter().copied(), ());
+        assert!(container
+            .tiles_to_draw()
+            .iter()
+            .all(|tile| tile.index.z == 10));
+    }
+
+    #[test]
+    fn memory_usage() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        assert_eq!(container.memory_usage(), 0);
+
+        let first = WrappingTileIndex::new(0, 0, 3);
+        let second = WrappingTileIndex::new(1, 0, 3);
+        let wrapped = WrappingTileIndex {
+            display_x: 8,
+            ..first
+        };
+        container
+            .tile_provider()
+            .add_tile_with_size(first.into(), 100);
+        container
+            .tile_provider()
+            .add_tile_with_size(second.into(), 30);
+
+        container.update_displayed_tiles([first, second, wrapped], ());
+        assert_eq!(container.tiles().len(), 3);
+        assert_eq!(container.memory_usage(), 130);
+    }
+
+    #[test]
+    fn eviction_plan() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        container.set_min_retention(Duration::from_secs(60));
+
+        let first = WrappingTileIndex::new(0, 0, 3);
+        let pinned = WrappingTileIndex::new(2, 2, 3);
+        let displayed = WrappingTileIndex::new(5, 5, 3);
+        for (index, size) in [(first, 100), (pinned, 50), (displayed, 30)] {
+            container
+                .tile_provider()
+                .add_tile_with_size(index.into(), size);
+        }
+
+        container.pin(pinned, ());
+        container.update_displayed_tiles([first, pinned], ());
+        container.update_displayed_tiles([displayed], ());
+        assert_eq!(container.memory_usage(), 180);
+
+        assert!(container.eviction_plan(180).is_empty());
+        assert_eq!(container.eviction_plan(100), vec![(first, ())]);
+        assert_eq!(
+            container.eviction_plan(0),
+            vec![(first, ()), (displayed, ())]
+        );
+        assert_eq!(container.tiles().len(), 3);
+
+        // The pinned tile is planned for eviction once it is unpinned.
+        container.unpin(pinned, ());
+        assert_eq!(
+            container.eviction_plan(0),
+            vec![(first, ()), (pinned, ()), (displayed, ())]
+        );
+    }
+
+    #[test]
+    fn identical_updates_are_skipped() {
+        let container = test_container();
+        let index = WrappingTileIndex::new(0, 0, 3);
+        container.tile_provider().add_tile(index.into());
+
+        assert!(container.update_displayed_tiles([index], ()));
+        assert!(container.update_displayed_tiles([index], ()));
+        assert_eq!(container.tile_provider().request_count(index.into()), 1);
+
+        let other = WrappingTileIndex::new(1, 0, 3);
+        assert!(!container.update_displayed_tiles([other], ()));
+        assert_eq!(container.tile_provider().request_count(other.into()), 1);
+    }
+
+    #[test]
+    fn pinned_tiles_are_not_dropped() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+
+        let pinned = WrappingTileIndex::new(0, 0, 3);
+        let other = WrappingTileIndex::new(1, 0, 3);
+        let current = WrappingTileIndex::new(5, 5, 3);
+        for index in [pinned, other, current] {
+            container.tile_provider().add_tile(index.into());
+        }
+
+        container.pin(pinned, ());
+        container.update_displayed_tiles([pinned, other], ());
+        container.update_displayed_tiles([current], ());
+        {
+            let tiles = container.tiles();
+            assert_eq!(tiles.len(), 2);
+            assert!(tiles[&(pinned, ())].is_hidden());
+            assert!(!tiles.contains_key(&(other, ())));
+        }
+
+        container.unpin(pinned, ());
+        container.invalidate_last_update();
+        container.update_displayed_tiles([current], ());
+        assert_eq!(container.tiles().len(), 1);
+    }
+
+    #[test]
+    fn restored_tiles_fade_in_only_over_opaque_tiles() {
+        let container = test_container();
+        let child = WrappingTileIndex::new(2, 2, 3);
+        let parent = WrappingTileIndex::new(1, 1, 2);
+        let far = WrappingTileIndex::new(7, 7, 3);
+        for index in [child, parent, far] {
+            container.tile_provider().add_tile(index.into());
+        }
+
+        container.set_fade_in_duration(Duration::ZERO);
+        container.update_displayed_tiles([child], ());
+        container.update_displayed_tiles([far], ());
+        container.update_displayed_tiles([parent], ());
+
+        container.set_fade_in_duration(Duration::from_secs(1));
+        container.update_displayed_tiles([child], ());
+        assert_eq!(container.tiles()[&(child, ())].opacity, 0.0);
+
+        container.set_fade_in_duration(Duration::ZERO);
+        container.update_displayed_tiles([far], ());
+        assert!(!container.tiles().contains_key(&(child, ())));
+
+        container.set_fade_in_duration(Duration::from_secs(1));
+        container.update_displayed_tiles([child], ());
+        assert_eq!(container.tiles()[&(child, ())].opacity, 1.0);
+    }
+
+    #[test]
+    fn metrics() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+
+        let parent = WrappingTileIndex::new(0, 0, 2);
+        let loaded = WrappingTileIndex::new(0, 0, 3);
+        let covered = WrappingTileIndex::new(1, 1, 3);
+        let uncovered = WrappingTileIndex::new(7, 7, 3);
+        container.tile_provider().add_tile(parent.into());
+        container.tile_provider().add_tile(loaded.into());
+
+        container.update_displayed_tiles([parent], ());
+        assert_eq!(
+            container.metrics(),
+            TilesMetrics {
+                direct_hits: 1,
+                substituted: 0,
+                blanks: 0,
+                requests_issued: 1,
+            }
+        );
+
+        container.update_displayed_tiles([loaded, covered, uncovered], ());
+        assert_eq!(
+            container.metrics(),
+            TilesMetrics {
+                direct_hits: 2,
+                substituted: 1,
+                blanks: 1,
+                requests_issued: 4,
+            }
+        );
+
+        container.invalidate_last_update();
+        container.update_displayed_tiles([loaded, covered, uncovered], ());
+        assert_eq!(
+            container.metrics(),
+            TilesMetrics {
+                direct_hits: 3,
+                substituted: 2,
+                blanks: 2,
+                requests_issued: 6,
+            }
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn displayed_snapshot() {
+        let container = TilesContainer::new(TileSchema::web(18), StyledProvider);
+        container.set_fade_in_duration(Duration::from_secs(1));
+        container.set_fade_out_duration(Duration::from_secs(1));
+        let parent = WrappingTileIndex::new(0, 0, 1);
+        let children = [
+            WrappingTileIndex::new(1, 1, 2),
+            WrappingTileIndex::new(0, 1, 2),
+            WrappingTileIndex::new(1, 0, 2),
+            WrappingTileIndex::new(0, 0, 2),
+        ];
+        let now = web_time::Instant::now();
+
+        container.update_displayed_tiles_at([parent], 1, now);
+        container.update_displayed_tiles_at([parent], 1, now + Duration::from_secs(1));
+        assert_eq!(container.displayed_snapshot(), vec![(parent, 1, 1.0)]);
+
+        container.update_displayed_tiles_at(children, 2, now + Duration::from_secs(2));
+        container.update_displayed_tiles_at(children, 2, now + Duration::from_millis(2500));
+
+        // The parent substitute is drawn first, then the children column by column.
+        assert_eq!(
+            container.displayed_snapshot(),
+            vec![
+                (parent, 1, 1.0),
+                (children[3], 2, 0.5),
+                (children[1], 2, 0.5),
+                (children[2], 2, 0.5),
+                (children[0], 2, 0.5),
+            ]
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn inject_displayed() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::from_secs(100));
+
+        let parent = WrappingTileIndex::new(0, 0, 2);
+        let child = WrappingTileIndex::new(1, 1, 3);
+        let far = WrappingTileIndex::new(7, 7, 3);
+        container.inject_displayed(vec![
+            (parent, (), Arc::new(TestBundle(0)), 1.0),
+            (child, (), Arc::new(TestBundle(0)), 0.5),
+            (far, (), Arc::new(TestBundle(0)), 1.0),
+        ]);
+        assert_eq!(container.displayed_tiles().len(), 3);
+
+        container.update_displayed_tiles([child], ());
+        {
+            let tiles = container.tiles();
+            let opacity = tiles[&(child, ())].opacity;
+            assert!((0.5..0.51).contains(&opacity));
+            assert!(!tiles[&(parent, ())].is_hidden());
+            assert!(!tiles.contains_key(&(far, ())));
+        }
+        assert_eq!(container.tile_provider().request_count(child.into()), 0);
+    }
+
+    #[test]
+    fn display_zoom_range() {
+        let container = test_container();
+        assert_eq!(container.min_zoom(), 0);
+        assert_eq!(container.max_zoom(), 17);
+
+        container.set_min_zoom(3);
+        container.set_max_zoom(5);
+        let needed = [2, 3, 5, 6].map(|z| WrappingTileIndex::new(0, 0, z));
+        for index in needed {
+            container.tile_provider().add_tile(index.into());
+        }
+
+        container.update_displayed_tiles(needed, ());
+        for z in [2, 6] {
+            let index = WrappingTileIndex::new(0, 0, z);
+            assert_eq!(container.tile_provider().request_count(index.into()), 0);
+            assert!(!container.tiles().contains_key(&(index, ())));
+        }
+        for z in [3, 5] {
+            let index = WrappingTileIndex::new(0, 0, z);
+            assert_eq!(container.tile_provider().request_count(index.into()), 1);
+        }
+
+        container.set_max_zoom(30);
+        assert_eq!(container.max_zoom(), 17);
+    }
+
+    #[test]
+    fn supported_zoom_range() {
+        let provider = TestProvider {
+            zoom_range: Some(2..=4),
+            ..Default::default()
+        };
+        let container = TilesContainer::new(TileSchema::web(18), provider);
+
+        let needed = [
+            WrappingTileIndex::new(0, 0, 6),
+            WrappingTileIndex::new(1, 0, 6),
+            WrappingTileIndex {
+                display_x: 64,
+                ..WrappingTileIndex::new(0, 0, 6)
+            },
+            WrappingTileIndex::new(0, 0, 1),
+            WrappingTileIndex::new(3, 3, 3),
+        ];
+        container.update_displayed_tiles(needed, ());
+
+        let requested = container.tile_provider().requested.lock().clone();
+        assert_eq!(
+            requested,
+            vec![
+                TileIndex::new(0, 0, 4),
+                TileIndex::new(0, 0, 4),
+                TileIndex::new(3, 3, 3),
+            ]
+        );
+        assert!(container
+            .supported_indices(needed)
+            .contains(&WrappingTileIndex {
+                display_x: 16,
+                ..WrappingTileIndex::new(0, 0, 4)
+            }));
+    }
+
+    #[test]
+    fn center_tile() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+
+        let displayed = WrappingTileIndex::new(4, 3, 3);
+        container.tile_provider().add_tile(displayed.into());
+        container.update_displayed_tiles([displayed, WrappingTileIndex::new(3, 3, 3)], ());
+
+        let tile_size = 20037508.342787 / 4.0;
+        let center = Point2::new(tile_size / 2.0, tile_size / 2.0);
+        assert_eq!(container.center_tile(center, 3), Some(displayed));
+
+        let missing_center = Point2::new(-tile_size / 2.0, tile_size / 2.0);
+        assert_eq!(container.center_tile(missing_center, 3), None);
+        assert_eq!(container.center_tile(center, 4), None);
+    }
+
+    /// Provider that has all the tiles in every style.
+    struct StyledProvider;
+
+    impl TileProvider<u8> for StyledProvider {
+        fn get_tile(&self, _index: TileIndex, _style_id: u8) -> Option<Arc<dyn PackedBundle>> {
+            Some(Arc::new(TestBundle(0)))
+        }
+    }
+
+    /// Provider of a packed archive that reads all the requested tiles at once.
+    #[derive(Default)]
+    struct ArchiveProvider {
+        tiles: HashSet<TileIndex>,
+        batches: Mutex<Vec<Vec<TileIndex>>>,
+        single_requests: AtomicUsize,
+    }
+
+    impl TileProvider<()> for ArchiveProvider {
+        fn get_tile(&self, index: TileIndex, _style_id: ()) -> Option<Arc<dyn PackedBundle>> {
+            self.single_requests.fetch_add(1, Ordering::Relaxed);
+            self.tiles
+                .contains(&index)
+                .then(|| Arc::new(TestBundle(0)) as Arc<dyn PackedBundle>)
+        }
+
+        fn get_tiles(
+            &self,
+            indices: &[TileIndex],
+            _style_id: (),
+        ) -> Vec<Option<Arc<dyn PackedBundle>>> {
+            self.batches.lock().push(indices.to_vec());
+            indices
+                .iter()
+                .map(|index| {
+                    self.tiles
+                        .contains(index)
+                        .then(|| Arc::new(TestBundle(index.x as u64)) as Arc<dyn PackedBundle>)
+                })
+                .collect()
+        }
+
+        fn tile_states(&self, indices: &[TileIndex], style_id: ()) -> Vec<TileState> {
+            batch_tile_states(self, indices, style_id)
+        }
+    }
+
+    #[test]
+    fn get_tiles_preserves_order() {
+        let provider = TestProvider::default();
+        provider.add_tile_with_size(TileIndex::new(2, 0, 3), 2);
+        provider.add_tile_with_size(TileIndex::new(0, 0, 3), 0);
+
+        let indices = [
+            TileIndex::new(2, 0, 3),
+            TileIndex::new(1, 0, 3),
+            TileIndex::new(0, 0, 3),
+        ];
+        let sizes: Vec<_> = provider
+            .get_tiles(&indices, ())
+            .iter()
+            .map(|bundle| bundle.as_ref().map(|bundle| bundle.size_bytes()))
+            .collect();
+        assert_eq!(sizes, vec![Some(2), None, Some(0)]);
+        assert_eq!(*provider.requested.lock(), indices);
+    }
+
+    #[test]
+    fn update_requests_missing_tiles_in_one_batch() {
+        let provider = ArchiveProvider {
+            tiles: [1, 3]
+                .map(|x| TileIndex::new(x, 0, 3))
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let container = TilesContainer::new(TileSchema::web(18), provider);
+        container.set_fade_in_duration(Duration::ZERO);
+
+        container.update_displayed_tiles(indices(3, 2), ());
+        container.update_displayed_tiles(indices(3, 4), ());
+
+        let provider = container.tile_provider();
+        assert_eq!(provider.single_requests.load(Ordering::Relaxed), 0);
+        assert_eq!(
+            *provider.batches.lock(),
+            vec![
+                vec![TileIndex::new(0, 0, 3), TileIndex::new(1, 0, 3)],
+                vec![
+                    TileIndex::new(0, 0, 3),
+                    TileIndex::new(2, 0, 3),
+                    TileIndex::new(3, 0, 3)
+                ],
+            ]
+        );
+
+        let mut displayed: Vec<_> = container
+            .displayed_tiles()
+            .iter()
+            .map(|tile| (tile.index.x, tile.bundle.size_bytes()))
+            .collect();
+        displayed.sort();
+        assert_eq!(displayed, vec![(1, 1), (3, 3)]);
+        assert_eq!(container.metrics().requests_issued, 5);
+    }
+
+    #[test]
+    fn update_requests_prefetch_ring() {
+        let container = test_container();
+        container.set_prefetch_ring(1);
+        let needed = vec![WrappingTileIndex::new(5, 5, 4)];
+        let ring: Vec<_> = (4..=6)
+            .flat_map(|x| (4..=6).map(move |y| TileIndex::new(x, y, 4)))
+            .filter(|index| *index != TileIndex::new(5, 5, 4))
+            .collect();
+        for index in &ring {
+            container.tile_provider().add_tile(*index);
+        }
+
+        let requires_redraw = container.update_displayed_tiles(needed, ());
+        assert!(!requires_redraw);
+        assert!(container.displayed_tiles().is_empty());
+        for index in ring {
+            assert_eq!(container.tile_provider().request_count(index), 1);
+        }
+    }
+
+    #[test]
+    fn prefetch_follows_pan_velocity() {
+        let container = test_container();
+        let needed: Vec<_> = (10..=12)
+            .flat_map(|x| (10..=12).map(move |y| WrappingTileIndex::new(x, y, 5)))
+            .collect();
+        assert!(container.prefetch_indices(&needed).is_empty());
+
+        container.set_prefetch_ring(1);
+        assert_eq!(container.prefetch_indices(&needed).len(), 16);
+
+        container.set_pan_velocity(Vector2::new(100.0, 0.0));
+        let prefetch = container.prefetch_indices(&needed);
+        let east = prefetch.iter().filter(|index| index.x > 12).count();
+        let west = prefetch.iter().filter(|index| index.x < 10).count();
+        assert_eq!(east, 10);
+        assert_eq!(west, 5);
+        assert!(prefetch.iter().all(|index| !needed.contains(index)));
+    }
+
+    #[test]
+    fn focus_orders_requests() {
+        let needed: Vec<_> = (0..3)
+            .flat_map(|x| (0..3).map(move |y| WrappingTileIndex::new(x, y, 2)))
+            .collect();
+        let center = TileIndex::new(1, 1, 2);
+        let corners = [
+            TileIndex::new(0, 0, 2),
+            TileIndex::new(2, 0, 2),
+            TileIndex::new(0, 2, 2),
+            TileIndex::new(2, 2, 2),
+        ];
+
+        let container = test_container();
+        container.update_displayed_tiles(needed.clone(), ());
+        assert_eq!(container.tile_provider().requested.lock()[0], corners[0]);
+
+        let container = test_container();
+        let focus = container.tile_schema().tile_center(center).unwrap();
+        container.set_focus(Some(focus));
+        container.update_displayed_tiles(needed.clone(), ());
+
+        {
+            let provider = container.tile_provider();
+            let requested = provider.requested.lock();
+            assert_eq!(requested.len(), 9);
+            assert_eq!(requested[0], center);
+            let position = |index: &TileIndex| requested.iter().position(|r| r == index).unwrap();
+            assert!(corners.iter().all(|corner| position(corner) >= 5));
+        }
+
+        // Moving the focus takes effect in the same frame.
+        container.tile_provider().requested.lock().clear();
+        let focus = container.tile_schema().tile_center(corners[3]).unwrap();
+        container.set_focus(Some(focus));
+        container.update_displayed_tiles(needed, ());
+
+        let provider = container.tile_provider();
+        let requested = provider.requested.lock();
+        assert_eq!(requested.len(), 9);
+        assert_eq!(requested[0], corners[3]);
+        assert_eq!(requested[8], corners[0]);
+    }
+
+    #[test]
+    fn needed_tiles_change_only_at_tile_boundaries() {
+        let container = test_container();
+        let resolution = container.tile_schema().lod_resolution(5).unwrap();
+        let tile_size = resolution * 256.0;
+        let view_at = |dx: f64| {
+            MapView::new_projected(
+                &Point2::new(tile_size * (0.5 + dx), tile_size / 2.0),
+                resolution,
+            )
+            .with_size(Size::new(512.0, 512.0))
+        };
+
+        let initial = container.needed_indices(&view_at(0.0)).unwrap();
+        assert_eq!(initial.len(), 9);
+
+        for dx in [0.1, 0.2, 0.3, 0.4, 0.1] {
+            assert_eq!(container.needed_indices(&view_at(dx)).unwrap(), initial);
+        }
+
+        let crossed = container.needed_indices(&view_at(0.6)).unwrap();
+        assert_ne!(crossed, initial);
+        assert_eq!(
+            crossed,
+            container
+                .tile_schema()
+                .iter_tiles(&view_at(0.6))
+                .unwrap()
+                .collect::<Vec<_>>()
+        );
+
+        container.set_track_pan(false);
+        assert_eq!(container.needed_indices(&view_at(0.0)).unwrap(), initial);
+    }
+
+    #[test]
+    fn needed_tiles_follow_setting_changes() {
+        let container = test_container();
+        let resolution = container.tile_schema().lod_resolution(5).unwrap();
+        let view = MapView::new_projected(&Point2::new(0.0, 0.0), resolution)
+            .with_size(Size::new(512.0, 512.0));
+        let levels = |container: &TilesContainer<(), TestProvider>| {
+            let mut levels: Vec<u32> = container
+                .needed_indices(&view)
+                .unwrap()
+                .iter()
+                .map(|index| index.z)
+                .collect();
+            levels.dedup();
+            levels
+        };
+
+        assert_eq!(levels(&container), vec![5]);
+
+        let provider = TestProvider {
+            zoom_range: Some(0..=3),
+            ..Default::default()
+        };
+        container.set_tile_provider(provider, false);
+        assert_eq!(levels(&container), vec![3]);
+
+        container.set_tile_provider(TestProvider::default(), false);
+        assert_eq!(levels(&container), vec![5]);
+
+        container.set_max_zoom(4);
+        assert!(levels(&container).is_empty());
+    }
+
+    #[test]
+    fn request_filter() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        container.set_request_filter(Some(Box::new(|index: WrappingTileIndex| match index.x {
+            0 => None,
+            1 => Some(WrappingTileIndex::new(5, index.y, index.z)),
+            _ => Some(index),
+        })));
+
+        let remapped = TileIndex::new(5, 0, 3);
+        container.tile_provider().add_tile(TileIndex::new(0, 0, 3));
+        container.tile_provider().add_tile(remapped);
+        container.update_displayed_tiles(indices(3, 3), ());
+
+        let provider = container.tile_provider();
+        assert_eq!(provider.request_count(TileIndex::new(0, 0, 3)), 0);
+        assert_eq!(provider.request_count(TileIndex::new(1, 0, 3)), 0);
+        assert_eq!(provider.request_count(remapped), 1);
+        assert_eq!(provider.request_count(TileIndex::new(2, 0, 3)), 1);
+
+        let displayed: Vec<_> = container
+            .displayed_tiles()
+            .iter()
+            .map(|tile| tile.index)
+            .collect();
+        assert_eq!(displayed, vec![WrappingTileIndex::new(1, 0, 3)]);
+    }
+
+    #[test]
+    fn easing() {
+        for easing in [Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+            assert_eq!(easing.apply(2.0), 1.0);
+        }
+
+        assert_abs_diff_eq!(Easing::EaseInOut.apply(0.25), 0.0625);
+        assert_abs_diff_eq!(Easing::EaseInOut.apply(0.75), 0.9375);
+        assert_eq!(Easing::Custom(|_| 2.0).apply(0.5), 1.0);
+        assert_eq!(Easing::Custom(|t| t.sqrt()).apply(1.0), 1.0);
+
+        let container = test_container();
+        container.set_fade_in_duration(Duration::from_millis(100));
+        container.set_fade_easing(Easing::EaseInOut);
+        let index = WrappingTileIndex::new(0, 0, 3);
+        container.tile_provider().add_tile(index.into());
+
+        let start = web_time::Instant::now();
+        container.update_displayed_tiles_at([index], (), start);
+        container.update_displayed_tiles_at([index], (), start + Duration::from_millis(25));
+        let opacity = container.displayed_tiles()[0].opacity;
+        assert_abs_diff_eq!(opacity, 0.0625, epsilon = 1e-3);
+
+        container.update_displayed_tiles_at([index], (), start + Duration::from_millis(100));
+        assert!(container.displayed_tiles()[0].is_opaque());
+    }
+
+    #[test]
+    fn fade_out() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::from_millis(100));
+        container.set_fade_out_duration(Duration::from_millis(100));
+        let first = WrappingTileIndex::new(0, 0, 3);
+        let second = WrappingTileIndex::new(4, 4, 3);
+        container.tile_provider().add_tile(first.into());
+        container.tile_provider().add_tile(second.into());
+        let opacity = |index| {
+            container
+                .displayed_tiles()
+                .iter()
+                .find(|tile| tile.index == index)
+                .map(|tile| tile.opacity)
+        };
+
+        let start = web_time::Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        container.update_displayed_tiles_at([first], (), at(0));
+        container.update_displayed_tiles_at([first], (), at(200));
+        assert_eq!(opacity(first), Some(1.0));
+
+        assert!(container.update_displayed_tiles_at([second], (), at(300)));
+        assert!(container.update_displayed_tiles_at([second], (), at(350)));
+        assert_abs_diff_eq!(opacity(first).unwrap(), 0.5, epsilon = 0.01);
+
+        // Needed again in the middle of the fade out, the tile fades in from where it was.
+        container.update_displayed_tiles_at([first], (), at(360));
+        assert_abs_diff_eq!(opacity(first).unwrap(), 0.5, epsilon = 0.01);
+        container.update_displayed_tiles_at([first], (), at(400));
+        assert_abs_diff_eq!(opacity(first).unwrap(), 0.9, epsilon = 0.01);
+
+        container.update_displayed_tiles_at([first], (), at(600));
+        assert_eq!(opacity(first), Some(1.0));
+        assert_eq!(opacity(second), None);
+    }
+
+    #[test]
+    fn draw_order() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        container.set_min_retention(Duration::from_secs(60));
+        let parent = WrappingTileIndex::new(0, 0, 1);
+        let children = [
+            WrappingTileIndex::new(1, 1, 2),
+            WrappingTileIndex::new(0, 1, 2),
+            WrappingTileIndex::new(1, 0, 2),
+            WrappingTileIndex::new(0, 0, 2),
+        ];
+        container.tile_provider().add_tile(parent.into());
+        container.update_displayed_tiles([parent], ());
+
+        // Only some of the children are loaded, so the parent is kept to fill the gap.
+        for child in &children[..3] {
+            container.tile_provider().add_tile((*child).into());
+        }
+        container.update_displayed_tiles(children, ());
+
+        let order: Vec<_> = container
+            .tiles_to_draw()
+            .iter()
+            .map(|tile| tile.index)
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                parent,
+                WrappingTileIndex::new(0, 1, 2),
+                WrappingTileIndex::new(1, 0, 2),
+                WrappingTileIndex::new(1, 1, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn concurrent_readers() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        let index = WrappingTileIndex::new(0, 0, 1);
+        container.tile_provider().add_tile(index.into());
+        container.update_displayed_tiles([index], ());
+
+        let tiles = container.tiles();
+        std::thread::scope(|scope| {
+            let reader = scope.spawn(|| {
+                let tiles = container
+                    .tiles
+                    .try_read()
+                    .expect("reader is blocked by another reader");
+                tiles.len()
+            });
+            assert_eq!(reader.join().unwrap(), 1);
+            assert_eq!(container.displayed_tiles().len(), 1);
+        });
+
+        assert!(container.tiles.try_write().is_none());
+        drop(tiles);
+        assert!(container.tiles.try_write().is_some());
+    }
+
+    /// Deterministic pseudo-random numbers for the generated test data.
+    fn lcg(seed: &mut u64) -> u32 {
+        *seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (*seed >> 33) as u32
+    }
+
+    fn random_tiles(
+        schema: &TileSchema,
+        count: usize,
+        seed: &mut u64,
+    ) -> Vec<(WrappingTileIndex, u8)> {
+        let mut found = HashSet::default();
+        let mut keys = vec![];
+        while keys.len() < count {
+            let z = 2 + lcg(seed) % 6;
+            let size = 1i32 << z;
+            let display_x = (lcg(seed) % (size as u32 * 3)) as i32 - size;
+            let y = (lcg(seed) % size as u32) as i32;
+            let index = WrappingTileIndex {
+                x: display_x.rem_euclid(size),
+                y,
+                z,
+                display_x,
+            };
+            let key = (index, (lcg(seed) % 2) as u8);
+            if schema.tile_bbox(index).is_some() && found.insert(key) {
+                keys.push(key);
+            }
+        }
+
+        keys
+    }
+
+    #[test]
+    fn tile_lookup_matches_full_scan() {
+        let schema = TileSchema::web(18);
+        let mut seed = 42;
+        let mut order = random_tiles(&schema, 2000, &mut seed);
+        let mut lookup = TileLookup::new(&schema, order.iter().copied());
+
+        for round in 0..200 {
+            let target = order[lcg(&mut seed) as usize % order.len()].0;
+            let bbox = schema.tile_bbox(target).unwrap();
+            let max_z = target.z + round % 3;
+            let level_filter = |z: u32| z <= max_z;
+            let intersects = |key: &(WrappingTileIndex, u8)| {
+                schema
+                    .tile_bbox(key.0)
+                    .is_some_and(|tile_bbox| tile_bbox.intersects(bbox))
+            };
+
+            let expected: Vec<_> = order
+                .iter()
+                .filter(|key| level_filter(key.0.z) && intersects(key))
+                .copied()
+                .collect();
+            let actual: Vec<_> = lookup
+                .candidates(&schema, bbox, level_filter)
+                .into_iter()
+                .filter(intersects)
+                .collect();
+            assert_eq!(actual, expected, "bbox of {target:?}");
+            assert!(!actual.is_empty());
+
+            // Tiles selected as substitutes are moved to the end of the iteration order.
+            for key in actual.iter().step_by(2) {
+                order.retain(|k| k != key);
+                order.push(*key);
+                lookup.moved(*key);
+            }
+        }
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture substitution_lookup_benchmark`.
+    #[test]
+    #[ignore = "benchmark"]
+    fn substitution_lookup_benchmark() {
+        let schema = TileSchema::web(18);
+        let mut seed = 7;
+        let keys = random_tiles(&schema, 5000, &mut seed);
+        let targets: Vec<Rect> = keys
+            .iter()
+            .take(500)
+            .filter_map(|key| schema.tile_bbox(key.0))
+            .collect();
+
+        let started = web_time::Instant::now();
+        let mut full_scan_found = 0;
+        for bbox in &targets {
+            full_scan_found += keys
+                .iter()
+                .filter_map(|key| schema.tile_bbox(key.0))
+                .filter(|tile_bbox| tile_bbox.intersects(*bbox))
+                .count();
+        }
+        let full_scan = started.elapsed();
+
+        let started = web_time::Instant::now();
+        let lookup = TileLookup::new(&schema, keys.iter().copied());
+        let mut lookup_found = 0;
+        for bbox in &targets {
+            lookup_found += lookup
+                .candidates(&schema, *bbox, |_| true)
+                .into_iter()
+                .filter_map(|key| schema.tile_bbox(key.0))
+                .filter(|tile_bbox| tile_bbox.intersects(*bbox))
+                .count();
+        }
+        let indexed = started.elapsed();
+
+        assert_eq!(full_scan_found, lookup_found);
+        println!(
+            "{} tiles, {} substitutions: full scan {full_scan:?}, lookup {indexed:?}",
+            keys.len(),
+            targets.len()
+        );
+
+        // Only the generated tiles are available, so the tiles of the finer level are
+        // substituted by them.
+        let container = TilesContainer::new(schema, TestProvider::default());
+        container.set_fade_in_duration(Duration::ZERO);
+        container.set_min_retention(Duration::from_secs(3600));
+        for key in &keys {
+            container.tile_provider().add_tile(key.0.into());
+        }
+        for chunk in keys.chunks(64) {
+            container.update_displayed_tiles(chunk.iter().map(|key| key.0), ());
+        }
+
+        let missing = indices(8, 64);
+        let started = web_time::Instant::now();
+        for _ in 0..100 {
+            container.invalidate_last_update();
+            container.update_displayed_tiles(missing.clone(), ());
+        }
+        println!(
+            "update with {} cached tiles: {:?}",
+            container.tiles().len(),
+            started.elapsed() / 100
+        );
+    }
+
+    /// Tiles of the rectangle of `xs` and `ys` of the level `z`.
+    fn tile_block(
+        z: u32,
+        xs: RangeInclusive<i32>,
+        ys: RangeInclusive<i32>,
+    ) -> Vec<WrappingTileIndex> {
+        let size = 1i32 << z;
+        xs.flat_map(|display_x| {
+            ys.clone().map(move |y| WrappingTileIndex {
+                x: display_x.rem_euclid(size),
+                y,
+                z,
+                display_x,
+            })
+        })
+        .collect()
+    }
+
+    /// Tiles to substitute with their bounding boxes.
+    type Substituted = Vec<(Rect, WrappingTileIndex)>;
+
+    /// Tiles to substitute of different viewport shapes with the expected numbers of merged
+    /// blocks.
+    fn viewport_shapes(schema: &TileSchema) -> Vec<(Substituted, Option<usize>)> {
+        let wide = tile_block(5, -2..=12, 10..=12);
+        let tall = tile_block(5, 3..=4, 0..=20);
+        let square = tile_block(6, 10..=19, 20..=29);
+        let single = tile_block(4, 7..=7, 7..=7);
+        let corner: Vec<_> = tile_block(5, 0..=9, 0..=2)
+            .into_iter()
+            .chain(tile_block(5, 0..=2, 3..=9))
+            .collect();
+        let levels: Vec<_> = tile_block(6, 0..=5, 0..=5)
+            .into_iter()
+            .chain(tile_block(4, 0..=1, 0..=1))
+            .collect();
+        let mut seed = 5;
+        let scattered: Vec<_> = random_tiles(schema, 100, &mut seed)
+            .into_iter()
+            .map(|key| key.0)
+            .collect();
+
+        [
+            (wide, Some(1)),
+            (tall, Some(1)),
+            (square, Some(1)),
+            (single, Some(1)),
+            (corner, Some(2)),
+            (levels, Some(2)),
+            (scattered, None),
+        ]
+        .into_iter()
+        .map(|(shape, blocks)| {
+            let tiles = shape
+                .into_iter()
+                .filter_map(|index| Some((schema.tile_bbox(index)?, index)))
+                .collect();
+            (tiles, blocks)
+        })
+        .collect()
+    }
+
+    #[test]
+    fn merged_substitution_keeps_retained_tiles() {
+        // The substitution of a tile depends only on the candidates it iterates over, so the
+        // retained tiles are the same as long as every tile gets the same candidates in the same
+        // order as from its own lookup.
+        let schema = TileSchema::web(18);
+        let mut seed = 11;
+        let keys = random_tiles(&schema, 2000, &mut seed);
+        let level_filter = |z: u32, subst_z: u32| z.abs_diff(subst_z) <= 3;
+
+        for (tiles, expected_blocks) in viewport_shapes(&schema) {
+            if let Some(expected_blocks) = expected_blocks {
+                assert_eq!(merge_tiles(&tiles).len(), expected_blocks);
+            }
+
+            let mut lookup = TileLookup::new(&schema, keys.iter().copied());
+            let merged = SubstitutionCandidates::new(&schema, &lookup, &tiles, level_filter);
+            let mut bboxes = BboxCache::new(&schema);
+            for (position, (bbox, index)) in tiles.iter().enumerate() {
+                let expected: Vec<_> = lookup
+                    .candidates(&schema, *bbox, |z| level_filter(z, index.z))
+                    .into_iter()
+                    .filter(|key| {
+                        schema
+                            .tile_bbox(key.0)
+                            .is_some_and(|tile_bbox| tile_bbox.intersects(*bbox))
+                    })
+                    .collect();
+                let found = merged.get(position, *bbox, &lookup, &mut bboxes);
+                assert_eq!(found, expected);
+
+                // Substituting tiles are moved to the end of the iteration order.
+                for key in found {
+                    lookup.moved(key);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bbox_cache() {
+        let schema = TileSchema::web(18);
+        let mut bboxes = BboxCache::new(&schema);
+        let indices = [
+            WrappingTileIndex::new(1, 2, 3),
+            WrappingTileIndex::new(5, 2, 3),
+            WrappingTileIndex {
+                x: 1,
+                y: 2,
+                z: 3,
+                display_x: 9,
+            },
+            WrappingTileIndex::new(0, 0, 30),
+        ];
+
+        for _ in 0..3 {
+            for index in indices {
+                assert_eq!(bboxes.get(index), schema.tile_bbox(index));
+            }
+        }
+
+        assert_eq!(bboxes.get(indices[3]), None);
+        assert_ne!(bboxes.get(indices[0]), bboxes.get(indices[2]));
+        assert_eq!(bboxes.calculated(), indices.len());
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture bbox_cache_benchmark`.
+    #[test]
+    #[ignore = "benchmark"]
+    fn bbox_cache_benchmark() {
+        let schema = TileSchema::web(18);
+        let mut seed = 3;
+        let keys = random_tiles(&schema, 1000, &mut seed);
+        let lookups = 100;
+
+        let started = web_time::Instant::now();
+        let mut direct = 0.0;
+        for _ in 0..lookups {
+            for key in &keys {
+                direct += schema.tile_bbox(key.0).map_or(0.0, |bbox| bbox.width());
+            }
+        }
+        let direct_time = started.elapsed();
+
+        let started = web_time::Instant::now();
+        let mut bboxes = BboxCache::new(&schema);
+        let mut cached = 0.0;
+        for _ in 0..lookups {
+            for key in &keys {
+                cached += bboxes.get(key.0).map_or(0.0, |bbox| bbox.width());
+            }
+        }
+        let cached_time = started.elapsed();
+
+        assert_eq!(direct, cached);
+        println!(
+            "{} lookups: {} bbox calculations in {direct_time:?} without cache, {} in \
+             {cached_time:?} with cache",
+            keys.len() * lookups,
+            keys.len() * lookups,
+            bboxes.calculated()
+        );
+    }
+
+    #[test]
+    fn cache_stats() {
+        let container = TilesContainer::new(TileSchema::web(18), StyledProvider);
+        container.set_fade_in_duration(Duration::ZERO);
+        container.set_fade_out_duration(Duration::from_secs(1));
+        let needed = indices(1, 2);
+        let now = web_time::Instant::now();
+
+        container.update_displayed_tiles_at(needed.clone(), 1, now);
+        assert_eq!(
+            container.stats(),
+            TileCacheStats {
+                displayed: 2,
+                fading: 0,
+                styles: 1,
+                substituted: 0,
+            }
+        );
+
+        // Tiles of the previous style fade out behind the tiles of the new one.
+        container.update_displayed_tiles_at(needed.clone(), 2, now + Duration::from_millis(100));
+        container.update_displayed_tiles_at(needed.clone(), 2, now + Duration::from_millis(600));
+        assert_eq!(
+            container.stats(),
+            TileCacheStats {
+                displayed: 4,
+                fading: 2,
+                styles: 2,
+                substituted: 0,
+            }
+        );
+
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        let parent = WrappingTileIndex::new(0, 0, 2);
+        container.tile_provider().add_tile(parent.into());
+        container.update_displayed_tiles([parent], ());
+
+        let children = [
+            WrappingTileIndex::new(0, 0, 3),
+            WrappingTileIndex::new(1, 1, 3),
+        ];
+        for _ in 0..2 {
+            container.invalidate_last_update();
+            container.update_displayed_tiles(children, ());
+            assert_eq!(container.stats().substituted, 2);
+        }
+        assert_eq!(container.stats().displayed, 1);
+    }
+
+    /// Clock that only moves when it is advanced.
+    #[test]
+    fn custom_clock() {
+        let clock = Arc::new(ManualClock::new());
+        let container = test_container();
+        container.set_clock(clock.clone());
+        container.set_fade_in_duration(Duration::from_millis(300));
+        let index = WrappingTileIndex::new(0, 0, 1);
+        container.tile_provider().add_tile(index.into());
+        let opacity =
+            |container: &TilesContainer<(), TestProvider>| container.displayed_tiles()[0].opacity;
+
+        assert!(container.update_displayed_tiles([index], ()));
+        assert!(container.displayed_tiles().is_empty());
+
+        clock.advance(Duration::from_millis(150));
+        assert!(container.update_displayed_tiles([index], ()));
+        assert_eq!(opacity(&container), 0.5);
+
+        // Without the clock moving the result of the update is the same.
+        container.invalidate_last_update();
+        container.update_displayed_tiles([index], ());
+        assert_eq!(opacity(&container), 0.5);
+
+        clock.advance(Duration::from_millis(150));
+        container.update_displayed_tiles([index], ());
+        assert_eq!(opacity(&container), 1.0);
+    }
+
+    #[test]
+    fn fade_in_ends_at_duration() {
+        let clock = Arc::new(ManualClock::new());
+        let container = test_container();
+        container.set_clock(clock.clone());
+        container.set_fade_in_duration(Duration::from_millis(300));
+        let index = WrappingTileIndex::new(0, 0, 1);
+        container.tile_provider().add_tile(index.into());
+        let opacity =
+            |container: &TilesContainer<(), TestProvider>| container.tiles()[&(index, ())].opacity;
+
+        container.update_displayed_tiles([index], ());
+        clock.advance(Duration::from_millis(298));
+        assert!(container.update_displayed_tiles([index], ()));
+        assert!(opacity(&container) < 1.0);
+
+        // The next update is far enough not to count as the same frame.
+        clock.advance(SAME_FRAME_INTERVAL);
+        assert!(!container.update_displayed_tiles([index], ()));
+        assert_eq!(opacity(&container), 1.0);
+    }
+
+    #[test]
+    fn layer_opacity() {
+        let clock = Arc::new(ManualClock::new());
+        let container = test_container();
+        container.set_clock(clock.clone());
+        container.set_fade_in_duration(Duration::from_millis(300));
+        let index = WrappingTileIndex::new(0, 0, 1);
+        container.tile_provider().add_tile(index.into());
+
+        container.update_displayed_tiles([index], ());
+        clock.advance(Duration::from_millis(150));
+        container.update_displayed_tiles([index], ());
+        let fading = container.tiles()[&(index, ())].clone();
+
+        // Changing the layer opacity mid-fade does not move the fade timeline.
+        container.set_layer_opacity(0.5);
+        assert_eq!(container.draw_opacity(&fading), fading.opacity * 0.5);
+        container.invalidate_last_update();
+        container.update_displayed_tiles([index], ());
+        assert_eq!(container.tiles()[&(index, ())].opacity, fading.opacity);
+
+        clock.advance(Duration::from_millis(150));
+        container.update_displayed_tiles([index], ());
+        let tile = &container.tiles()[&(index, ())];
+        assert_eq!(tile.opacity, 1.0);
+        assert_eq!(container.draw_opacity(tile), 0.5);
+
+        container.set_layer_opacity(2.0);
+        assert_eq!(container.layer_opacity(), 1.0);
+    }
+
+    #[test]
+    fn set_tile_provider() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        let first = WrappingTileIndex::new(0, 0, 1);
+        let second = WrappingTileIndex::new(1, 0, 1);
+        container.tile_provider().add_tile(first.into());
+        container.update_displayed_tiles([first], ());
+
+        let replacement = TestProvider::default();
+        replacement.add_tile(second.into());
+        container.set_tile_provider(replacement, false);
+        container.update_displayed_tiles([first, second], ());
+        assert_eq!(container.tile_provider().request_count(second.into()), 1);
+        assert!(!container.tiles()[&(first, ())].is_hidden());
+        assert!(!container.tiles()[&(second, ())].is_hidden());
+
+        let replacement = TestProvider::default();
+        replacement.add_tile(first.into());
+        container.set_tile_provider(replacement, true);
+        assert!(container.tiles().is_empty());
+
+        container.update_displayed_tiles([first], ());
+        assert_eq!(container.tile_provider().request_count(first.into()), 1);
+        assert!(container.tiles().contains_key(&(first, ())));
+    }
+
+    #[test]
+    fn tile_delta() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        let row = indices(3, 4);
+        for index in &row {
+            container.tile_provider().add_tile((*index).into());
+        }
+
+        container.update_displayed_tiles(row[..2].to_vec(), ());
+        assert_eq!(container.last_delta(), None);
+
+        container.set_record_delta(true);
+        container.update_displayed_tiles(row[1..3].to_vec(), ());
+        assert_eq!(
+            container.last_delta(),
+            Some(TileDelta {
+                added: vec![(row[2], ())],
+                removed: vec![(row[0], ())],
+            })
+        );
+
+        container.update_displayed_tiles(row[2..4].to_vec(), ());
+        assert_eq!(
+            container.last_delta(),
+            Some(TileDelta {
+                added: vec![(row[3], ())],
+                removed: vec![(row[1], ())],
+            })
+        );
+
+        container.set_record_delta(false);
+        assert_eq!(container.last_delta(), None);
+    }
+
+    #[test]
+    fn collect_renderables() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        let coarse = WrappingTileIndex::new(0, 0, 2);
+        let fine = [
+            WrappingTileIndex::new(1, 0, 3),
+            WrappingTileIndex::new(0, 0, 3),
+        ];
+        container.tile_provider().add_tile(coarse.into());
+        container.tile_provider().add_tile(fine[1].into());
+
+        container.update_displayed_tiles([coarse], ());
+        container.update_displayed_tiles(fine, ());
+        container.set_layer_opacity(0.5);
+
+        let snapshot = container.collect_renderables();
+        let indices: Vec<_> = snapshot.iter().map(|(index, _, _)| *index).collect();
+        assert_eq!(indices, vec![coarse, fine[1]]);
+        assert!(snapshot.iter().all(|(_, _, opacity)| *opacity == 0.5));
+
+        // The coarse tile stays under the new one for the frame it appears in.
+        container.tile_provider().add_tile(fine[0].into());
+        container.update_displayed_tiles(fine, ());
+        container.invalidate_last_update();
+        container.update_displayed_tiles(fine, ());
+        container.set_layer_opacity(1.0);
+        let updated: Vec<_> = container
+            .collect_renderables()
+            .into_iter()
+            .map(|(index, _, _)| index)
+            .collect();
+        assert_eq!(updated, vec![fine[1], fine[0]]);
+
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].0, coarse);
+        assert!(snapshot.iter().all(|(_, _, opacity)| *opacity == 0.5));
+    }
+
+    #[test]
+    fn is_animating() {
+        let clock = Arc::new(ManualClock::new());
+        let container = test_container();
+        container.set_clock(clock.clone());
+        container.set_fade_in_duration(Duration::from_millis(300));
+        let index = WrappingTileIndex::new(0, 0, 1);
+        container.tile_provider().add_tile(index.into());
+        assert!(!container.is_animating());
+
+        container.update_displayed_tiles([index], ());
+        assert!(container.is_animating());
+
+        clock.advance(Duration::from_millis(150));
+        assert!(container.is_animating());
+        container.set_animations_paused(true);
+        assert!(!container.is_animating());
+        container.set_animations_paused(false);
+
+        clock.advance(Duration::from_millis(300));
+        assert!(!container.is_animating());
+    }
+
+    #[test]
+    fn fade_in_linear_space() {
+        let clock = Arc::new(ManualClock::new());
+        let index = WrappingTileIndex::new(0, 0, 1);
+        let mid_fade_opacity = |linear: bool| {
+            let container = test_container();
+            container.set_clock(clock.clone());
+            container.set_fade_in_duration(Duration::from_millis(200));
+            container.set_fade_in_linear_space(linear);
+            container.tile_provider().add_tile(index.into());
+
+            container.update_displayed_tiles([index], ());
+            clock.advance(Duration::from_millis(100));
+            container.update_displayed_tiles([index], ());
+            let opacity = container.tiles()[&(index, ())].opacity;
+            opacity
+        };
+
+        // Half of the white tile coverage over black in linear light is 0.735 in sRGB, so the
+        // tile is drawn brighter than with the plain sRGB blending.
+        assert_eq!(mid_fade_opacity(false), 0.5);
+        assert_abs_diff_eq!(mid_fade_opacity(true), 0.7354, epsilon = 1e-4);
+
+        assert_eq!(linear_to_srgb(0.0), 0.0);
+        assert_abs_diff_eq!(linear_to_srgb(1.0), 1.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn paused_animations() {
+        let clock = Arc::new(ManualClock::new());
+        let container = test_container();
+        container.set_clock(clock.clone());
+        container.set_fade_in_duration(Duration::from_secs(1));
+        let index = WrappingTileIndex::new(0, 0, 1);
+        container.tile_provider().add_tile(index.into());
+        let opacity =
+            |container: &TilesContainer<(), TestProvider>| container.tiles()[&(index, ())].opacity;
+
+        container.update_displayed_tiles([index], ());
+        clock.advance(Duration::from_millis(250));
+        assert!(container.update_displayed_tiles([index], ()));
+        assert_eq!(opacity(&container), 0.25);
+
+        container.set_animations_paused(true);
+        assert!(container.animations_paused());
+        assert_eq!(container.next_fade_deadline(), None);
+        for _ in 0..3 {
+            clock.advance(Duration::from_millis(500));
+            assert!(!container.update_displayed_tiles([index], ()));
+            assert_eq!(opacity(&container), 0.25);
+        }
+
+        // The fade continues from the opacity it was paused at.
+        container.set_animations_paused(false);
+        assert!(container.update_displayed_tiles([index], ()));
+        assert_eq!(opacity(&container), 0.25);
+
+        clock.advance(Duration::from_millis(250));
+        assert!(container.update_displayed_tiles([index], ()));
+        assert_eq!(opacity(&container), 0.5);
+        assert_eq!(
+            container.next_fade_deadline(),
+            Some(clock.now() + Duration::from_millis(500))
+        );
+    }
+
+    #[test]
+    fn retry_backoff() {
+        let container = test_container();
+        container.set_retry_backoff(Duration::from_secs(1));
+        let index = WrappingTileIndex::new(0, 0, 3);
+        let tile = TileIndex::from(index);
+        container.tile_provider().fail_tile(tile);
+        let failure = |container: &TilesContainer<(), TestProvider>| {
+            container.retry.failures.lock().get(&(index, ())).copied()
+        };
+        let now = web_time::Instant::now();
+
+        container.update_displayed_tiles_at([index], (), now);
+        assert_eq!(container.tile_provider().request_count(tile), 1);
+        let first = failure(&container).unwrap();
+        assert_eq!(first.count, 1);
+        assert_eq!(first.retry_at, Some(now + Duration::from_secs(1)));
+
+        // The tile is not requested until the backoff is over.
+        container.update_displayed_tiles_at([index], (), now + Duration::from_millis(500));
+        assert_eq!(container.tile_provider().request_count(tile), 1);
+
+        // Then the provider forgets the failure and the tile is loaded again.
+        container.update_displayed_tiles_at([index], (), now + Duration::from_secs(1));
+        assert_eq!(container.tile_provider().request_count(tile), 2);
+        assert!(!container.tile_provider().is_failed(tile, ()));
+        assert_eq!(failure(&container).unwrap().retry_at, None);
+
+        // Every next failure doubles the backoff.
+        container.tile_provider().fail_tile(tile);
+        let failed_at = now + Duration::from_millis(1100);
+        container.update_displayed_tiles_at([index], (), failed_at);
+        let second = failure(&container).unwrap();
+        assert_eq!(second.count, 2);
+        assert_eq!(second.retry_at, Some(failed_at + Duration::from_secs(2)));
+
+        // A successful load clears the failure.
+        container.tile_provider().add_tile(tile);
+        container.update_displayed_tiles_at([index], (), failed_at + Duration::from_secs(2));
+        assert!(failure(&container).is_none());
+        assert_eq!(container.tiles().len(), 1);
+    }
+
+    #[test]
+    fn request_debounce() {
+        let container = test_container();
+        container.set_request_debounce(Some(Duration::from_millis(100)));
+        let tiles: Vec<_> = (0..5).map(|x| WrappingTileIndex::new(x, 0, 3)).collect();
+        let t0 = web_time::Instant::now();
+        let after = |millis| t0 + Duration::from_millis(millis);
+        let settled = |requests: &[WrappingTileIndex], now| {
+            let mut requests = requests.to_vec();
+            let deferred = container.defer_requests(&mut requests, (), now);
+            (requests, deferred)
+        };
+
+        // Three rapid updates within the debounce interval.
+        assert_eq!(settled(&tiles[0..2], t0), (vec![], true));
+        assert_eq!(settled(&tiles[2..4], after(30)), (vec![], true));
+        assert_eq!(settled(&tiles[3..5], after(60)), (vec![], true));
+
+        // Each tile is requested once it has been requested for the whole interval.
+        assert_eq!(settled(&tiles[3..5], after(130)), (vec![tiles[3]], true));
+        assert_eq!(settled(&tiles[4..5], after(160)), (vec![tiles[4]], false));
+        assert!(container
+            .debounce
+            .deferred
+            .lock()
+            .contains_key(&(tiles[4], ())));
+
+        // The tiles that are not requested anymore are forgotten.
+        assert_eq!(settled(&tiles[0..1], after(170)), (vec![], true));
+        assert_eq!(container.debounce.deferred.lock().len(), 1);
+
+        // The tiles the provider has are displayed without delay.
+        container.set_fade_in_duration(Duration::ZERO);
+        container.tile_provider().add_tile(tiles[1].into());
+        assert!(container.update_displayed_tiles_at([tiles[1]], (), after(180)));
+        assert_eq!(container.displayed_tiles().len(), 1);
+        assert_eq!(container.tile_provider().request_count(tiles[1].into()), 1);
+    }
+
+    #[test]
+    fn setters_invalidate_last_update() {
+        let clock = Arc::new(ManualClock::new());
+        let container = test_container();
+        container.set_clock(clock);
+        let index = WrappingTileIndex::new(0, 0, 3);
+        let request_count = || container.tile_provider().request_count(index.into());
+
+        // The missing tile is requested again by every update that is not skipped.
+        container.update_displayed_tiles([index], ());
+        container.update_displayed_tiles([index], ());
+        assert_eq!(request_count(), 1);
+
+        let setters: [&dyn Fn(); 4] = [
+            &|| container.set_request_debounce(None),
+            &|| container.set_layer_opacity(0.5),
+            &|| container.set_eager_first_frame(true),
+            &|| container.set_prefetch_ring(1),
+        ];
+        for (i, setter) in setters.iter().enumerate() {
+            setter();
+            container.update_displayed_tiles([index], ());
+            assert_eq!(request_count(), i + 2);
+        }
+    }
+
+    #[test]
+    fn style_transition() {
+        let container = TilesContainer::new(TileSchema::web(18), StyledProvider);
+        container.set_fade_in_duration(Duration::from_secs(1));
+        let needed = indices(1, 2);
+        let now = web_time::Instant::now();
+        let opacities = |container: &TilesContainer<u8, StyledProvider>, style_id: u8| {
+            container
+                .displayed_tiles()
+                .into_iter()
+                .filter(|tile| tile.style_id == style_id)
+                .map(|tile| tile.opacity)
+                .collect::<Vec<_>>()
+        };
+
+        container.update_displayed_tiles_at(needed.clone(), 1, now);
+        container.update_displayed_tiles_at(needed.clone(), 1, now + Duration::from_secs(2));
+        assert_eq!(opacities(&container, 1), vec![1.0, 1.0]);
+
+        container.begin_style_transition(1, 2);
+        let start = now + Duration::from_secs(3);
+        assert!(container.update_displayed_tiles_at(needed.clone(), 2, start));
+        assert!(container.update_displayed_tiles_at(
+            needed.clone(),
+            2,
+            start + Duration::from_millis(500)
+        ));
+
+        // Both styles are displayed mid-fade, one fading in and the other one out.
+        for opacity in opacities(&container, 1) {
+            assert_abs_diff_eq!(opacity, 0.5, epsilon = 0.01);
+        }
+        for opacity in opacities(&container, 2) {
+            assert_abs_diff_eq!(opacity, 0.5, epsilon = 0.01);
+        }
+        assert_eq!(container.stats().styles, 2);
+        assert_eq!(container.stats().fading, 4);
+
+        container.update_displayed_tiles_at(needed.clone(), 2, start + Duration::from_secs(1));
+        assert!(opacities(&container, 1).is_empty());
+        assert_eq!(opacities(&container, 2), vec![1.0, 1.0]);
+    }
+
+    #[test]
+    fn invalidation() {
+        let container = TilesContainer::new(TileSchema::web(18), StyledProvider);
+        container.set_fade_in_duration(Duration::ZERO);
+        container.set_fade_out_duration(Duration::from_secs(1));
+        let first = WrappingTileIndex::new(0, 0, 1);
+        let second = WrappingTileIndex::new(1, 0, 1);
+        let keys = |container: &TilesContainer<u8, StyledProvider>| {
+            let mut keys: Vec<_> = container.tiles().keys().copied().collect();
+            keys.sort_by_key(|(index, style_id)| (*style_id, index.x));
+            keys
+        };
+
+        container.update_displayed_tiles([first, second], 1);
+        container.update_displayed_tiles([first, second], 2);
+        assert_eq!(keys(&container).len(), 4);
+
+        assert!(container.invalidate_style(1));
+        assert_eq!(keys(&container), vec![(first, 2), (second, 2)]);
+        assert!(!container.invalidate_style(1));
+
+        assert!(container.invalidate_index(first));
+        assert_eq!(keys(&container), vec![(second, 2)]);
+
+        container.update_displayed_tiles([first], 1);
+        assert!(container.clear());
+        assert!(container.tiles().is_empty());
+        assert!(container.displayed_tiles().is_empty());
+        assert!(!container.clear());
+
+        container.update_displayed_tiles([first], 1);
+        assert_eq!(keys(&container), vec![(first, 1)]);
+    }
+
+    #[test]
+    fn antimeridian_wrapping() {
+        let wrapped = |x, display_x, z| WrappingTileIndex {
+            x,
+            y: 0,
+            z,
+            display_x,
+        };
+
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        container.set_min_retention(Duration::from_secs(60));
+        container.tile_provider().add_tile(TileIndex::new(0, 0, 1));
+        container.tile_provider().add_tile(TileIndex::new(1, 0, 1));
+
+        let west = [wrapped(1, -1, 1), wrapped(0, 0, 1), wrapped(1, 1, 1)];
+        container.update_displayed_tiles(west, ());
+        assert_eq!(container.displayed_tiles().len(), 3);
+
+        let east = [wrapped(0, 0, 1), wrapped(1, 1, 1), wrapped(0, 2, 1)];
+        container.update_displayed_tiles(east, ());
+        let mut displayed: Vec<_> = container
+            .displayed_tiles()
+            .iter()
+            .map(|tile| tile.index)
+            .collect();
+        displayed.sort_by_key(|index| index.display_x);
+        assert_eq!(displayed, east);
+
+        let tiles = container.tiles();
+        for index in west.iter().chain(&east) {
+            assert!(tiles.contains_key(&(*index, ())), "{index:?} is dropped");
+        }
+        drop(tiles);
+
+        // Only the copy of the parent tile at the same wrap offset substitutes the missing tile.
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        container.tile_provider().add_tile(TileIndex::new(0, 0, 0));
+        container.update_displayed_tiles([wrapped(0, -1, 0), wrapped(0, 0, 0)], ());
+
+        container.update_displayed_tiles([wrapped(1, -1, 1)], ());
+        let displayed = container.displayed_tiles();
+        assert_eq!(displayed.len(), 1);
+        assert_eq!(displayed[0].index, wrapped(0, -1, 0));
+    }
+
+    #[test]
+    fn substitution_depth() {
+        let displayed_after_zoom_jump = |parents: u32| {
+            let container = test_container();
+            container.set_fade_in_duration(Duration::ZERO);
+            container.set_substitution_parents(parents);
+            container.tile_provider().add_tile(TileIndex::new(0, 0, 2));
+            container.update_displayed_tiles([WrappingTileIndex::new(0, 0, 2)], ());
+
+            container.update_displayed_tiles([WrappingTileIndex::new(0, 0, 4)], ());
+            container.displayed_tiles()
+        };
+
+        assert!(displayed_after_zoom_jump(1).is_empty());
+        let displayed = displayed_after_zoom_jump(2);
+        assert_eq!(displayed.len(), 1);
+        assert_eq!(displayed[0].index, WrappingTileIndex::new(0, 0, 2));
+    }
+
+    #[test]
+    fn substitution_prefers_children() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        container.set_min_retention(Duration::from_secs(3600));
+        let parent = WrappingTileIndex::new(0, 0, 2);
+        let children: Vec<_> = (0..2)
+            .flat_map(|x| (0..2).map(move |y| WrappingTileIndex::new(x, y, 4)))
+            .collect();
+        container.tile_provider().add_tile(parent.into());
+        container.update_displayed_tiles([parent], ());
+        for child in &children {
+            container.tile_provider().add_tile((*child).into());
+        }
+        container.update_displayed_tiles(children.clone(), ());
+
+        container.update_displayed_tiles([WrappingTileIndex::new(0, 0, 3)], ());
+        let mut displayed: Vec<_> = container
+            .displayed_tiles()
+            .iter()
+            .map(|tile| tile.index)
+            .collect();
+        displayed.sort_by_key(|index| (index.x, index.y));
+        assert_eq!(displayed, children);
+
+        container.set_substitution_children(0);
+        container.update_displayed_tiles([WrappingTileIndex::new(0, 0, 3)], ());
+        let displayed = container.displayed_tiles();
+        assert_eq!(displayed.len(), 1);
+        assert_eq!(displayed[0].index, parent);
+    }
+
+    #[test]
+    fn max_cached_tiles() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        container.set_min_retention(Duration::from_secs(3600));
+        container.set_max_cached_tiles(3);
+
+        let coarse = WrappingTileIndex::new(0, 0, 2);
+        let tile = |x| WrappingTileIndex::new(x, 0, 3);
+        for index in [coarse, tile(4), tile(5), tile(6)] {
+            container.tile_provider().add_tile(index.into());
+        }
+
+        container.update_displayed_tiles([coarse], ());
+        container.update_displayed_tiles([tile(4)], ());
+        container.update_displayed_tiles([tile(5)], ());
+        assert_eq!(container.tiles().len(), 3);
+
+        // The coarse tile is the oldest one, but it is kept as a substitute for the missing tile.
+        container.update_displayed_tiles([tile(0), tile(6)], ());
+        let mut cached: Vec<_> = container.tiles().keys().map(|key| key.0).collect();
+        cached.sort_by_key(|index| (index.z, index.x));
+        assert_eq!(cached, vec![coarse, tile(5), tile(6)]);
+    }
+
+    #[test]
+    fn max_cache_bytes() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        container.set_min_retention(Duration::from_secs(3600));
+        container.set_max_cache_bytes(250);
+
+        // Every second tile, so that the previous ones are not used as substitutes.
+        let tile = |x| WrappingTileIndex::new(x, 0, 3);
+        for x in [0, 2, 4] {
+            container
+                .tile_provider()
+                .add_tile_with_size(tile(x).into(), 100);
+        }
+
+        container.update_displayed_tiles([tile(0)], ());
+        container.update_displayed_tiles([tile(2)], ());
+        assert_eq!(container.memory_usage(), 200);
+
+        container.update_displayed_tiles([tile(4)], ());
+        let cached: Vec<_> = container.tiles().keys().map(|key| key.0).collect();
+        assert_eq!(cached, vec![tile(2), tile(4)]);
+        assert_eq!(container.memory_usage(), 200);
+    }
+
+    #[test]
+    fn max_displayed_levels() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        container.set_min_retention(Duration::from_secs(3600));
+        container.set_max_displayed_levels(2);
+        for z in 1..=5 {
+            container.tile_provider().add_tile(TileIndex::new(0, 0, z));
+            container.update_displayed_tiles([WrappingTileIndex::new(0, 0, z)], ());
+        }
+
+        container.update_displayed_tiles([WrappingTileIndex::new(0, 0, 6)], ());
+        let mut levels: Vec<u32> = container
+            .displayed_tiles()
+            .iter()
+            .map(|tile| tile.index.z)
+            .collect();
+        levels.sort();
+        assert_eq!(levels, vec![4, 5]);
+
+        container.set_max_displayed_levels(usize::MAX);
+        container.update_displayed_tiles([WrappingTileIndex::new(0, 0, 6)], ());
+        assert_eq!(container.displayed_tiles().len(), 5);
+    }
+
+    #[test]
+    fn error_policy() {
+        let displayed_after_failure = |policy: ErrorPolicy| {
+            let container = test_container();
+            container.set_fade_in_duration(Duration::ZERO);
+            container.set_on_tile_error(policy);
+            container.tile_provider().add_tile(TileIndex::new(0, 0, 2));
+            container.update_displayed_tiles([WrappingTileIndex::new(0, 0, 2)], ());
+
+            container.tile_provider().fail_tile(TileIndex::new(0, 0, 3));
+            container.update_displayed_tiles([WrappingTileIndex::new(0, 0, 3)], ());
+            container.displayed_tiles()
+        };
+
+        let displayed = displayed_after_failure(ErrorPolicy::KeepSubstitute);
+        assert_eq!(displayed.len(), 1);
+        assert_eq!(displayed[0].index, WrappingTileIndex::new(0, 0, 2));
+
+        let displayed = displayed_after_failure(ErrorPolicy::Blank);
+        assert!(displayed.is_empty());
+
+        let error_tile: Arc<dyn PackedBundle> = Arc::new(TestBundle(0));
+        let displayed = displayed_after_failure(ErrorPolicy::ErrorTile(error_tile.clone()));
+        assert_eq!(displayed.len(), 1);
+        assert_eq!(displayed[0].index, WrappingTileIndex::new(0, 0, 3));
+        assert!(Arc::ptr_eq(&displayed[0].bundle, &error_tile));
+        assert_eq!(displayed[0].opacity, 1.0);
+    }
+
+    #[test]
+    fn provider_tile_states() {
+        let index = WrappingTileIndex::new(0, 0, 3);
+        let container = || {
+            let container = test_container();
+            container.set_fade_in_duration(Duration::ZERO);
+            container.set_on_tile_error(ErrorPolicy::Blank);
+            container
+        };
+
+        // Present tiles are displayed right away.
+        let present = container();
+        present.tile_provider().add_tile(index.into());
+        assert!(present.update_displayed_tiles([index], ()));
+        assert_eq!(present.displayed_tiles().len(), 1);
+        assert!(present.is_fully_loaded());
+
+        // Loading tiles are requested again at every update until they are provided.
+        let loading = container();
+        assert!(!loading.update_displayed_tiles([index], ()));
+        loading.invalidate_last_update();
+        assert!(!loading.update_displayed_tiles([index], ()));
+        assert_eq!(loading.tile_provider().request_count(index.into()), 2);
+        assert!(loading.displayed_tiles().is_empty());
+        assert!(!loading.is_fully_loaded());
+
+        // Failed tiles are not going to be provided, so the view counts as loaded without them.
+        let failed = container();
+        failed.tile_provider().fail_tile(index.into());
+        assert!(!failed.update_displayed_tiles([index], ()));
+        assert!(failed.displayed_tiles().is_empty());
+        assert!(failed.is_fully_loaded());
+    }
+
+    /// Provider returning the tile states set by the test rather than bundles.
+    #[derive(Default)]
+    struct StateProvider {
+        states: Mutex<HashMap<TileIndex, TileState>>,
+        requested: Mutex<Vec<TileIndex>>,
+    }
+
+    impl TileProvider<()> for StateProvider {
+        fn get_tile(&self, index: TileIndex, style_id: ()) -> Option<Arc<dyn PackedBundle>> {
+            match self.tile_state(index, style_id) {
+                TileState::Present(bundle) => Some(bundle),
+                _ => None,
+            }
+        }
+
+        fn tile_state(&self, index: TileIndex, _style_id: ()) -> TileState {
+            self.requested.lock().push(index);
+            self.states
+                .lock()
+                .get(&index)
+                .cloned()
+                .unwrap_or(TileState::Loading)
+        }
+    }
+
+    #[test]
+    fn tile_state_effects() {
+        let parent = WrappingTileIndex::new(0, 0, 2);
+        let index = WrappingTileIndex::new(0, 0, 3);
+        let container = |state: TileState| {
+            let container = TilesContainer::new(TileSchema::web(18), StateProvider::default());
+            container.set_fade_in_duration(Duration::ZERO);
+            container.set_on_tile_error(ErrorPolicy::Blank);
+            let parent_bundle: Arc<dyn PackedBundle> = Arc::new(TestBundle(0));
+            let provider = container.tile_provider();
+            provider
+                .states
+                .lock()
+                .insert(parent.into(), TileState::Present(parent_bundle));
+            provider.states.lock().insert(index.into(), state);
+            drop(provider);
+
+            container.update_displayed_tiles([parent], ());
+            container
+        };
+        let displayed = |container: &TilesContainer<(), StateProvider>| {
+            container
+                .displayed_tiles()
+                .iter()
+                .map(|tile| tile.index)
+                .collect::<Vec<_>>()
+        };
+
+        // Present tiles are displayed right away.
+        let present = container(TileState::Present(Arc::new(TestBundle(1))));
+        assert!(present.update_displayed_tiles([index], ()));
+        assert!(displayed(&present).contains(&index));
+        assert!(present.is_fully_loaded());
+
+        // Loading tiles are substituted and requested again at every update.
+        let loading = container(TileState::Loading);
+        assert!(!loading.update_displayed_tiles([index], ()));
+        loading.invalidate_last_update();
+        assert!(!loading.update_displayed_tiles([index], ()));
+        assert_eq!(displayed(&loading), vec![parent]);
+        let requested = loading.tile_provider().requested.lock().clone();
+        let requests = requested.iter().filter(|r| **r == TileIndex::from(index));
+        assert_eq!(requests.count(), 2);
+        assert!(!loading.is_fully_loaded());
+
+        // Missing tiles are not going to be provided, so they are not substituted and the view
+        // counts as loaded without them.
+        let missing = container(TileState::Missing);
+        assert!(!missing.update_displayed_tiles([index], ()));
+        assert!(displayed(&missing).is_empty());
+        assert!(missing.is_fully_loaded());
+
+        // Providers returning only bundles report the tiles they do not have as loading.
+        assert!(matches!(TileState::from(None), TileState::Loading));
+    }
+
+    #[test]
+    fn rekey_for_offset_keeps_cached_tiles() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+        let cached = WrappingTileIndex::new(0, 0, 3);
+        let new = WrappingTileIndex::new(1, 0, 3);
+        container.tile_provider().add_tile(cached.into());
+        container.update_displayed_tiles([cached], ());
+
+        container.rekey_for_offset(1);
+        assert_eq!(container.zoom_offset(), 1);
+        container.update_displayed_tiles([cached, new], ());
+
+        let provider = container.tile_provider();
+        assert_eq!(provider.request_count(cached.into()), 1);
+        assert_eq!(provider.request_count(TileIndex::new(1, 0, 4)), 1);
+        assert_eq!(provider.request_count(new.into()), 0);
+        assert!(container.tiles().contains_key(&(cached, ())));
+
+        container.rekey_for_offset(-4);
+        assert_eq!(container.filter_request(cached), None);
+    }
+
+    #[test]
+    fn update_timing() {
+        let container = test_container();
+        container.tile_provider().add_tile(TileIndex::new(0, 0, 3));
+
+        container.update_displayed_tiles(indices(3, 4), ());
+        assert_eq!(container.last_update_timing(), None);
+
+        container.set_record_timing(true);
+        container.invalidate_last_update();
+        container.update_displayed_tiles(indices(3, 4), ());
+        let timing = container.last_update_timing().expect("timing is recorded");
+        let total = timing.gather + timing.substitution + timing.fade + timing.rebuild;
+        assert!(total >= timing.gather);
+
+        container.set_record_timing(false);
+        assert_eq!(container.last_update_timing(), None);
+    }
+
+    struct TestCanvas;
+
+    impl Canvas for TestCanvas {
+        fn size(&self) -> Size {
+            Size::new(16.0, 16.0)
+        }
+
+        fn pack_bundle(
+            &self,
+            _bundle: &crate::render::render_bundle::RenderBundle,
+        ) -> Box<dyn PackedBundle> {
+            Box::new(TestBundle(0))
+        }
+
+        fn draw_bundles(
+            &mut self,
+            _bundles: &[crate::render::BundleToDraw],
+            _options: crate::render::RenderOptions,
+        ) {
+        }
+
+        fn draw_screen_sets(&mut self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn multi_layer_updater() {
+        let containers: Vec<_> = (0..3).map(|_| test_container()).collect();
+        let clock = Arc::new(ManualClock::new());
+        let index = WrappingTileIndex::new(16, 16, 5);
+        for container in &containers {
+            container.set_clock(clock.clone());
+            container.set_fade_in_duration(Duration::ZERO);
+        }
+        containers[0].tile_provider().add_tile(index.into());
+        // The settings of every container apply to its needed tiles.
+        containers[1].tile_provider().add_tile(index.into());
+        containers[1].set_max_zoom(4);
+        // The tile of the last container is missing.
+
+        let packed = Mutex::new(vec![]);
+        let mut updater = MultiLayerUpdater::new();
+        for container in &containers {
+            updater.add(container, (), |needed, _canvas| {
+                packed.lock().extend_from_slice(needed);
+            });
+        }
+
+        let resolution = containers[0].tile_schema().lod_resolution(5).unwrap();
+        let tile_size = resolution * 256.0;
+        let view =
+            MapView::new_projected(&Point2::new(tile_size / 2.0, -tile_size / 2.0), resolution)
+                .with_size(Size::new(16.0, 16.0));
+
+        assert!(updater.update(&view, &TestCanvas));
+        assert_eq!(*packed.lock(), vec![index, index]);
+        let request_count = |container: &TilesContainer<(), TestProvider>| {
+            container.tile_provider().request_count(index.into())
+        };
+        assert_eq!(request_count(&containers[0]), 1);
+        assert_eq!(containers[0].displayed_tiles()[0].index, index);
+        assert_eq!(request_count(&containers[1]), 0);
+        assert!(containers[1].displayed_tiles().is_empty());
+        assert_eq!(request_count(&containers[2]), 1);
+
+        // Rendering reuses the update however late it is done, but only once.
+        let container = &containers[2];
+        let needed = container.needed_indices(&view).unwrap();
+        clock.advance(Duration::from_secs(1));
+        container.update_displayed_tiles(needed.clone(), ());
+        assert_eq!(request_count(container), 1);
+
+        clock.advance(Duration::from_secs(1));
+        container.update_displayed_tiles(needed, ());
+        assert_eq!(request_count(container), 2);
+    }
+
+    #[test]
+    fn occluded_substitutes_are_dropped() {
+        let container = test_container();
+        container.set_fade_in_duration(Duration::ZERO);
+
+        let parent = WrappingTileIndex::new(1, 1, 2);
+        let children = [
+            WrappingTileIndex::new(2, 2, 3),
+            WrappingTileIndex::new(3, 2, 3),
+            WrappingTileIndex::new(2, 3, 3),
+            WrappingTileIndex::new(3, 3, 3),
+        ];
+        let missing_neighbour = WrappingTileIndex::new(4, 2, 3);
+        for index in std::iter::once(parent).chain(children) {
+            container.tile_provider().add_tile(index.into());
+        }
+
+        container.update_displayed_tiles([parent], ());
+        container.update_displayed_tiles(
+            children
+                .into_iter()
+                .chain(std::iter::once(missing_neighbour)),
+            (),
+        );
+
+        let tiles = container.tiles();
+        assert!(!tiles.contains_key(&(parent, ())));
+        assert!(children
+            .iter()
+            .all(|index| tiles.contains_key(&(*index, ()))));
+    }
+
+    #[test]
+    fn eager_first_frame() {
+        let coarse = WrappingTileIndex::new(0, 0, 0);
+        let needed = indices(4, 2);
+
+        let container = test_container();
+        container.tile_provider().add_tile(coarse.into());
+        container.update_displayed_tiles(needed.clone(), ());
+        assert!(container.displayed_tiles().is_empty());
+
+        let container = test_container();
+        container.set_eager_first_frame(true);
+        container.tile_provider().add_tile(coarse.into());
+        container.update_displayed_tiles(needed.clone(), ());
+        let displayed = container.displayed_tiles();
+        assert_eq!(displayed.len(), 1);
+        assert_eq!(displayed[0].index, coarse);
+        assert_eq!(displayed[0].opacity, 1.0);
+
+        // The coarsest tiles are displayed once they are loaded after the first update.
+        let container = test_container();
+        container.set_eager_first_frame(true);
+        container.update_displayed_tiles(needed.clone(), ());
+        assert!(container.displayed_tiles().is_empty());
+        assert!(container.is_first_frame_pending());
+
+        container.tile_provider().add_tile(coarse.into());
+        container.invalidate_last_update();
+        container.update_displayed_tiles(needed.clone(), ());
+        assert_eq!(container.displayed_tiles()[0].index, coarse);
+        assert!(!container.is_first_frame_pending());
+    }
+
+    #[test]
+    fn coarse_underlay_fades_after_fine_tiles_are_opaque() {
+        let container = test_container();
+        container.set_coarse_underlay_until_loaded(true);
+        container.set_fade_in_duration(Duration::ZERO);
+
+        let parent = WrappingTileIndex::new(1, 1, 2);
+        let children = [
+            WrappingTileIndex::new(2, 2, 3),
+            WrappingTileIndex::new(3, 2, 3),
+            WrappingTileIndex::new(2, 3, 3),
+            WrappingTileIndex::new(3, 3, 3),
+        ];
+        for index in std::iter::once(parent).chain(children) {
+            container.tile_provider().add_tile(index.into());
+        }
+
+        container.update_displayed_tiles([parent], ());
+
+        container.set_fade_in_duration(Duration::from_secs(100));
+        container.update_displayed_tiles(children, ());
+        assert_eq!(container.tiles()[&(parent, ())].opacity, 1.0);
+
+        for index in children {
+            let mut tiles = container.tiles.write();
+            let tile = tiles.get_mut(&(index, ())).unwrap();
+            tile.displayed_at -= Duration::from_secs(100);
+        }
+        container.invalidate_last_update();
+        assert!(container.update_displayed_tiles(children, ()));
+        {
+            let tiles = container.tiles();
+            assert!(children
+                .iter()
+                .all(|index| tiles[&(*index, ())].is_opaque()));
+            assert!(!tiles[&(parent, ())].is_hidden());
+            assert!(tiles[&(parent, ())].opacity > 0.0);
+        }
+
+        container.set_fade_in_duration(Duration::ZERO);
+        container.update_displayed_tiles(children, ());
+        assert!(!container.tiles().contains_key(&(parent, ())));
     }
 }
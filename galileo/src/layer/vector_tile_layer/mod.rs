@@ -21,7 +21,7 @@ use crate::layer::Layer;
 use crate::messenger::Messenger;
 use crate::render::render_bundle::RenderBundle;
 use crate::render::{BundleToDraw, Canvas, PackedBundle, PolygonPaint, RenderOptions};
-use crate::tile_schema::{TileIndex, TileSchema};
+use crate::tile_schema::{TileIndex, TileSchema, WrappingTileIndex};
 use crate::view::MapView;
 use crate::Color;
 
@@ -31,7 +31,7 @@ pub mod tile_provider;
 mod vector_tile;
 pub use builder::VectorTileLayerBuilder;
 
-use super::tiles::TilesContainer;
+use super::tiles::{Clock, TilesContainer};
 
 /// Vector tile layers use [tile providers](VectorTileProvider) to load prepared vector tiles, and then render them using
 /// specified [styles](VectorTileStyle).
@@ -68,18 +68,21 @@ impl Layer for VectorTileLayer {
             return;
         };
 
-        let displayed_tiles = self.displayed_tiles.tiles.lock();
-        let to_render: Vec<_> =
-            std::iter::once(BundleToDraw::with_opacity(&*background_bundle, 1.0))
-                .chain(displayed_tiles.values().filter_map(|v| {
-                    let bbox = self.tile_schema.tile_bbox(v.index)?;
-                    Some(BundleToDraw::new(
-                        &*v.bundle,
-                        v.opacity,
-                        Vector2::new(bbox.x_min() as f32, bbox.y_max() as f32),
-                    ))
-                }))
-                .collect();
+        let layer_opacity = self.displayed_tiles.layer_opacity();
+        let renderables = self.displayed_tiles.collect_renderables();
+        let to_render: Vec<_> = std::iter::once(BundleToDraw::with_opacity(
+            &*background_bundle,
+            layer_opacity,
+        ))
+        .chain(renderables.iter().filter_map(|(index, bundle, opacity)| {
+            let bbox = self.displayed_tiles.tile_schema().tile_bbox(*index)?;
+            Some(BundleToDraw::new(
+                &**bundle,
+                *opacity,
+                Vector2::new(bbox.x_min() as f32, bbox.y_max() as f32),
+            ))
+        }))
+        .collect();
 
         canvas.draw_bundles(&to_render, RenderOptions::default());
     }
@@ -139,17 +142,34 @@ impl VectorTileLayer {
         }
     }
 
-    fn update_displayed_tiles(&self, view: &MapView, canvas: &dyn Canvas) {
-        let Some(tile_iter) = self.tile_schema.iter_tiles(view) else {
-            return;
-        };
+    pub(crate) fn tile_container(&self) -> &TilesContainer<VtStyleId, VectorTileProvider> {
+        &self.displayed_tiles
+    }
 
-        let needed_indices: Vec<_> = tile_iter.collect();
-        let mut to_pack: Vec<TileIndex> = needed_indices.iter().map(|t| (*t).into()).collect();
+    pub(crate) fn style_id(&self) -> VtStyleId {
+        self.style_id
+    }
+
+    /// Packs the loaded tiles among the `needed` ones with the current style, so that the tile
+    /// container can display them.
+    pub(crate) fn pack_tiles(&self, needed: &[WrappingTileIndex], canvas: &dyn Canvas) {
+        let mut to_pack: Vec<TileIndex> = needed
+            .iter()
+            .filter_map(|index| self.displayed_tiles.filter_request(*index))
+            .map(TileIndex::from)
+            .collect();
         to_pack.dedup();
 
         self.tile_provider
             .pack_tiles(&to_pack, self.style_id, canvas);
+    }
+
+    fn update_displayed_tiles(&self, view: &MapView, canvas: &dyn Canvas) {
+        let Some(needed_indices) = self.displayed_tiles.needed_indices(view) else {
+            return;
+        };
+
+        self.pack_tiles(&needed_indices, canvas);
         let requires_redraw = self
             .displayed_tiles
             .update_displayed_tiles(needed_indices, self.style_id);
@@ -169,13 +189,34 @@ impl VectorTileLayer {
         if let Some(curr_style) = self.tile_provider.get_style(self.style_id) {
             *self.prev_background.lock() = Some(PreviousBackground {
                 color: curr_style.background,
-                replaced_at: web_time::Instant::now(),
+                replaced_at: self.displayed_tiles.now(),
             });
         }
         self.tile_provider.drop_style(self.style_id);
+        self.displayed_tiles
+            .begin_style_transition(self.style_id, new_style_id);
         self.style_id = new_style_id;
     }
 
+    /// Sets the clock the fade animations of the tiles and the background are driven by, so that
+    /// the frames can be rendered for a fixed time. The real time is used by default.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.displayed_tiles.set_clock(clock);
+    }
+
+    /// Sets the opacity the whole layer, including its background, is drawn with. The fade in of
+    /// the tiles is applied on top of it.
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.displayed_tiles.set_layer_opacity(opacity);
+        self.tile_provider.request_redraw();
+    }
+
+    /// Removes the tiles of the current style from the displayed set, so that they are taken from
+    /// the tile provider again at the next redraw. Returns true if the layer should be redrawn.
+    pub fn invalidate_displayed_tiles(&self) -> bool {
+        self.displayed_tiles.invalidate_style(self.style_id)
+    }
+
     /// Returns features, visible in the layer at the given point with the given map view.
     pub fn get_features_at(
         &self,
@@ -201,7 +242,8 @@ impl VectorTileLayer {
                     continue;
                 };
 
-                let tile_resolution = lod_resolution * self.tile_schema.tile_width() as f64;
+                let tile_resolution =
+                    lod_resolution * self.tile_schema.tile_width_at(index.z) as f64;
 
                 let tile_point = Point2::new(
                     ((point.x() - tile_bbox.x_min()) / tile_resolution) as f32,
@@ -266,7 +308,9 @@ impl VectorTileLayer {
         let mut prev_background = self.prev_background.lock();
         let color = match *prev_background {
             Some(prev) => {
-                let k = web_time::Instant::now()
+                let k = self
+                    .displayed_tiles
+                    .now()
                     .duration_since(prev.replaced_at)
                     .as_secs_f32()
                     / self.fade_in_time().as_secs_f32();
@@ -290,6 +334,29 @@ impl VectorTileLayer {
         Some(canvas.pack_bundle(&bundle))
     }
 
+    /// Replaces the provider the tiles of the layer are loaded from, e.g. to switch from a local
+    /// cache to a remote server. The style of the layer is registered in the new provider.
+    ///
+    /// If `clear_cache` is set, the tiles of the old provider are removed at once. Otherwise they
+    /// stay displayed and are cross-faded to the tiles of the new provider as they are loaded.
+    pub fn set_provider(&mut self, mut provider: VectorTileProvider, clear_cache: bool) {
+        let style = self.style();
+        let new_style_id = provider.add_style((*style).clone());
+        provider.inherit_messenger(&self.tile_provider);
+
+        self.tile_provider.drop_style(self.style_id);
+        self.tile_provider = provider.clone();
+        self.displayed_tiles
+            .set_tile_provider(provider, clear_cache);
+        if !clear_cache {
+            self.displayed_tiles
+                .begin_style_transition(self.style_id, new_style_id);
+        }
+        self.style_id = new_style_id;
+
+        self.tile_provider.request_redraw();
+    }
+
     /// Returns the reference to the layer's tile provider.
     pub fn provider(&self) -> &VectorTileProvider {
         &self.tile_provider
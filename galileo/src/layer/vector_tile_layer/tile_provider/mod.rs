@@ -59,6 +59,10 @@ impl TileProvider<VtStyleId> for VectorTileProvider {
     fn get_tile(&self, index: TileIndex, style_id: VtStyleId) -> Option<Arc<dyn PackedBundle>> {
         VectorTileProvider::get_tile(self, index, style_id)
     }
+
+    fn is_failed(&self, index: TileIndex, style_id: VtStyleId) -> bool {
+        self.tiles.read().is_failed(index, style_id)
+    }
 }
 
 impl VectorTileProvider {
@@ -176,6 +180,13 @@ impl VectorTileProvider {
         self.messenger = Some(messenger.into());
     }
 
+    /// Uses the messenger of the `other` provider, if this provider does not have one.
+    pub(crate) fn inherit_messenger(&mut self, other: &VectorTileProvider) {
+        if self.messenger.is_none() {
+            self.messenger = other.messenger.clone();
+        }
+    }
+
     /// Notifies the messenger about a change to be processed by the map.
     // TODO: This method should not be here. This requires some refactoring.
     pub fn request_redraw(&self) {
@@ -190,6 +190,12 @@ impl TileStore {
         })
     }
 
+    pub fn is_failed(&self, index: TileIndex, style_id: VtStyleId) -> bool {
+        self.processed
+            .get(&(index, style_id))
+            .is_some_and(|entry| matches!(entry.prepared_tile, PreparedTileState::Error))
+    }
+
     pub fn get_mvt_tile(&self, index: TileIndex) -> Option<Arc<MvtTile>> {
         match self
             .mvt_tiles
@@ -40,10 +40,10 @@ impl VtProcessor {
         let lod_resolution = tile_schema.lod_resolution(index.z).ok_or_else(|| {
             GalileoError::Generic(format!("cannot get lod resolution for lod {}", index.z))
         })?;
-        let tile_resolution = lod_resolution * tile_schema.tile_width() as f64;
+        let tile_resolution = lod_resolution * tile_schema.tile_width_at(index.z) as f64;
 
-        let width = tile_schema.tile_width() as f64;
-        let height = tile_schema.tile_height() as f64;
+        let width = tile_schema.tile_width_at(index.z) as f64;
+        let height = tile_schema.tile_height_at(index.z) as f64;
         let bbox = Rect::new(0.0, 0.0, width * lod_resolution, -height * lod_resolution);
 
         let bounds = Polygon::new(
@@ -51,9 +51,49 @@ pub trait Canvas {
 pub trait PackedBundle: MaybeSend + MaybeSync {
     /// Used to convert from trait object into a specific type by the rendering backend.
     fn as_any(&self) -> &dyn Any;
+
+    /// Approximate size of the data held by the bundle (for example, GPU buffers and textures) in
+    /// bytes.
+    fn size_bytes(&self) -> u64 {
+        0
+    }
+
+    /// Whether all the pixels drawn by the bundle are fully opaque, so that the bundle can be
+    /// drawn without blending when it is drawn with full opacity.
+    ///
+    /// Bundles with semi-transparent content (e.g. translucent fills) must return `false`.
+    fn is_content_opaque(&self) -> bool {
+        true
+    }
 }
 
 /// Packed bundle that is ready to be renderred with the given parameters.
+///
+/// Opacity and offset are applied by the canvas at draw time, so the same packed bundle can be
+/// drawn with different parameters in every frame (e.g. while a tile is fading in) without being
+/// packed again.
+///
+/// ```
+/// use std::any::Any;
+///
+/// use galileo::render::{BundleToDraw, PackedBundle};
+///
+/// struct Tile;
+///
+/// impl PackedBundle for Tile {
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+/// }
+///
+/// let tile = Tile;
+/// let fading = BundleToDraw::with_opacity(&tile, 0.5);
+/// let opaque = BundleToDraw::with_opacity(&tile, 1.0);
+///
+/// assert_eq!(fading.opacity(), 0.5);
+/// assert_eq!(opaque.opacity(), 1.0);
+/// assert!(std::ptr::addr_eq(fading.bundle(), opaque.bundle()));
+/// ```
 pub struct BundleToDraw<'a> {
     bundle: &'a dyn PackedBundle,
     opacity: f32,
@@ -78,6 +118,29 @@ impl<'a> BundleToDraw<'a> {
             offset: Default::default(),
         }
     }
+
+    /// The packed bundle to draw.
+    pub fn bundle(&self) -> &'a dyn PackedBundle {
+        self.bundle
+    }
+
+    /// Returns true if the bundle must be blended with the content under it: either it is drawn
+    /// with partial opacity, or its content is not [opaque](PackedBundle::is_content_opaque).
+    ///
+    /// A canvas can disable blending for the bundles that do not require it.
+    pub fn requires_blending(&self) -> bool {
+        self.opacity < 1.0 || !self.bundle.is_content_opaque()
+    }
+
+    /// Opacity the bundle is drawn with, from `0.0` to `1.0`.
+    ///
+    /// A canvas multiplies the alpha of every pixel of the bundle by this value and blends the
+    /// result over the target in the target's (sRGB) color space. Any correction of the fades,
+    /// e.g. for blending in linear light, is already applied to the value, so the canvas must not
+    /// adjust it further.
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
 }
 
 /// Rendering options.
@@ -140,3 +203,33 @@ pub struct ImagePaint {
     /// opacity and this value represented in percents.
     pub opacity: u8,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestBundle {
+        opaque: bool,
+    }
+
+    impl PackedBundle for TestBundle {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn is_content_opaque(&self) -> bool {
+            self.opaque
+        }
+    }
+
+    #[test]
+    fn requires_blending() {
+        let opaque = TestBundle { opaque: true };
+        assert!(!BundleToDraw::with_opacity(&opaque, 1.0).requires_blending());
+        assert!(BundleToDraw::with_opacity(&opaque, 0.5).requires_blending());
+
+        let translucent = TestBundle { opaque: false };
+        assert!(BundleToDraw::with_opacity(&translucent, 1.0).requires_blending());
+        assert!(BundleToDraw::with_opacity(&translucent, 0.5).requires_blending());
+    }
+}
@@ -1240,6 +1240,7 @@ struct WgpuPackedBundle {
     image_buffers: Vec<WgpuImage>,
 
     screen_sets: Vec<Arc<Mutex<WgpuScreenSet>>>,
+    size_bytes: u64,
 }
 
 struct WgpuScreenSet {
@@ -1267,6 +1268,12 @@ struct WgpuDotBuffers {
     point_count: u32,
 }
 
+impl WgpuVertexBuffers {
+    fn size_bytes(&self) -> u64 {
+        self.vertex.size() + self.index.size()
+    }
+}
+
 impl WgpuPackedBundle {
     fn new(
         bundle: &RenderBundle,
@@ -1310,6 +1317,16 @@ impl WgpuPackedBundle {
             })
         };
 
+        let mut size_bytes = poly_buffers.size_bytes()
+            + clip_area_buffers
+                .as_ref()
+                .map_or(0, WgpuVertexBuffers::size_bytes)
+            + dot_buffers.as_ref().map_or(0, |dots| dots.buffer.size());
+        size_bytes += image_store
+            .iter()
+            .map(|image| image.byte_size() as u64)
+            .sum::<u64>();
+
         let textures: Vec<_> = image_store
             .iter()
             .map(|decoded_image| renderer.get_or_create_image_texture(decoded_image))
@@ -1355,10 +1372,12 @@ impl WgpuPackedBundle {
                         vertex: vertex_buffer,
                         index_count: buffers.indices.len() as u32,
                     };
+                    size_bytes += buffers.size_bytes();
 
                     WgpuScreenSetData::Vertex(buffers)
                 }
                 ScreenSetData::Image { vertices, bitmap } => {
+                    size_bytes += bitmap.byte_size() as u64;
                     let bind_group = renderer.get_or_create_image_texture(bitmap);
                     let image = renderer_targets
                         .pipelines
@@ -1384,6 +1403,7 @@ impl WgpuPackedBundle {
             image_buffers,
             dot_buffers,
             screen_sets,
+            size_bytes,
         }
     }
 
@@ -1422,6 +1442,15 @@ impl PackedBundle for WgpuPackedBundle {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn size_bytes(&self) -> u64 {
+        self.size_bytes
+    }
+
+    // Fills and images of the bundle can have any alpha, and the pipelines always blend anyway.
+    fn is_content_opaque(&self) -> bool {
+        false
+    }
 }
 
 #[repr(C)]
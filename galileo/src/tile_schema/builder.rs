@@ -2,26 +2,59 @@
 
 use core::f64;
 
-use galileo_types::cartesian::{Point2, Rect};
+use galileo_types::cartesian::{CartesianPoint2d, Point2, Rect};
+use galileo_types::geo::impls::projection::WebMercator;
+use galileo_types::geo::impls::GeoPoint2d;
+use galileo_types::geo::{NewGeoPoint, Projection};
 
-use super::schema::{TileSchema, VerticalDirection};
+use super::schema::{LodLookup, PartialEdgePolicy, TileSchema, VerticalDirection};
+
+/// Deepest z-level created when the levels are derived from a target resolution.
+const MAX_DERIVED_Z_LEVEL: u32 = 30;
 
 /// Builder for [`TileSchema`].
 ///
 /// The builder validates all the input parameters and guarantees that the created schema is valid.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TileSchemaBuilder {
     origin: Point2,
     bounds: Rect,
+    /// Part of the bounds the tiles actually exist in. The grid and the resolutions are still
+    /// derived from the whole bounds.
+    clip: Option<Rect>,
     lods: Lods,
     tile_width: u32,
     tile_height: u32,
+    tile_sizes: Vec<(u32, u32)>,
     y_direction: VerticalDirection,
+    matrix_sizes: Vec<(u32, u32, u32)>,
+    tile_offset: (f64, f64),
+    partial_edge_policy: PartialEdgePolicy,
+    index_base: u32,
+    contiguous_levels_required: bool,
+    power_of_two_levels_required: bool,
+    dpi_scale: f32,
+    tile_inset: f32,
+    top_level_tiles: Option<(u32, u32)>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum Lods {
-    Logarithmic(Vec<u32>),
+    /// Levels with the resolution divided by `factor` at each next level.
+    Logarithmic {
+        z_levels: Vec<u32>,
+        factor: f64,
+    },
+    /// Logarithmic levels from 0 down to the first one not coarser than the given resolution.
+    ToResolution(f64),
+    Arithmetic {
+        start: f64,
+        step: f64,
+        count: u32,
+    },
+    Explicit(Vec<f64>),
+    /// Resolutions copied from an existing schema, with `NaN` for the missing levels.
+    Resolved(Vec<f64>),
 }
 
 /// Errors that can occur during building a [`TileSchema`].
@@ -39,31 +72,361 @@ pub enum TileSchemaError {
         /// Tile height
         height: u32,
     },
+
+    /// Bounds cannot be covered by a whole number of tiles at the top z-level
+    #[error("Aspect ratio of {width}x{height} tiles is inconsistent with the schema bounds")]
+    InconsistentAspectRatio {
+        /// Tile width
+        width: u32,
+        /// Tile height
+        height: u32,
+    },
+
+    /// Invalid tile offset
+    #[error("Invalid tile offset: ({dx}, {dy})")]
+    InvalidTileOffset {
+        /// Offset along x index
+        dx: f64,
+        /// Offset along y index
+        dy: f64,
+    },
+
+    /// Invalid tile inset
+    #[error("Invalid tile inset: {pixels}")]
+    InvalidTileInset {
+        /// Inset in pixels
+        pixels: f32,
+    },
+
+    /// Invalid explicit tile matrix size
+    #[error("Invalid tile matrix size for z-level {z}: {width}x{height}")]
+    InvalidMatrixSize {
+        /// Z-level
+        z: u32,
+        /// Number of tile columns
+        width: u32,
+        /// Number of tile rows
+        height: u32,
+    },
+
+    /// The same z-level is given more than once
+    #[error("Duplicate z-level {z}")]
+    DuplicateZLevel {
+        /// Z-level
+        z: u32,
+    },
+
+    /// Z-levels have a gap while contiguous levels are required
+    #[error("Z-level {missing} is missing")]
+    NonContiguousZLevels {
+        /// The first missing z-level
+        missing: u32,
+    },
+
+    /// Resolutions of adjacent z-levels do not differ by a factor of 2 while it is required
+    #[error("Resolution of z-level {z} is not half of the previous level resolution")]
+    NonPowerOfTwoLevels {
+        /// Z-level
+        z: u32,
+    },
+
+    /// Tile matrix set with the given identifier is not found in a WMTS capabilities document
+    #[error("Tile matrix set {identifier} is not found")]
+    TileMatrixSetNotFound {
+        /// Identifier of the tile matrix set
+        identifier: String,
+    },
+
+    /// WMTS capabilities document cannot be parsed or describes a tile matrix set that cannot be
+    /// represented by a tile schema
+    #[error("Invalid WMTS capabilities: {message}")]
+    InvalidWmtsCapabilities {
+        /// Description of the problem
+        message: String,
+    },
+
+    /// Bounds of the schema are empty, not finite or have the minimum corner above the maximum one
+    #[error("Invalid schema bounds: {width}x{height}")]
+    InvalidBounds {
+        /// Width of the bounds
+        width: f64,
+        /// Height of the bounds
+        height: f64,
+    },
+
+    /// Origin is not at the corner of the bounds the tiles are counted from
+    #[error("Origin ({x}, {y}) is not at the corner of the bounds matching the y direction")]
+    OriginOutsideBounds {
+        /// X coordinate of the origin
+        x: f64,
+        /// Y coordinate of the origin
+        y: f64,
+    },
+
+    /// DPI scale is not a finite positive number
+    #[error("Invalid DPI scale: {scale}")]
+    InvalidDpiScale {
+        /// Given scale
+        scale: f32,
+    },
+
+    /// Target resolution to derive the z-levels from is not a finite positive number
+    #[error("Invalid target resolution: {resolution}")]
+    InvalidTargetResolution {
+        /// Given resolution
+        resolution: f64,
+    },
+
+    /// Factor between the resolutions of adjacent logarithmic z-levels is not greater than 1
+    #[error("Invalid zoom factor: {factor}")]
+    InvalidZoomFactor {
+        /// Given factor
+        factor: f64,
+    },
+
+    /// Resolution of a z-level is not a positive number
+    #[error("Non-positive resolution of z-level {z}")]
+    NonPositiveResolution {
+        /// Z-level
+        z: u32,
+    },
+
+    /// There is no standard tile grid known for the projection with the given EPSG code
+    #[error("Unsupported EPSG code: {code}")]
+    UnsupportedEpsg {
+        /// Given code
+        code: u32,
+    },
+
+    /// Explicitly given resolution is not a finite positive number
+    #[error("Invalid resolution of z-level {z}: {resolution}")]
+    InvalidResolution {
+        /// Z-level
+        z: u32,
+        /// Given resolution
+        resolution: f64,
+    },
+}
+
+impl TileSchema {
+    /// Creates a builder with the parameters of this schema, so that a modified copy of the schema
+    /// can be built.
+    ///
+    /// The resolutions of the levels are copied as they are, so changing the tile size of the
+    /// builder changes the area covered by a tile, but not the resolutions. The zoom bias is a
+    /// display setting and is not copied.
+    pub fn to_builder(&self) -> TileSchemaBuilder {
+        // The builder applies the DPI scale to the base tile size and resolutions, so the scale is
+        // reverted here.
+        let tile_width = ((self.tile_width as f32 / self.dpi_scale).round() as u32).max(1);
+        let tile_height = ((self.tile_height as f32 / self.dpi_scale).round() as u32).max(1);
+        let scale = self.tile_width as f64 / tile_width as f64;
+        let unscale = |size: u32| ((size as f32 / self.dpi_scale).round() as u32).max(1);
+
+        // Clipped schemas have the origin outside of the bounds, so the grid is restored as the
+        // area from the origin to the far corner of the bounds.
+        let (near_y, far_y) = match self.y_direction {
+            VerticalDirection::TopToBottom => (self.bounds.y_max(), self.bounds.y_min()),
+            VerticalDirection::BottomToTop => (self.bounds.y_min(), self.bounds.y_max()),
+        };
+        let tolerance = self.bounds.width().max(self.bounds.height()) * 1e-9;
+        let is_clipped = (self.origin.x() - self.bounds.x_min()).abs() > tolerance
+            || (self.origin.y() - near_y).abs() > tolerance;
+        let (bounds, clip) = if is_clipped {
+            let grid = Rect::new(self.origin.x(), self.origin.y(), self.bounds.x_max(), far_y);
+            (grid, Some(self.bounds))
+        } else {
+            (self.bounds, None)
+        };
+
+        TileSchemaBuilder {
+            origin: self.origin,
+            bounds,
+            clip,
+            lods: Lods::Resolved(self.lods.iter().map(|lod| lod * scale).collect()),
+            tile_width,
+            tile_height,
+            tile_sizes: self
+                .tile_sizes
+                .iter()
+                .flatten()
+                .map(|(width, height)| (unscale(*width), unscale(*height)))
+                .collect(),
+            y_direction: self.y_direction,
+            matrix_sizes: self
+                .matrix_sizes
+                .iter()
+                .enumerate()
+                .filter_map(|(z, size)| size.map(|(width, height)| (z as u32, width, height)))
+                .collect(),
+            tile_offset: self.tile_offset,
+            partial_edge_policy: self.partial_edge_policy,
+            index_base: self.index_base,
+            contiguous_levels_required: false,
+            power_of_two_levels_required: false,
+            dpi_scale: self.dpi_scale,
+            tile_inset: self.tile_inset,
+            top_level_tiles: None,
+        }
+    }
+}
+
+impl Default for TileSchemaBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TileSchemaBuilder {
+    /// Create a new builder for a custom tile grid.
+    ///
+    /// The builder starts with 256x256 pixel tiles counted from the top left corner. The bounds,
+    /// the origin and the z-levels of the grid must be set before building the schema.
+    pub fn new() -> Self {
+        const TILE_SIZE: u32 = 256;
+
+        Self {
+            origin: Point2::new(0.0, 0.0),
+            bounds: Rect::new(0.0, 0.0, 0.0, 0.0),
+            clip: None,
+            lods: Lods::Logarithmic {
+                z_levels: Vec::new(),
+                factor: 2.0,
+            },
+            tile_width: TILE_SIZE,
+            tile_height: TILE_SIZE,
+            tile_sizes: Vec::new(),
+            y_direction: VerticalDirection::TopToBottom,
+            matrix_sizes: Vec::new(),
+            tile_offset: (0.0, 0.0),
+            partial_edge_policy: PartialEdgePolicy::default(),
+            index_base: 0,
+            contiguous_levels_required: false,
+            power_of_two_levels_required: false,
+            dpi_scale: 1.0,
+            tile_inset: 0.0,
+            top_level_tiles: None,
+        }
+    }
+
     /// Create a new builder with default parameters.
     pub fn build(self) -> Result<TileSchema, TileSchemaError> {
-        let lods = match self.lods {
-            Lods::Logarithmic(z_levels) => {
+        let lods = match self.lods.clone() {
+            Lods::ToResolution(finest) => {
+                if !finest.is_finite() || finest <= 0.0 {
+                    return Err(TileSchemaError::InvalidTargetResolution { resolution: finest });
+                }
+
+                let top_resolution = self.top_resolution()?;
+                let max_z = (top_resolution / finest)
+                    .log2()
+                    .ceil()
+                    .clamp(0.0, MAX_DERIVED_Z_LEVEL as f64) as u32;
+                Lods::Logarithmic {
+                    z_levels: (0..=max_z).collect(),
+                    factor: 2.0,
+                }
+            }
+            lods => lods,
+        };
+
+        let lods = match lods {
+            Lods::ToResolution(_) => unreachable!("converted to logarithmic levels above"),
+            Lods::Logarithmic { z_levels, factor } => {
                 if z_levels.is_empty() {
                     return Err(TileSchemaError::NoZLevelsProvided);
                 }
 
-                let top_resolution = self.bounds.width() / self.tile_width as f64;
+                if !factor.is_finite() || factor <= 1.0 {
+                    return Err(TileSchemaError::InvalidZoomFactor { factor });
+                }
+
+                let mut sorted = z_levels.clone();
+                sorted.sort_unstable();
+                for pair in sorted.windows(2) {
+                    if pair[0] == pair[1] {
+                        return Err(TileSchemaError::DuplicateZLevel { z: pair[0] });
+                    }
+
+                    if self.contiguous_levels_required && pair[1] > pair[0] + 1 {
+                        return Err(TileSchemaError::NonContiguousZLevels {
+                            missing: pair[0] + 1,
+                        });
+                    }
+                }
+
+                let top_resolution = self.top_resolution()?;
 
                 let max_z_level = *z_levels.iter().max().unwrap_or(&0);
                 let mut lods = vec![f64::NAN; max_z_level as usize + 1];
 
                 for z in z_levels {
-                    let resolution = top_resolution / f64::powi(2.0, z as i32);
+                    let resolution = top_resolution / factor.powi(z as i32);
                     lods[z as usize] = resolution;
                 }
 
+                lods
+            }
+            Lods::Arithmetic { start, step, count } => {
+                if count == 0 {
+                    return Err(TileSchemaError::NoZLevelsProvided);
+                }
+
+                let lods: Vec<f64> = (0..count).map(|z| start - step * z as f64).collect();
+                if let Some(z) = lods
+                    .iter()
+                    .position(|resolution| !resolution.is_finite() || *resolution <= 0.0)
+                {
+                    return Err(TileSchemaError::NonPositiveResolution { z: z as u32 });
+                }
+
+                lods
+            }
+            Lods::Explicit(lods) => {
+                if lods.is_empty() {
+                    return Err(TileSchemaError::NoZLevelsProvided);
+                }
+
+                if let Some((z, resolution)) = lods
+                    .iter()
+                    .enumerate()
+                    .find(|(_, resolution)| !resolution.is_finite() || **resolution <= 0.0)
+                {
+                    return Err(TileSchemaError::InvalidResolution {
+                        z: z as u32,
+                        resolution: *resolution,
+                    });
+                }
+
+                lods
+            }
+            Lods::Resolved(lods) => {
+                if lods.iter().all(|resolution| resolution.is_nan()) {
+                    return Err(TileSchemaError::NoZLevelsProvided);
+                }
+
+                if let Some((z, resolution)) = lods.iter().enumerate().find(|(_, resolution)| {
+                    !resolution.is_nan() && (!resolution.is_finite() || **resolution <= 0.0)
+                }) {
+                    return Err(TileSchemaError::InvalidResolution {
+                        z: z as u32,
+                        resolution: *resolution,
+                    });
+                }
+
                 lods
             }
         };
 
+        if self.power_of_two_levels_required {
+            for z in 1..lods.len() {
+                let ratio = lods[z - 1] / lods[z];
+                if ratio.is_finite() && (ratio - 2.0).abs() > 1e-3 {
+                    return Err(TileSchemaError::NonPowerOfTwoLevels { z: z as u32 });
+                }
+            }
+        }
+
         if self.tile_width == 0 || self.tile_height == 0 {
             return Err(TileSchemaError::InvalidTileSize {
                 width: self.tile_width,
@@ -71,16 +434,159 @@ impl TileSchemaBuilder {
             });
         }
 
+        if !self.dpi_scale.is_finite() || self.dpi_scale <= 0.0 {
+            return Err(TileSchemaError::InvalidDpiScale {
+                scale: self.dpi_scale,
+            });
+        }
+
+        // Scaled tiles cover the same area as the base ones, so the resolutions are divided by
+        // the actual ratio of the tile sizes.
+        let tile_width = (self.tile_width as f32 * self.dpi_scale).round() as u32;
+        let tile_height = (self.tile_height as f32 * self.dpi_scale).round() as u32;
+        if tile_width == 0 || tile_height == 0 {
+            return Err(TileSchemaError::InvalidDpiScale {
+                scale: self.dpi_scale,
+            });
+        }
+        let scale = tile_width as f64 / self.tile_width as f64;
+        let lods: Vec<f64> = lods.into_iter().map(|lod| lod / scale).collect();
+
+        let mut tile_sizes = Vec::with_capacity(self.tile_sizes.len());
+        for (width, height) in self.tile_sizes {
+            let scaled_width = (width as f32 * self.dpi_scale).round() as u32;
+            let scaled_height = (height as f32 * self.dpi_scale).round() as u32;
+            if scaled_width == 0 || scaled_height == 0 {
+                return Err(TileSchemaError::InvalidTileSize { width, height });
+            }
+
+            tile_sizes.push((scaled_width, scaled_height));
+        }
+
+        let (width, height) = (self.bounds.width(), self.bounds.height());
+        if !width.is_finite()
+            || !height.is_finite()
+            || width <= 0.0
+            || height <= 0.0
+            || self.bounds.x_min() > self.bounds.x_max()
+            || self.bounds.y_min() > self.bounds.y_max()
+        {
+            return Err(TileSchemaError::InvalidBounds { width, height });
+        }
+
+        let expected_y = match self.y_direction {
+            VerticalDirection::TopToBottom => self.bounds.y_max(),
+            VerticalDirection::BottomToTop => self.bounds.y_min(),
+        };
+        let tolerance = width.max(height) * 1e-9;
+        if (self.origin.x() - self.bounds.x_min()).abs() > tolerance
+            || (self.origin.y() - expected_y).abs() > tolerance
+        {
+            return Err(TileSchemaError::OriginOutsideBounds {
+                x: self.origin.x(),
+                y: self.origin.y(),
+            });
+        }
+
+        let bounds = match self.clip {
+            Some(clip) => {
+                let clipped = self.bounds.limit(clip);
+                let (width, height) = (clipped.width(), clipped.height());
+                if !width.is_finite() || !height.is_finite() || width <= 0.0 || height <= 0.0 {
+                    return Err(TileSchemaError::InvalidBounds { width, height });
+                }
+
+                clipped
+            }
+            None => self.bounds,
+        };
+
+        let (dx, dy) = self.tile_offset;
+        if !dx.is_finite() || !dy.is_finite() {
+            return Err(TileSchemaError::InvalidTileOffset { dx, dy });
+        }
+
+        if !self.tile_inset.is_finite() || self.tile_inset < 0.0 {
+            return Err(TileSchemaError::InvalidTileInset {
+                pixels: self.tile_inset,
+            });
+        }
+
+        let mut matrix_sizes = vec![];
+        for (z, width, height) in self.matrix_sizes {
+            let level_exists = lods.get(z as usize).is_some_and(|r| r.is_finite());
+            if !level_exists || width == 0 || height == 0 {
+                return Err(TileSchemaError::InvalidMatrixSize { z, width, height });
+            }
+
+            if matrix_sizes.len() <= z as usize {
+                matrix_sizes.resize(z as usize + 1, None);
+            }
+            matrix_sizes[z as usize] = Some((width, height));
+        }
+
         Ok(TileSchema {
             origin: self.origin,
-            bounds: self.bounds,
+            bounds,
             lods,
-            tile_width: self.tile_width,
-            tile_height: self.tile_height,
+            tile_width,
+            tile_height,
+            tile_sizes: (!tile_sizes.is_empty()).then_some(tile_sizes),
             y_direction: self.y_direction,
+            matrix_sizes,
+            zoom_bias: 0,
+            tile_offset: self.tile_offset,
+            partial_edge_policy: self.partial_edge_policy,
+            index_base: self.index_base,
+            dpi_scale: self.dpi_scale,
+            tile_inset: self.tile_inset,
+            lod_lookup: LodLookup::default(),
         })
     }
 
+    /// Resolution of the z-level 0 of the logarithmic levels.
+    ///
+    /// By default at z-level 0 the shorter side of the bounds is covered by a single tile, and the
+    /// longer one by a whole number of tiles. If the number of the top level tiles is set
+    /// explicitly, the tiles must cover the bounds exactly along both axes.
+    fn top_resolution(&self) -> Result<f64, TileSchemaError> {
+        let inconsistent = || TileSchemaError::InconsistentAspectRatio {
+            width: self.tile_width,
+            height: self.tile_height,
+        };
+
+        if let Some((columns, rows)) = self.top_level_tiles {
+            if columns == 0 || rows == 0 {
+                return Err(TileSchemaError::InvalidMatrixSize {
+                    z: 0,
+                    width: columns,
+                    height: rows,
+                });
+            }
+
+            let x_resolution = self.bounds.width() / (columns as f64 * self.tile_width as f64);
+            let y_resolution = self.bounds.height() / (rows as f64 * self.tile_height as f64);
+            if (x_resolution - y_resolution).abs()
+                > x_resolution.abs().max(y_resolution.abs()) * 1e-6
+            {
+                return Err(inconsistent());
+            }
+
+            return Ok(x_resolution);
+        }
+
+        let x_resolution = self.bounds.width() / self.tile_width as f64;
+        let y_resolution = self.bounds.height() / self.tile_height as f64;
+        let top_resolution = x_resolution.min(y_resolution);
+
+        let tiles_ratio = x_resolution.max(y_resolution) / top_resolution;
+        if tiles_ratio.is_finite() && (tiles_ratio - tiles_ratio.round()).abs() > 1e-6 {
+            return Err(inconsistent());
+        }
+
+        Ok(top_resolution)
+    }
+
     /// Standard Web Mercator based tile scheme (used, for example, by OSM and Google maps).
     pub fn web_mercator(z_levels: impl IntoIterator<Item = u32>) -> Self {
         const TILE_SIZE: u32 = 256;
@@ -90,6 +596,151 @@ impl TileSchemaBuilder {
             .with_rect_tile_size(TILE_SIZE)
     }
 
+    /// Web Mercator tile scheme with rows numbered from the bottom as defined by the TMS
+    /// specification (used, for example, by `gdal2tiles`).
+    pub fn web_mercator_tms(z_levels: impl IntoIterator<Item = u32>) -> Self {
+        let mut builder = Self::web_mercator(z_levels);
+        builder.origin = Point2::new(builder.bounds.x_min(), builder.bounds.y_min());
+        builder.y_direction = VerticalDirection::BottomToTop;
+
+        builder
+    }
+
+    /// Web Mercator tile scheme covering only the given extent in degrees, e.g. a single country.
+    ///
+    /// The corners of the extent are projected to EPSG:3857 and used as the bounds of the schema,
+    /// with the origin at the top left corner. Latitudes are clamped to the range valid for Web
+    /// Mercator (about ±85.05°). The levels have the same resolutions as in
+    /// [`TileSchemaBuilder::web_mercator`], but the tiles are counted from the corner of the
+    /// extent, so their indices differ from the ones of the global grid.
+    pub fn web_mercator_extent(
+        min_lon: f64,
+        min_lat: f64,
+        max_lon: f64,
+        max_lat: f64,
+        z_levels: impl IntoIterator<Item = u32>,
+    ) -> Self {
+        const MAX_LATITUDE: f64 = 85.051_128_779_806_59;
+
+        let z_levels: Vec<u32> = z_levels.into_iter().collect();
+        let mut builder = Self::web_mercator(z_levels.iter().copied());
+
+        let top_resolution = builder.bounds.width() / builder.tile_width as f64;
+        let max_z_level = z_levels.iter().max().map_or(0, |z| *z as usize + 1);
+        let mut lods = vec![f64::NAN; max_z_level];
+        for z in z_levels {
+            lods[z as usize] = top_resolution / 2f64.powi(z as i32);
+        }
+        builder.lods = Lods::Resolved(lods);
+
+        let projection = WebMercator::<GeoPoint2d, Point2>::default();
+        let project = |lon: f64, lat: f64| {
+            projection.project(&GeoPoint2d::latlon(
+                lat.clamp(-MAX_LATITUDE, MAX_LATITUDE),
+                lon,
+            ))
+        };
+        builder.bounds = match (project(min_lon, min_lat), project(max_lon, max_lat)) {
+            (Some(min), Some(max)) => Rect::new(min.x(), min.y(), max.x(), max.y()),
+            _ => Rect::new(f64::NAN, f64::NAN, f64::NAN, f64::NAN),
+        };
+        builder.origin = Point2::new(builder.bounds.x_min(), builder.bounds.y_max());
+
+        builder
+    }
+
+    /// Standard Web Mercator tile scheme with the tiles existing only inside the `bounds` (in
+    /// EPSG:3857 coordinates).
+    ///
+    /// The origin and the resolutions are the same as of [`TileSchemaBuilder::web_mercator`], so
+    /// the tiles have the same indices as in the global grid, but the tiles outside of the bounds
+    /// are not [valid](TileSchema::is_valid_index) and are not counted or iterated over. Bounds
+    /// outside of the world extent are cut by it.
+    pub fn web_mercator_clipped(bounds: Rect, z_levels: impl IntoIterator<Item = u32>) -> Self {
+        let mut builder = Self::web_mercator(z_levels);
+        builder.clip = Some(bounds);
+
+        builder
+    }
+
+    /// Web Mercator tile scheme with z-levels from 0 down to the first level with resolution
+    /// equal or finer than `finest` (in meters per pixel), but not deeper than level 30.
+    ///
+    /// If `finest` is not a positive number, building the schema fails with
+    /// [`TileSchemaError::InvalidTargetResolution`].
+    pub fn web_mercator_to_resolution(finest: f64) -> Self {
+        const TILE_SIZE: u32 = 256;
+
+        let mut builder = Self::web_mercator_base().with_rect_tile_size(TILE_SIZE);
+        builder.lods = Lods::ToResolution(finest);
+
+        builder
+    }
+
+    /// Geographic (EPSG:4326) tile scheme with the world covered by two tiles at z-level 0, as
+    /// used by many WMTS services serving plate carrée tiles.
+    pub fn geographic(z_levels: impl IntoIterator<Item = u32>) -> Self {
+        const TILE_SIZE: u32 = 256;
+
+        Self {
+            origin: Point2::new(-180.0, 90.0),
+            bounds: Rect::new(-180.0, -90.0, 180.0, 90.0),
+            clip: None,
+            lods: Lods::Logarithmic {
+                z_levels: Vec::new(),
+                factor: 2.0,
+            },
+            tile_width: 0,
+            tile_height: 0,
+            tile_sizes: Vec::new(),
+            y_direction: VerticalDirection::TopToBottom,
+            matrix_sizes: Vec::new(),
+            tile_offset: (0.0, 0.0),
+            partial_edge_policy: PartialEdgePolicy::default(),
+            index_base: 0,
+            contiguous_levels_required: false,
+            power_of_two_levels_required: false,
+            dpi_scale: 1.0,
+            tile_inset: 0.0,
+            top_level_tiles: None,
+        }
+        .with_logarithmic_z_levels(z_levels)
+        .with_rect_tile_size(TILE_SIZE)
+    }
+
+    /// Creates a builder with the standard tile grid of the projection with the given EPSG code.
+    ///
+    /// Supported codes are:
+    /// * 3857 (and its legacy alias 900913) - same as [`TileSchemaBuilder::web_mercator`],
+    /// * 4326 - same as [`TileSchemaBuilder::geographic`],
+    /// * 3413 and 3031 - NSIDC polar stereographic projections of the Arctic and the Antarctic
+    ///   with the extent of ±4194304 meters covered by a single tile at z-level 0.
+    ///
+    /// Returns [`TileSchemaError::UnsupportedEpsg`] for all other codes.
+    pub fn from_epsg(
+        code: u32,
+        z_levels: impl IntoIterator<Item = u32>,
+    ) -> Result<Self, TileSchemaError> {
+        const TILE_SIZE: u32 = 256;
+        const POLAR_EXTENT: f64 = 4194304.0;
+
+        match code {
+            3857 | 900913 => Ok(Self::web_mercator(z_levels)),
+            4326 => Ok(Self::geographic(z_levels)),
+            3413 | 3031 => {
+                let mut builder = Self::web_mercator_base();
+                builder.origin = Point2::new(-POLAR_EXTENT, POLAR_EXTENT);
+                builder.bounds =
+                    Rect::new(-POLAR_EXTENT, -POLAR_EXTENT, POLAR_EXTENT, POLAR_EXTENT);
+
+                Ok(builder
+                    .with_logarithmic_z_levels(z_levels)
+                    .with_rect_tile_size(TILE_SIZE))
+            }
+            _ => Err(TileSchemaError::UnsupportedEpsg { code }),
+        }
+    }
+
     fn web_mercator_base() -> Self {
         const MAX_COORD_VALUE: f64 = 20037508.342787;
 
@@ -101,23 +752,240 @@ impl TileSchemaBuilder {
                 MAX_COORD_VALUE,
                 MAX_COORD_VALUE,
             ),
-            lods: Lods::Logarithmic(Vec::new()),
+            clip: None,
+            lods: Lods::Logarithmic {
+                z_levels: Vec::new(),
+                factor: 2.0,
+            },
             tile_width: 0,
             tile_height: 0,
+            tile_sizes: Vec::new(),
             y_direction: VerticalDirection::TopToBottom,
+            matrix_sizes: Vec::new(),
+            tile_offset: (0.0, 0.0),
+            partial_edge_policy: PartialEdgePolicy::default(),
+            index_base: 0,
+            contiguous_levels_required: false,
+            power_of_two_levels_required: false,
+            dpi_scale: 1.0,
+            tile_inset: 0.0,
+            top_level_tiles: None,
         }
     }
 
+    /// Set the point the tile indices are counted from.
+    ///
+    /// The origin must be at the left corner of the bounds: the top one for
+    /// [`VerticalDirection::TopToBottom`] and the bottom one for
+    /// [`VerticalDirection::BottomToTop`], otherwise building the schema fails with
+    /// [`TileSchemaError::OriginOutsideBounds`].
+    pub fn with_origin(mut self, origin: Point2) -> Self {
+        self.origin = origin;
+
+        self
+    }
+
+    /// Set the area covered by the tiles of the schema.
+    ///
+    /// Building the schema fails with [`TileSchemaError::InvalidBounds`] if the bounds are empty
+    /// or inverted.
+    pub fn with_bounds(mut self, bounds: Rect) -> Self {
+        self.bounds = bounds;
+
+        self
+    }
+
+    /// Set the direction the tile rows are counted in.
+    ///
+    /// Defaults to [`VerticalDirection::TopToBottom`].
+    pub fn with_y_direction(mut self, y_direction: VerticalDirection) -> Self {
+        self.y_direction = y_direction;
+
+        self
+    }
+
     /// Set both tile width and height to `tile_size`.
-    pub fn with_rect_tile_size(mut self, tile_size: u32) -> Self {
-        self.tile_width = tile_size;
-        self.tile_height = tile_size;
+    pub fn with_rect_tile_size(self, tile_size: u32) -> Self {
+        self.with_tile_size(tile_size, tile_size)
+    }
+
+    /// Set tile width and height in pixels.
+    ///
+    /// With [logarithmic z-levels](TileSchemaBuilder::with_logarithmic_z_levels) the bounds must
+    /// be covered by a whole number of tiles at level 0, otherwise building the schema fails with
+    /// [`TileSchemaError::InconsistentAspectRatio`].
+    pub fn with_tile_size(mut self, width: u32, height: u32) -> Self {
+        self.tile_width = width;
+        self.tile_height = height;
+
+        self
+    }
+
+    /// Set tile width and height in pixels for the first z-levels, starting from level 0.
+    ///
+    /// The levels not covered by `sizes` use the size set by [`TileSchemaBuilder::with_tile_size`].
+    /// The resolutions of the levels do not depend on these sizes, so tiles of a larger size cover
+    /// a larger area. If any of the sizes is zero, building the schema fails with
+    /// [`TileSchemaError::InvalidTileSize`].
+    pub fn with_tile_sizes_per_level(
+        mut self,
+        sizes: impl IntoIterator<Item = (u32, u32)>,
+    ) -> Self {
+        self.tile_sizes = sizes.into_iter().collect();
+
+        self
+    }
+
+    /// Set explicit number of tile columns (`width`) and rows (`height`) for the z-level `z`.
+    ///
+    /// By default the size of the tile matrix is derived from the bounds of the schema. Explicit
+    /// size can be used for the matrices that cover only a part of the bounds, as it is allowed
+    /// by OGC TileMatrixSet definitions.
+    pub fn with_matrix_size(mut self, z: u32, width: u32, height: u32) -> Self {
+        self.matrix_sizes.push((z, width, height));
+
+        self
+    }
+
+    /// Set the number of tile columns and rows that cover the bounds at z-level 0 of the
+    /// logarithmic levels.
+    ///
+    /// By default the shorter side of the bounds is covered by a single tile. With this override
+    /// the resolution of level 0 is derived from the given number of tiles, which must cover the
+    /// bounds exactly, otherwise building the schema fails with
+    /// [`TileSchemaError::InconsistentAspectRatio`].
+    pub fn with_top_level_tiles(mut self, columns: u32, rows: u32) -> Self {
+        self.top_level_tiles = Some((columns, rows));
+
+        self
+    }
+
+    /// Shift the tile grid by `dx` and `dy` fractions of a tile along the x and y indices.
+    ///
+    /// This can be used to align a layer, which grid is staggered relative to the standard grid,
+    /// for example by half a tile.
+    pub fn with_tile_offset(mut self, dx: f64, dy: f64) -> Self {
+        self.tile_offset = (dx, dy);
+
+        self
+    }
+
+    /// Expand every rendered tile by `pixels` on each side, so that neighbouring tiles overlap and
+    /// no hairline seams are visible between them.
+    ///
+    /// The inset does not change which tiles are needed for a view. Building the schema fails with
+    /// [`TileSchemaError::InvalidTileInset`] if the inset is negative or not finite.
+    ///
+    /// Defaults to `0.0`.
+    pub fn with_tile_inset(mut self, pixels: f32) -> Self {
+        self.tile_inset = pixels;
+
+        self
+    }
+
+    /// Set the way the tiles that are only partially inside the schema bounds are treated.
+    ///
+    /// Defaults to [`PartialEdgePolicy::Include`].
+    pub fn with_partial_edge_policy(mut self, policy: PartialEdgePolicy) -> Self {
+        self.partial_edge_policy = policy;
+
+        self
+    }
+
+    /// Set the index of the first tile column and row, for the tile servers that count tiles
+    /// from 1 instead of 0.
+    ///
+    /// Defaults to `0`.
+    pub fn with_index_base(mut self, base: u32) -> Self {
+        self.index_base = base;
+
+        self
+    }
+
+    /// Scale the tiles by `scale` for high-DPI screens, keeping the area covered by each tile.
+    ///
+    /// Tile width and height are multiplied by the scale and the resolutions of all levels are
+    /// divided by it, so that, for example, `@2x` tiles of 512 pixels can be used instead of
+    /// 256 pixel ones. Building the schema fails with [`TileSchemaError::InvalidDpiScale`] if the
+    /// scale is not a positive number.
+    ///
+    /// Defaults to `1.0`.
+    pub fn with_dpi_scale(mut self, scale: f32) -> Self {
+        self.dpi_scale = scale;
+
+        self
+    }
+
+    /// Set `count` z-levels with resolutions decreasing by a fixed `step`, starting from `start`
+    /// at level 0.
+    ///
+    /// Building the schema fails with [`TileSchemaError::NonPositiveResolution`] if the
+    /// resolution of any of the levels is not positive.
+    pub fn with_arithmetic_z_levels(mut self, start: f64, step: f64, count: u32) -> Self {
+        self.lods = Lods::Arithmetic { start, step, count };
+
+        self
+    }
+
+    /// Require the z-levels set with [`TileSchemaBuilder::with_logarithmic_z_levels`] to have no
+    /// gaps between the coarsest and the finest of them.
+    ///
+    /// Building the schema fails with [`TileSchemaError::NonContiguousZLevels`] reporting the
+    /// first missing level otherwise.
+    pub fn with_contiguous_levels_required(mut self) -> Self {
+        self.contiguous_levels_required = true;
+
+        self
+    }
+
+    /// Require the resolution of each z-level to be half of the resolution of the previous one.
+    ///
+    /// Building the schema fails with [`TileSchemaError::NonPowerOfTwoLevels`] reporting the
+    /// first level that breaks this rule otherwise. Gaps between the levels are not checked.
+    pub fn with_power_of_two_levels_required(mut self) -> Self {
+        self.power_of_two_levels_required = true;
+
+        self
+    }
+
+    /// Set resolutions of the z-levels explicitly, starting from level 0.
+    ///
+    /// This can be used for the tile sets with irregular resolutions, for example the ones
+    /// defined in WMTS capabilities documents. Building the schema fails with
+    /// [`TileSchemaError::InvalidResolution`] if any of the resolutions is not a finite positive
+    /// number.
+    pub fn with_custom_resolutions(mut self, resolutions: impl IntoIterator<Item = f64>) -> Self {
+        self.lods = Lods::Explicit(resolutions.into_iter().collect());
 
         self
     }
 
-    fn with_logarithmic_z_levels(mut self, z_levels: impl IntoIterator<Item = u32>) -> Self {
-        self.lods = Lods::Logarithmic(z_levels.into_iter().collect());
+    /// Set z-levels with resolutions halving at each level. At level 0 the shorter side of the
+    /// bounds is covered by a single tile.
+    ///
+    /// The levels can be given in any order, but each of them only once, otherwise building the
+    /// schema fails with [`TileSchemaError::DuplicateZLevel`]. Gaps between the levels are
+    /// allowed unless [`TileSchemaBuilder::with_contiguous_levels_required`] is set.
+    pub fn with_logarithmic_z_levels(self, z_levels: impl IntoIterator<Item = u32>) -> Self {
+        self.with_logarithmic_z_levels_factor(z_levels, 2.0)
+    }
+
+    /// Set z-levels with resolutions divided by `factor` at each level, e.g. for the tile
+    /// pyramids that step by 3 between the levels. At level 0 the shorter side of the bounds is
+    /// covered by a single tile.
+    ///
+    /// Building the schema fails with [`TileSchemaError::InvalidZoomFactor`] if `factor` is not
+    /// greater than 1. Otherwise the levels are validated the same way as in
+    /// [`TileSchemaBuilder::with_logarithmic_z_levels`].
+    pub fn with_logarithmic_z_levels_factor(
+        mut self,
+        z_levels: impl IntoIterator<Item = u32>,
+        factor: f64,
+    ) -> Self {
+        self.lods = Lods::Logarithmic {
+            z_levels: z_levels.into_iter().collect(),
+            factor,
+        };
 
         self
     }
@@ -128,7 +996,60 @@ mod tests {
     use approx::assert_abs_diff_eq;
 
     use super::*;
-    use crate::tile_schema::VerticalDirection;
+    use crate::tile_schema::{TileIndex, VerticalDirection, WrappingTileIndex};
+
+    #[test]
+    fn to_builder_round_trip() {
+        for schema in [
+            TileSchemaBuilder::web_mercator(0..=18).build().unwrap(),
+            TileSchemaBuilder::web_mercator([0, 2, 5]).build().unwrap(),
+            TileSchemaBuilder::web_mercator_tms(0..=3)
+                .with_tile_offset(0.5, 0.0)
+                .with_index_base(1)
+                .build()
+                .unwrap(),
+        ] {
+            // Missing levels are `NaN`, which are not equal to each other, so the debug
+            // representations are compared.
+            let rebuilt = schema.to_builder().build().unwrap();
+            assert_eq!(format!("{rebuilt:?}"), format!("{schema:?}"));
+        }
+
+        let schema = TileSchemaBuilder::web_mercator(0..=18).build().unwrap();
+        let resized = schema
+            .to_builder()
+            .with_rect_tile_size(512)
+            .build()
+            .unwrap();
+        let mut expected = schema.clone();
+        expected.tile_width = 512;
+        expected.tile_height = 512;
+        assert_eq!(resized, expected);
+    }
+
+    #[test]
+    fn logarithmic_z_levels_factor() {
+        let schema = TileSchemaBuilder::web_mercator_base()
+            .with_logarithmic_z_levels_factor(0..=3, 3.0)
+            .with_rect_tile_size(256)
+            .build()
+            .unwrap();
+        assert_eq!(schema.lods.len(), 4);
+        assert_abs_diff_eq!(schema.lods[0], 156543.03392802345);
+        assert_abs_diff_eq!(schema.lods[1], schema.lods[0] / 3.0);
+        assert_abs_diff_eq!(schema.lods[2], schema.lods[0] / 9.0);
+
+        for factor in [1.0, 0.5, -2.0, f64::NAN, f64::INFINITY] {
+            let result = TileSchemaBuilder::web_mercator_base()
+                .with_logarithmic_z_levels_factor(0..=3, factor)
+                .with_rect_tile_size(256)
+                .build();
+            assert!(matches!(
+                result,
+                Err(TileSchemaError::InvalidZoomFactor { .. })
+            ));
+        }
+    }
 
     #[test]
     fn schema_builder_normal_web_mercator() {
@@ -160,6 +1081,203 @@ mod tests {
         assert_eq!(schema.y_direction, VerticalDirection::TopToBottom);
     }
 
+    #[test]
+    fn dpi_scale() {
+        let base = TileSchemaBuilder::web_mercator(0..=2).build().unwrap();
+        let retina = TileSchemaBuilder::web_mercator(0..=2)
+            .with_dpi_scale(2.0)
+            .build()
+            .unwrap();
+
+        assert_eq!(retina.dpi_scale(), 2.0);
+        assert_eq!(retina.tile_width, 512);
+        assert_eq!(retina.tile_height, 512);
+        for z in 0..=2 {
+            assert_abs_diff_eq!(retina.lods[z], base.lods[z] / 2.0);
+        }
+
+        let index = WrappingTileIndex::new(1, 2, 2);
+        let retina_bbox = retina.tile_bbox(index).unwrap();
+        let base_bbox = base.tile_bbox(index).unwrap();
+        assert_abs_diff_eq!(retina_bbox.x_min(), base_bbox.x_min(), epsilon = 1e-6);
+        assert_abs_diff_eq!(retina_bbox.y_max(), base_bbox.y_max(), epsilon = 1e-6);
+        assert_abs_diff_eq!(retina_bbox.width(), base_bbox.width(), epsilon = 1e-6);
+
+        let result = TileSchemaBuilder::web_mercator(0..=2)
+            .with_dpi_scale(0.0)
+            .build();
+        assert!(
+            matches!(result, Err(TileSchemaError::InvalidDpiScale { .. })),
+            "Got {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn web_mercator_tms() {
+        let tms = TileSchemaBuilder::web_mercator_tms(0..=2).build().unwrap();
+        let xyz = TileSchemaBuilder::web_mercator(0..=2).build().unwrap();
+        assert_eq!(tms.y_direction, VerticalDirection::BottomToTop);
+        assert_eq!(tms.origin, Point2::new(-20037508.342787, -20037508.342787));
+
+        let index = WrappingTileIndex::new(1, 0, 2);
+        let tms_bbox = tms.tile_bbox(index).unwrap();
+        let xyz_bbox = xyz.tile_bbox(index).unwrap();
+
+        assert_abs_diff_eq!(tms_bbox.y_min(), -20037508.342787, epsilon = 1e-6);
+        assert_abs_diff_eq!(xyz_bbox.y_max(), 20037508.342787, epsilon = 1e-6);
+        assert_abs_diff_eq!(tms_bbox.x_min(), xyz_bbox.x_min());
+    }
+
+    #[test]
+    fn geographic() {
+        let schema = TileSchemaBuilder::geographic(0..=3).build().unwrap();
+        assert_eq!(schema.lods.len(), 4);
+        assert_abs_diff_eq!(schema.lods[0], 360.0 / 512.0);
+        assert_abs_diff_eq!(schema.lods[3], 360.0 / 512.0 / 8.0);
+        assert_eq!(schema.origin, Point2::new(-180.0, 90.0));
+
+        let bbox = Rect::new(-179.0, -89.0, 179.0, 89.0);
+        let mut tiles: Vec<_> = schema
+            .tiles_covering_multi(bbox, [0])
+            .map(|index| (index.x, index.y))
+            .collect();
+        tiles.sort();
+        assert_eq!(tiles, vec![(0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn from_epsg() {
+        assert_eq!(
+            TileSchemaBuilder::from_epsg(3857, 0..=18)
+                .unwrap()
+                .build()
+                .unwrap(),
+            TileSchemaBuilder::web_mercator(0..=18).build().unwrap()
+        );
+        assert_eq!(
+            TileSchemaBuilder::from_epsg(4326, 0..=10)
+                .unwrap()
+                .build()
+                .unwrap(),
+            TileSchemaBuilder::geographic(0..=10).build().unwrap()
+        );
+
+        let polar = TileSchemaBuilder::from_epsg(3413, 0..=5)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(polar.origin, Point2::new(-4194304.0, 4194304.0));
+        assert_abs_diff_eq!(polar.lods[0], 8388608.0 / 256.0);
+
+        assert!(matches!(
+            TileSchemaBuilder::from_epsg(27700, 0..=5),
+            Err(TileSchemaError::UnsupportedEpsg { code: 27700 })
+        ));
+    }
+
+    #[test]
+    fn web_mercator_extent() {
+        let global = TileSchemaBuilder::web_mercator(0..=10).build().unwrap();
+        let schema = TileSchemaBuilder::web_mercator_extent(5.0, 45.0, 15.0, 55.0, 0..=10)
+            .build()
+            .unwrap();
+
+        let bounds = schema.bounds;
+        assert_abs_diff_eq!(bounds.x_min(), 556597.45, epsilon = 0.01);
+        assert_abs_diff_eq!(bounds.y_min(), 5621521.49, epsilon = 0.01);
+        assert_abs_diff_eq!(bounds.x_max(), 1669792.36, epsilon = 0.01);
+        assert_abs_diff_eq!(bounds.y_max(), 7361866.11, epsilon = 0.01);
+        assert_eq!(schema.origin, Point2::new(bounds.x_min(), bounds.y_max()));
+        assert_eq!(schema.lods, global.lods);
+
+        let schema = TileSchemaBuilder::web_mercator_extent(-180.0, -90.0, 180.0, 90.0, [3])
+            .build()
+            .unwrap();
+        assert_abs_diff_eq!(schema.bounds.y_max(), 20037508.34, epsilon = 0.01);
+        assert_abs_diff_eq!(schema.bounds.y_min(), -20037508.34, epsilon = 0.01);
+        assert!(schema.lod_resolution(2).is_none());
+        assert_eq!(schema.lod_resolution(3), global.lod_resolution(3));
+    }
+
+    #[test]
+    fn web_mercator_clipped() {
+        let global = TileSchemaBuilder::web_mercator(0..=10).build().unwrap();
+        // The north-eastern quarter of the world without the third tile column of z-level 2, cut
+        // by the world extent.
+        let bounds = Rect::new(1.1e7, 0.0, 3e7, 3e7);
+        let schema = TileSchemaBuilder::web_mercator_clipped(bounds, 0..=10)
+            .build()
+            .unwrap();
+
+        assert_eq!(schema.origin, global.origin);
+        assert_eq!(schema.lods, global.lods);
+        assert_eq!(
+            schema.bounds,
+            Rect::new(1.1e7, 0.0, 20037508.342787, 20037508.342787)
+        );
+
+        assert_eq!(schema.tile_count(0), Some((1, 1)));
+        assert_eq!(schema.tile_count(2), Some((1, 2)));
+        assert!(schema.is_valid_index(TileIndex::new(3, 0, 2)));
+        assert!(schema.is_valid_index(TileIndex::new(3, 1, 2)));
+        assert!(!schema.is_valid_index(TileIndex::new(2, 0, 2)));
+        assert!(!schema.is_valid_index(TileIndex::new(3, 2, 2)));
+        assert_eq!(
+            schema.tile_bbox(TileIndex::new(3, 1, 2).into_wrapping()),
+            global.tile_bbox(TileIndex::new(3, 1, 2).into_wrapping())
+        );
+        assert_eq!(
+            schema.point_to_index(Point2::new(1.5e7, 1e6), 5),
+            global.point_to_index(Point2::new(1.5e7, 1e6), 5)
+        );
+
+        let rebuilt = schema.to_builder().build().unwrap();
+        assert_eq!(rebuilt.origin, schema.origin);
+        assert_eq!(rebuilt.bounds, schema.bounds);
+
+        assert!(matches!(
+            TileSchemaBuilder::web_mercator_clipped(Rect::new(3e7, 0.0, 4e7, 1.0), [0]).build(),
+            Err(TileSchemaError::InvalidBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn web_mercator_to_resolution() {
+        let schema = TileSchemaBuilder::web_mercator_to_resolution(0.1)
+            .build()
+            .unwrap();
+        assert_eq!(schema.lods.len(), 22);
+        assert!(schema.lods[21] <= 0.1);
+        assert!(schema.lods[20] > 0.1);
+
+        let schema = TileSchemaBuilder::web_mercator_to_resolution(1e6)
+            .build()
+            .unwrap();
+        assert_eq!(schema.lods.len(), 1);
+
+        let schema = TileSchemaBuilder::web_mercator_to_resolution(0.5)
+            .build()
+            .unwrap();
+        assert_eq!(schema.lods.len(), 20);
+        assert!(schema.lods[19] <= 0.5);
+        assert!(schema.lods[18] > 0.5);
+
+        let schema = TileSchemaBuilder::web_mercator_to_resolution(1e-12)
+            .build()
+            .unwrap();
+        assert_eq!(schema.lods.len(), 31);
+
+        for resolution in [0.0, -1.0, f64::NAN] {
+            let result = TileSchemaBuilder::web_mercator_to_resolution(resolution).build();
+            assert!(
+                matches!(result, Err(TileSchemaError::InvalidTargetResolution { .. })),
+                "Got {:?}",
+                result
+            );
+        }
+    }
+
     #[test]
     fn schema_builder_no_z_levels() {
         let result = TileSchemaBuilder::web_mercator(std::iter::empty()).build();
@@ -179,6 +1297,289 @@ mod tests {
         assert_abs_diff_eq!(schema.lods[10], 156543.03392802345 / 2f64.powi(10));
     }
 
+    #[test]
+    fn arithmetic_z_levels() {
+        let schema = TileSchemaBuilder::web_mercator(0..=0)
+            .with_arithmetic_z_levels(100.0, 20.0, 4)
+            .build()
+            .unwrap();
+        assert_eq!(schema.lods, vec![100.0, 80.0, 60.0, 40.0]);
+
+        let result = TileSchemaBuilder::web_mercator(0..=0)
+            .with_arithmetic_z_levels(100.0, 25.0, 5)
+            .build();
+        assert!(
+            matches!(result, Err(TileSchemaError::NonPositiveResolution { z: 4 })),
+            "Got {:?}",
+            result
+        );
+
+        let result = TileSchemaBuilder::web_mercator(0..=0)
+            .with_arithmetic_z_levels(100.0, 60.0, 3)
+            .build();
+        assert!(
+            matches!(result, Err(TileSchemaError::NonPositiveResolution { z: 2 })),
+            "Got {:?}",
+            result
+        );
+
+        let result = TileSchemaBuilder::web_mercator(0..=0)
+            .with_arithmetic_z_levels(100.0, 20.0, 0)
+            .build();
+        assert!(matches!(result, Err(TileSchemaError::NoZLevelsProvided)));
+    }
+
+    #[test]
+    fn duplicate_and_missing_z_levels() {
+        let result = TileSchemaBuilder::web_mercator([0, 1, 1, 3]).build();
+        assert!(
+            matches!(result, Err(TileSchemaError::DuplicateZLevel { z: 1 })),
+            "Got {:?}",
+            result
+        );
+
+        let schema = TileSchemaBuilder::web_mercator([3, 0, 1]).build().unwrap();
+        assert_eq!(schema.lods.len(), 4);
+        assert!(schema.lods[2].is_nan());
+
+        let result = TileSchemaBuilder::web_mercator([3, 0, 1, 5])
+            .with_contiguous_levels_required()
+            .build();
+        assert!(
+            matches!(
+                result,
+                Err(TileSchemaError::NonContiguousZLevels { missing: 2 })
+            ),
+            "Got {:?}",
+            result
+        );
+
+        let schema = TileSchemaBuilder::web_mercator(5..=10)
+            .with_contiguous_levels_required()
+            .build()
+            .unwrap();
+        assert_eq!(schema.lods.len(), 11);
+    }
+
+    #[test]
+    fn non_square_tiles() {
+        let schema = TileSchemaBuilder::web_mercator(0..=2)
+            .with_tile_size(512, 256)
+            .build()
+            .unwrap();
+        assert_eq!(schema.tile_width, 512);
+        assert_eq!(schema.tile_height, 256);
+        assert_abs_diff_eq!(schema.lods[0], 40075016.685574 / 512.0);
+
+        let schema = TileSchemaBuilder::geographic(0..=2)
+            .with_tile_size(512, 256)
+            .build()
+            .unwrap();
+        assert_abs_diff_eq!(schema.lods[0], 360.0 / 512.0);
+
+        let result = TileSchemaBuilder::geographic(0..=2)
+            .with_tile_size(300, 256)
+            .build();
+        assert!(
+            matches!(
+                result,
+                Err(TileSchemaError::InconsistentAspectRatio {
+                    width: 300,
+                    height: 256
+                })
+            ),
+            "Got {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn clone_builder() {
+        let base = TileSchemaBuilder::web_mercator_base()
+            .with_rect_tile_size(512)
+            .with_logarithmic_z_levels(0..=3);
+        let finer = base
+            .clone()
+            .with_logarithmic_z_levels(0..=10)
+            .build()
+            .unwrap();
+        let original = base.build().unwrap();
+
+        assert_eq!(finer.lods.len(), 11);
+        assert_eq!(original.lods.len(), 4);
+        assert_eq!(original.tile_width, 512);
+        assert_eq!(finer.tile_width, 512);
+        assert_eq!(original.lods[..], finer.lods[..4]);
+    }
+
+    #[test]
+    fn top_level_tiles() {
+        let schema = TileSchemaBuilder::geographic(0..=2)
+            .with_top_level_tiles(4, 2)
+            .build()
+            .unwrap();
+        assert_abs_diff_eq!(schema.lods[0], 360.0 / 1024.0);
+        assert_abs_diff_eq!(schema.lods[2], 360.0 / 4096.0);
+
+        // 1000x600 bounds cannot be covered by square tiles with a single tile on the shorter side.
+        let grid = || {
+            TileSchemaBuilder::new()
+                .with_bounds(Rect::new(0.0, 0.0, 1000.0, 600.0))
+                .with_origin(Point2::new(0.0, 600.0))
+                .with_logarithmic_z_levels(0..=2)
+        };
+        assert!(matches!(
+            grid().build(),
+            Err(TileSchemaError::InconsistentAspectRatio { .. })
+        ));
+
+        let schema = grid().with_top_level_tiles(5, 3).build().unwrap();
+        assert_abs_diff_eq!(schema.lods[0], 200.0 / 256.0);
+
+        assert!(matches!(
+            grid().with_top_level_tiles(4, 3).build(),
+            Err(TileSchemaError::InconsistentAspectRatio { .. })
+        ));
+        assert!(matches!(
+            grid().with_top_level_tiles(0, 3).build(),
+            Err(TileSchemaError::InvalidMatrixSize { z: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn custom_grid() {
+        let bounds = Rect::new(100_000.0, 0.0, 1_124_000.0, 512_000.0);
+        let schema = TileSchemaBuilder::new()
+            .with_bounds(bounds)
+            .with_origin(Point2::new(100_000.0, 512_000.0))
+            .with_logarithmic_z_levels(0..=2)
+            .build()
+            .unwrap();
+        assert_eq!(schema.lods, vec![2000.0, 1000.0, 500.0]);
+        assert_eq!(
+            schema.tile_bbox(WrappingTileIndex::new(1, 0, 0)),
+            Some(Rect::new(612_000.0, 0.0, 1_124_000.0, 512_000.0))
+        );
+
+        let schema = TileSchemaBuilder::new()
+            .with_bounds(bounds)
+            .with_origin(Point2::new(100_000.0, 0.0))
+            .with_y_direction(VerticalDirection::BottomToTop)
+            .with_custom_resolutions([4000.0, 1500.0])
+            .build()
+            .unwrap();
+        assert_eq!(schema.lods, vec![4000.0, 1500.0]);
+
+        let result = TileSchemaBuilder::new()
+            .with_bounds(bounds)
+            .with_origin(Point2::new(100_000.0, 0.0))
+            .with_logarithmic_z_levels(0..=2)
+            .build();
+        assert!(matches!(
+            result,
+            Err(TileSchemaError::OriginOutsideBounds { .. })
+        ));
+
+        let result = TileSchemaBuilder::new()
+            .with_logarithmic_z_levels(0..=2)
+            .build();
+        assert!(matches!(result, Err(TileSchemaError::InvalidBounds { .. })));
+    }
+
+    #[test]
+    fn presets_validate_bounds_and_origin() {
+        const MAX: f64 = 20037508.342787;
+
+        // Inverted corners are normalized by the rectangle, so they describe the same bounds.
+        let inverted = TileSchemaBuilder::web_mercator(0..=2)
+            .with_bounds(Rect::new(MAX, MAX, -MAX, -MAX))
+            .build()
+            .unwrap();
+        let default = TileSchemaBuilder::web_mercator(0..=2).build().unwrap();
+        assert_eq!(inverted, default);
+
+        // Deserialized rectangles are not normalized.
+        let inverted: Rect = serde_json::from_str(&format!(
+            r#"{{"x_min": {MAX}, "y_min": {MAX}, "x_max": {}, "y_max": {}}}"#,
+            -MAX, -MAX
+        ))
+        .unwrap();
+        let result = TileSchemaBuilder::web_mercator(0..=2)
+            .with_bounds(inverted)
+            .build();
+        assert!(
+            matches!(result, Err(TileSchemaError::InvalidBounds { .. })),
+            "Got {:?}",
+            result
+        );
+
+        let result = TileSchemaBuilder::web_mercator(0..=2)
+            .with_bounds(Rect::new(-MAX, 0.0, MAX, 0.0))
+            .build();
+        assert!(
+            matches!(result, Err(TileSchemaError::InvalidBounds { .. })),
+            "Got {:?}",
+            result
+        );
+
+        let result = TileSchemaBuilder::web_mercator(0..=2)
+            .with_bounds(Rect::new(-MAX, -MAX, f64::INFINITY, MAX))
+            .build();
+        assert!(
+            matches!(result, Err(TileSchemaError::InvalidBounds { .. })),
+            "Got {:?}",
+            result
+        );
+
+        for origin in [Point2::new(-2.0 * MAX, MAX), Point2::new(-MAX, 2.0 * MAX)] {
+            let result = TileSchemaBuilder::web_mercator(0..=2)
+                .with_origin(origin)
+                .build();
+            assert!(
+                matches!(result, Err(TileSchemaError::OriginOutsideBounds { .. })),
+                "Got {:?}",
+                result
+            );
+        }
+
+        let result = TileSchemaBuilder::web_mercator_tms(0..=2)
+            .with_origin(Point2::new(-MAX, MAX))
+            .build();
+        assert!(
+            matches!(result, Err(TileSchemaError::OriginOutsideBounds { .. })),
+            "Got {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn custom_resolutions() {
+        let resolutions = vec![500.0, 270.0, 100.0, 12.5];
+        let schema = TileSchemaBuilder::web_mercator(0..=0)
+            .with_custom_resolutions(resolutions.clone())
+            .build()
+            .unwrap();
+        assert_eq!(schema.lods, resolutions);
+
+        for (invalid, z) in [(f64::NAN, 1), (f64::INFINITY, 2), (0.0, 0), (-1.0, 3)] {
+            let mut resolutions = resolutions.clone();
+            resolutions[z] = invalid;
+            let result = TileSchemaBuilder::web_mercator(0..=0)
+                .with_custom_resolutions(resolutions)
+                .build();
+            assert!(
+                matches!(result, Err(TileSchemaError::InvalidResolution { z: error_z, .. }) if error_z == z as u32),
+                "Got {:?}",
+                result
+            );
+        }
+
+        let result = TileSchemaBuilder::web_mercator(0..=0)
+            .with_custom_resolutions([])
+            .build();
+        assert!(matches!(result, Err(TileSchemaError::NoZLevelsProvided)));
+    }
+
     #[test]
     fn zero_tile_size() {
         let result = TileSchemaBuilder::web_mercator(0..=20)
@@ -196,4 +1597,126 @@ mod tests {
             result
         );
     }
+
+    #[test]
+    fn tile_offset() {
+        let schema = TileSchemaBuilder::web_mercator(0..=5)
+            .with_tile_offset(0.5, 0.0)
+            .build()
+            .unwrap();
+        let default_schema = TileSchemaBuilder::web_mercator(0..=5).build().unwrap();
+
+        let index = WrappingTileIndex::new(3, 2, 3);
+        let bbox = schema.tile_bbox(index).unwrap();
+        let default_bbox = default_schema.tile_bbox(index).unwrap();
+        assert_abs_diff_eq!(
+            bbox.x_min() - default_bbox.x_min(),
+            default_bbox.width() / 2.0,
+            epsilon = 1e-6
+        );
+        assert_abs_diff_eq!(bbox.y_min(), default_bbox.y_min());
+
+        let result = TileSchemaBuilder::web_mercator(0..=5)
+            .with_tile_offset(f64::NAN, 0.0)
+            .build();
+        assert!(
+            matches!(result, Err(TileSchemaError::InvalidTileOffset { .. })),
+            "Got {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn tile_inset() {
+        let schema = TileSchemaBuilder::web_mercator(0..=5)
+            .with_tile_inset(1.5)
+            .build()
+            .unwrap();
+        assert_eq!(schema.tile_inset(), 1.5);
+        assert_eq!(schema.to_builder().build().unwrap().tile_inset(), 1.5);
+
+        let default_schema = TileSchemaBuilder::web_mercator(0..=5).build().unwrap();
+        assert_eq!(default_schema.tile_inset(), 0.0);
+
+        let index = WrappingTileIndex::new(3, 2, 3);
+        assert_eq!(schema.tile_bbox(index), default_schema.tile_bbox(index));
+
+        for pixels in [-1.0, f32::NAN, f32::INFINITY] {
+            let result = TileSchemaBuilder::web_mercator(0..=5)
+                .with_tile_inset(pixels)
+                .build();
+            assert!(
+                matches!(result, Err(TileSchemaError::InvalidTileInset { .. })),
+                "Got {:?}",
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn tile_sizes_per_level() {
+        let schema = TileSchemaBuilder::web_mercator(0..=3)
+            .with_tile_sizes_per_level([(512, 512), (512, 256)])
+            .with_dpi_scale(2.0)
+            .build()
+            .unwrap();
+        assert_eq!(schema.tile_width_at(0), 1024);
+        assert_eq!(schema.tile_height_at(1), 512);
+        assert_eq!(schema.tile_width_at(2), 512);
+        assert_eq!(schema.to_builder().build().unwrap(), schema);
+
+        let result = TileSchemaBuilder::web_mercator(0..=3)
+            .with_tile_sizes_per_level([(256, 256), (0, 256)])
+            .build();
+        assert!(
+            matches!(
+                result,
+                Err(TileSchemaError::InvalidTileSize {
+                    width: 0,
+                    height: 256
+                })
+            ),
+            "Got {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn explicit_matrix_size() {
+        let schema = TileSchemaBuilder::web_mercator(0..=5)
+            .with_matrix_size(3, 8, 5)
+            .build()
+            .unwrap();
+        assert_eq!(schema.matrix_size(3), Some((8, 5)));
+        assert_eq!(schema.matrix_size(2), None);
+        assert_eq!(schema.matrix_size(10), None);
+    }
+
+    #[test]
+    fn invalid_matrix_size() {
+        let result = TileSchemaBuilder::web_mercator(0..=5)
+            .with_matrix_size(6, 8, 5)
+            .build();
+        assert!(
+            matches!(
+                result,
+                Err(TileSchemaError::InvalidMatrixSize {
+                    z: 6,
+                    width: 8,
+                    height: 5
+                })
+            ),
+            "Got {:?}",
+            result
+        );
+
+        let result = TileSchemaBuilder::web_mercator(0..=5)
+            .with_matrix_size(3, 0, 5)
+            .build();
+        assert!(
+            matches!(result, Err(TileSchemaError::InvalidMatrixSize { z: 3, .. })),
+            "Got {:?}",
+            result
+        );
+    }
 }
@@ -0,0 +1,88 @@
+//! Export of the tile grid of a [`TileSchema`] as GeoJSON for debugging.
+
+use geojson::{Feature, FeatureCollection, GeoJson, Geometry, JsonObject, JsonValue, Value};
+
+use super::TileSchema;
+
+impl TileSchema {
+    /// Returns a GeoJSON `FeatureCollection` with a polygon for every tile of the level `z`.
+    ///
+    /// Properties of each feature are the `x`, `y` and `z` indices of the tile. The coordinates
+    /// are in the projection of the schema, so the output can be checked in any GIS application
+    /// that supports the projection, e.g. to confirm the origin, bounds and y direction of a
+    /// custom schema. If the level is not present in the schema, the collection is empty.
+    pub fn grid_to_geojson(&self, z: u32) -> String {
+        let features = self
+            .level_tiles(z)
+            .into_iter()
+            .flatten()
+            .filter_map(|index| {
+                let rect = self.tile_world_rect(index)?;
+                let ring = vec![
+                    vec![rect.x_min(), rect.y_min()],
+                    vec![rect.x_max(), rect.y_min()],
+                    vec![rect.x_max(), rect.y_max()],
+                    vec![rect.x_min(), rect.y_max()],
+                    vec![rect.x_min(), rect.y_min()],
+                ];
+
+                let mut properties = JsonObject::new();
+                properties.insert("x".to_string(), JsonValue::from(index.x));
+                properties.insert("y".to_string(), JsonValue::from(index.y));
+                properties.insert("z".to_string(), JsonValue::from(index.z));
+
+                Some(Feature {
+                    bbox: None,
+                    geometry: Some(Geometry::new(Value::Polygon(vec![ring]))),
+                    id: None,
+                    properties: Some(properties),
+                    foreign_members: None,
+                })
+            })
+            .collect();
+
+        GeoJson::from(FeatureCollection {
+            bbox: None,
+            features,
+            foreign_members: None,
+        })
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile_schema::TileSchemaBuilder;
+
+    fn parse_features(geojson: &str) -> Vec<Feature> {
+        match geojson.parse::<GeoJson>().expect("invalid GeoJSON") {
+            GeoJson::FeatureCollection(collection) => collection.features,
+            other => panic!("Expected a feature collection, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn web_mercator_grid() {
+        const MAX: f64 = 20037508.342787;
+
+        let schema = TileSchemaBuilder::web_mercator([0, 2]).build().unwrap();
+        let features = parse_features(&schema.grid_to_geojson(2));
+        assert_eq!(features.len(), 16);
+
+        let first = &features[0];
+        assert_eq!(first.property("x"), Some(&JsonValue::from(0)));
+        assert_eq!(first.property("y"), Some(&JsonValue::from(0)));
+        assert_eq!(first.property("z"), Some(&JsonValue::from(2)));
+
+        // Rows of web mercator tiles are counted from the top.
+        let Some(Value::Polygon(rings)) = first.geometry.as_ref().map(|g| &g.value) else {
+            panic!("Expected a polygon");
+        };
+        assert_eq!(rings[0].len(), 5);
+        assert!(rings[0].iter().all(|point| point[1] >= MAX / 2.0 - 1e-6));
+
+        assert!(parse_features(&schema.grid_to_geojson(1)).is_empty());
+        assert!(parse_features(&schema.grid_to_geojson(5)).is_empty());
+    }
+}
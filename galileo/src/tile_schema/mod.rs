@@ -1,9 +1,14 @@
 //! [`TileSchema`] is used by tile layers to calculate [tile indices](TileIndex) needed for a given ['MapView'].
 
 mod builder;
+#[cfg(feature = "geojson")]
+mod geojson;
 mod schema;
 mod tile_index;
+#[cfg(feature = "wmts")]
+mod wmts;
 
 pub use builder::{TileSchemaBuilder, TileSchemaError};
-pub use schema::{TileSchema, VerticalDirection};
-pub use tile_index::{TileIndex, WrappingTileIndex};
+pub(crate) use schema::TileRange;
+pub use schema::{FitMode, PartialEdgePolicy, TileOrder, TileSchema, VerticalDirection};
+pub use tile_index::{QuadkeyError, TileIndex, WrappingTileIndex};
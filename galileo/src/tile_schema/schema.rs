@@ -1,16 +1,29 @@
 //! Tile schema definition.
 
-use galileo_types::cartesian::{CartesianPoint2d, Point2, Rect};
+use std::hash::{Hash, Hasher};
+use std::ops::RangeInclusive;
+use std::sync::OnceLock;
+
+use ahash::{HashMap, HashSet};
+use galileo_types::cartesian::{CartesianPoint2d, Point2, Rect, Size};
+use galileo_types::geometry::Geom;
+use galileo_types::geometry_type::{CartesianSpace2d, GeometryType, PointGeometryType};
+use galileo_types::{
+    CartesianGeometry2d, Contour, MultiContour, MultiPoint, MultiPolygon, Polygon,
+};
 use serde::{Deserialize, Serialize};
 
-use super::tile_index::WrappingTileIndex;
+use super::tile_index::{TileIndex, WrappingTileIndex};
 use crate::lod::Lod;
 use crate::view::MapView;
 
 const RESOLUTION_TOLERANCE: f64 = 0.01;
 
+/// Length of one degree of longitude at the equator of the Web Mercator sphere, in meters.
+pub(super) const METERS_PER_DEGREE: f64 = 2.0 * std::f64::consts::PI * 6378137.0 / 360.0;
+
 /// Direction of the Y index of tiles.
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum VerticalDirection {
     /// Tiles with `Y == 0` are at the top of the map.
     TopToBottom,
@@ -18,26 +31,238 @@ pub enum VerticalDirection {
     BottomToTop,
 }
 
+impl VerticalDirection {
+    /// Returns true if tiles with `Y == 0` are at the top of the map.
+    pub fn is_top_to_bottom(&self) -> bool {
+        *self == Self::TopToBottom
+    }
+
+    /// Returns true if tiles with `Y == 0` are at the bottom of the map.
+    pub fn is_bottom_to_top(&self) -> bool {
+        *self == Self::BottomToTop
+    }
+
+    /// Converts the index of a row counted in this direction to the index counted from the top of
+    /// a tile matrix with `total_rows` rows. The conversion is symmetric, so it also converts the
+    /// rows counted from the top to this direction.
+    ///
+    /// Rows outside of the matrix are clamped to its bounds.
+    pub fn flip_row(&self, row: u32, total_rows: u32) -> u32 {
+        match self {
+            Self::TopToBottom => row,
+            Self::BottomToTop => total_rows.saturating_sub(1).saturating_sub(row),
+        }
+    }
+}
+
+/// Way a bounding box is fitted into a viewport when selecting a z-level for it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FitMode {
+    /// The whole bounding box is visible in the viewport. Gives an overview of the area.
+    Contain,
+    /// The bounding box covers the whole viewport. Gives the maximum detail of the area.
+    Cover,
+}
+
+/// Order in which tiles covering an area are traversed.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TileOrder {
+    /// Row by row, with the X index changing first.
+    RowMajor,
+    /// Column by column, with the Y index changing first.
+    ColumnMajor,
+    /// Starting from the center tile in a clockwise spiral outwards.
+    Spiral,
+    /// Along a Hilbert curve, so that consecutive tiles are close to each other.
+    Hilbert,
+}
+
+/// Way the tiles that are only partially inside the schema bounds are treated.
+///
+/// Such tiles appear at the far edges of the schema when the size of the bounds is not a multiple
+/// of the tile size at some level.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum PartialEdgePolicy {
+    /// Partial tiles are valid, and their bounding boxes are clipped by the schema bounds.
+    Clamp,
+    /// Partial tiles are valid, and their bounding boxes have the full tile size.
+    #[default]
+    Include,
+    /// Partial tiles are not valid, so only the tiles fully inside the bounds are used.
+    Exclude,
+}
+
+/// Rectangular range of tiles of a single z-level, with inclusive bounds of display x and y
+/// indices.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct TileRange {
+    pub(crate) z: u32,
+    pub(crate) x_min: i32,
+    pub(crate) x_max: i32,
+    pub(crate) y_min: i32,
+    pub(crate) y_max: i32,
+}
+
 /// Tile schema specifies how tile indices are calculated based on the map position and resolution.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+///
+/// Schemas are compared and hashed by all their parameters, with the floating point values
+/// compared exactly and the skipped levels of the same z-levels considered equal, so identically
+/// built schemas can be used as map keys.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TileSchema {
     /// Position where all tiles have `X == 0, Y == 0` indices.
     pub(super) origin: Point2,
     /// Rectangle that contains all tiles of the tile scheme.
     pub(super) bounds: Rect,
     /// Sorted set of levels of detail that specify resolutions for each z-level.
+    #[serde(with = "nan_as_null")]
     pub(super) lods: Vec<f64>,
     /// Width of a single tile in pixels.
     pub(super) tile_width: u32,
     /// Height of a single tile in pixels.
     pub(super) tile_height: u32,
+    /// Tile sizes in pixels overriding `tile_width` and `tile_height` for the first z-levels,
+    /// indexed by z-level.
+    #[serde(default)]
+    pub(super) tile_sizes: Option<Vec<(u32, u32)>>,
     /// Direction of the Y-axis.
     pub(super) y_direction: VerticalDirection,
+    /// Explicit number of tile columns and rows for the z-levels whose tile matrix does not cover
+    /// the whole `bounds`, indexed by z-level.
+    #[serde(default)]
+    pub(super) matrix_sizes: Vec<Option<(u32, u32)>>,
+    /// Number of z-levels to shift the selected level by. Positive values select coarser levels.
+    #[serde(default)]
+    pub(super) zoom_bias: i32,
+    /// Shift of the tile grid relative to the origin in tile fractions along the x and y indices.
+    #[serde(default)]
+    pub(super) tile_offset: (f64, f64),
+    /// Way the tiles that are only partially inside the bounds are treated.
+    #[serde(default)]
+    pub(super) partial_edge_policy: PartialEdgePolicy,
+    /// Index of the first tile column and row. Internally indices are always counted from 0.
+    #[serde(default)]
+    pub(super) index_base: u32,
+    /// Number of tile pixels per pixel of the base tile size the resolutions are given for.
+    #[serde(default = "default_dpi_scale")]
+    pub(super) dpi_scale: f32,
+    /// Number of pixels each rendered tile is expanded by on every side to hide seams between
+    /// neighbouring tiles.
+    #[serde(default)]
+    pub(super) tile_inset: f32,
+    /// Cached list of valid levels. Must be reset every time `lods` are changed.
+    #[serde(skip)]
+    pub(super) lod_lookup: LodLookup,
+}
+
+/// Serialization of the resolutions with `NaN` values for the missing z-levels. Since `NaN` cannot
+/// be represented in some formats (e.g. JSON), the missing levels are stored as nulls.
+mod nan_as_null {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(lods: &[f64], serializer: S) -> Result<S::Ok, S::Error> {
+        lods.iter()
+            .map(|lod| (!lod.is_nan()).then_some(*lod))
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<f64>, D::Error> {
+        let lods = Vec::<Option<f64>>::deserialize(deserializer)?;
+        Ok(lods
+            .into_iter()
+            .map(|lod| lod.unwrap_or(f64::NAN))
+            .collect())
+    }
+}
+
+fn default_dpi_scale() -> f32 {
+    1.0
+}
+
+/// Lazily built lookup table of the valid levels of detail of a schema.
+#[derive(Debug, Clone, Default)]
+pub(super) struct LodLookup(OnceLock<Vec<Lod>>);
+
+impl PartialEq for TileSchema {
+    fn eq(&self, other: &Self) -> bool {
+        // The lookup is derived from `lods`, so it does not affect the equality.
+        self.float_fields().map(float_bits) == other.float_fields().map(float_bits)
+            && self.lods.len() == other.lods.len()
+            && self
+                .lods
+                .iter()
+                .zip(&other.lods)
+                .all(|(a, b)| float_bits(*a) == float_bits(*b))
+            && self.tile_width == other.tile_width
+            && self.tile_height == other.tile_height
+            && self.tile_sizes == other.tile_sizes
+            && self.y_direction == other.y_direction
+            && self.matrix_sizes == other.matrix_sizes
+            && self.zoom_bias == other.zoom_bias
+            && self.partial_edge_policy == other.partial_edge_policy
+            && self.index_base == other.index_base
+    }
+}
+
+impl Eq for TileSchema {}
+
+impl Hash for TileSchema {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.float_fields().map(float_bits).hash(state);
+        state.write_usize(self.lods.len());
+        for lod in &self.lods {
+            float_bits(*lod).hash(state);
+        }
+        self.tile_width.hash(state);
+        self.tile_height.hash(state);
+        self.tile_sizes.hash(state);
+        self.y_direction.hash(state);
+        self.matrix_sizes.hash(state);
+        self.zoom_bias.hash(state);
+        self.partial_edge_policy.hash(state);
+        self.index_base.hash(state);
+    }
+}
+
+/// Bits of the value for the exact comparison, with all `NaN` values mapped to the same bits.
+fn float_bits(value: f64) -> u64 {
+    if value.is_nan() {
+        f64::NAN.to_bits()
+    } else {
+        value.to_bits()
+    }
 }
 
 impl TileSchema {
+    /// Floating point parameters of the schema except for the resolutions, in a fixed order.
+    fn float_fields(&self) -> [f64; 10] {
+        [
+            self.origin.x(),
+            self.origin.y(),
+            self.bounds.x_min(),
+            self.bounds.y_min(),
+            self.bounds.x_max(),
+            self.bounds.y_max(),
+            self.tile_offset.0,
+            self.tile_offset.1,
+            self.dpi_scale as f64,
+            self.tile_inset as f64,
+        ]
+    }
+
     /// Resolution of the given z-level, if exists.
     pub fn lod_resolution(&self, z: u32) -> Option<f64> {
+        self.resolution_at(z)
+    }
+
+    /// Resolution of the z-level `z`, or `None` if the level is not present in the schema.
+    ///
+    /// All the grid computations get their resolutions from here, so the levels skipped by the
+    /// schema (with `NaN` resolution) give `None` instead of the `NaN` coordinates.
+    fn resolution_at(&self, z: u32) -> Option<f64> {
         let resolution = *self.lods.get(z as usize)?;
         if resolution.is_finite() && resolution > 0.0 {
             Some(resolution)
@@ -46,6 +271,49 @@ impl TileSchema {
         }
     }
 
+    /// Resolution of the given z-level in degrees per pixel, if the level exists.
+    ///
+    /// For [geographic](TileSchema::is_geographic) schemas this is the same as
+    /// [`TileSchema::lod_resolution`]. For projected schemas the resolution is assumed to be in
+    /// meters and is converted using the length of a degree at the equator.
+    pub fn resolution_degrees(&self, z: u32) -> Option<f64> {
+        let resolution = self.resolution_at(z)?;
+        if self.is_geographic() {
+            Some(resolution)
+        } else {
+            Some(resolution / METERS_PER_DEGREE)
+        }
+    }
+
+    /// Scale denominator of the given z-level when its tiles are displayed with `dpi` pixels per
+    /// inch, e.g. `50000.0` for the scale 1:50000, if the level exists.
+    ///
+    /// The resolution of projected schemas is assumed to be in meters, and for
+    /// [geographic](TileSchema::is_geographic) schemas it is converted using the length of a
+    /// degree at the equator.
+    pub fn scale_denominator(&self, z: u32, dpi: f64) -> Option<f64> {
+        const INCHES_PER_METER: f64 = 1.0 / 0.0254;
+
+        let resolution = self.resolution_at(z)?;
+        let meters_per_pixel = if self.is_geographic() {
+            resolution * METERS_PER_DEGREE
+        } else {
+            resolution
+        };
+
+        Some(meters_per_pixel * dpi * INCHES_PER_METER)
+    }
+
+    /// Returns true if the schema bounds are within the range of geographic coordinates, which
+    /// means the schema is in degrees rather than in projected units.
+    pub fn is_geographic(&self) -> bool {
+        const TOLERANCE: f64 = 1e-6;
+        self.bounds.x_min() >= -180.0 - TOLERANCE
+            && self.bounds.x_max() <= 180.0 + TOLERANCE
+            && self.bounds.y_min() >= -90.0 - TOLERANCE
+            && self.bounds.y_max() <= 90.0 + TOLERANCE
+    }
+
     /// Width of a single tile.
     pub fn tile_width(&self) -> u32 {
         self.tile_width
@@ -56,28 +324,347 @@ impl TileSchema {
         self.tile_height
     }
 
+    /// Width of a single tile of the level `z`.
+    ///
+    /// Equals to [`TileSchema::tile_width`] unless a different size is set for the level.
+    pub fn tile_width_at(&self, z: u32) -> u32 {
+        self.tile_size_at(z)
+            .map_or(self.tile_width, |(width, _)| width)
+    }
+
+    /// Height of a single tile of the level `z`.
+    ///
+    /// Equals to [`TileSchema::tile_height`] unless a different size is set for the level.
+    pub fn tile_height_at(&self, z: u32) -> u32 {
+        self.tile_size_at(z)
+            .map_or(self.tile_height, |(_, height)| height)
+    }
+
+    fn tile_size_at(&self, z: u32) -> Option<(u32, u32)> {
+        self.tile_sizes.as_ref()?.get(z as usize).copied()
+    }
+
+    /// Scale of the tiles relative to the base tile size, e.g. `2.0` for `@2x` tiles on high-DPI
+    /// screens. Tile sizes and resolutions of the schema already include this scale.
+    pub fn dpi_scale(&self) -> f32 {
+        self.dpi_scale
+    }
+
+    /// Number of pixels each tile is expanded by on every side when rendered, so that neighbouring
+    /// tiles overlap slightly and no seams are visible between them.
+    ///
+    /// The inset affects only the rendered extent of tiles: the tile bounding boxes and the sets of
+    /// tiles needed for a view do not depend on it.
+    pub fn tile_inset(&self) -> f32 {
+        self.tile_inset
+    }
+
+    /// Returns true if images of tiles of the schema are expected to have the given size in pixels.
+    ///
+    /// Can be used to detect misconfigured sources, for example 512 pixel tiles loaded into a
+    /// schema with 256 pixel tiles. Sizes set for individual levels are not taken into account,
+    /// see [`TileSchema::tile_width_at`].
+    pub fn expects_tile_size(&self, width: u32, height: u32) -> bool {
+        width == self.tile_width && height == self.tile_height
+    }
+
+    /// Direction of the Y index of tiles.
+    pub fn y_direction(&self) -> VerticalDirection {
+        self.y_direction
+    }
+
+    /// Shift of the tile grid relative to the `origin` in fractions of a tile, along the direction
+    /// of the x and y indices.
+    pub fn tile_offset(&self) -> (f64, f64) {
+        self.tile_offset
+    }
+
+    /// Explicit number of tile columns and rows of the given z-level, if set.
+    ///
+    /// If not set, the size of the tile matrix is derived from the schema bounds and the level
+    /// resolution.
+    pub fn matrix_size(&self, z: u32) -> Option<(u32, u32)> {
+        self.matrix_sizes.get(z as usize).copied().flatten()
+    }
+
+    /// Number of z-levels the level selected by [`TileSchema::select_lod`] is shifted by.
+    pub fn zoom_bias(&self) -> i32 {
+        self.zoom_bias
+    }
+
+    /// Sets the number of z-levels the level selected by [`TileSchema::select_lod`] is shifted by.
+    ///
+    /// Positive bias makes the schema select coarser levels than the resolution requires (for
+    /// example, `1` selects the next coarser level), which reduces the number of loaded tiles at
+    /// the expense of the image quality. Negative bias selects finer levels. Levels without
+    /// resolution are skipped, and the selection is clamped to the available levels.
+    pub fn set_zoom_bias(&mut self, bias: i32) {
+        self.zoom_bias = bias;
+    }
+
+    /// Way the tiles that are only partially inside the schema bounds are treated.
+    pub fn partial_edge_policy(&self) -> PartialEdgePolicy {
+        self.partial_edge_policy
+    }
+
+    /// Sets the way the tiles that are only partially inside the schema bounds are treated.
+    pub fn set_partial_edge_policy(&mut self, policy: PartialEdgePolicy) {
+        self.partial_edge_policy = policy;
+    }
+
+    /// Index of the first tile column and row of every level.
+    ///
+    /// Most tile servers count tiles from 0, but some legacy servers use 1-based indices.
+    pub fn index_base(&self) -> u32 {
+        self.index_base
+    }
+
+    /// Sets the index of the first tile column and row of every level.
+    ///
+    /// All indices returned by the schema are shifted by this value, and all indices given to the
+    /// schema are expected to be shifted by it.
+    pub fn set_index_base(&mut self, base: u32) {
+        self.index_base = base;
+    }
+
+    /// Sets the resolution of the z-level `z`.
+    ///
+    /// Setting resolution to `NaN` removes the level from the schema. Resolutions of the levels must
+    /// decrease with increase of the z-level.
+    pub fn set_lod_resolution(&mut self, z: u32, resolution: f64) {
+        if self.lods.len() <= z as usize {
+            self.lods.resize(z as usize + 1, f64::NAN);
+        }
+
+        self.lods[z as usize] = resolution;
+        self.lod_lookup = LodLookup::default();
+    }
+
+    /// Returns a copy of the schema with only the levels of the given range, the rest of the levels
+    /// are removed.
+    ///
+    /// The tile grid of the levels in the range is the same as in the original schema, so the
+    /// tiles of the two schemas can be used interchangeably.
+    pub fn with_zoom_range(&self, range: RangeInclusive<u32>) -> TileSchema {
+        let mut schema = self.clone();
+        for (z, resolution) in schema.lods.iter_mut().enumerate() {
+            if !range.contains(&(z as u32)) {
+                *resolution = f64::NAN;
+            }
+        }
+
+        schema.lod_lookup = LodLookup::default();
+        schema
+    }
+
     /// Select a level of detail for the given resolution.
     pub fn select_lod(&self, resolution: f64) -> Option<Lod> {
-        if !resolution.is_finite() || self.lods.is_empty() {
+        if !resolution.is_finite() {
             return None;
         }
 
-        let mut selected_lod = None;
-
-        for index in 0..self.lods.len() {
-            let Some(lod_resolution) = self.lod_resolution(index as u32) else {
-                continue;
-            };
+        let lods = self.lod_lookup();
+        let mut selected = None;
 
-            selected_lod = Some(Lod::new(lod_resolution, index as u32)?);
+        for (position, lod) in lods.iter().enumerate() {
+            selected = Some(position);
 
-            let adjusted_resolution = lod_resolution * (1.0 - RESOLUTION_TOLERANCE);
+            let adjusted_resolution = lod.resolution() * (1.0 - RESOLUTION_TOLERANCE);
             if adjusted_resolution < resolution {
                 break;
             }
         }
 
-        selected_lod
+        let biased = (selected? as i64 - self.zoom_bias as i64).clamp(0, lods.len() as i64 - 1);
+        Some(lods[biased as usize])
+    }
+
+    /// Selects the finest z-level at which the `bbox` fits the `viewport` (size in pixels) in the
+    /// given `mode`.
+    ///
+    /// If the bounding box is too large to fit even the coarsest level, the coarsest level is
+    /// returned. Returns `None` if the schema has no valid levels or the viewport is empty.
+    pub fn fit_zoom(&self, bbox: Rect, viewport: Size, mode: FitMode) -> Option<u32> {
+        if viewport.width() <= 0.0 || viewport.height() <= 0.0 {
+            return None;
+        }
+
+        let x_resolution = bbox.width() / viewport.width();
+        let y_resolution = bbox.height() / viewport.height();
+        let required_resolution = match mode {
+            FitMode::Contain => x_resolution.max(y_resolution),
+            FitMode::Cover => x_resolution.min(y_resolution),
+        };
+
+        if !required_resolution.is_finite() {
+            return None;
+        }
+
+        let lods = self.lod_lookup();
+        lods.iter()
+            .rev()
+            .find(|lod| lod.resolution() * (1.0 + RESOLUTION_TOLERANCE) >= required_resolution)
+            .or(lods.first())
+            .map(|lod| lod.z_index())
+    }
+
+    /// Number of z-levels with resolution between `res_a` and `res_b` (inclusive). The order of
+    /// the arguments doesn't matter.
+    pub fn levels_between(&self, res_a: f64, res_b: f64) -> u32 {
+        let min = res_a.min(res_b) * (1.0 - RESOLUTION_TOLERANCE);
+        let max = res_a.max(res_b) * (1.0 + RESOLUTION_TOLERANCE);
+
+        self.lod_lookup()
+            .iter()
+            .filter(|lod| lod.resolution() >= min && lod.resolution() <= max)
+            .count() as u32
+    }
+
+    /// Z-level with resolution closest to the given one on a logarithmic scale.
+    ///
+    /// Levels without valid resolution are skipped, so resolutions outside of the range of the
+    /// schema give the coarsest or the finest level. Returns `None` if the schema has no valid
+    /// levels or `resolution` is not a positive number.
+    pub fn nearest_lod(&self, resolution: f64) -> Option<u32> {
+        if !resolution.is_finite() || resolution <= 0.0 {
+            return None;
+        }
+
+        let target = resolution.ln();
+        self.lod_lookup()
+            .iter()
+            .min_by(|a, b| {
+                let a = (a.resolution().ln() - target).abs();
+                let b = (b.resolution().ln() - target).abs();
+                a.total_cmp(&b)
+            })
+            .map(|lod| lod.z_index())
+    }
+
+    /// Resolution of the level returned by [`TileSchema::nearest_lod`], for snapping the view to
+    /// the native resolution of the tiles.
+    ///
+    /// Returns `None` if the schema has no valid levels or `resolution` is not a positive number.
+    pub fn snap_resolution(&self, resolution: f64) -> Option<f64> {
+        self.lod_resolution(self.nearest_lod(resolution)?)
+    }
+
+    /// Fractional zoom level of the given resolution.
+    ///
+    /// The zoom is interpolated logarithmically between the two valid levels surrounding the
+    /// resolution, so a resolution halfway between levels 3 and 4 on a logarithmic scale gives
+    /// `3.5`. Resolutions outside of the range of the schema give the coarsest or the finest
+    /// level. Returns `NaN` if the schema has no valid levels or `resolution` is not a positive
+    /// number.
+    pub fn resolution_to_zoom(&self, resolution: f64) -> f64 {
+        let lods = self.lod_lookup();
+        let (Some(first), Some(last)) = (lods.first(), lods.last()) else {
+            return f64::NAN;
+        };
+
+        if !resolution.is_finite() || resolution <= 0.0 {
+            return f64::NAN;
+        }
+
+        if resolution >= first.resolution() {
+            return first.z_index() as f64;
+        }
+
+        for pair in lods.windows(2) {
+            let (coarse, fine) = (pair[0], pair[1]);
+            if resolution >= fine.resolution() {
+                let t = (coarse.resolution().ln() - resolution.ln())
+                    / (coarse.resolution().ln() - fine.resolution().ln());
+                return coarse.z_index() as f64
+                    + t * (fine.z_index() as f64 - coarse.z_index() as f64);
+            }
+        }
+
+        last.z_index() as f64
+    }
+
+    /// Resolution of the given fractional zoom level. This is the inverse of
+    /// [`TileSchema::resolution_to_zoom`].
+    ///
+    /// Zoom levels outside of the range of the schema give the resolution of the coarsest or the
+    /// finest level. Returns `NaN` if the schema has no valid levels or `zoom` is `NaN`.
+    pub fn zoom_to_resolution(&self, zoom: f64) -> f64 {
+        let lods = self.lod_lookup();
+        let (Some(first), Some(last)) = (lods.first(), lods.last()) else {
+            return f64::NAN;
+        };
+
+        if zoom.is_nan() {
+            return f64::NAN;
+        }
+
+        if zoom <= first.z_index() as f64 {
+            return first.resolution();
+        }
+
+        for pair in lods.windows(2) {
+            let (coarse, fine) = (pair[0], pair[1]);
+            if zoom <= fine.z_index() as f64 {
+                if zoom == fine.z_index() as f64 {
+                    return fine.resolution();
+                }
+
+                let t = (zoom - coarse.z_index() as f64)
+                    / (fine.z_index() as f64 - coarse.z_index() as f64);
+                return (coarse.resolution().ln()
+                    + t * (fine.resolution().ln() - coarse.resolution().ln()))
+                .exp();
+            }
+        }
+
+        last.resolution()
+    }
+
+    /// Caps the z-level `z` at the finest level with a valid resolution.
+    ///
+    /// Tiles of the returned level can be displayed scaled up when the map is zoomed in beyond
+    /// the levels of the schema. If the schema has no valid levels, `z` is returned unchanged.
+    pub fn clamp_zoom_for_display(&self, z: u32) -> u32 {
+        self.lod_lookup()
+            .last()
+            .map_or(z, |lod| z.min(lod.z_index()))
+    }
+
+    /// The coarsest z-level with a valid resolution, if any.
+    pub fn min_z(&self) -> Option<u32> {
+        self.lod_lookup().first().map(|lod| lod.z_index())
+    }
+
+    /// Z-levels that have a valid resolution with their resolutions, sorted by z-level.
+    ///
+    /// Levels skipped by the schema are not returned.
+    pub fn valid_lods(&self) -> impl Iterator<Item = (u32, f64)> + '_ {
+        self.lod_lookup()
+            .iter()
+            .map(|lod| (lod.z_index(), lod.resolution()))
+    }
+
+    /// Resolution of the finest valid level, if any.
+    pub fn min_resolution(&self) -> Option<f64> {
+        self.valid_lods()
+            .map(|(_, resolution)| resolution)
+            .reduce(f64::min)
+    }
+
+    /// Resolution of the coarsest valid level, if any.
+    pub fn max_resolution(&self) -> Option<f64> {
+        self.valid_lods()
+            .map(|(_, resolution)| resolution)
+            .reduce(f64::max)
+    }
+
+    /// Levels of detail that have valid resolution, sorted by z-level.
+    fn lod_lookup(&self) -> &[Lod] {
+        self.lod_lookup.0.get_or_init(|| {
+            (0..self.lods.len() as u32)
+                .filter_map(|z| Lod::new(self.resolution_at(z)?, z))
+                .collect()
+        })
     }
 
     /// Iterate over tile indices that should be displayed for the given map view.
@@ -87,217 +674,1837 @@ impl TileSchema {
         self.iter_tiles_over_bbox(resolution, bounding_box)
     }
 
-    fn iter_tiles_over_bbox(
-        &self,
-        resolution: f64,
-        bounding_box: Rect,
-    ) -> Option<impl Iterator<Item = WrappingTileIndex>> {
-        let lod = self.select_lod(resolution)?;
+    /// Tiles needed for a non-rotated view of `viewport` size (in pixels) centered at `center`
+    /// while it is zoomed from `old_resolution` to `new_resolution`.
+    ///
+    /// The tiles are of the level selected for `new_resolution`, and cover the area visible at
+    /// the coarser of the two resolutions, so that they stay sufficient during the whole zoom
+    /// animation. As the center doesn't change, the area is derived directly from it without
+    /// projecting the view. If the resolutions are equal, the result is the same as of
+    /// [`TileSchema::iter_tiles`] for the view.
+    pub fn needed_tiles_for_zoom(
+        &self,
+        center: Point2,
+        old_resolution: f64,
+        new_resolution: f64,
+        viewport: Size,
+    ) -> Option<impl Iterator<Item = WrappingTileIndex>> {
+        let extent_resolution = old_resolution.max(new_resolution);
+        let half_width = viewport.width() * extent_resolution / 2.0;
+        let half_height = viewport.height() * extent_resolution / 2.0;
+        let bbox = Rect::new(
+            center.x() - half_width,
+            center.y() - half_height,
+            center.x() + half_width,
+            center.y() + half_height,
+        );
+
+        self.iter_tiles_over_bbox(new_resolution, bbox)
+    }
+
+    fn iter_tiles_over_bbox(
+        &self,
+        resolution: f64,
+        bounding_box: Rect,
+    ) -> Option<impl Iterator<Item = WrappingTileIndex>> {
+        let lod = self.select_lod(resolution)?;
+        Some(self.iter_lod_tiles_over_bbox(lod, bounding_box))
+    }
+
+    /// Returns tiles of the level `z` that cover the given bounding box in the given order.
+    ///
+    /// Unlike [`TileSchema::iter_tiles`] the traversal order is guaranteed, which is useful when
+    /// the tiles are exported to some other tiled layout (for example, pages of a print).
+    pub fn tiles_covering_ordered(
+        &self,
+        bbox: Rect,
+        z: u32,
+        order: TileOrder,
+    ) -> impl Iterator<Item = WrappingTileIndex> {
+        let mut tiles: Vec<WrappingTileIndex> = self
+            .resolution_at(z)
+            .and_then(|resolution| Lod::new(resolution, z))
+            .map(|lod| self.iter_lod_tiles_over_bbox(lod, bbox).collect())
+            .unwrap_or_default();
+
+        match order {
+            TileOrder::RowMajor => tiles.sort_by_key(|index| (index.y, index.display_x)),
+            TileOrder::ColumnMajor => tiles.sort_by_key(|index| (index.display_x, index.y)),
+            TileOrder::Spiral => tiles = Self::spiral_order(tiles),
+            TileOrder::Hilbert => Self::hilbert_order(&mut tiles),
+        }
+
+        tiles.into_iter()
+    }
+
+    /// Returns tiles of the level `z` covering the given bounding box ordered along a Hilbert
+    /// curve.
+    ///
+    /// The order is deterministic and keeps the tiles that are close in space close in the
+    /// sequence, which improves locality of disk-backed caches when the tiles are downloaded.
+    pub fn tiles_covering_hilbert(&self, bbox: Rect, z: u32) -> impl Iterator<Item = TileIndex> {
+        self.tiles_covering_ordered(bbox, z, TileOrder::Hilbert)
+            .map(TileIndex::from)
+    }
+
+    fn hilbert_order(tiles: &mut [WrappingTileIndex]) {
+        let (Some(x_min), Some(x_max), Some(y_min), Some(y_max)) = (
+            tiles.iter().map(|index| index.display_x).min(),
+            tiles.iter().map(|index| index.display_x).max(),
+            tiles.iter().map(|index| index.y).min(),
+            tiles.iter().map(|index| index.y).max(),
+        ) else {
+            return;
+        };
+
+        let side = ((x_max - x_min).max(y_max - y_min) as u32 + 1).next_power_of_two();
+        tiles.sort_by_cached_key(|index| {
+            hilbert_distance(
+                side,
+                (index.display_x - x_min) as u32,
+                (index.y - y_min) as u32,
+            )
+        });
+    }
+
+    /// Returns the tiles of the level `z` that intersect the given bounding box.
+    ///
+    /// Indices are limited to the range of the tiles of the level, and the tiles that are wrapped
+    /// over the 180 longitude line are returned with their actual indices, each only once. If the
+    /// level is not present in the schema, nothing is returned.
+    pub fn tiles_in_bbox(&self, bbox: Rect, z: u32) -> impl Iterator<Item = TileIndex> {
+        self.tiles_covering_multi(bbox, [z])
+    }
+
+    /// Returns tiles covering the given bounding box at every level of `z_levels`.
+    ///
+    /// Levels are traversed from the coarsest to the finest one regardless of the order they are
+    /// given in, so that the tiles can be loaded in cascade (e.g. when downloading a region for
+    /// offline use). Levels that are not present in the schema are skipped. Every tile is returned
+    /// only once.
+    pub fn tiles_covering_multi(
+        &self,
+        bbox: Rect,
+        z_levels: impl IntoIterator<Item = u32>,
+    ) -> impl Iterator<Item = TileIndex> {
+        let mut lods: Vec<Lod> = z_levels
+            .into_iter()
+            .filter_map(|z| Lod::new(self.resolution_at(z)?, z))
+            .collect();
+        lods.sort_by(|a, b| b.resolution().total_cmp(&a.resolution()));
+        lods.dedup_by_key(|lod| lod.z_index());
+
+        let mut found = HashSet::default();
+        let mut tiles = vec![];
+        for lod in lods {
+            for index in self.iter_lod_tiles_over_bbox(lod, bbox) {
+                let index = TileIndex::from(index);
+                if found.insert(index) {
+                    tiles.push(index);
+                }
+            }
+        }
+
+        tiles.into_iter()
+    }
+
+    fn spiral_order(tiles: Vec<WrappingTileIndex>) -> Vec<WrappingTileIndex> {
+        let (Some(x_min), Some(x_max), Some(y_min), Some(y_max)) = (
+            tiles.iter().map(|index| index.display_x).min(),
+            tiles.iter().map(|index| index.display_x).max(),
+            tiles.iter().map(|index| index.y).min(),
+            tiles.iter().map(|index| index.y).max(),
+        ) else {
+            return tiles;
+        };
+
+        let mut by_position: HashMap<(i32, i32), WrappingTileIndex> = tiles
+            .into_iter()
+            .map(|index| ((index.display_x, index.y), index))
+            .collect();
+        let mut ordered = Vec::with_capacity(by_position.len());
+
+        let (mut x, mut y) = (x_min + (x_max - x_min) / 2, y_min + (y_max - y_min) / 2);
+        let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+        let mut direction = 0;
+        let mut step_length = 1;
+
+        if let Some(index) = by_position.remove(&(x, y)) {
+            ordered.push(index);
+        }
+
+        while !by_position.is_empty() {
+            for _ in 0..2 {
+                let (dx, dy) = directions[direction];
+                for _ in 0..step_length {
+                    x += dx;
+                    y += dy;
+                    if let Some(index) = by_position.remove(&(x, y)) {
+                        ordered.push(index);
+                    }
+                }
+
+                direction = (direction + 1) % directions.len();
+            }
+
+            step_length += 1;
+        }
+
+        ordered
+    }
+
+    /// Returns tiles of the level `z` that are within `buffer` distance from the polyline going
+    /// through the given `points`.
+    ///
+    /// This can be used to preload tiles along a route. Every tile is returned only once.
+    pub fn tiles_along_line(
+        &self,
+        points: &[Point2],
+        z: u32,
+        buffer: f64,
+    ) -> impl Iterator<Item = TileIndex> {
+        let mut tiles = vec![];
+        let lod = self
+            .resolution_at(z)
+            .and_then(|resolution| Lod::new(resolution, z));
+
+        if let Some(lod) = lod {
+            let mut found = HashSet::default();
+            let segments = points
+                .windows(2)
+                .map(|pair| (pair[0], pair[1]))
+                .chain(match points {
+                    [single] => Some((*single, *single)),
+                    _ => None,
+                });
+
+            for (from, to) in segments {
+                // Only the tiles around the segment are checked, so the complexity is proportional
+                // to the number of found tiles rather than to the product of tiles and segments.
+                let segment_bbox = Rect::new(
+                    from.x().min(to.x()) - buffer,
+                    from.y().min(to.y()) - buffer,
+                    from.x().max(to.x()) + buffer,
+                    from.y().max(to.y()) + buffer,
+                );
+
+                for index in self.iter_lod_tiles_over_bbox(lod, segment_bbox) {
+                    let Some(tile_bbox) = self.tile_bbox(index) else {
+                        continue;
+                    };
+
+                    if segment_to_rect_distance(from, to, tile_bbox) <= buffer {
+                        let index = TileIndex::from(index);
+                        if found.insert(index) {
+                            tiles.push(index);
+                        }
+                    }
+                }
+            }
+        }
+
+        tiles.into_iter()
+    }
+
+    /// Returns tiles of the level `z` that cover the given cartesian `geometry`.
+    ///
+    /// The geometry must be in the projection of the schema, so a GeoJSON feature should be
+    /// projected before calling this method. If `precise` is false, all tiles covering the
+    /// bounding rectangle of the geometry are returned. Otherwise only the tiles that actually
+    /// intersect the geometry are returned, which is slower but skips, for example, the corners of
+    /// the bounding rectangle of a diagonal line.
+    pub fn tiles_for_geometry<P>(
+        &self,
+        geometry: &Geom<P>,
+        z: u32,
+        precise: bool,
+    ) -> impl Iterator<Item = TileIndex>
+    where
+        P: CartesianPoint2d<Num = f64>
+            + GeometryType<Type = PointGeometryType, Space = CartesianSpace2d>
+            + Copy,
+    {
+        let mut tiles = vec![];
+        let lod = self
+            .resolution_at(z)
+            .and_then(|resolution| Lod::new(resolution, z));
+
+        if let (Some(lod), Some(bbox)) = (lod, geometry.bounding_rectangle()) {
+            let segments = if precise {
+                geometry_segments(geometry)
+            } else {
+                vec![]
+            };
+            let is_areal = matches!(geometry, Geom::Polygon(_) | Geom::MultiPolygon(_));
+            let mut found = HashSet::default();
+
+            for index in self.iter_lod_tiles_over_bbox(lod, bbox) {
+                if precise {
+                    let Some(tile_bbox) = self.tile_bbox(index) else {
+                        continue;
+                    };
+
+                    let intersects = segments
+                        .iter()
+                        .any(|(from, to)| segment_intersects_rect(*from, *to, tile_bbox))
+                        || (is_areal && geometry.is_point_inside(&tile_bbox.center(), 0.0));
+                    if !intersects {
+                        continue;
+                    }
+                }
+
+                let index = TileIndex::from(index);
+                if found.insert(index) {
+                    tiles.push(index);
+                }
+            }
+        }
+
+        tiles.into_iter()
+    }
+
+    /// Returns the tile of the level `target_z` that contains the center of the tile `index`.
+    ///
+    /// For a coarser level this is the ancestor of the tile. For a finer level the center of the
+    /// tile lies on the corner between 4 descendants, and the one with the greater X and Y indices
+    /// is returned. The schema is assumed to have every level twice as fine as the previous one.
+    pub fn index_at_level(&self, index: TileIndex, target_z: u32) -> TileIndex {
+        let base = self.index_base as i32;
+        let (x, y) = (index.x - base, index.y - base);
+        let (x, y) = if target_z <= index.z {
+            let factor = 1 << (index.z - target_z);
+            (x.div_euclid(factor), y.div_euclid(factor))
+        } else {
+            let factor = 1 << (target_z - index.z);
+            let center_shift = factor / 2;
+            (x * factor + center_shift, y * factor + center_shift)
+        };
+
+        TileIndex::new(x + base, y + base, target_z)
+    }
+
+    /// Returns tiles of the `other` schema at level `z` that overlap with the tile `index` of this
+    /// schema.
+    ///
+    /// This is useful when tiles of layers with different schemas (for example with 256 and 512
+    /// pixel tiles) are composed together. Overlaps smaller than half a pixel of the tile `index`
+    /// are ignored.
+    pub fn overlapping_tiles(
+        &self,
+        index: TileIndex,
+        other: &TileSchema,
+        z: u32,
+    ) -> Vec<TileIndex> {
+        let (Some(bbox), Some(resolution)) = (
+            self.tile_bbox(index.into_wrapping()),
+            self.resolution_at(index.z),
+        ) else {
+            return vec![];
+        };
+        let bbox = bbox.shrink(resolution / 2.0);
+        let Some(lod) = other
+            .resolution_at(z)
+            .and_then(|resolution| Lod::new(resolution, z))
+        else {
+            return vec![];
+        };
+
+        let mut tiles: Vec<TileIndex> = other
+            .iter_lod_tiles_over_bbox(lod, bbox)
+            .map(TileIndex::from)
+            .collect();
+        tiles.dedup();
+
+        tiles
+    }
+
+    /// Returns the tile of the level `z` that contains the given point.
+    ///
+    /// Points to the east or to the west of the schema bounds are wrapped around, so the returned
+    /// index has `display_x` outside of the schema range. Returns `None` if the level does not
+    /// exist or the point is above or below the schema bounds.
+    pub fn tile_at_point(&self, point: Point2, z: u32) -> Option<WrappingTileIndex> {
+        let lod = Lod::new(self.resolution_at(z)?, z)?;
+        let tile_w = lod.resolution() * self.tile_width_at(lod.z_index()) as f64;
+        let tile_h = lod.resolution() * self.tile_height_at(lod.z_index()) as f64;
+
+        let display_x = (self.x_adj(point.x(), lod) / tile_w).floor() as i32;
+        let y = (self.y_adj(point.y(), lod) / tile_h).floor() as i32;
+        if y < self.min_y_index(lod) || y > self.max_y_index(lod) {
+            return None;
+        }
+
+        let schema_x_min = self.min_x_index(lod);
+        let index_range = self.max_x_index(lod) - schema_x_min + 1;
+        let x = (display_x - schema_x_min).rem_euclid(index_range) + schema_x_min;
+
+        let base = self.index_base as i32;
+        Some(WrappingTileIndex {
+            x: x + base,
+            y: y + base,
+            z,
+            display_x: display_x + base,
+        })
+    }
+
+    /// Returns the tile of the level `z` that contains the given point.
+    ///
+    /// Unlike [`TileSchema::tile_at_point`] the points outside of the schema bounds are not
+    /// wrapped, and `None` is returned for them. A point on the boundary between tiles belongs to
+    /// the lower left of them, unless it is on the edge of the bounds.
+    pub fn point_to_index(&self, point: Point2, z: u32) -> Option<TileIndex> {
+        let lod = Lod::new(self.resolution_at(z)?, z)?;
+        if !self.bounds.contains(&point) {
+            return None;
+        }
+
+        let tile_w = lod.resolution() * self.tile_width_at(lod.z_index()) as f64;
+        let tile_h = lod.resolution() * self.tile_height_at(lod.z_index()) as f64;
+
+        // Rounding errors must not move the points on the tile boundaries to the neighbour tiles.
+        let snap = |value: f64| {
+            let rounded = value.round();
+            if (value - rounded).abs() < 1e-9 {
+                rounded
+            } else {
+                value
+            }
+        };
+
+        let x = snap(self.x_adj(point.x(), lod) / tile_w).ceil() as i32 - 1;
+        let y_tiles = snap(self.y_adj(point.y(), lod) / tile_h);
+        let y = match self.y_direction {
+            VerticalDirection::TopToBottom => y_tiles.floor() as i32,
+            VerticalDirection::BottomToTop => y_tiles.ceil() as i32 - 1,
+        };
+
+        let base = self.index_base as i32;
+        Some(TileIndex {
+            x: x.clamp(self.min_x_index(lod), self.max_x_index(lod)) + base,
+            y: y.clamp(self.min_y_index(lod), self.max_y_index(lod)) + base,
+            z,
+        })
+    }
+
+    fn iter_lod_tiles_over_bbox(
+        &self,
+        lod: Lod,
+        bounding_box: Rect,
+    ) -> impl Iterator<Item = WrappingTileIndex> {
+        self.iter_tile_range(self.lod_tile_range(lod, bounding_box))
+    }
+
+    /// Returns the range of tiles that should be displayed for the given map view.
+    ///
+    /// The range only changes when the view crosses a tile boundary, so it can be used to check if
+    /// the set of tiles returned by [`TileSchema::iter_tiles`] has changed without collecting it.
+    pub(crate) fn tile_range(&self, view: &MapView) -> Option<TileRange> {
+        let lod = self.select_lod(view.resolution())?;
+        let bounding_box = view.get_bbox()?;
+        Some(self.lod_tile_range(lod, bounding_box))
+    }
+
+    fn lod_tile_range(&self, lod: Lod, bounding_box: Rect) -> TileRange {
+        let tile_w = lod.resolution() * self.tile_width_at(lod.z_index()) as f64;
+        let tile_h = lod.resolution() * self.tile_height_at(lod.z_index()) as f64;
+
+        let x_min = (self.x_adj(bounding_box.x_min(), lod) / tile_w).floor() as i32;
+        let x_min = x_min.max(self.min_x_displayed_index(lod));
+
+        let x_max_adj = self.x_adj(bounding_box.x_max(), lod);
+        let x_add_one = if (x_max_adj % tile_w) < 0.001 { -1 } else { 0 };
+
+        let x_max = (x_max_adj / tile_w) as i32 + x_add_one;
+        let x_max = x_max.min(self.max_x_displayed_index(lod));
+
+        let (top, bottom) = if self.y_direction == VerticalDirection::TopToBottom {
+            (bounding_box.y_min(), bounding_box.y_max())
+        } else {
+            (bounding_box.y_max(), bounding_box.y_min())
+        };
+
+        let y_min = (self.y_adj(bottom, lod) / tile_h).floor() as i32;
+        let y_min = y_min.max(self.min_y_index(lod));
+
+        let y_max_adj = self.y_adj(top, lod);
+        let y_add_one = if (y_max_adj % tile_h) < 0.001 { -1 } else { 0 };
+
+        let y_max = (y_max_adj / tile_h) as i32 + y_add_one;
+        let y_max = y_max.min(self.max_y_index(lod));
+
+        TileRange {
+            z: lod.z_index(),
+            x_min,
+            x_max,
+            y_min,
+            y_max,
+        }
+    }
+
+    /// Returns the range of tiles of the level `z` that can intersect or touch the `bbox`.
+    ///
+    /// The range is extended by one tile on every side to be robust to rounding errors, and it is
+    /// not limited by the bounds of the schema, so every tile whose bounding box intersects the
+    /// `bbox` is guaranteed to be in the range.
+    pub(crate) fn touching_tile_range(&self, bbox: Rect, z: u32) -> Option<TileRange> {
+        let resolution = self.resolution_at(z)?;
+        let origin = self.lod_origin(resolution, z);
+        let tile_w = resolution * self.tile_width_at(z) as f64;
+        let tile_h = resolution * self.tile_height_at(z) as f64;
+
+        let index = |value: f64, size: f64| (value / size).floor() as i32;
+        let x_min = index(bbox.x_min() - origin.x(), tile_w);
+        let x_max = index(bbox.x_max() - origin.x(), tile_w);
+        let (y_min, y_max) = match self.y_direction {
+            VerticalDirection::TopToBottom => (
+                index(origin.y() - bbox.y_max(), tile_h),
+                index(origin.y() - bbox.y_min(), tile_h),
+            ),
+            VerticalDirection::BottomToTop => (
+                index(bbox.y_min() - origin.y(), tile_h),
+                index(bbox.y_max() - origin.y(), tile_h),
+            ),
+        };
+
+        Some(TileRange {
+            z,
+            x_min: x_min.saturating_sub(1),
+            x_max: x_max.saturating_add(1),
+            y_min: y_min.saturating_sub(1),
+            y_max: y_max.saturating_add(1),
+        })
+    }
+
+    /// Iterates over the tiles of the given range.
+    pub(crate) fn iter_tile_range(
+        &self,
+        range: TileRange,
+    ) -> impl Iterator<Item = WrappingTileIndex> {
+        let TileRange {
+            z,
+            x_min,
+            x_max,
+            y_min,
+            y_max,
+        } = range;
+
+        let (schema_x_min, schema_x_max) = self
+            .resolution_at(z)
+            .and_then(|resolution| Lod::new(resolution, z))
+            .map(|lod| (self.min_x_index(lod), self.max_x_index(lod)))
+            .unwrap_or((x_min, x_max));
+        let index_range = (schema_x_max - schema_x_min + 1).max(1);
+        let base = self.index_base as i32;
+
+        let actual_x =
+            move |display_x: i32| (display_x - schema_x_min).rem_euclid(index_range) + schema_x_min;
+
+        (x_min..=x_max).flat_map(move |x| {
+            (y_min..=y_max).map(move |y| WrappingTileIndex {
+                x: actual_x(x) + base,
+                y: y + base,
+                z,
+                display_x: x + base,
+            })
+        })
+    }
+
+    fn x_adj(&self, x: f64, lod: Lod) -> f64 {
+        x - self.lod_origin(lod.resolution(), lod.z_index()).x()
+    }
+
+    fn y_adj(&self, y: f64, lod: Lod) -> f64 {
+        let origin = self.lod_origin(lod.resolution(), lod.z_index());
+        match self.y_direction {
+            VerticalDirection::TopToBottom => origin.y() - y,
+            VerticalDirection::BottomToTop => y - origin.y(),
+        }
+    }
+
+    /// Position of the `X == 0, Y == 0` tile corner at the given resolution, taking the tile
+    /// offset into account.
+    fn lod_origin(&self, resolution: f64, z: u32) -> Point2 {
+        let (dx, dy) = self.tile_offset;
+        let shift_x = dx * self.tile_width_at(z) as f64 * resolution;
+        let shift_y = dy * self.tile_height_at(z) as f64 * resolution;
+
+        match self.y_direction {
+            VerticalDirection::TopToBottom => {
+                Point2::new(self.origin.x() + shift_x, self.origin.y() - shift_y)
+            }
+            VerticalDirection::BottomToTop => {
+                Point2::new(self.origin.x() + shift_x, self.origin.y() + shift_y)
+            }
+        }
+    }
+
+    /// Standard Web Mercator based tile scheme (used, for example, by OSM and Google maps).
+    pub fn web(lods_count: u32) -> Self {
+        const ORIGIN: Point2 = Point2::new(-20037508.342787, 20037508.342787);
+        const TOP_RESOLUTION: f64 = 156543.03392800014;
+
+        Self::quad(
+            ORIGIN,
+            Rect::new(
+                -20037508.342787,
+                -20037508.342787,
+                20037508.342787,
+                20037508.342787,
+            ),
+            TOP_RESOLUTION,
+            lods_count,
+        )
+    }
+
+    /// Creates one of the well-known tile schemas by its OGC TileMatrixSet identifier.
+    ///
+    /// Supported identifiers are:
+    /// * `WebMercatorQuad` - Web Mercator (EPSG:3857) with a single tile at level 0, levels
+    ///   `0..=24`.
+    /// * `WorldCRS84Quad` - geographic coordinates (CRS84) with two tiles at level 0, levels
+    ///   `0..=23`.
+    /// * `WorldMercatorWGS84Quad` - World Mercator (EPSG:3395) with a single tile at level 0,
+    ///   levels `0..=24`.
+    ///
+    /// Returns `None` for an unknown identifier.
+    pub fn preset(name: &str) -> Option<Self> {
+        const MERCATOR_MAX: f64 = 20037508.3427892;
+        const MERCATOR_TOP_RESOLUTION: f64 = 156543.03392804097;
+
+        let mercator_bounds = Rect::new(-MERCATOR_MAX, -MERCATOR_MAX, MERCATOR_MAX, MERCATOR_MAX);
+        let mercator_origin = Point2::new(-MERCATOR_MAX, MERCATOR_MAX);
+
+        match name {
+            "WebMercatorQuad" | "WorldMercatorWGS84Quad" => Some(Self::quad(
+                mercator_origin,
+                mercator_bounds,
+                MERCATOR_TOP_RESOLUTION,
+                25,
+            )),
+            "WorldCRS84Quad" => Some(Self::quad(
+                Point2::new(-180.0, 90.0),
+                Rect::new(-180.0, -90.0, 180.0, 90.0),
+                180.0 / 256.0,
+                24,
+            )),
+            _ => None,
+        }
+    }
+
+    /// Top-to-bottom schema of 256 pixel tiles with resolution halving at every level.
+    fn quad(origin: Point2, bounds: Rect, top_resolution: f64, lods_count: u32) -> Self {
+        let mut lods = vec![top_resolution];
+        for i in 1..lods_count {
+            lods.push(lods[(i - 1) as usize] / 2.0);
+        }
+
+        TileSchema {
+            origin,
+            bounds,
+            lods,
+            tile_width: 256,
+            tile_height: 256,
+            tile_sizes: None,
+            y_direction: VerticalDirection::TopToBottom,
+            matrix_sizes: Vec::new(),
+            zoom_bias: 0,
+            tile_offset: (0.0, 0.0),
+            partial_edge_policy: PartialEdgePolicy::default(),
+            index_base: 0,
+            dpi_scale: 1.0,
+            tile_inset: 0.0,
+            lod_lookup: LodLookup::default(),
+        }
+    }
+
+    /// Number of tile columns and rows of the level `z`, or `None` if the level does not exist in
+    /// the schema.
+    ///
+    /// Tiles that are [valid](TileSchema::is_valid_index) at the level are the ones within this
+    /// grid.
+    pub fn tile_count(&self, z: u32) -> Option<(u32, u32)> {
+        let lod = Lod::new(self.resolution_at(z)?, z)?;
+
+        let columns = self.max_x_index(lod) - self.min_x_index(lod) + 1;
+        let rows = self.max_y_index(lod) - self.min_y_index(lod) + 1;
+        Some((columns.max(0) as u32, rows.max(0) as u32))
+    }
+
+    /// Returns all the [valid](TileSchema::is_valid_index) tiles of the level `z` row by row, or
+    /// `None` if the level is not present in the schema.
+    pub fn level_tiles(&self, z: u32) -> Option<impl Iterator<Item = TileIndex>> {
+        let lod = Lod::new(self.resolution_at(z)?, z)?;
+
+        let base = self.index_base as i32;
+        let columns = self.min_x_index(lod)..=self.max_x_index(lod);
+        let rows = self.min_y_index(lod)..=self.max_y_index(lod);
+        Some(rows.flat_map(move |y| {
+            columns
+                .clone()
+                .map(move |x| TileIndex::new(x + base, y + base, z))
+        }))
+    }
+
+    /// Returns true if the level `index.z` exists in the schema and the tile is inside the schema
+    /// bounds.
+    ///
+    /// For Web Mercator schemas the rows are limited by the bounds of the projection (about
+    /// ±85.051° of latitude), so rows beyond the poles are not valid.
+    pub fn is_valid_index(&self, index: TileIndex) -> bool {
+        let Some(lod) = self
+            .resolution_at(index.z)
+            .and_then(|resolution| Lod::new(resolution, index.z))
+        else {
+            return false;
+        };
+
+        let base = self.index_base as i32;
+        (self.min_x_index(lod)..=self.max_x_index(lod)).contains(&(index.x - base))
+            && (self.min_y_index(lod)..=self.max_y_index(lod)).contains(&(index.y - base))
+    }
+
+    /// Returns the closest valid index to the given one, moving its column and row into the tile
+    /// matrix of the level.
+    ///
+    /// Returns `None` if the schema has no resolution for the level of the index.
+    pub fn clamp_index(&self, index: TileIndex) -> Option<TileIndex> {
+        let lod = Lod::new(self.resolution_at(index.z)?, index.z)?;
+
+        let base = self.index_base as i32;
+        let x = (index.x - base).clamp(self.min_x_index(lod), self.max_x_index(lod));
+        let y = (index.y - base).clamp(self.min_y_index(lod), self.max_y_index(lod));
+        Some(TileIndex::new(x + base, y + base, index.z))
+    }
+
+    /// Same as [`TileSchema::clamp_index`], but the columns outside the tile matrix are wrapped
+    /// around it horizontally, as for the copies of the world to the sides of the antimeridian.
+    /// Rows are still clamped.
+    pub fn wrap_index(&self, index: TileIndex) -> Option<TileIndex> {
+        let lod = Lod::new(self.resolution_at(index.z)?, index.z)?;
+
+        let base = self.index_base as i32;
+        let x_min = self.min_x_index(lod);
+        let columns = self.max_x_index(lod) - x_min + 1;
+        let x = (index.x - base - x_min).rem_euclid(columns) + x_min;
+        let y = (index.y - base).clamp(self.min_y_index(lod), self.max_y_index(lod));
+        Some(TileIndex::new(x + base, y + base, index.z))
+    }
+
+    /// Returns the bounding rectangle of the given tile index, if the index is valid.
+    pub fn tile_bbox(&self, index: WrappingTileIndex) -> Option<Rect> {
+        let base = self.index_base as i32;
+        let x_index = index.display_x - base;
+        let y_index = index.y - base;
+
+        let resolution = self.resolution_at(index.z)?;
+        let origin = self.lod_origin(resolution, index.z);
+        let tile_w = self.tile_width_at(index.z) as f64 * resolution;
+        let tile_h = self.tile_height_at(index.z) as f64 * resolution;
+        let x_min = origin.x() + x_index as f64 * tile_w;
+        let y_min = match self.y_direction {
+            VerticalDirection::TopToBottom => origin.y() - (y_index + 1) as f64 * tile_h,
+            VerticalDirection::BottomToTop => origin.y() + y_index as f64 * tile_h,
+        };
+
+        let bbox = Rect::new(x_min, y_min, x_min + tile_w, y_min + tile_h);
+
+        if self.partial_edge_policy != PartialEdgePolicy::Clamp {
+            return Some(bbox);
+        }
+
+        // Wrapped copies of the tile are clipped by the copies of the bounds they are in.
+        let wrap_shift = (index.display_x - index.x) as f64 * tile_w;
+        Some(bbox.limit(self.bounds.shift(wrap_shift, 0.0)))
+    }
+
+    /// Returns the extent of the tile in map coordinates.
+    ///
+    /// Unlike [`TileSchema::tile_bbox`] the index is checked against the schema first, so `None`
+    /// is returned for tiles outside the tile matrix or at levels without a resolution. Row `0` is
+    /// the northernmost row for [`VerticalDirection::TopToBottom`] schemas and the southernmost one
+    /// for [`VerticalDirection::BottomToTop`] schemas.
+    pub fn tile_world_rect(&self, index: TileIndex) -> Option<Rect> {
+        if !self.is_valid_index(index) {
+            return None;
+        }
+
+        self.tile_bbox(index.into_wrapping())
+    }
+
+    /// Returns the center point of the given tile, if the index is valid.
+    pub fn tile_center(&self, index: TileIndex) -> Option<Point2> {
+        self.tile_bbox(index.into_wrapping())
+            .map(|bbox| bbox.center())
+    }
+
+    /// Returns the rectangle in screen pixels the given tile occupies in the view.
+    ///
+    /// `view_origin` is the position of the top left corner of the view in map coordinates, and
+    /// `view_resolution` is the size of a screen pixel in map units. Screen Y axis goes downwards.
+    pub fn tile_screen_rect(
+        &self,
+        index: WrappingTileIndex,
+        view_origin: Point2,
+        view_resolution: f64,
+    ) -> Option<Rect> {
+        let bbox = self.tile_bbox(index)?;
+        Some(Rect::new(
+            (bbox.x_min() - view_origin.x()) / view_resolution,
+            (view_origin.y() - bbox.y_max()) / view_resolution,
+            (bbox.x_max() - view_origin.x()) / view_resolution,
+            (view_origin.y() - bbox.y_min()) / view_resolution,
+        ))
+    }
+
+    fn wrap_x(&self) -> bool {
+        // TODO: https://github.com/Maximkaaa/galileo/issues/221
+        true
+    }
+
+    fn min_x_displayed_index(&self, lod: Lod) -> i32 {
+        if self.wrap_x() {
+            i32::MIN
+        } else {
+            self.min_x_index(lod)
+        }
+    }
+
+    fn max_x_displayed_index(&self, lod: Lod) -> i32 {
+        if self.wrap_x() {
+            i32::MAX
+        } else {
+            self.max_x_index(lod)
+        }
+    }
+
+    fn min_x_index(&self, lod: Lod) -> i32 {
+        let origin = self.lod_origin(lod.resolution(), lod.z_index());
+        ((self.bounds.x_min() - origin.x())
+            / lod.resolution()
+            / self.tile_width_at(lod.z_index()) as f64)
+            .floor() as i32
+    }
+
+    fn max_x_index(&self, lod: Lod) -> i32 {
+        if let Some((width, _)) = self.matrix_size(lod.z_index()) {
+            return self.min_x_index(lod) + width as i32 - 1;
+        }
+
+        let origin = self.lod_origin(lod.resolution(), lod.z_index());
+        let pix_bound = (self.bounds.x_max() - origin.x()) / lod.resolution();
+        self.last_index(pix_bound, self.tile_width_at(lod.z_index()))
+    }
+
+    fn min_y_index(&self, lod: Lod) -> i32 {
+        let resolution = lod.resolution();
+        let origin = self.lod_origin(resolution, lod.z_index());
+        let pix_bound = match self.y_direction {
+            VerticalDirection::TopToBottom => (origin.y() - self.bounds.y_max()) / resolution,
+            VerticalDirection::BottomToTop => (self.bounds.y_min() - origin.y()) / resolution,
+        };
+
+        // At deep levels rounding errors can put the bound a fraction of a pixel outside of the
+        // tile edge, which would add a row beyond the pole of Web Mercator schemas.
+        let rounded = pix_bound.round();
+        let pix_bound = if (pix_bound - rounded).abs() < 0.1 {
+            rounded
+        } else {
+            pix_bound
+        };
+
+        (pix_bound / self.tile_height_at(lod.z_index()) as f64).floor() as i32
+    }
+
+    fn max_y_index(&self, lod: Lod) -> i32 {
+        if let Some((_, height)) = self.matrix_size(lod.z_index()) {
+            return self.min_y_index(lod) + height as i32 - 1;
+        }
+
+        let resolution = lod.resolution();
+        let origin = self.lod_origin(resolution, lod.z_index());
+        let pix_bound = match self.y_direction {
+            VerticalDirection::TopToBottom => (origin.y() - self.bounds.y_min()) / resolution,
+            VerticalDirection::BottomToTop => (self.bounds.y_max() - origin.y()) / resolution,
+        };
+        self.last_index(pix_bound, self.tile_height_at(lod.z_index()))
+    }
+
+    /// Index of the last tile before the far bound of the schema, given as the distance in pixels
+    /// from the origin.
+    fn last_index(&self, pix_bound: f64, tile_size: u32) -> i32 {
+        // Bounds within a fraction of a pixel from the tile edge are considered to be on the edge.
+        let rounded = pix_bound.round();
+        let pix_bound = if (pix_bound - rounded).abs() < 0.1 {
+            rounded
+        } else {
+            pix_bound
+        };
+
+        let tiles = pix_bound / tile_size as f64;
+        let full_tiles = tiles.floor() as i32;
+        let has_partial = tiles > tiles.floor();
+        if has_partial && self.partial_edge_policy != PartialEdgePolicy::Exclude {
+            full_tiles
+        } else {
+            full_tiles - 1
+        }
+    }
+}
+
+/// All segments of the geometry. Points are returned as zero length segments.
+fn geometry_segments<P>(geometry: &Geom<P>) -> Vec<(Point2, Point2)>
+where
+    P: CartesianPoint2d<Num = f64> + Copy,
+{
+    let point = |p: P| Point2::new(p.x(), p.y());
+    let contour_segments = |contour: &galileo_types::impls::Contour<P>| {
+        contour
+            .iter_segments()
+            .map(|segment| (point(segment.0), point(segment.1)))
+            .collect::<Vec<_>>()
+    };
+    let polygon_segments = |polygon: &galileo_types::impls::Polygon<P>| {
+        polygon
+            .iter_segments()
+            .map(|segment| (point(segment.0), point(segment.1)))
+            .collect::<Vec<_>>()
+    };
+
+    match geometry {
+        Geom::Point(p) => vec![(point(*p), point(*p))],
+        Geom::MultiPoint(points) => points.iter_points().map(|p| (point(p), point(p))).collect(),
+        Geom::Contour(contour) => contour_segments(contour),
+        Geom::MultiContour(contours) => contours.contours().flat_map(contour_segments).collect(),
+        Geom::Polygon(polygon) => polygon_segments(polygon),
+        Geom::MultiPolygon(polygons) => polygons.polygons().flat_map(polygon_segments).collect(),
+    }
+}
+
+/// Shortest distance between the segment `from-to` and the rectangle. Zero if they intersect.
+fn segment_to_rect_distance(from: Point2, to: Point2, rect: Rect) -> f64 {
+    if segment_intersects_rect(from, to, rect) {
+        return 0.0;
+    }
+
+    let corners = rect.into_quadrangle();
+    let corners_distance = corners
+        .iter()
+        .map(|corner| segment_to_point_distance(from, to, *corner))
+        .fold(f64::INFINITY, f64::min);
+
+    corners_distance
+        .min(rect_to_point_distance(rect, from))
+        .min(rect_to_point_distance(rect, to))
+}
+
+fn segment_to_point_distance(from: Point2, to: Point2, point: Point2) -> f64 {
+    let (dx, dy) = (to.x() - from.x(), to.y() - from.y());
+    let length_sq = dx * dx + dy * dy;
+    let t = if length_sq > 0.0 {
+        (((point.x() - from.x()) * dx + (point.y() - from.y()) * dy) / length_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    let (closest_x, closest_y) = (from.x() + t * dx, from.y() + t * dy);
+    ((point.x() - closest_x).powi(2) + (point.y() - closest_y).powi(2)).sqrt()
+}
+
+fn rect_to_point_distance(rect: Rect, point: Point2) -> f64 {
+    let dx = (rect.x_min() - point.x())
+        .max(point.x() - rect.x_max())
+        .max(0.0);
+    let dy = (rect.y_min() - point.y())
+        .max(point.y() - rect.y_max())
+        .max(0.0);
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// Liang-Barsky clipping of the segment by the rectangle.
+fn segment_intersects_rect(from: Point2, to: Point2, rect: Rect) -> bool {
+    let (dx, dy) = (to.x() - from.x(), to.y() - from.y());
+    let checks = [
+        (-dx, from.x() - rect.x_min()),
+        (dx, rect.x_max() - from.x()),
+        (-dy, from.y() - rect.y_min()),
+        (dy, rect.y_max() - from.y()),
+    ];
+
+    let (mut t_min, mut t_max) = (0.0f64, 1.0f64);
+    for (p, q) in checks {
+        if p == 0.0 {
+            if q < 0.0 {
+                return false;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t_min = t_min.max(t);
+            } else {
+                t_max = t_max.min(t);
+            }
+        }
+    }
+
+    t_min <= t_max
+}
+
+/// Distance of the cell `(x, y)` along the Hilbert curve filling a square grid of `side` cells,
+/// where `side` is a power of two.
+fn hilbert_distance(side: u32, mut x: u32, mut y: u32) -> u64 {
+    let mut distance = 0;
+    let mut s = side / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u32;
+        let ry = (y & s > 0) as u32;
+        distance += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+
+            std::mem::swap(&mut x, &mut y);
+        }
+
+        s /= 2;
+    }
+
+    distance
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::tile_schema::{TileSchemaBuilder, WrappingTileIndex};
+
+    #[test]
+    fn serde_round_trip() {
+        let schema = TileSchema::web(18);
+        let json = serde_json::to_string(&schema).expect("serialization failed");
+        let parsed: TileSchema = serde_json::from_str(&json).expect("deserialization failed");
+        assert_eq!(parsed, schema);
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+
+        let schema = TileSchemaBuilder::web_mercator([0, 2, 5]).build().unwrap();
+        let json = serde_json::to_string(&schema).expect("serialization failed");
+        let parsed: TileSchema = serde_json::from_str(&json).expect("deserialization failed");
+        assert_eq!(parsed.lods.len(), 6);
+        assert!(parsed.lods[1].is_nan());
+        assert!(parsed.lods[4].is_nan());
+        assert_eq!(parsed.lods[2], schema.lods[2]);
+        assert_eq!(parsed.lod_resolution(5), schema.lod_resolution(5));
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+    }
+
+    fn simple_schema() -> TileSchema {
+        TileSchema {
+            origin: Point2::default(),
+            bounds: Rect::new(0.0, 0.0, 2048.0, 2048.0),
+            lods: vec![8.0, 4.0, 2.0],
+            tile_width: 256,
+            tile_height: 256,
+            tile_sizes: None,
+            y_direction: VerticalDirection::BottomToTop,
+            matrix_sizes: Vec::new(),
+            zoom_bias: 0,
+            tile_offset: (0.0, 0.0),
+            partial_edge_policy: PartialEdgePolicy::default(),
+            index_base: 0,
+            dpi_scale: 1.0,
+            tile_inset: 0.0,
+            lod_lookup: LodLookup::default(),
+        }
+    }
+
+    fn get_view(resolution: f64, bbox: Rect) -> MapView {
+        MapView::new_projected(&bbox.center(), resolution).with_size(Size::new(
+            bbox.width() / resolution,
+            bbox.height() / resolution,
+        ))
+    }
+
+    #[test]
+    fn needed_tiles_for_zoom() {
+        let schema = simple_schema();
+        let center = Point2::new(1000.0, 700.0);
+        let viewport = Size::new(300.0, 200.0);
+        let sorted = |mut tiles: Vec<WrappingTileIndex>| {
+            tiles.sort_by_key(|index| (index.z, index.y, index.display_x));
+            tiles
+        };
+
+        for resolution in [8.0, 4.0, 2.0, 3.0] {
+            let view = MapView::new_projected(&center, resolution).with_size(viewport);
+            let expected: Vec<_> = schema.iter_tiles(&view).unwrap().collect();
+            let actual: Vec<_> = schema
+                .needed_tiles_for_zoom(center, resolution, resolution, viewport)
+                .unwrap()
+                .collect();
+            assert_eq!(sorted(actual), sorted(expected));
+        }
+
+        // Zooming in, the tiles of the finer level cover the area visible before the zoom.
+        let view = MapView::new_projected(&center, 4.0).with_size(viewport);
+        let expected: Vec<_> = schema
+            .iter_tiles_over_bbox(2.0, view.get_bbox().unwrap())
+            .unwrap()
+            .collect();
+        let actual: Vec<_> = schema
+            .needed_tiles_for_zoom(center, 4.0, 2.0, viewport)
+            .unwrap()
+            .collect();
+        assert_eq!(sorted(actual), sorted(expected));
+    }
+
+    #[test]
+    fn nearest_lod() {
+        let schema = TileSchemaBuilder::web_mercator(5..=10).build().unwrap();
+        let z5 = schema.lod_resolution(5).unwrap();
+        let z7 = schema.lod_resolution(7).unwrap();
+
+        assert_eq!(schema.nearest_lod(z7), Some(7));
+        assert_eq!(schema.nearest_lod(z7 * 1.4), Some(7));
+        assert_eq!(schema.nearest_lod(z7 * 1.5), Some(6));
+        assert_eq!(schema.nearest_lod(z7 / 1.5), Some(8));
+        assert_eq!(schema.nearest_lod(z5 * 1000.0), Some(5));
+        assert_eq!(schema.nearest_lod(1e-6), Some(10));
+        assert_eq!(schema.nearest_lod(f64::NAN), None);
+        assert_eq!(schema.nearest_lod(0.0), None);
+
+        let schema = TileSchemaBuilder::web_mercator([3]).build().unwrap();
+        assert_eq!(schema.nearest_lod(1e10), Some(3));
+        assert_eq!(schema.nearest_lod(1e-10), Some(3));
+    }
+
+    #[test]
+    fn snap_resolution() {
+        let schema = TileSchemaBuilder::web_mercator(0..=10).build().unwrap();
+        let z3 = schema.lod_resolution(3).unwrap();
+        let z4 = schema.lod_resolution(4).unwrap();
+
+        assert_eq!(schema.snap_resolution(z3), Some(z3));
+        assert_eq!(schema.snap_resolution(z4), Some(z4));
+        assert_eq!(schema.snap_resolution(z3 / 1.4), Some(z3));
+        assert_eq!(schema.snap_resolution(z3 / 1.5), Some(z4));
+        assert_eq!(schema.snap_resolution(f64::NAN), None);
+        assert_eq!(schema.snap_resolution(-1.0), None);
+
+        let schema = TileSchemaBuilder::web_mercator([3, 5]).build().unwrap();
+        let z5 = schema.lod_resolution(5).unwrap();
+        assert_eq!(schema.lod_resolution(4), None);
+        assert_eq!(schema.snap_resolution(z3 / 2.5), Some(z5));
+    }
+
+    #[test]
+    fn fractional_zoom() {
+        let schema = TileSchemaBuilder::web_mercator(2..=8).build().unwrap();
+        for z in 2..=8 {
+            let resolution = schema.lods[z];
+            assert_eq!(schema.resolution_to_zoom(resolution), z as f64);
+            assert_eq!(schema.zoom_to_resolution(z as f64), resolution);
+        }
+
+        let halfway = (schema.lods[3] * schema.lods[4]).sqrt();
+        assert_abs_diff_eq!(schema.resolution_to_zoom(halfway), 3.5, epsilon = 1e-9);
+        assert_abs_diff_eq!(schema.zoom_to_resolution(3.5), halfway, epsilon = 1e-6);
+        assert_abs_diff_eq!(
+            schema.resolution_to_zoom(schema.zoom_to_resolution(5.25)),
+            5.25,
+            epsilon = 1e-9
+        );
+
+        assert_eq!(schema.resolution_to_zoom(1e10), 2.0);
+        assert_eq!(schema.resolution_to_zoom(1e-10), 8.0);
+        assert_eq!(schema.zoom_to_resolution(0.0), schema.lods[2]);
+        assert_eq!(schema.zoom_to_resolution(20.0), schema.lods[8]);
+
+        // Levels 4 and 5 are missing, so the zoom is interpolated between levels 3 and 6.
+        let schema = TileSchemaBuilder::web_mercator([3, 6]).build().unwrap();
+        let between = (schema.lods[3] * schema.lods[6]).sqrt();
+        assert_abs_diff_eq!(schema.resolution_to_zoom(between), 4.5, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn select_lod() {
+        let schema = simple_schema();
+        assert_eq!(schema.select_lod(8.0).unwrap().z_index(), 0);
+        assert_eq!(schema.select_lod(9.0).unwrap().z_index(), 0);
+        assert_eq!(schema.select_lod(16.0).unwrap().z_index(), 0);
+        assert_eq!(schema.select_lod(7.99).unwrap().z_index(), 0);
+        assert_eq!(schema.select_lod(7.5).unwrap().z_index(), 1);
+        assert_eq!(schema.select_lod(4.1).unwrap().z_index(), 1);
+        assert_eq!(schema.select_lod(4.0).unwrap().z_index(), 1);
+        assert_eq!(schema.select_lod(1.5).unwrap().z_index(), 2);
+        assert_eq!(schema.select_lod(1.0).unwrap().z_index(), 2);
+    }
+
+    #[test]
+    fn select_lod_with_zoom_bias() {
+        let mut schema = simple_schema();
+        schema.set_zoom_bias(1);
+        assert_eq!(schema.select_lod(4.0).unwrap().z_index(), 0);
+        assert_eq!(schema.select_lod(2.0).unwrap().z_index(), 1);
+        assert_eq!(schema.select_lod(8.0).unwrap().z_index(), 0);
+
+        schema.set_zoom_bias(-1);
+        assert_eq!(schema.select_lod(8.0).unwrap().z_index(), 1);
+        assert_eq!(schema.select_lod(2.0).unwrap().z_index(), 2);
+    }
+
+    #[test]
+    fn zoom_bias_skips_empty_levels() {
+        let mut schema = simple_schema();
+        schema.lods = vec![8.0, f64::NAN, 2.0];
+        schema.set_zoom_bias(1);
+        assert_eq!(schema.select_lod(2.0).unwrap().z_index(), 0);
+    }
+
+    #[test]
+    fn levels_between() {
+        let schema = TileSchema::web(18);
+        let z2 = schema.lod_resolution(2).unwrap();
+        let z4 = schema.lod_resolution(4).unwrap();
+
+        assert_eq!(schema.levels_between(z2, z4), 3);
+        assert_eq!(schema.levels_between(z4, z2), 3);
+        assert_eq!(schema.levels_between(z2 * 0.9, z4 * 1.1), 1);
+        assert_eq!(schema.levels_between(1e10, 1e11), 0);
+
+        let schema = schema.with_zoom_range(3..=18);
+        assert_eq!(schema.levels_between(z2, z4), 2);
+    }
+
+    #[test]
+    fn tile_center() {
+        let schema = TileSchema::web(18);
+        let center = schema.tile_center(TileIndex::new(0, 0, 0)).unwrap();
+        assert_abs_diff_eq!(center.x(), schema.bounds.center().x(), epsilon = 1e-3);
+        assert_abs_diff_eq!(center.y(), schema.bounds.center().y(), epsilon = 1e-3);
+
+        let schema = simple_schema();
+        assert_eq!(
+            schema.tile_center(TileIndex::new(1, 0, 1)),
+            Some(Point2::new(1536.0, 512.0))
+        );
+        assert_eq!(schema.tile_center(TileIndex::new(0, 0, 5)), None);
+    }
+
+    #[test]
+    fn tile_screen_rect() {
+        let schema = simple_schema();
+        let index = WrappingTileIndex::new(1, 1, 1);
+        let origin = Point2::new(0.0, 2048.0);
+
+        assert_eq!(
+            schema.tile_screen_rect(index, origin, 4.0),
+            Some(Rect::new(256.0, 0.0, 512.0, 256.0))
+        );
+        assert_eq!(
+            schema.tile_screen_rect(index, origin, 2.0),
+            Some(Rect::new(512.0, 0.0, 1024.0, 512.0))
+        );
+        assert_eq!(
+            schema.tile_screen_rect(index, Point2::new(512.0, 1536.0), 4.0),
+            Some(Rect::new(128.0, -128.0, 384.0, 128.0))
+        );
+        assert_eq!(
+            schema.tile_screen_rect(WrappingTileIndex::new(0, 0, 7), origin, 4.0),
+            None
+        );
+    }
+
+    #[test]
+    fn tile_offset_shifts_tile_bbox() {
+        let mut schema = simple_schema();
+        let bbox = schema.tile_bbox(WrappingTileIndex::new(1, 1, 1)).unwrap();
+        assert_eq!(bbox, Rect::new(1024.0, 1024.0, 2048.0, 2048.0));
+
+        schema.tile_offset = (0.5, 0.0);
+        let bbox = schema.tile_bbox(WrappingTileIndex::new(1, 1, 1)).unwrap();
+        assert_eq!(bbox, Rect::new(1536.0, 1024.0, 2560.0, 2048.0));
+
+        schema.tile_offset = (0.0, 0.5);
+        let bbox = schema.tile_bbox(WrappingTileIndex::new(1, 1, 1)).unwrap();
+        assert_eq!(bbox, Rect::new(1024.0, 1536.0, 2048.0, 2560.0));
+    }
+
+    #[test]
+    fn iter_tiles_with_tile_offset() {
+        let mut schema = simple_schema();
+        schema.tile_offset = (0.5, 0.5);
+
+        let bbox = Rect::new(100.0, 100.0, 900.0, 900.0);
+        let view = get_view(4.0, bbox);
+        let mut tiles: Vec<_> = schema
+            .iter_tiles(&view)
+            .unwrap()
+            .map(|index| (index.display_x, index.y))
+            .collect();
+        tiles.sort();
+        assert_eq!(tiles, vec![(-1, -1), (-1, 0), (0, -1), (0, 0)]);
+    }
+
+    #[test]
+    fn select_lod_after_lod_change() {
+        let mut schema = simple_schema();
+        assert_eq!(schema.select_lod(2.0).unwrap().z_index(), 2);
+
+        schema.set_lod_resolution(2, f64::NAN);
+        assert_eq!(schema.select_lod(2.0).unwrap().z_index(), 1);
+
+        schema.set_lod_resolution(3, 1.0);
+        assert_eq!(schema.select_lod(2.0).unwrap().z_index(), 3);
+        assert_eq!(schema.select_lod(1.0).unwrap().z_index(), 3);
+        assert_eq!(schema.lod_resolution(3), Some(1.0));
+    }
+
+    #[test]
+    fn overlapping_tiles_of_different_tile_sizes() {
+        let schema_256 = TileSchema::web(18);
+        let schema_512 = TileSchemaBuilder::web_mercator(0..=18)
+            .with_rect_tile_size(512)
+            .build()
+            .unwrap();
+
+        let index = TileIndex::new(5, 2, 3);
+        assert_eq!(
+            schema_256.overlapping_tiles(index, &schema_512, 3),
+            vec![TileIndex::new(5, 2, 3)]
+        );
+        assert_eq!(
+            schema_256.overlapping_tiles(index, &schema_512, 2),
+            vec![TileIndex::new(2, 1, 2)]
+        );
+        assert_eq!(schema_256.overlapping_tiles(index, &schema_512, 4).len(), 4);
+    }
+
+    #[test]
+    fn fit_zoom_modes() {
+        let schema = simple_schema();
+        let viewport = Size::new(256.0, 256.0);
+
+        let bbox = Rect::new(0.0, 0.0, 2048.0, 512.0);
+        assert_eq!(schema.fit_zoom(bbox, viewport, FitMode::Contain), Some(0));
+        assert_eq!(schema.fit_zoom(bbox, viewport, FitMode::Cover), Some(2));
+
+        let bbox = Rect::new(0.0, 0.0, 1024.0, 1024.0);
+        assert_eq!(schema.fit_zoom(bbox, viewport, FitMode::Contain), Some(1));
+        assert_eq!(schema.fit_zoom(bbox, viewport, FitMode::Cover), Some(1));
+
+        let bbox = Rect::new(0.0, 0.0, 10000.0, 10000.0);
+        assert_eq!(schema.fit_zoom(bbox, viewport, FitMode::Contain), Some(0));
+
+        let bbox = Rect::new(0.0, 0.0, 10.0, 10.0);
+        assert_eq!(schema.fit_zoom(bbox, viewport, FitMode::Contain), Some(2));
+    }
+
+    #[test]
+    fn tiles_covering_ordered() {
+        let schema = simple_schema();
+        let bbox = Rect::new(0.0, 0.0, 1536.0, 1024.0);
+        let ordered = |order| {
+            schema
+                .tiles_covering_ordered(bbox, 2, order)
+                .map(|index| (index.x, index.y))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            ordered(TileOrder::RowMajor),
+            vec![(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1)]
+        );
+        assert_eq!(
+            ordered(TileOrder::ColumnMajor),
+            vec![(0, 0), (0, 1), (1, 0), (1, 1), (2, 0), (2, 1)]
+        );
+        assert_eq!(
+            ordered(TileOrder::Spiral),
+            vec![(1, 0), (2, 0), (2, 1), (1, 1), (0, 1), (0, 0)]
+        );
+
+        assert_eq!(
+            schema
+                .tiles_covering_ordered(bbox, 5, TileOrder::RowMajor)
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn tiles_covering_hilbert() {
+        let schema = simple_schema();
+        let bbox = Rect::new(1.0, 1.0, 2047.0, 2047.0);
+
+        let hilbert: Vec<TileIndex> = schema.tiles_covering_hilbert(bbox, 2).collect();
+        assert_eq!(hilbert.len(), 16);
+        assert_eq!(
+            hilbert,
+            schema.tiles_covering_hilbert(bbox, 2).collect::<Vec<_>>()
+        );
+
+        let mut sorted = hilbert.clone();
+        sorted.sort_by_key(|index| (index.y, index.x));
+        let row_major: Vec<TileIndex> = schema
+            .tiles_covering_ordered(bbox, 2, TileOrder::RowMajor)
+            .map(TileIndex::from)
+            .collect();
+        assert_eq!(sorted, row_major);
+
+        for pair in hilbert.windows(2) {
+            let distance = (pair[0].x - pair[1].x).abs() + (pair[0].y - pair[1].y).abs();
+            assert_eq!(
+                distance, 1,
+                "{:?} and {:?} are not adjacent",
+                pair[0], pair[1]
+            );
+        }
+    }
+
+    #[test]
+    fn point_to_index() {
+        let schema = TileSchema::web(18);
+        let max = 20037508.342787;
+        assert_eq!(
+            schema.point_to_index(Point2::new(-max, max), 1),
+            Some(TileIndex::new(0, 0, 1))
+        );
+        assert_eq!(
+            schema.point_to_index(Point2::new(0.0, 0.0), 1),
+            Some(TileIndex::new(0, 1, 1))
+        );
+        assert_eq!(
+            schema.point_to_index(Point2::new(1000.0, 1000.0), 1),
+            Some(TileIndex::new(1, 0, 1))
+        );
+        assert_eq!(
+            schema.point_to_index(Point2::new(max, -max), 1),
+            Some(TileIndex::new(1, 1, 1))
+        );
+        assert_eq!(schema.point_to_index(Point2::new(max + 1.0, 0.0), 1), None);
+        assert_eq!(schema.point_to_index(Point2::new(0.0, -max - 1.0), 1), None);
+
+        let schema = simple_schema();
+        assert_eq!(
+            schema.point_to_index(Point2::new(0.0, 0.0), 1),
+            Some(TileIndex::new(0, 0, 1))
+        );
+        assert_eq!(
+            schema.point_to_index(Point2::new(1024.0, 1024.0), 1),
+            Some(TileIndex::new(0, 0, 1))
+        );
+        assert_eq!(
+            schema.point_to_index(Point2::new(1500.0, 1500.0), 1),
+            Some(TileIndex::new(1, 1, 1))
+        );
+        assert_eq!(schema.point_to_index(Point2::new(-1.0, 100.0), 1), None);
+
+        let with_gap = TileSchemaBuilder::web_mercator([0, 2]).build().unwrap();
+        assert_eq!(with_gap.point_to_index(Point2::new(0.0, 0.0), 1), None);
+    }
+
+    #[test]
+    fn vertical_direction_flip_row() {
+        let top = VerticalDirection::TopToBottom;
+        let bottom = VerticalDirection::BottomToTop;
+        assert!(top.is_top_to_bottom() && !top.is_bottom_to_top());
+        assert!(bottom.is_bottom_to_top() && !bottom.is_top_to_bottom());
+
+        assert_eq!(top.flip_row(0, 8), 0);
+        assert_eq!(top.flip_row(3, 8), 3);
+        assert_eq!(top.flip_row(7, 8), 7);
+
+        assert_eq!(bottom.flip_row(0, 8), 7);
+        assert_eq!(bottom.flip_row(3, 8), 4);
+        assert_eq!(bottom.flip_row(7, 8), 0);
+        assert_eq!(bottom.flip_row(bottom.flip_row(5, 8), 8), 5);
+
+        let schema = TileSchemaBuilder::web_mercator_tms(0..=3).build().unwrap();
+        let (_, rows) = schema.tile_count(2).unwrap();
+        assert_eq!(schema.y_direction().flip_row(0, rows), 3);
+    }
+
+    #[test]
+    fn clamp_zoom_for_display() {
+        let schema = TileSchema::web(19);
+        assert_eq!(schema.clamp_zoom_for_display(5), 5);
+        assert_eq!(schema.clamp_zoom_for_display(18), 18);
+        assert_eq!(schema.clamp_zoom_for_display(20), 18);
+
+        let mut with_hole = TileSchema::web(19);
+        with_hole.set_lod_resolution(18, f64::NAN);
+        assert_eq!(with_hole.clamp_zoom_for_display(20), 17);
+    }
+
+    #[test]
+    fn skipped_level() {
+        let schema = TileSchemaBuilder::web_mercator([3, 5]).build().unwrap();
+        let index = TileIndex::new(1, 1, 4);
+        let point = Point2::new(0.0, 0.0);
+        let bbox = Rect::new(-1000.0, -1000.0, 1000.0, 1000.0);
+
+        assert_eq!(schema.lod_resolution(4), None);
+        assert_eq!(schema.resolution_degrees(4), None);
+        assert_eq!(schema.scale_denominator(4, 96.0), None);
+        assert_eq!(schema.tile_count(4), None);
+        assert!(schema.level_tiles(4).is_none());
+        assert!(!schema.is_valid_index(index));
+        assert_eq!(schema.clamp_index(index), None);
+        assert_eq!(schema.wrap_index(index), None);
+        assert_eq!(schema.tile_bbox(index.into_wrapping()), None);
+        assert_eq!(schema.tile_world_rect(index), None);
+        assert_eq!(schema.tile_center(index), None);
+        assert_eq!(
+            schema.tile_screen_rect(index.into_wrapping(), point, 1.0),
+            None
+        );
+        assert_eq!(schema.tile_at_point(point, 4), None);
+        assert_eq!(schema.point_to_index(point, 4), None);
+        assert_eq!(schema.tiles_in_bbox(bbox, 4).count(), 0);
+
+        assert!(schema
+            .tile_bbox(TileIndex::new(1, 1, 3).into_wrapping())
+            .is_some());
+        assert!(schema
+            .tile_bbox(TileIndex::new(1, 1, 5).into_wrapping())
+            .is_some());
+    }
+
+    #[test]
+    fn tile_count() {
+        let schema = TileSchema::web(18);
+        assert_eq!(schema.tile_count(0), Some((1, 1)));
+        assert_eq!(schema.tile_count(2), Some((4, 4)));
+        assert_eq!(schema.tile_count(19), None);
 
-        let tile_w = lod.resolution() * self.tile_width as f64;
-        let tile_h = lod.resolution() * self.tile_height as f64;
+        assert!(schema.is_valid_index(TileIndex::new(3, 3, 2)));
+        assert!(!schema.is_valid_index(TileIndex::new(4, 0, 2)));
+        assert!(!schema.is_valid_index(TileIndex::new(0, 4, 2)));
 
-        let x_min = (self.x_adj(bounding_box.x_min()) / tile_w).floor() as i32;
-        let x_min = x_min.max(self.min_x_displayed_index(lod.resolution()));
+        let with_gap = TileSchemaBuilder::web_mercator([0, 2]).build().unwrap();
+        assert_eq!(with_gap.tile_count(1), None);
+        assert!(!with_gap.is_valid_index(TileIndex::new(0, 0, 1)));
 
-        let x_max_adj = self.x_adj(bounding_box.x_max());
-        let x_add_one = if (x_max_adj % tile_w) < 0.001 { -1 } else { 0 };
+        let geographic = TileSchemaBuilder::geographic(0..=1).build().unwrap();
+        assert_eq!(geographic.tile_count(1), Some((4, 2)));
+    }
 
-        let x_max = (x_max_adj / tile_w) as i32 + x_add_one;
-        let x_max = x_max.min(self.max_x_displayed_index(lod.resolution()));
+    #[test]
+    fn tiles_in_bbox() {
+        let schema = simple_schema();
+        let mut tiles: Vec<_> = schema
+            .tiles_in_bbox(Rect::new(600.0, 100.0, 1500.0, 700.0), 2)
+            .map(|index| (index.x, index.y))
+            .collect();
+        tiles.sort();
+        assert_eq!(tiles, vec![(1, 0), (1, 1), (2, 0), (2, 1)]);
 
-        let (top, bottom) = if self.y_direction == VerticalDirection::TopToBottom {
-            (bounding_box.y_min(), bounding_box.y_max())
-        } else {
-            (bounding_box.y_max(), bounding_box.y_min())
-        };
+        let tiles: Vec<_> = schema
+            .tiles_in_bbox(Rect::new(10.0, 10.0, 20.0, 20.0), 2)
+            .collect();
+        assert_eq!(tiles, vec![TileIndex::new(0, 0, 2)]);
 
-        let y_min = (self.y_adj(bottom) / tile_h) as i32;
-        let y_min = y_min.max(self.min_y_index(lod.resolution()));
+        let tiles: Vec<_> = schema
+            .tiles_in_bbox(Rect::new(-5000.0, -5000.0, 5000.0, 5000.0), 1)
+            .collect();
+        assert_eq!(tiles.len(), 4);
 
-        let y_max_adj = self.y_adj(top);
-        let y_add_one = if (y_max_adj % tile_h) < 0.001 { -1 } else { 0 };
+        let web = TileSchema::web(18);
+        let corner = Rect::new(-20037500.0, 20037000.0, -20037000.0, 20037500.0);
+        let tiles: Vec<_> = web.tiles_in_bbox(corner, 1).collect();
+        assert_eq!(tiles, vec![TileIndex::new(0, 0, 1)]);
 
-        let y_max = (y_max_adj / tile_h) as i32 + y_add_one;
-        let y_max = y_max.min(self.max_y_index(lod.resolution()));
+        let with_gap = TileSchemaBuilder::web_mercator([0, 2]).build().unwrap();
+        assert_eq!(with_gap.tiles_in_bbox(corner, 1).count(), 0);
+        assert_eq!(with_gap.tiles_in_bbox(corner, 2).count(), 1);
+        assert_eq!(schema.tiles_in_bbox(corner, 10).count(), 0);
+    }
 
-        let schema_x_min = self.min_x_index(lod.resolution());
-        let schema_x_max = self.max_x_index(lod.resolution());
-        let index_range = schema_x_max - schema_x_min + 1;
+    #[test]
+    fn tiles_covering_multi() {
+        let schema = TileSchema::web(18);
+        let bbox = Rect::new(1.0, 1.0, 20037507.0, 20037507.0);
 
-        let actual_x =
-            move |display_x: i32| (display_x - schema_x_min).rem_euclid(index_range) + schema_x_min;
+        let tiles: Vec<TileIndex> = schema.tiles_covering_multi(bbox, [4, 2, 3, 2]).collect();
+        let count_at = |z| tiles.iter().filter(|index| index.z == z).count();
+        assert_eq!(count_at(2), 4);
+        assert_eq!(count_at(3), 16);
+        assert_eq!(count_at(4), 64);
+        assert_eq!(tiles.len(), 84);
+        assert!(tiles.windows(2).all(|pair| pair[0].z <= pair[1].z));
 
-        Some((x_min..=x_max).flat_map(move |x| {
-            (y_min..=y_max).map(move |y| WrappingTileIndex {
-                x: actual_x(x),
-                y,
-                z: lod.z_index(),
-                display_x: x,
-            })
-        }))
+        assert_eq!(schema.tiles_covering_multi(bbox, 2..=4).count(), 84);
+        assert_eq!(schema.tiles_covering_multi(bbox, [30]).count(), 0);
     }
 
-    fn x_adj(&self, x: f64) -> f64 {
-        x - self.origin.x()
-    }
+    #[test]
+    fn tiles_along_line() {
+        let schema = simple_schema();
+        let line = [Point2::new(100.0, 300.0), Point2::new(1900.0, 2000.0)];
 
-    fn y_adj(&self, y: f64) -> f64 {
-        match self.y_direction {
-            VerticalDirection::TopToBottom => self.origin.y() - y,
-            VerticalDirection::BottomToTop => y - self.origin.y(),
-        }
+        let tiles: Vec<_> = schema
+            .tiles_along_line(&line, 2, 0.0)
+            .map(|index| (index.x, index.y))
+            .collect();
+        assert_eq!(
+            tiles,
+            vec![(0, 0), (0, 1), (1, 1), (1, 2), (2, 2), (2, 3), (3, 3)]
+        );
+
+        let buffered: Vec<_> = schema.tiles_along_line(&line, 2, 200.0).collect();
+        assert!(buffered.contains(&TileIndex::new(1, 0, 2)));
+        assert!(!buffered.contains(&TileIndex::new(2, 0, 2)));
+
+        assert_eq!(schema.tiles_along_line(&line, 10, 0.0).count(), 0);
     }
 
-    /// Standard Web Mercator based tile scheme (used, for example, by OSM and Google maps).
-    pub fn web(lods_count: u32) -> Self {
-        const ORIGIN: Point2 = Point2::new(-20037508.342787, 20037508.342787);
-        const TOP_RESOLUTION: f64 = 156543.03392800014;
+    #[test]
+    fn tiles_for_geometry() {
+        let schema = simple_schema();
+        let triangle = galileo_types::impls::Polygon::new(
+            galileo_types::impls::ClosedContour::new(vec![
+                Point2::new(0.0, 0.0),
+                Point2::new(2000.0, 0.0),
+                Point2::new(0.0, 2000.0),
+            ]),
+            vec![],
+        );
+        let geometry: Geom<Point2> = Geom::from(triangle);
 
-        let mut lods = vec![TOP_RESOLUTION];
-        for i in 1..lods_count {
-            lods.push(lods[(i - 1) as usize] / 2.0);
-        }
+        assert_eq!(schema.tiles_for_geometry(&geometry, 2, false).count(), 16);
 
-        TileSchema {
-            origin: ORIGIN,
-            bounds: Rect::new(
-                -20037508.342787,
-                -20037508.342787,
-                20037508.342787,
-                20037508.342787,
-            ),
-            lods,
-            tile_width: 256,
-            tile_height: 256,
-            y_direction: VerticalDirection::TopToBottom,
-        }
+        let tiles: Vec<TileIndex> = schema.tiles_for_geometry(&geometry, 2, true).collect();
+        assert_eq!(tiles.len(), 10);
+        assert!(tiles.iter().all(|index| index.x + index.y <= 3));
+        assert!(tiles.contains(&TileIndex::new(3, 0, 2)));
+        assert!(!tiles.contains(&TileIndex::new(3, 3, 2)));
     }
 
-    /// Returns the bounding rectangle of the given tile index, if the index is valid.
-    pub fn tile_bbox(&self, index: WrappingTileIndex) -> Option<Rect> {
-        let x_index = index.display_x;
-        let y_index = index.y;
+    #[test]
+    fn tile_at_point() {
+        let schema = simple_schema();
+        assert_eq!(
+            schema.tile_at_point(Point2::new(600.0, 1100.0), 2),
+            Some(WrappingTileIndex::new(1, 2, 2))
+        );
+        assert_eq!(
+            schema.tile_at_point(Point2::new(2100.0, 100.0), 2),
+            Some(WrappingTileIndex {
+                x: 0,
+                y: 0,
+                z: 2,
+                display_x: 4,
+            })
+        );
+        assert_eq!(schema.tile_at_point(Point2::new(100.0, 2100.0), 2), None);
+        assert_eq!(schema.tile_at_point(Point2::new(100.0, 100.0), 3), None);
+    }
 
-        let resolution = self.lod_resolution(index.z)?;
-        let x_min = self.origin.x() + (x_index as f64) * self.tile_width as f64 * resolution;
-        let y_min = match self.y_direction {
-            VerticalDirection::TopToBottom => {
-                self.origin.y() - (y_index + 1) as f64 * self.tile_height as f64 * resolution
-            }
-            VerticalDirection::BottomToTop => {
-                self.origin.y() + (y_index as f64) * self.tile_height as f64 * resolution
-            }
+    #[test]
+    fn partial_edge_policy() {
+        let mut schema = simple_schema();
+        schema.bounds = Rect::new(0.0, 0.0, 1800.0, 2048.0);
+        let east_tile = WrappingTileIndex::new(3, 0, 2);
+        let columns = |schema: &TileSchema| {
+            let mut columns: Vec<i32> = schema
+                .tiles_covering_ordered(schema.bounds, 2, TileOrder::RowMajor)
+                .map(|index| index.x)
+                .collect();
+            columns.sort();
+            columns.dedup();
+            columns
         };
 
-        Some(Rect::new(
-            x_min,
-            y_min,
-            x_min + self.tile_width as f64 * resolution,
-            y_min + self.tile_height as f64 * resolution,
-        ))
-    }
+        assert_eq!(schema.partial_edge_policy(), PartialEdgePolicy::Include);
+        assert_eq!(columns(&schema), vec![0, 1, 2, 3]);
+        assert!(schema.is_valid_index(east_tile.into()));
+        assert_eq!(
+            schema.tile_bbox(east_tile),
+            Some(Rect::new(1536.0, 0.0, 2048.0, 512.0))
+        );
 
-    fn wrap_x(&self) -> bool {
-        // TODO: https://github.com/Maximkaaa/galileo/issues/221
-        true
+        schema.set_partial_edge_policy(PartialEdgePolicy::Clamp);
+        assert_eq!(columns(&schema), vec![0, 1, 2, 3]);
+        assert_eq!(
+            schema.tile_bbox(east_tile),
+            Some(Rect::new(1536.0, 0.0, 1800.0, 512.0))
+        );
+
+        schema.set_partial_edge_policy(PartialEdgePolicy::Exclude);
+        assert_eq!(columns(&schema), vec![0, 1, 2]);
+        assert!(!schema.is_valid_index(east_tile.into()));
     }
 
-    fn min_x_displayed_index(&self, resolution: f64) -> i32 {
-        if self.wrap_x() {
-            i32::MIN
-        } else {
-            self.min_x_index(resolution)
+    #[test]
+    fn with_zoom_range() {
+        let schema = TileSchema::web(21);
+        let limited = schema.with_zoom_range(8..=14);
+
+        for z in 0..=20 {
+            if (8..=14).contains(&z) {
+                assert_eq!(limited.lod_resolution(z), schema.lod_resolution(z));
+            } else {
+                assert_eq!(limited.lod_resolution(z), None);
+                assert!(limited.lods[z as usize].is_nan());
+            }
         }
+
+        assert_eq!(limited.min_z(), Some(8));
+        assert_eq!(
+            limited
+                .select_lod(schema.lod_resolution(3).unwrap())
+                .map(|lod| lod.z_index()),
+            Some(8)
+        );
+        assert_eq!(limited.tile_width(), schema.tile_width());
+        assert_eq!(limited.y_direction(), schema.y_direction());
     }
 
-    fn max_x_displayed_index(&self, resolution: f64) -> i32 {
-        if self.wrap_x() {
-            i32::MAX
-        } else {
-            self.max_x_index(resolution)
+    #[test]
+    fn resolution_degrees() {
+        let geographic = TileSchema::preset("WorldCRS84Quad").unwrap();
+        assert!(geographic.is_geographic());
+        for z in [0, 3, 10] {
+            let expected = 360.0 / (2.0 * 256.0 * 2f64.powi(z as i32));
+            assert_abs_diff_eq!(geographic.resolution_degrees(z).unwrap(), expected);
         }
-    }
+        assert_eq!(geographic.resolution_degrees(30), None);
 
-    fn min_x_index(&self, resolution: f64) -> i32 {
-        ((self.bounds.x_min() - self.origin.x()) / resolution / self.tile_width as f64).floor()
-            as i32
+        let web = TileSchema::web(18);
+        assert!(!web.is_geographic());
+        assert_abs_diff_eq!(
+            web.resolution_degrees(0).unwrap(),
+            360.0 / 256.0,
+            epsilon = 1e-9
+        );
     }
 
-    fn max_x_index(&self, resolution: f64) -> i32 {
-        let pix_bound = (self.bounds.x_max() - self.origin.x()) / resolution;
-        let floored = pix_bound.floor();
-        if (pix_bound - floored).abs() < 0.1 {
-            (floored / self.tile_width as f64) as i32 - 1
-        } else {
-            (floored / self.tile_width as f64) as i32
-        }
+    #[test]
+    fn scale_denominator() {
+        let schema = TileSchemaBuilder::web_mercator(0..=18).build().unwrap();
+        assert_abs_diff_eq!(
+            schema.scale_denominator(0, 96.0).unwrap(),
+            591_658_710.9,
+            epsilon = 1.0
+        );
+        assert_abs_diff_eq!(
+            schema.scale_denominator(18, 96.0).unwrap(),
+            591_658_710.9 / 262_144.0,
+            epsilon = 1e-3
+        );
+        assert_abs_diff_eq!(
+            schema.scale_denominator(0, 192.0).unwrap(),
+            2.0 * schema.scale_denominator(0, 96.0).unwrap()
+        );
+        assert_eq!(schema.scale_denominator(19, 96.0), None);
+
+        let mut schema = schema;
+        schema.lods[3] = f64::NAN;
+        assert_eq!(schema.scale_denominator(3, 96.0), None);
     }
 
-    fn min_y_index(&self, resolution: f64) -> i32 {
-        match self.y_direction {
-            VerticalDirection::TopToBottom => {
-                ((self.bounds.y_min() + self.origin.y()) / resolution / self.tile_height as f64)
-                    .floor() as i32
-            }
-            VerticalDirection::BottomToTop => {
-                ((self.bounds.y_min() - self.origin.y()) / resolution / self.tile_height as f64)
-                    .floor() as i32
-            }
+    #[test]
+    fn valid_lods() {
+        let full = TileSchemaBuilder::web_mercator(0..=10).build().unwrap();
+        let schema = TileSchemaBuilder::web_mercator(5..=10).build().unwrap();
+
+        let lods: Vec<_> = schema.valid_lods().collect();
+        assert_eq!(lods.len(), 6);
+        for (i, (z, resolution)) in lods.into_iter().enumerate() {
+            assert_eq!(z, i as u32 + 5);
+            assert_abs_diff_eq!(resolution, full.lod_resolution(z).unwrap());
         }
-    }
 
-    fn max_y_index(&self, resolution: f64) -> i32 {
-        let pix_bound = match self.y_direction {
-            VerticalDirection::TopToBottom => (self.bounds.y_max() + self.origin.y()) / resolution,
-            VerticalDirection::BottomToTop => (self.bounds.y_max() - self.origin.y()) / resolution,
-        };
-        let floored = pix_bound.floor();
-        if (pix_bound - floored).abs() < 0.1 {
-            (floored / self.tile_height as f64) as i32 - 1
-        } else {
-            (floored / self.tile_height as f64) as i32
+        assert_eq!(schema.min_resolution(), full.lod_resolution(10));
+        assert_eq!(schema.max_resolution(), full.lod_resolution(5));
+
+        let mut empty = schema;
+        for z in 5..=10 {
+            empty.set_lod_resolution(z, f64::NAN);
         }
+        assert_eq!(empty.valid_lods().count(), 0);
+        assert_eq!(empty.min_resolution(), None);
+        assert_eq!(empty.max_resolution(), None);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use galileo_types::cartesian::Size;
+    #[test]
+    fn one_based_index() {
+        let zero_based = TileSchemaBuilder::web_mercator(0..=3).build().unwrap();
+        let one_based = TileSchemaBuilder::web_mercator(0..=3)
+            .with_index_base(1)
+            .build()
+            .unwrap();
+        assert_eq!(one_based.index_base(), 1);
 
-    use super::*;
-    use crate::tile_schema::WrappingTileIndex;
+        let top_left = one_based
+            .tiles_covering_ordered(one_based.bounds, 2, TileOrder::RowMajor)
+            .next()
+            .unwrap();
+        assert_eq!((top_left.x, top_left.y, top_left.display_x), (1, 1, 1));
 
-    fn simple_schema() -> TileSchema {
-        TileSchema {
-            origin: Point2::default(),
-            bounds: Rect::new(0.0, 0.0, 2048.0, 2048.0),
-            lods: vec![8.0, 4.0, 2.0],
-            tile_width: 256,
-            tile_height: 256,
-            y_direction: VerticalDirection::BottomToTop,
-        }
+        let corner = Point2::new(
+            one_based.bounds.x_min() + 1.0,
+            one_based.bounds.y_max() - 1.0,
+        );
+        assert_eq!(one_based.tile_at_point(corner, 2), Some(top_left));
+        assert_eq!(
+            one_based.tile_bbox(top_left),
+            zero_based.tile_bbox(WrappingTileIndex::new(0, 0, 2))
+        );
+
+        assert!(one_based.is_valid_index(TileIndex::new(1, 1, 2)));
+        assert!(one_based.is_valid_index(TileIndex::new(4, 4, 2)));
+        assert!(!one_based.is_valid_index(TileIndex::new(0, 0, 2)));
+        assert!(!one_based.is_valid_index(TileIndex::new(5, 1, 2)));
+
+        assert_eq!(
+            one_based.index_at_level(TileIndex::new(4, 4, 2), 1),
+            TileIndex::new(2, 2, 1)
+        );
     }
 
-    fn get_view(resolution: f64, bbox: Rect) -> MapView {
-        MapView::new_projected(&bbox.center(), resolution).with_size(Size::new(
-            bbox.width() / resolution,
-            bbox.height() / resolution,
-        ))
+    #[test]
+    fn index_at_level() {
+        let schema = TileSchema::web(18);
+        let index = TileIndex::new(500, 301, 10);
+
+        let ancestor = schema.index_at_level(index, 7);
+        assert_eq!(ancestor, TileIndex::new(62, 37, 7));
+
+        let descendant = schema.index_at_level(ancestor, 10);
+        assert_eq!(descendant, TileIndex::new(500, 300, 10));
+        assert_eq!(schema.index_at_level(descendant, 7), ancestor);
+
+        assert_eq!(schema.index_at_level(index, 10), index);
+        assert_eq!(
+            schema.index_at_level(index, 11),
+            TileIndex::new(1001, 603, 11)
+        );
     }
 
     #[test]
-    fn select_lod() {
-        let schema = simple_schema();
-        assert_eq!(schema.select_lod(8.0).unwrap().z_index(), 0);
-        assert_eq!(schema.select_lod(9.0).unwrap().z_index(), 0);
-        assert_eq!(schema.select_lod(16.0).unwrap().z_index(), 0);
-        assert_eq!(schema.select_lod(7.99).unwrap().z_index(), 0);
-        assert_eq!(schema.select_lod(7.5).unwrap().z_index(), 1);
-        assert_eq!(schema.select_lod(4.1).unwrap().z_index(), 1);
-        assert_eq!(schema.select_lod(4.0).unwrap().z_index(), 1);
-        assert_eq!(schema.select_lod(1.5).unwrap().z_index(), 2);
-        assert_eq!(schema.select_lod(1.0).unwrap().z_index(), 2);
+    fn presets() {
+        let schema = TileSchema::preset("WebMercatorQuad").unwrap();
+        assert_eq!(schema.lods.len(), 25);
+        assert_abs_diff_eq!(schema.lods[0], 156543.03392804097);
+        assert_abs_diff_eq!(schema.lods[24], 0.009330691929342784, epsilon = 1e-12);
+
+        let schema = TileSchema::preset("WorldCRS84Quad").unwrap();
+        assert_eq!(schema.lods.len(), 24);
+        assert_abs_diff_eq!(schema.lods[0], 0.703125);
+        assert_abs_diff_eq!(schema.lods[1], 0.3515625);
+        assert_eq!(
+            schema.tile_bbox(WrappingTileIndex::new(1, 0, 0)),
+            Some(Rect::new(0.0, -90.0, 180.0, 90.0))
+        );
+
+        let schema = TileSchema::preset("WorldMercatorWGS84Quad").unwrap();
+        assert_abs_diff_eq!(schema.lods[0], 156543.03392804097);
+
+        assert!(TileSchema::preset("UnknownQuad").is_none());
     }
 
     #[test]
@@ -389,4 +2596,240 @@ mod tests {
             4
         );
     }
+
+    #[test]
+    fn iter_tiles_explicit_matrix_size() {
+        let mut schema = simple_schema();
+        schema.matrix_sizes = vec![None, None, Some((4, 2))];
+
+        let bbox = Rect::new(0.0, 0.0, 2048.0, 2048.0);
+        let view = get_view(2.0, bbox);
+        let tiles: Vec<WrappingTileIndex> = schema.iter_tiles(&view).unwrap().collect();
+        assert_eq!(tiles.len(), 8);
+        for tile in tiles {
+            assert!(tile.x >= 0 && tile.x <= 3);
+            assert!(tile.y >= 0 && tile.y <= 1);
+            assert_eq!(tile.z, 2);
+        }
+
+        let view = get_view(4.0, bbox);
+        assert_eq!(schema.iter_tiles(&view).unwrap().count(), 4);
+    }
+
+    #[test]
+    fn pole_rows_are_excluded() {
+        const MAX: f64 = 20037508.342787;
+
+        let schema = TileSchema::web(21);
+        let z = 20;
+        let last_row = (1 << z) - 1;
+
+        let top = Rect::new(-100.0, MAX - 100.0, 100.0, MAX + 1000.0);
+        let rows: Vec<i32> = schema
+            .tiles_covering_ordered(top, z, TileOrder::RowMajor)
+            .map(|index| index.y)
+            .collect();
+        assert_eq!(rows.iter().min(), Some(&0));
+
+        let bottom = Rect::new(-100.0, -MAX - 1000.0, 100.0, -MAX + 100.0);
+        let rows: Vec<i32> = schema
+            .tiles_covering_ordered(bottom, z, TileOrder::RowMajor)
+            .map(|index| index.y)
+            .collect();
+        assert_eq!(rows.iter().max(), Some(&last_row));
+
+        assert!(schema.is_valid_index(TileIndex::new(0, 0, z)));
+        assert!(schema.is_valid_index(TileIndex::new(0, last_row, z)));
+        assert!(!schema.is_valid_index(TileIndex::new(0, -1, z)));
+        assert!(!schema.is_valid_index(TileIndex::new(0, last_row + 1, z)));
+        assert!(!schema.is_valid_index(TileIndex::new(0, 0, 21)));
+    }
+
+    #[test]
+    fn level_tiles() {
+        let schema = TileSchemaBuilder::web_mercator([0, 2])
+            .with_index_base(1)
+            .build()
+            .unwrap();
+        let tiles: Vec<TileIndex> = schema.level_tiles(2).unwrap().collect();
+        assert_eq!(tiles.len(), 16);
+        assert_eq!(tiles[0], TileIndex::new(1, 1, 2));
+        assert_eq!(tiles[1], TileIndex::new(2, 1, 2));
+        assert_eq!(tiles[15], TileIndex::new(4, 4, 2));
+        assert!(tiles.iter().all(|index| schema.is_valid_index(*index)));
+
+        assert!(schema.level_tiles(1).is_none());
+        assert!(schema.level_tiles(3).is_none());
+    }
+
+    #[test]
+    fn clamp_and_wrap_index() {
+        let schema = TileSchemaBuilder::web_mercator([0, 2]).build().unwrap();
+
+        let past_right = TileIndex::new(4, 1, 2);
+        assert_eq!(
+            schema.clamp_index(past_right),
+            Some(TileIndex::new(3, 1, 2))
+        );
+        assert_eq!(schema.wrap_index(past_right), Some(TileIndex::new(0, 1, 2)));
+
+        let past_left = TileIndex::new(-1, 1, 2);
+        assert_eq!(schema.clamp_index(past_left), Some(TileIndex::new(0, 1, 2)));
+        assert_eq!(schema.wrap_index(past_left), Some(TileIndex::new(3, 1, 2)));
+
+        let above_top = TileIndex::new(2, -1, 2);
+        assert_eq!(schema.clamp_index(above_top), Some(TileIndex::new(2, 0, 2)));
+        assert_eq!(schema.wrap_index(above_top), Some(TileIndex::new(2, 0, 2)));
+
+        let below_bottom = TileIndex::new(9, 7, 2);
+        assert_eq!(
+            schema.clamp_index(below_bottom),
+            Some(TileIndex::new(3, 3, 2))
+        );
+        assert_eq!(
+            schema.wrap_index(below_bottom),
+            Some(TileIndex::new(1, 3, 2))
+        );
+
+        let valid = TileIndex::new(1, 2, 2);
+        assert_eq!(schema.clamp_index(valid), Some(valid));
+        assert_eq!(schema.wrap_index(valid), Some(valid));
+
+        assert_eq!(schema.clamp_index(TileIndex::new(0, 0, 1)), None);
+        assert_eq!(schema.wrap_index(TileIndex::new(0, 0, 3)), None);
+
+        let schema = TileSchemaBuilder::web_mercator([0, 2])
+            .with_index_base(1)
+            .build()
+            .unwrap();
+        assert_eq!(
+            schema.clamp_index(TileIndex::new(5, 0, 2)),
+            Some(TileIndex::new(4, 1, 2))
+        );
+        assert_eq!(
+            schema.wrap_index(TileIndex::new(5, 0, 2)),
+            Some(TileIndex::new(1, 1, 2))
+        );
+    }
+
+    #[test]
+    fn tile_world_rect() {
+        const MAX: f64 = 20037508.342787;
+        const HALF: f64 = MAX / 2.0;
+
+        let assert_rect = |actual: Option<Rect>, expected: Rect| {
+            let actual = actual.expect("tile must be valid");
+            assert_abs_diff_eq!(actual.x_min(), expected.x_min(), epsilon = 1e-6);
+            assert_abs_diff_eq!(actual.y_min(), expected.y_min(), epsilon = 1e-6);
+            assert_abs_diff_eq!(actual.x_max(), expected.x_max(), epsilon = 1e-6);
+            assert_abs_diff_eq!(actual.y_max(), expected.y_max(), epsilon = 1e-6);
+        };
+
+        let xyz = TileSchemaBuilder::web_mercator([0, 2]).build().unwrap();
+        assert_rect(
+            xyz.tile_world_rect(TileIndex::new(0, 0, 0)),
+            Rect::new(-MAX, -MAX, MAX, MAX),
+        );
+        assert_rect(
+            xyz.tile_world_rect(TileIndex::new(0, 0, 2)),
+            Rect::new(-MAX, HALF, -HALF, MAX),
+        );
+        assert_rect(
+            xyz.tile_world_rect(TileIndex::new(3, 3, 2)),
+            Rect::new(HALF, -MAX, MAX, -HALF),
+        );
+        assert_rect(
+            xyz.tile_world_rect(TileIndex::new(1, 2, 2)),
+            Rect::new(-HALF, -HALF, 0.0, 0.0),
+        );
+
+        let tms = TileSchemaBuilder::web_mercator_tms([0, 2]).build().unwrap();
+        assert_rect(
+            tms.tile_world_rect(TileIndex::new(0, 0, 2)),
+            Rect::new(-MAX, -MAX, -HALF, -HALF),
+        );
+        assert_rect(
+            tms.tile_world_rect(TileIndex::new(3, 3, 2)),
+            Rect::new(HALF, HALF, MAX, MAX),
+        );
+        assert_rect(
+            tms.tile_world_rect(TileIndex::new(1, 2, 2)),
+            Rect::new(-HALF, 0.0, 0.0, HALF),
+        );
+
+        for schema in [&xyz, &tms] {
+            assert_eq!(schema.tile_world_rect(TileIndex::new(4, 0, 2)), None);
+            assert_eq!(schema.tile_world_rect(TileIndex::new(0, -1, 2)), None);
+            assert_eq!(schema.tile_world_rect(TileIndex::new(0, 0, 1)), None);
+            assert_eq!(schema.tile_world_rect(TileIndex::new(0, 0, 3)), None);
+        }
+    }
+
+    #[test]
+    fn tile_sizes_per_level() {
+        let schema = TileSchemaBuilder::new()
+            .with_origin(Point2::new(0.0, 4096.0))
+            .with_bounds(Rect::new(0.0, 0.0, 4096.0, 4096.0))
+            .with_rect_tile_size(256)
+            .with_custom_resolutions([8.0, 4.0, 2.0, 1.0])
+            .with_tile_sizes_per_level([(512, 512), (512, 512)])
+            .build()
+            .unwrap();
+
+        assert_eq!(schema.tile_width(), 256);
+        assert_eq!(schema.tile_width_at(1), 512);
+        assert_eq!(schema.tile_height_at(1), 512);
+        assert_eq!(schema.tile_width_at(2), 256);
+        assert_eq!(schema.tile_height_at(3), 256);
+
+        let level_tiles = |z| schema.level_tiles(z).unwrap().count();
+        assert_eq!(level_tiles(0), 1);
+        assert_eq!(level_tiles(1), 4);
+        assert_eq!(level_tiles(2), 64);
+        assert_eq!(level_tiles(3), 256);
+
+        assert_eq!(
+            schema.tile_bbox(WrappingTileIndex::new(1, 1, 1)),
+            Some(Rect::new(2048.0, 0.0, 4096.0, 2048.0))
+        );
+        assert_eq!(
+            schema.tile_bbox(WrappingTileIndex::new(1, 0, 2)),
+            Some(Rect::new(512.0, 3584.0, 1024.0, 4096.0))
+        );
+
+        let point = Point2::new(1500.0, 3000.0);
+        assert_eq!(
+            schema.point_to_index(point, 1),
+            Some(TileIndex::new(0, 0, 1))
+        );
+        assert_eq!(
+            schema.point_to_index(point, 2),
+            Some(TileIndex::new(2, 2, 2))
+        );
+    }
+
+    #[test]
+    fn equality_and_hash() {
+        use std::collections::hash_map::DefaultHasher;
+
+        fn hash(schema: &TileSchema) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            schema.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let first = TileSchemaBuilder::web_mercator([3, 5]).build().unwrap();
+        let second = TileSchemaBuilder::web_mercator([3, 5]).build().unwrap();
+        // Lookup cache state must not affect the comparison.
+        let _ = first.lod_lookup();
+        assert_eq!(first, second);
+        assert_eq!(hash(&first), hash(&second));
+
+        assert_eq!(TileSchema::web(18), TileSchema::web(18));
+        assert_eq!(hash(&TileSchema::web(18)), hash(&TileSchema::web(18)));
+
+        let other = TileSchemaBuilder::web_mercator([3, 6]).build().unwrap();
+        assert_ne!(first, other);
+        assert_ne!(TileSchema::web(18), TileSchema::web(17));
+    }
 }
@@ -54,6 +54,105 @@ impl TileIndex {
             display_x: self.x,
         }
     }
+
+    /// Returns the tile of the previous z-level that contains this tile in a quad tree, or `None`
+    /// for z-level 0.
+    pub fn parent(&self) -> Option<TileIndex> {
+        let z = self.z.checked_sub(1)?;
+        Some(TileIndex::new(self.x >> 1, self.y >> 1, z))
+    }
+
+    /// Returns the four tiles of the next z-level that this tile is split into in a quad tree.
+    ///
+    /// The children are ordered by rows and then by columns: `(2x, 2y)`, `(2x + 1, 2y)`,
+    /// `(2x, 2y + 1)`, `(2x + 1, 2y + 1)`. For schemas with
+    /// [`VerticalDirection::TopToBottom`](super::VerticalDirection::TopToBottom) this is NW, NE,
+    /// SW, SE, and for [`VerticalDirection::BottomToTop`](super::VerticalDirection::BottomToTop)
+    /// it is SW, SE, NW, NE.
+    pub fn children(&self) -> [TileIndex; 4] {
+        let (x, y, z) = (self.x * 2, self.y * 2, self.z + 1);
+        [
+            TileIndex::new(x, y, z),
+            TileIndex::new(x + 1, y, z),
+            TileIndex::new(x, y + 1, z),
+            TileIndex::new(x + 1, y + 1, z),
+        ]
+    }
+
+    /// Returns the tile of the same z-level shifted by `dx` columns and `dy` rows.
+    ///
+    /// The index is neither wrapped nor clamped, so it can be outside of the tile matrix. Use
+    /// [`TileSchema::wrap_index`](super::TileSchema::wrap_index) or
+    /// [`TileSchema::clamp_index`](super::TileSchema::clamp_index) to bring it back.
+    pub fn neighbor(&self, dx: i32, dy: i32) -> TileIndex {
+        TileIndex::new(self.x + dx, self.y + dy, self.z)
+    }
+}
+
+/// Deepest z-level that can be addressed by a quadkey with `i32` tile indices.
+const MAX_QUADKEY_LEVEL: usize = 31;
+
+/// Errors of parsing a quadkey with [`TileIndex::from_quadkey`].
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum QuadkeyError {
+    /// Quadkey contains a character other than digits from 0 to 3
+    #[error("Invalid quadkey character '{character}' at position {position}")]
+    InvalidCharacter {
+        /// Position of the character in the key
+        position: usize,
+        /// The invalid character
+        character: char,
+    },
+
+    /// Quadkey is longer than the deepest supported z-level
+    #[error("Quadkey length {length} is larger than 31")]
+    TooLong {
+        /// Length of the key
+        length: usize,
+    },
+}
+
+impl TileIndex {
+    /// Returns the quadkey of the tile as used by Bing Maps, with one digit per z-level.
+    ///
+    /// The index is expected to be valid for the Web Mercator tile schema, i.e. both `x` and `y`
+    /// must be in the range `0..2^z`.
+    pub fn to_quadkey(&self) -> String {
+        (1..=self.z)
+            .rev()
+            .map(|level| {
+                let mask = 1 << (level - 1);
+                let digit = (self.x & mask != 0) as u8 + 2 * (self.y & mask != 0) as u8;
+                char::from(b'0' + digit)
+            })
+            .collect()
+    }
+
+    /// Parses the Bing Maps quadkey of a tile. Empty key is the tile of z-level 0.
+    pub fn from_quadkey(key: &str) -> Result<TileIndex, QuadkeyError> {
+        let length = key.chars().count();
+        if length > MAX_QUADKEY_LEVEL {
+            return Err(QuadkeyError::TooLong { length });
+        }
+
+        let mut index = TileIndex::new(0, 0, length as u32);
+        for (position, character) in key.chars().enumerate() {
+            let digit = match character {
+                '0'..='3' => character as i32 - '0' as i32,
+                _ => {
+                    return Err(QuadkeyError::InvalidCharacter {
+                        position,
+                        character,
+                    })
+                }
+            };
+
+            index.x = (index.x << 1) | (digit & 1);
+            index.y = (index.y << 1) | (digit >> 1);
+        }
+
+        Ok(index)
+    }
 }
 
 impl From<WrappingTileIndex> for TileIndex {
@@ -65,3 +164,91 @@ impl From<WrappingTileIndex> for TileIndex {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile_schema::TileSchema;
+
+    #[test]
+    fn parent_and_children() {
+        assert_eq!(TileIndex::new(0, 0, 0).parent(), None);
+        assert_eq!(
+            TileIndex::new(5, 2, 3).parent(),
+            Some(TileIndex::new(2, 1, 2))
+        );
+        assert_eq!(
+            TileIndex::new(0, 0, 0).children(),
+            [
+                TileIndex::new(0, 0, 1),
+                TileIndex::new(1, 0, 1),
+                TileIndex::new(0, 1, 1),
+                TileIndex::new(1, 1, 1),
+            ]
+        );
+
+        for index in [
+            TileIndex::new(0, 0, 0),
+            TileIndex::new(3, 6, 4),
+            TileIndex::new(-1, 2, 3),
+        ] {
+            for child in index.children() {
+                assert_eq!(child.parent(), Some(index));
+            }
+        }
+    }
+
+    #[test]
+    fn neighbor() {
+        let schema = TileSchema::web(18);
+        let index = TileIndex::new(0, 1, 2);
+        assert_eq!(index.neighbor(1, 0), TileIndex::new(1, 1, 2));
+        assert_eq!(index.neighbor(0, -1), TileIndex::new(0, 0, 2));
+
+        let west = index.neighbor(-1, 0);
+        assert_eq!(west, TileIndex::new(-1, 1, 2));
+        assert_eq!(schema.wrap_index(west), Some(TileIndex::new(3, 1, 2)));
+
+        let north = TileIndex::new(2, 0, 2).neighbor(0, -1);
+        assert_eq!(schema.clamp_index(north), Some(TileIndex::new(2, 0, 2)));
+    }
+
+    #[test]
+    fn quadkey_round_trip() {
+        assert_eq!(TileIndex::new(0, 0, 0).to_quadkey(), "");
+        assert_eq!(TileIndex::from_quadkey(""), Ok(TileIndex::new(0, 0, 0)));
+        assert_eq!(TileIndex::new(3, 5, 3).to_quadkey(), "213");
+        assert_eq!(TileIndex::from_quadkey("213"), Ok(TileIndex::new(3, 5, 3)));
+
+        for z in [1, 2, 5, 12, 31] {
+            let last = ((1u64 << z) - 1) as i32;
+            for (x, y) in [
+                (0, 0),
+                (last, 0),
+                (0, last),
+                (last, last),
+                (last / 3, last / 2),
+            ] {
+                let index = TileIndex::new(x, y, z);
+                let key = index.to_quadkey();
+                assert_eq!(key.len(), z as usize);
+                assert_eq!(TileIndex::from_quadkey(&key), Ok(index));
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_quadkey() {
+        assert_eq!(
+            TileIndex::from_quadkey("0142"),
+            Err(QuadkeyError::InvalidCharacter {
+                position: 2,
+                character: '4'
+            })
+        );
+        assert_eq!(
+            TileIndex::from_quadkey(&"1".repeat(32)),
+            Err(QuadkeyError::TooLong { length: 32 })
+        );
+    }
+}
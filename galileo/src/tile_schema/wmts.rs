@@ -0,0 +1,353 @@
+//! Construction of [`TileSchema`] from OGC WMTS capabilities documents.
+
+use galileo_types::cartesian::{CartesianPoint2d, Point2, Rect};
+use roxmltree::{Document, Node};
+
+use super::schema::METERS_PER_DEGREE;
+use super::{TileSchema, TileSchemaBuilder, TileSchemaError};
+use crate::error::TileError;
+
+/// Size of a pixel in meters assumed by the WMTS standard to calculate scale denominators.
+const STANDARDIZED_PIXEL_SIZE: f64 = 0.00028;
+
+/// Parameters of a single `TileMatrix` element.
+struct TileMatrix {
+    scale_denominator: f64,
+    top_left_corner: Point2,
+    tile_width: u32,
+    tile_height: u32,
+    matrix_width: u32,
+    matrix_height: u32,
+}
+
+impl TileSchema {
+    /// Creates a tile schema from the `TileMatrixSet` with the identifier `set_identifier` of the
+    /// WMTS capabilities document `xml`.
+    ///
+    /// See [`TileSchemaBuilder::wmts`] for details.
+    pub fn from_wmts_tile_matrix_set(
+        xml: &str,
+        set_identifier: &str,
+    ) -> Result<TileSchema, TileError> {
+        Ok(TileSchemaBuilder::wmts(xml, set_identifier)?.build()?)
+    }
+}
+
+impl TileSchemaBuilder {
+    /// Creates a builder with the parameters of the `TileMatrixSet` with the identifier
+    /// `set_identifier` of the WMTS capabilities document `xml`.
+    ///
+    /// Tile matrices are ordered from the coarsest to the finest, and their resolutions are
+    /// calculated from the scale denominators with the standardized 0.28 mm pixel size. All the
+    /// matrices must have the same top left corner. If the tile sizes of the matrices differ, they
+    /// are set for every level with [`TileSchemaBuilder::with_tile_sizes_per_level`].
+    ///
+    /// Returns [`TileError::Parse`] if `xml` is not a valid XML document, and
+    /// [`TileError::Schema`] if the document does not describe a usable tile matrix set.
+    pub fn wmts(xml: &str, set_identifier: &str) -> Result<Self, TileError> {
+        let document = Document::parse(xml)
+            .map_err(|err| TileError::Parse(format!("WMTS capabilities: {err}")))?;
+        let set = document
+            .descendants()
+            .filter(|node| node.tag_name().name() == "TileMatrixSet")
+            .find(|node| child_text(*node, "Identifier") == Some(set_identifier))
+            .ok_or_else(|| TileSchemaError::TileMatrixSetNotFound {
+                identifier: set_identifier.to_string(),
+            })?;
+
+        let crs = child_text(set, "SupportedCRS").unwrap_or_default();
+        let is_geographic = crs.ends_with(":4326") || crs.ends_with("CRS84");
+        // EPSG:4326 defines latitude as the first axis, so the corners are given as `lat lon`.
+        let swap_axes = crs.ends_with(":4326");
+        let meters_per_unit = if is_geographic {
+            METERS_PER_DEGREE
+        } else {
+            1.0
+        };
+
+        let mut matrices = set
+            .children()
+            .filter(|node| node.tag_name().name() == "TileMatrix")
+            .map(|node| parse_tile_matrix(node, swap_axes))
+            .collect::<Result<Vec<_>, _>>()?;
+        matrices.sort_by(|a, b| b.scale_denominator.total_cmp(&a.scale_denominator));
+
+        let Some(first) = matrices.first() else {
+            return Err(TileSchemaError::NoZLevelsProvided.into());
+        };
+
+        let origin = first.top_left_corner;
+        let (tile_width, tile_height) = (first.tile_width, first.tile_height);
+        let resolution = |matrix: &TileMatrix| {
+            matrix.scale_denominator * STANDARDIZED_PIXEL_SIZE / meters_per_unit
+        };
+
+        let mut width: f64 = 0.0;
+        let mut height: f64 = 0.0;
+        for matrix in &matrices {
+            let tolerance = resolution(matrix) * 1e-3;
+            if (matrix.top_left_corner.x() - origin.x()).abs() > tolerance
+                || (matrix.top_left_corner.y() - origin.y()).abs() > tolerance
+            {
+                return Err(invalid("tile matrices have different top left corners").into());
+            }
+
+            width = width
+                .max(resolution(matrix) * matrix.tile_width as f64 * matrix.matrix_width as f64);
+            height = height
+                .max(resolution(matrix) * matrix.tile_height as f64 * matrix.matrix_height as f64);
+        }
+
+        let mut builder = TileSchemaBuilder::new()
+            .with_origin(origin)
+            .with_bounds(Rect::new(
+                origin.x(),
+                origin.y() - height,
+                origin.x() + width,
+                origin.y(),
+            ))
+            .with_tile_size(tile_width, tile_height)
+            .with_custom_resolutions(matrices.iter().map(resolution));
+        for (z, matrix) in matrices.iter().enumerate() {
+            builder = builder.with_matrix_size(z as u32, matrix.matrix_width, matrix.matrix_height);
+        }
+
+        let tile_sizes: Vec<_> = matrices
+            .iter()
+            .map(|matrix| (matrix.tile_width, matrix.tile_height))
+            .collect();
+        if tile_sizes
+            .iter()
+            .any(|size| *size != (tile_width, tile_height))
+        {
+            builder = builder.with_tile_sizes_per_level(tile_sizes);
+        }
+
+        Ok(builder)
+    }
+}
+
+fn parse_tile_matrix(node: Node, swap_axes: bool) -> Result<TileMatrix, TileSchemaError> {
+    let corner = required_text(node, "TopLeftCorner")?;
+    let coords = corner
+        .split_whitespace()
+        .map(|value| value.parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| invalid(format!("invalid TopLeftCorner value '{corner}'")))?;
+    let &[first, second] = coords.as_slice() else {
+        return Err(invalid(format!("invalid TopLeftCorner value '{corner}'")));
+    };
+    let top_left_corner = if swap_axes {
+        Point2::new(second, first)
+    } else {
+        Point2::new(first, second)
+    };
+
+    Ok(TileMatrix {
+        scale_denominator: parse_value(node, "ScaleDenominator")?,
+        top_left_corner,
+        tile_width: parse_value(node, "TileWidth")?,
+        tile_height: parse_value(node, "TileHeight")?,
+        matrix_width: parse_value(node, "MatrixWidth")?,
+        matrix_height: parse_value(node, "MatrixHeight")?,
+    })
+}
+
+fn parse_value<T: std::str::FromStr>(node: Node, name: &str) -> Result<T, TileSchemaError> {
+    let text = required_text(node, name)?;
+    text.parse()
+        .map_err(|_| invalid(format!("invalid {name} value '{text}'")))
+}
+
+fn required_text<'a>(node: Node<'a, '_>, name: &str) -> Result<&'a str, TileSchemaError> {
+    child_text(node, name).ok_or_else(|| invalid(format!("{name} is missing in a TileMatrix")))
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|child| child.tag_name().name() == name)
+        .and_then(|child| child.text())
+        .map(str::trim)
+}
+
+fn invalid(message: impl Into<String>) -> TileSchemaError {
+    TileSchemaError::InvalidWmtsCapabilities {
+        message: message.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::tile_schema::WrappingTileIndex;
+
+    const CAPABILITIES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Capabilities xmlns="http://www.opengis.net/wmts/1.0" xmlns:ows="http://www.opengis.net/ows/1.1" version="1.0.0">
+  <Contents>
+    <Layer>
+      <ows:Identifier>osm</ows:Identifier>
+      <TileMatrixSetLink>
+        <TileMatrixSet>GoogleMapsCompatible</TileMatrixSet>
+      </TileMatrixSetLink>
+    </Layer>
+    <TileMatrixSet>
+      <ows:Identifier>GoogleMapsCompatible</ows:Identifier>
+      <ows:SupportedCRS>urn:ogc:def:crs:EPSG:6.18.3:3857</ows:SupportedCRS>
+      <WellKnownScaleSet>urn:ogc:def:wkss:OGC:1.0:GoogleMapsCompatible</WellKnownScaleSet>
+      <TileMatrix>
+        <ows:Identifier>1</ows:Identifier>
+        <ScaleDenominator>279541132.0143589</ScaleDenominator>
+        <TopLeftCorner>-20037508.34278925 20037508.34278925</TopLeftCorner>
+        <TileWidth>256</TileWidth>
+        <TileHeight>256</TileHeight>
+        <MatrixWidth>2</MatrixWidth>
+        <MatrixHeight>2</MatrixHeight>
+      </TileMatrix>
+      <TileMatrix>
+        <ows:Identifier>0</ows:Identifier>
+        <ScaleDenominator>559082264.0287178</ScaleDenominator>
+        <TopLeftCorner>-20037508.34278925 20037508.34278925</TopLeftCorner>
+        <TileWidth>256</TileWidth>
+        <TileHeight>256</TileHeight>
+        <MatrixWidth>1</MatrixWidth>
+        <MatrixHeight>1</MatrixHeight>
+      </TileMatrix>
+      <TileMatrix>
+        <ows:Identifier>2</ows:Identifier>
+        <ScaleDenominator>139770566.0071794</ScaleDenominator>
+        <TopLeftCorner>-20037508.34278925 20037508.34278925</TopLeftCorner>
+        <TileWidth>256</TileWidth>
+        <TileHeight>256</TileHeight>
+        <MatrixWidth>4</MatrixWidth>
+        <MatrixHeight>4</MatrixHeight>
+      </TileMatrix>
+    </TileMatrixSet>
+    <TileMatrixSet>
+      <ows:Identifier>Local</ows:Identifier>
+      <ows:SupportedCRS>urn:ogc:def:crs:EPSG::4326</ows:SupportedCRS>
+      <TileMatrix>
+        <ows:Identifier>0</ows:Identifier>
+        <ScaleDenominator>279541132.0143589</ScaleDenominator>
+        <TopLeftCorner>90 -180</TopLeftCorner>
+        <TileWidth>256</TileWidth>
+        <TileHeight>256</TileHeight>
+        <MatrixWidth>2</MatrixWidth>
+        <MatrixHeight>1</MatrixHeight>
+      </TileMatrix>
+      <TileMatrix>
+        <ows:Identifier>1</ows:Identifier>
+        <ScaleDenominator>93180377.33811964</ScaleDenominator>
+        <TopLeftCorner>90 -180</TopLeftCorner>
+        <TileWidth>256</TileWidth>
+        <TileHeight>256</TileHeight>
+        <MatrixWidth>6</MatrixWidth>
+        <MatrixHeight>3</MatrixHeight>
+      </TileMatrix>
+    </TileMatrixSet>
+  </Contents>
+</Capabilities>"#;
+
+    #[test]
+    fn google_maps_compatible() {
+        let schema =
+            TileSchema::from_wmts_tile_matrix_set(CAPABILITIES, "GoogleMapsCompatible").unwrap();
+        let web = TileSchemaBuilder::web_mercator(0..=2).build().unwrap();
+
+        assert_eq!(schema.lods.len(), 3);
+        for z in 0..3 {
+            assert_abs_diff_eq!(schema.lods[z], web.lods[z], epsilon = 1e-6);
+        }
+        assert_eq!(schema.tile_width, 256);
+        assert_eq!(schema.tile_height, 256);
+        assert_abs_diff_eq!(schema.origin.x(), -20037508.34278925);
+        assert_abs_diff_eq!(schema.origin.y(), 20037508.34278925);
+        assert_abs_diff_eq!(schema.bounds.x_max(), 20037508.34278925, epsilon = 1e-3);
+        assert_abs_diff_eq!(schema.bounds.y_min(), -20037508.34278925, epsilon = 1e-3);
+
+        let bbox = schema.tile_bbox(WrappingTileIndex::new(3, 3, 2)).unwrap();
+        assert_abs_diff_eq!(bbox.x_max(), 20037508.34278925, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn different_tile_sizes() {
+        let capabilities = CAPABILITIES.replace(
+            "<TileWidth>256</TileWidth>
+        <TileHeight>256</TileHeight>
+        <MatrixWidth>4</MatrixWidth>
+        <MatrixHeight>4</MatrixHeight>",
+            "<TileWidth>512</TileWidth>
+        <TileHeight>512</TileHeight>
+        <MatrixWidth>2</MatrixWidth>
+        <MatrixHeight>2</MatrixHeight>",
+        );
+        let schema =
+            TileSchema::from_wmts_tile_matrix_set(&capabilities, "GoogleMapsCompatible").unwrap();
+
+        assert_eq!(schema.tile_width_at(1), 256);
+        assert_eq!(schema.tile_width_at(2), 512);
+        assert_abs_diff_eq!(schema.bounds.x_max(), 20037508.34278925, epsilon = 1e-3);
+
+        let bbox = schema.tile_bbox(WrappingTileIndex::new(1, 1, 2)).unwrap();
+        assert_abs_diff_eq!(bbox.x_min(), 0.0, epsilon = 1e-3);
+        assert_abs_diff_eq!(bbox.x_max(), 20037508.34278925, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn geographic_axis_order() {
+        let schema = TileSchema::from_wmts_tile_matrix_set(CAPABILITIES, "Local").unwrap();
+        assert_eq!(schema.origin, Point2::new(-180.0, 90.0));
+        assert_abs_diff_eq!(schema.lods[0], 360.0 / 512.0, epsilon = 1e-6);
+        assert_abs_diff_eq!(schema.bounds.y_min(), -90.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn power_of_two_levels() {
+        let result = TileSchemaBuilder::wmts(CAPABILITIES, "Local")
+            .unwrap()
+            .with_power_of_two_levels_required()
+            .build();
+        assert!(
+            matches!(result, Err(TileSchemaError::NonPowerOfTwoLevels { z: 1 })),
+            "Got {:?}",
+            result
+        );
+
+        let result = TileSchemaBuilder::wmts(CAPABILITIES, "GoogleMapsCompatible")
+            .unwrap()
+            .with_power_of_two_levels_required()
+            .build();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn errors() {
+        let result = TileSchema::from_wmts_tile_matrix_set(CAPABILITIES, "osm");
+        assert!(
+            matches!(
+                &result,
+                Err(TileError::Schema(TileSchemaError::TileMatrixSetNotFound { identifier }))
+                    if identifier == "osm"
+            ),
+            "Got {:?}",
+            result
+        );
+
+        let broken = CAPABILITIES.replace("<TileWidth>256</TileWidth>", "");
+        let result = TileSchema::from_wmts_tile_matrix_set(&broken, "GoogleMapsCompatible");
+        assert!(
+            matches!(
+                result,
+                Err(TileError::Schema(
+                    TileSchemaError::InvalidWmtsCapabilities { .. }
+                ))
+            ),
+            "Got {:?}",
+            result
+        );
+
+        let result = TileSchema::from_wmts_tile_matrix_set("<Capabilities>", "Local");
+        assert!(matches!(result, Err(TileError::Parse(_))));
+    }
+}